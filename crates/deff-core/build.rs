@@ -3,6 +3,11 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use syntect::{
+    dumps::dump_to_file,
+    parsing::{SyntaxDefinition, SyntaxSet},
+};
+
 fn main() {
     let syntax_directory = Path::new("assets/syntaxes");
     println!("cargo:rerun-if-changed={}", syntax_directory.display());
@@ -18,6 +23,10 @@ fn main() {
     let output_path = out_dir.join("bundled_syntaxes.rs");
     write_bundled_syntaxes(&output_path, &syntax_files)
         .unwrap_or_else(|error| panic!("failed to write {}: {error}", output_path.display()));
+
+    let pack_path = out_dir.join("syntaxes.packdump");
+    write_syntax_pack(&pack_path, &syntax_files)
+        .unwrap_or_else(|error| panic!("failed to write {}: {error}", pack_path.display()));
 }
 
 fn collect_syntax_files(directory: &Path) -> io::Result<Vec<PathBuf>> {
@@ -67,3 +76,22 @@ fn write_bundled_syntaxes(output_path: &Path, syntax_files: &[PathBuf]) -> io::R
     contents.push_str("];\n");
     fs::write(output_path, contents)
 }
+
+/// Links syntect's defaults with the bundled grammars once here, so startup only
+/// deserializes the finished set.
+fn write_syntax_pack(output_path: &Path, syntax_files: &[PathBuf]) -> io::Result<()> {
+    let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+    for syntax_file in syntax_files {
+        let source = fs::read_to_string(syntax_file)?;
+        let fallback_name = syntax_file.file_stem().and_then(|stem| stem.to_str());
+        let definition = SyntaxDefinition::load_from_str(&source, true, fallback_name)
+            .unwrap_or_else(|error| {
+                panic!(
+                    "failed to parse bundled syntax {}: {error}",
+                    syntax_file.display()
+                )
+            });
+        builder.add(definition);
+    }
+    dump_to_file(&builder.build(), output_path).map_err(io::Error::other)
+}
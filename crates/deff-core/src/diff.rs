@@ -0,0 +1,1789 @@
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tempfile::NamedTempFile;
+use tracing::warn;
+
+use crate::{
+    git::{get_git_dir, run_git, run_git_text, run_git_with_exit_code},
+    hexdump::{hex_dump_highlights, hex_dump_lines},
+    model::{
+        DiffFileDescriptor, DiffFileView, DiffOptions, FileContentSource, FileLineHighlights,
+        LoadProgress, Minified, PaneSide, Pickaxe, ResolvedComparison, StrategyId,
+        SyntaxHighlighting, ViewMode,
+    },
+    pretty::{is_minified, pretty_print},
+    profile::{self, Phase},
+    review::compute_review_key,
+    structural::{StructuralView, build_structural_view},
+    syntax::syntax_set,
+    syntax_tree::build_syntax_tree_highlights,
+    table::build_table_view,
+    text::get_max_normalized_line_length,
+};
+
+const MISSING_LEFT: &str = "<file does not exist in base revision>";
+const MISSING_RIGHT: &str = "<file does not exist in target revision>";
+const BINARY_PLACEHOLDER: &str = "<binary file preview not available>";
+const LOADING_PLACEHOLDER: &str = "<loading...>";
+const DOTENV_SYNTAX_NAME: &str = "Dotenv (deff)";
+const PRETTY_SCRATCH_DIR: &str = "deff/pretty";
+/// Files with a side larger than this are shown without syntax colours; highlighting
+/// giant generated files costs far more than it helps.
+const MAX_HIGHLIGHTED_BYTES: usize = 512 * 1024;
+
+static HUNK_HEADER_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^@@ -(\d+)(?:,(\d+))? \+(\d+)(?:,(\d+))? @@")
+        .expect("hunk header regex should be valid")
+});
+
+fn split_null_terminated(raw_output: &[u8]) -> Vec<String> {
+    raw_output
+        .split(|byte| *byte == b'\0')
+        .filter(|chunk| !chunk.is_empty())
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect()
+}
+
+/// A mode field of `git diff --raw`, or `None` for the all-zero mode of a missing side.
+fn parse_raw_mode(mode: Option<&str>) -> Option<String> {
+    mode.filter(|mode| mode.bytes().any(|byte| byte != b'0'))
+        .map(str::to_string)
+}
+
+/// Reads `git diff --raw -z`: a `:old_mode new_mode old_blob new_blob status` token,
+/// then one path, or two for a rename or copy.
+fn parse_diff_raw_output(
+    raw_output: &[u8],
+    base_source: FileContentSource,
+    head_source: FileContentSource,
+) -> Vec<DiffFileDescriptor> {
+    if raw_output.is_empty() {
+        return Vec::new();
+    }
+
+    let tokens = split_null_terminated(raw_output);
+    let mut files = Vec::new();
+    let mut index = 0;
+
+    while index < tokens.len() {
+        if index + 1 >= tokens.len() {
+            warn!(token = %tokens[index], "raw diff output ended with a dangling status token");
+        }
+        let metadata_token = match tokens.get(index) {
+            Some(value) => value,
+            None => break,
+        };
+        index += 1;
+
+        let mut fields = metadata_token.trim_start_matches(':').split(' ');
+        let base_mode = parse_raw_mode(fields.next());
+        let head_mode = parse_raw_mode(fields.next());
+        let status_token = fields.nth(2).unwrap_or_default().to_string();
+        let status_code = status_token.chars().next().unwrap_or_default();
+        if status_code == 'R' || status_code == 'C' {
+            let old_path = match tokens.get(index) {
+                Some(value) => value,
+                None => break,
+            };
+            let new_path = match tokens.get(index + 1) {
+                Some(value) => value,
+                None => break,
+            };
+            index += 2;
+
+            if old_path.is_empty() || new_path.is_empty() {
+                continue;
+            }
+
+            files.push(DiffFileDescriptor {
+                raw_status: status_token,
+                display_path: format!("{old_path} -> {new_path}"),
+                base_path: Some(old_path.clone()),
+                head_path: Some(new_path.clone()),
+                base_source,
+                head_source,
+                base_mode,
+                head_mode,
+            });
+            continue;
+        }
+
+        let path_value = match tokens.get(index) {
+            Some(value) => value,
+            None => break,
+        };
+        index += 1;
+
+        if path_value.is_empty() {
+            continue;
+        }
+
+        match status_code {
+            'A' => files.push(DiffFileDescriptor {
+                raw_status: status_token,
+                display_path: path_value.clone(),
+                base_path: None,
+                head_path: Some(path_value.clone()),
+                base_source: FileContentSource::Missing,
+                head_source,
+                base_mode: None,
+                head_mode,
+            }),
+            'D' => files.push(DiffFileDescriptor {
+                raw_status: status_token,
+                display_path: path_value.clone(),
+                base_path: Some(path_value.clone()),
+                head_path: None,
+                base_source,
+                head_source: FileContentSource::Missing,
+                base_mode,
+                head_mode: None,
+            }),
+            _ => files.push(DiffFileDescriptor {
+                raw_status: status_token,
+                display_path: path_value.clone(),
+                base_path: Some(path_value.clone()),
+                head_path: Some(path_value.clone()),
+                base_source,
+                head_source,
+                base_mode,
+                head_mode,
+            }),
+        }
+    }
+
+    files
+}
+
+/// The mode git would record for an untracked file when it is added.
+fn get_untracked_file_mode(repo_root: &Path, path: &str) -> Option<String> {
+    let metadata = fs::symlink_metadata(repo_root.join(path)).ok()?;
+    if metadata.file_type().is_symlink() {
+        return Some("120000".to_string());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 != 0 {
+            return Some("100755".to_string());
+        }
+    }
+    Some("100644".to_string())
+}
+
+fn parse_null_separated_list(raw_output: &[u8]) -> Vec<String> {
+    split_null_terminated(raw_output)
+}
+
+/// Untracked files are not seen by git's pickaxe, so they match when their whole
+/// content contains the string or has a line matching the regex, which `git grep -E`
+/// reads the same way `-G` does.
+fn untracked_file_matches_pickaxe(repo_root: &Path, path: &str, pickaxe: &Pickaxe) -> bool {
+    match pickaxe {
+        Pickaxe::String(value) => fs::read_to_string(repo_root.join(path))
+            .is_ok_and(|content| content.contains(value.as_str())),
+        Pickaxe::Regex(pattern) => run_git_with_exit_code(
+            [
+                "grep",
+                "--no-index",
+                "--quiet",
+                "--extended-regexp",
+                "-e",
+                pattern.as_str(),
+                "--",
+                path,
+            ],
+            repo_root,
+        )
+        .is_ok_and(|(code, _)| code == 0),
+    }
+}
+
+pub fn get_diff_file_descriptors(
+    repo_root: &Path,
+    comparison: &ResolvedComparison,
+    pickaxe: Option<&Pickaxe>,
+) -> Result<Vec<DiffFileDescriptor>> {
+    let pickaxe_arg = pickaxe.map(Pickaxe::git_arg);
+
+    match comparison.strategy_id {
+        // An amend records the index, as a commit of the staged changes does.
+        StrategyId::Staged | StrategyId::Amend => {
+            let mut diff_args = vec!["diff", "--cached", "--raw", "--find-renames", "-z"];
+            diff_args.extend(pickaxe_arg.as_deref());
+            diff_args.push(comparison.base_commit.as_str());
+            let staged_output = run_git(diff_args, repo_root)?;
+            return Ok(parse_diff_raw_output(
+                &staged_output,
+                FileContentSource::Commit,
+                FileContentSource::Index,
+            ));
+        }
+        StrategyId::Unstaged => {
+            // Untracked files are left out, as `git diff` leaves them out.
+            let mut diff_args = vec!["diff", "--raw", "--find-renames", "-z"];
+            diff_args.extend(pickaxe_arg.as_deref());
+            let unstaged_output = run_git(diff_args, repo_root)?;
+            return Ok(parse_diff_raw_output(
+                &unstaged_output,
+                FileContentSource::Index,
+                FileContentSource::WorkingTree,
+            ));
+        }
+        _ => {}
+    }
+
+    if comparison.includes_uncommitted {
+        let mut diff_args = vec!["diff", "--raw", "--find-renames", "-z"];
+        diff_args.extend(pickaxe_arg.as_deref());
+        diff_args.push(comparison.base_commit.as_str());
+        let tracked_output = run_git(diff_args, repo_root)?;
+
+        let mut descriptors = parse_diff_raw_output(
+            &tracked_output,
+            FileContentSource::Commit,
+            FileContentSource::WorkingTree,
+        );
+
+        let mut seen_paths: HashSet<String> = descriptors
+            .iter()
+            .filter_map(|descriptor| {
+                descriptor
+                    .head_path
+                    .clone()
+                    .or_else(|| descriptor.base_path.clone())
+            })
+            .collect();
+
+        let untracked_output = run_git(
+            ["ls-files", "--others", "--exclude-standard", "-z"],
+            repo_root,
+        )?;
+        let untracked_paths = parse_null_separated_list(&untracked_output);
+
+        for untracked_path in untracked_paths {
+            if seen_paths.contains(&untracked_path) {
+                continue;
+            }
+            if let Some(pickaxe) = pickaxe
+                && !untracked_file_matches_pickaxe(repo_root, &untracked_path, pickaxe)
+            {
+                continue;
+            }
+
+            descriptors.push(DiffFileDescriptor {
+                raw_status: "??".to_string(),
+                display_path: untracked_path.clone(),
+                base_path: None,
+                head_path: Some(untracked_path.clone()),
+                base_source: FileContentSource::Missing,
+                head_source: FileContentSource::WorkingTree,
+                base_mode: None,
+                head_mode: get_untracked_file_mode(repo_root, &untracked_path),
+            });
+            seen_paths.insert(untracked_path);
+        }
+
+        return Ok(descriptors);
+    }
+
+    let revision_range = format!("{}..{}", comparison.base_commit, comparison.head_commit);
+    let mut diff_args = vec!["diff", "--raw", "--find-renames", "-z"];
+    diff_args.extend(pickaxe_arg.as_deref());
+    diff_args.push(revision_range.as_str());
+    let committed_output = run_git(diff_args, repo_root)?;
+
+    Ok(parse_diff_raw_output(
+        &committed_output,
+        FileContentSource::Commit,
+        FileContentSource::Commit,
+    ))
+}
+
+/// First path component of the descriptor's display path, or `.` for root-level files.
+pub fn top_level_directory(descriptor: &DiffFileDescriptor) -> &str {
+    let path = descriptor
+        .head_path
+        .as_deref()
+        .or(descriptor.base_path.as_deref())
+        .unwrap_or(&descriptor.display_path);
+    match path.split_once('/') {
+        Some((directory, _)) => directory,
+        None => ".",
+    }
+}
+
+/// Counts descriptors per top-level directory, largest group first.
+pub fn group_by_top_level_directory(descriptors: &[DiffFileDescriptor]) -> Vec<(String, usize)> {
+    let mut groups: Vec<(String, usize)> = Vec::new();
+    for descriptor in descriptors {
+        let directory = top_level_directory(descriptor);
+        match groups.iter_mut().find(|(name, _)| name == directory) {
+            Some((_, count)) => *count += 1,
+            None => groups.push((directory.to_string(), 1)),
+        }
+    }
+    groups.sort_by(|left, right| right.1.cmp(&left.1).then_with(|| left.0.cmp(&right.0)));
+    groups
+}
+
+fn create_empty_line_highlights() -> FileLineHighlights {
+    FileLineHighlights {
+        left_deleted_line_indexes: HashSet::new(),
+        right_added_line_indexes: HashSet::new(),
+    }
+}
+
+fn create_range_line_indexes(line_count: usize) -> HashSet<usize> {
+    (0..line_count).collect()
+}
+
+fn parse_hunk_count(value: Option<&str>) -> usize {
+    match value {
+        None => 1,
+        Some(raw) => raw.parse::<usize>().unwrap_or(0),
+    }
+}
+
+fn parse_line_highlights_from_patch(diff_output: &str) -> FileLineHighlights {
+    let mut highlights = create_empty_line_highlights();
+
+    for line in diff_output.lines() {
+        let Some(captures) = HUNK_HEADER_RE.captures(line) else {
+            if line.starts_with("@@") {
+                warn!(line, "unparseable hunk header");
+            }
+            continue;
+        };
+
+        let old_start = captures
+            .get(1)
+            .and_then(|value| value.as_str().parse::<usize>().ok());
+        let old_count = parse_hunk_count(captures.get(2).map(|value| value.as_str()));
+        let new_start = captures
+            .get(3)
+            .and_then(|value| value.as_str().parse::<usize>().ok());
+        let new_count = parse_hunk_count(captures.get(4).map(|value| value.as_str()));
+
+        if let Some(start) = old_start {
+            let start_index = start.saturating_sub(1);
+            for offset in 0..old_count {
+                highlights
+                    .left_deleted_line_indexes
+                    .insert(start_index.saturating_add(offset));
+            }
+        }
+
+        if let Some(start) = new_start {
+            let start_index = start.saturating_sub(1);
+            for offset in 0..new_count {
+                highlights
+                    .right_added_line_indexes
+                    .insert(start_index.saturating_add(offset));
+            }
+        }
+    }
+
+    highlights
+}
+
+/// Added and deleted files are all-added or all-deleted without asking git.
+fn get_missing_side_highlights(
+    descriptor: &DiffFileDescriptor,
+    left_line_count: usize,
+    right_line_count: usize,
+) -> Option<FileLineHighlights> {
+    if descriptor.base_source == FileContentSource::Missing {
+        return Some(FileLineHighlights {
+            left_deleted_line_indexes: HashSet::new(),
+            right_added_line_indexes: create_range_line_indexes(right_line_count),
+        });
+    }
+
+    if descriptor.head_source == FileContentSource::Missing {
+        return Some(FileLineHighlights {
+            left_deleted_line_indexes: create_range_line_indexes(left_line_count),
+            right_added_line_indexes: HashSet::new(),
+        });
+    }
+
+    None
+}
+
+fn get_line_highlights_for_descriptor(
+    repo_root: &Path,
+    comparison: &ResolvedComparison,
+    descriptor: &DiffFileDescriptor,
+    options: &DiffOptions,
+    left_line_count: usize,
+    right_line_count: usize,
+    ignore_matching_lines: bool,
+) -> FileLineHighlights {
+    if let Some(highlights) =
+        get_missing_side_highlights(descriptor, left_line_count, right_line_count)
+    {
+        return highlights;
+    }
+
+    let Some(base_path) = descriptor.base_path.as_deref() else {
+        return create_empty_line_highlights();
+    };
+    let Some(head_path) = descriptor.head_path.as_deref() else {
+        return create_empty_line_highlights();
+    };
+
+    let path_specs = if base_path == head_path {
+        vec![base_path.to_string()]
+    } else {
+        vec![base_path.to_string(), head_path.to_string()]
+    };
+
+    let mut diff_args: Vec<OsString> = vec![
+        OsString::from("diff"),
+        OsString::from("--no-color"),
+        OsString::from("--unified=0"),
+    ];
+    if let Some(pattern) = &options.ignore_matching_lines
+        && ignore_matching_lines
+    {
+        diff_args.push(OsString::from(format!("--ignore-matching-lines={pattern}")));
+    }
+
+    match comparison.strategy_id {
+        StrategyId::Staged | StrategyId::Amend => {
+            diff_args.push(OsString::from("--cached"));
+            diff_args.push(OsString::from(comparison.base_commit.as_str()));
+        }
+        // Without a commit, `git diff` compares the index with the working tree.
+        StrategyId::Unstaged => {}
+        _ if comparison.includes_uncommitted => {
+            diff_args.push(OsString::from(comparison.base_commit.as_str()));
+        }
+        _ => {
+            diff_args.push(OsString::from("--find-renames"));
+            diff_args.push(OsString::from(format!(
+                "{}..{}",
+                comparison.base_commit, comparison.head_commit
+            )));
+        }
+    }
+
+    diff_args.push(OsString::from("--"));
+    for path_spec in path_specs {
+        diff_args.push(OsString::from(path_spec));
+    }
+
+    let diff_output = match run_git_text(diff_args, repo_root) {
+        Ok(value) => value,
+        Err(_) => return create_empty_line_highlights(),
+    };
+
+    parse_line_highlights_from_patch(&diff_output)
+}
+
+fn is_binary_content(content: &[u8]) -> bool {
+    let sample_size = content.len().min(8192);
+    content[..sample_size].contains(&0)
+}
+
+fn split_into_lines(content: &str) -> Vec<String> {
+    let normalized = content.replace("\r\n", "\n");
+
+    if normalized.is_empty() {
+        return vec![String::new()];
+    }
+
+    let mut lines: Vec<String> = normalized.split('\n').map(ToOwned::to_owned).collect();
+    if lines.len() > 1 && lines.last().is_some_and(|last| last.is_empty()) {
+        let _ = lines.pop();
+    }
+
+    if lines.is_empty() {
+        vec![String::new()]
+    } else {
+        lines
+    }
+}
+
+/// One side's lines, whether they are a hex dump of binary content, and whether the
+/// text's last line lacks a newline.
+struct SideContent {
+    lines: Vec<String>,
+    hex_dump: bool,
+    missing_final_newline: bool,
+}
+
+impl SideContent {
+    fn text(line: &str) -> Self {
+        Self {
+            lines: vec![line.to_string()],
+            hex_dump: false,
+            missing_final_newline: false,
+        }
+    }
+
+    fn from_bytes(content: &[u8], options: &DiffOptions) -> Self {
+        if !is_binary_content(content) {
+            return Self {
+                lines: split_into_lines(&String::from_utf8_lossy(content)),
+                hex_dump: false,
+                missing_final_newline: !content.is_empty() && !content.ends_with(b"\n"),
+            };
+        }
+        match options.hex_dump_bytes {
+            Some(limit) => Self {
+                lines: hex_dump_lines(content, limit),
+                hex_dump: true,
+                missing_final_newline: false,
+            },
+            None => Self::text(BINARY_PLACEHOLDER),
+        }
+    }
+}
+
+fn read_lines_at_revision(
+    repo_root: &Path,
+    revision: &str,
+    file_path: &str,
+    options: &DiffOptions,
+) -> SideContent {
+    let revision_spec = format!("{revision}:{file_path}");
+    match run_git(["show", revision_spec.as_str()], repo_root) {
+        Ok(output) => SideContent::from_bytes(&output, options),
+        Err(error) => {
+            warn!(revision, file_path, %error, "failed to read file at revision");
+            SideContent::text(&format!("<unable to load file: {error}>"))
+        }
+    }
+}
+
+/// The staged version of `file_path`.
+fn read_lines_at_index(repo_root: &Path, file_path: &str, options: &DiffOptions) -> SideContent {
+    read_lines_at_revision(repo_root, "", file_path, options)
+}
+
+fn read_lines_at_working_tree(
+    repo_root: &Path,
+    file_path: &str,
+    options: &DiffOptions,
+) -> SideContent {
+    read_lines_from_file(&repo_root.join(file_path), options)
+}
+
+fn read_lines_from_file(path: &Path, options: &DiffOptions) -> SideContent {
+    match fs::read(path) {
+        Ok(buffer) => SideContent::from_bytes(&buffer, options),
+        Err(error) => {
+            warn!(path = %path.display(), %error, "failed to read file");
+            SideContent::text(&format!("<unable to load file: {error}>"))
+        }
+    }
+}
+
+/// `--structural` JSON/YAML files whose sides both parse are shown by key path, and
+/// `--csv` tables by row.
+fn get_structural_view(
+    descriptor: &DiffFileDescriptor,
+    options: &DiffOptions,
+    left: &SideContent,
+    right: &SideContent,
+) -> Option<StructuralView> {
+    if left.hex_dump
+        || right.hex_dump
+        || descriptor.base_source == FileContentSource::Missing
+        || descriptor.head_source == FileContentSource::Missing
+    {
+        return None;
+    }
+    let path = descriptor
+        .head_path
+        .as_deref()
+        .or(descriptor.base_path.as_deref())?;
+    let (left_text, right_text) = (left.lines.join("\n"), right.lines.join("\n"));
+    if let Some(key) = &options.csv_key
+        && let Some(view) = build_table_view(path, key, &left_text, &right_text)
+    {
+        return Some(view);
+    }
+    if !options.structural {
+        return None;
+    }
+    build_structural_view(path, &left_text, &right_text)
+}
+
+/// `--structural` Rust and Go files keep their lines, but only lines with syntax tokens
+/// that changed are highlighted. An explicit `--ignore-matching-lines` keeps git's diff.
+fn get_syntax_tree_highlights(
+    descriptor: &DiffFileDescriptor,
+    options: &DiffOptions,
+    left: &SideContent,
+    right: &SideContent,
+) -> Option<FileLineHighlights> {
+    if !options.structural
+        || options.ignore_matching_lines.is_some()
+        || left.hex_dump
+        || right.hex_dump
+        || descriptor.base_source == FileContentSource::Missing
+        || descriptor.head_source == FileContentSource::Missing
+    {
+        return None;
+    }
+    let path = descriptor
+        .head_path
+        .as_deref()
+        .or(descriptor.base_path.as_deref())?;
+    build_syntax_tree_highlights(path, &left.lines, &right.lines)
+}
+
+/// git has no line hunks for binary files, so hex dumps are compared row by row.
+fn get_hex_dump_highlights(
+    descriptor: &DiffFileDescriptor,
+    left: &SideContent,
+    right: &SideContent,
+) -> Option<FileLineHighlights> {
+    if !left.hex_dump && !right.hex_dump {
+        return None;
+    }
+    Some(
+        get_missing_side_highlights(descriptor, left.lines.len(), right.lines.len())
+            .unwrap_or_else(|| hex_dump_highlights(&left.lines, &right.lines)),
+    )
+}
+
+/// Either existing side is JS, JSON, or CSS on one enormous line.
+fn is_minified_pair(
+    descriptor: &DiffFileDescriptor,
+    left: &SideContent,
+    right: &SideContent,
+) -> bool {
+    let side_is_minified = |path: Option<&str>, source: FileContentSource, side: &SideContent| {
+        source != FileContentSource::Missing
+            && !side.hex_dump
+            && path.is_some_and(|path| is_minified(path, &side.lines))
+    };
+    side_is_minified(
+        descriptor.base_path.as_deref(),
+        descriptor.base_source,
+        left,
+    ) || side_is_minified(
+        descriptor.head_path.as_deref(),
+        descriptor.head_source,
+        right,
+    )
+}
+
+/// Writes the re-indented sides where `git diff --no-index` can compare them. Each call
+/// gets its own files, so views built in parallel do not share them; they are removed
+/// when dropped.
+fn write_pretty_scratch_files(
+    repo_root: &Path,
+    left_lines: &[String],
+    right_lines: &[String],
+) -> Option<(NamedTempFile, NamedTempFile)> {
+    let scratch_dir = get_git_dir(repo_root).ok()?.join(PRETTY_SCRATCH_DIR);
+    fs::create_dir_all(&scratch_dir).ok()?;
+    let write_side = |lines: &[String]| {
+        let mut file = NamedTempFile::new_in(&scratch_dir).ok()?;
+        file.write_all((lines.join("\n") + "\n").as_bytes()).ok()?;
+        Some(file)
+    };
+    Some((write_side(left_lines)?, write_side(right_lines)?))
+}
+
+/// `--pretty` re-indents both sides of a minified file. git's hunks are against the one
+/// long line, so the re-indented sides are diffed again from scratch copies. Returned
+/// with the hunks `--ignore-matching-lines` dropped still in, to align the sides along.
+fn get_pretty_printed_view(
+    repo_root: &Path,
+    descriptor: &DiffFileDescriptor,
+    options: &DiffOptions,
+    left: &SideContent,
+    right: &SideContent,
+) -> Option<(StructuralView, Option<FileLineHighlights>)> {
+    if !options.pretty_minified {
+        return None;
+    }
+    let format_side = |path: Option<&str>, source: FileContentSource, side: &SideContent| {
+        if source == FileContentSource::Missing {
+            return Some(side.lines.clone());
+        }
+        let lines = pretty_print(path?, &side.lines.join("\n"))?;
+        Some(if lines.is_empty() {
+            vec![String::new()]
+        } else {
+            lines
+        })
+    };
+    let left_lines = format_side(
+        descriptor.base_path.as_deref(),
+        descriptor.base_source,
+        left,
+    )?;
+    let right_lines = format_side(
+        descriptor.head_path.as_deref(),
+        descriptor.head_source,
+        right,
+    )?;
+
+    let (highlights, hunks) =
+        match get_missing_side_highlights(descriptor, left_lines.len(), right_lines.len()) {
+            Some(highlights) => (highlights, None),
+            None => {
+                let scratch_files =
+                    write_pretty_scratch_files(repo_root, &left_lines, &right_lines);
+                with_dropped_hunks(options, |ignore_matching_lines| {
+                    scratch_files
+                        .as_ref()
+                        .and_then(|(left_file, right_file)| {
+                            get_no_index_highlights(
+                                repo_root,
+                                left_file.path(),
+                                right_file.path(),
+                                options,
+                                ignore_matching_lines,
+                            )
+                        })
+                        .unwrap_or_else(create_empty_line_highlights)
+                })
+            }
+        };
+    Some((
+        StructuralView {
+            left_lines,
+            right_lines,
+            highlights,
+        },
+        hunks,
+    ))
+}
+
+fn is_dotenv_file_name(file_name_lower: &str) -> bool {
+    file_name_lower == ".env" || file_name_lower.starts_with(".env.")
+}
+
+fn detect_syntax_name(file_path: Option<&str>, lines: &[String]) -> Option<String> {
+    let syntaxes = syntax_set();
+
+    if let Some(file_path) = file_path {
+        let path = PathBuf::from(file_path);
+
+        if let Some(file_name) = path.file_name().and_then(|name| name.to_str()) {
+            let file_name_lower = file_name.to_ascii_lowercase();
+
+            if is_dotenv_file_name(&file_name_lower)
+                && let Some(syntax) = syntaxes
+                    .find_syntax_by_name(DOTENV_SYNTAX_NAME)
+                    .or_else(|| syntaxes.find_syntax_by_token("dotenv"))
+                    .or_else(|| syntaxes.find_syntax_by_extension("env"))
+            {
+                return Some(syntax.name.clone());
+            }
+
+            if let Some(syntax) = syntaxes.find_syntax_by_token(file_name) {
+                return Some(syntax.name.clone());
+            }
+
+            if let Some(syntax) = syntaxes.find_syntax_by_token(&file_name_lower) {
+                return Some(syntax.name.clone());
+            }
+
+            if path.extension().is_none() {
+                if let Some(syntax) = syntaxes.find_syntax_by_extension(file_name) {
+                    return Some(syntax.name.clone());
+                }
+
+                if let Some(syntax) = syntaxes.find_syntax_by_extension(&file_name_lower) {
+                    return Some(syntax.name.clone());
+                }
+            }
+        }
+
+        if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+            if let Some(syntax) = syntaxes.find_syntax_by_extension(extension) {
+                return Some(syntax.name.clone());
+            }
+
+            let extension_lower = extension.to_ascii_lowercase();
+            if let Some(syntax) = syntaxes.find_syntax_by_extension(&extension_lower) {
+                return Some(syntax.name.clone());
+            }
+        }
+    }
+
+    let first_line = lines
+        .iter()
+        .find(|line| !line.trim().is_empty())
+        .or_else(|| lines.first())?;
+
+    syntaxes
+        .find_syntax_by_first_line(first_line)
+        .map(|syntax| syntax.name.clone())
+}
+
+fn strip_whitespace(line: &str) -> String {
+    line.chars()
+        .filter(|character| !character.is_whitespace())
+        .collect()
+}
+
+/// Like `git diff -w --ignore-blank-lines` coming back empty for content that changed.
+pub fn is_whitespace_only_change(left_lines: &[String], right_lines: &[String]) -> bool {
+    if left_lines == right_lines {
+        return false;
+    }
+
+    let significant = |lines: &[String]| -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| strip_whitespace(line))
+            .filter(|line| !line.is_empty())
+            .collect()
+    };
+    significant(left_lines) == significant(right_lines)
+}
+
+/// Stands in for a file whose view is still being built.
+pub fn loading_file_view(descriptor: &DiffFileDescriptor) -> DiffFileView {
+    assemble_file_view(
+        descriptor,
+        &DiffOptions::default(),
+        vec![LOADING_PLACEHOLDER.to_string()],
+        vec![LOADING_PLACEHOLDER.to_string()],
+        create_empty_line_highlights(),
+        None,
+        Minified::No,
+        false,
+    )
+}
+
+pub fn build_file_view(
+    repo_root: &Path,
+    comparison: &ResolvedComparison,
+    descriptor: &DiffFileDescriptor,
+    options: &DiffOptions,
+) -> DiffFileView {
+    let left = profile::time(Phase::ContentReads, || match descriptor.base_source {
+        FileContentSource::Missing => SideContent::text(MISSING_LEFT),
+        FileContentSource::WorkingTree => descriptor
+            .base_path
+            .as_deref()
+            .map(|path| read_lines_at_working_tree(repo_root, path, options))
+            .unwrap_or_else(|| SideContent::text(MISSING_LEFT)),
+        FileContentSource::Index => descriptor
+            .base_path
+            .as_deref()
+            .map(|path| read_lines_at_index(repo_root, path, options))
+            .unwrap_or_else(|| SideContent::text(MISSING_LEFT)),
+        FileContentSource::Commit => descriptor
+            .base_path
+            .as_deref()
+            .map(|path| read_lines_at_revision(repo_root, &comparison.base_commit, path, options))
+            .unwrap_or_else(|| SideContent::text(MISSING_LEFT)),
+    });
+
+    let right = profile::time(Phase::ContentReads, || match descriptor.head_source {
+        FileContentSource::Missing => SideContent::text(MISSING_RIGHT),
+        FileContentSource::WorkingTree => descriptor
+            .head_path
+            .as_deref()
+            .map(|path| read_lines_at_working_tree(repo_root, path, options))
+            .unwrap_or_else(|| SideContent::text(MISSING_RIGHT)),
+        FileContentSource::Index => descriptor
+            .head_path
+            .as_deref()
+            .map(|path| read_lines_at_index(repo_root, path, options))
+            .unwrap_or_else(|| SideContent::text(MISSING_RIGHT)),
+        FileContentSource::Commit => descriptor
+            .head_path
+            .as_deref()
+            .map(|path| read_lines_at_revision(repo_root, &comparison.head_commit, path, options))
+            .unwrap_or_else(|| SideContent::text(MISSING_RIGHT)),
+    });
+
+    if let Some(view) = get_structural_view(descriptor, options, &left, &right) {
+        return assemble_file_view(
+            descriptor,
+            options,
+            view.left_lines,
+            view.right_lines,
+            view.highlights,
+            None,
+            Minified::No,
+            false,
+        );
+    }
+
+    let minified = is_minified_pair(descriptor, &left, &right);
+    if minified
+        && let Some((view, hunks)) =
+            get_pretty_printed_view(repo_root, descriptor, options, &left, &right)
+    {
+        return assemble_file_view(
+            descriptor,
+            options,
+            view.left_lines,
+            view.right_lines,
+            view.highlights,
+            hunks,
+            Minified::PrettyPrinted,
+            true,
+        );
+    }
+
+    let (line_highlights, hunks) = profile::time(Phase::HighlightDiffs, || {
+        if let Some(highlights) = get_hex_dump_highlights(descriptor, &left, &right)
+            .or_else(|| get_syntax_tree_highlights(descriptor, options, &left, &right))
+        {
+            return (highlights, None);
+        }
+        with_dropped_hunks(options, |ignore_matching_lines| {
+            get_line_highlights_for_descriptor(
+                repo_root,
+                comparison,
+                descriptor,
+                options,
+                left.lines.len(),
+                right.lines.len(),
+                ignore_matching_lines,
+            )
+        })
+    });
+
+    let mut view = assemble_file_view(
+        descriptor,
+        options,
+        left.lines,
+        right.lines,
+        line_highlights,
+        hunks,
+        if minified {
+            Minified::AsIs
+        } else {
+            Minified::No
+        },
+        true,
+    );
+    view.left_missing_final_newline = left.missing_final_newline;
+    view.right_missing_final_newline = right.missing_final_newline;
+    view
+}
+
+/// git's highlights with `--ignore-matching-lines` applied, and when it is set, the hunks
+/// it dropped as well, which the sides are aligned along.
+fn with_dropped_hunks(
+    options: &DiffOptions,
+    git_highlights: impl Fn(bool) -> FileLineHighlights,
+) -> (FileLineHighlights, Option<FileLineHighlights>) {
+    (
+        git_highlights(true),
+        options
+            .ignore_matching_lines
+            .is_some()
+            .then(|| git_highlights(false)),
+    )
+}
+
+fn get_no_index_highlights(
+    repo_root: &Path,
+    left_file: &Path,
+    right_file: &Path,
+    options: &DiffOptions,
+    ignore_matching_lines: bool,
+) -> Option<FileLineHighlights> {
+    let mut diff_args: Vec<OsString> = vec![
+        OsString::from("diff"),
+        OsString::from("--no-index"),
+        // Keeps git from calling deff again when it is GIT_EXTERNAL_DIFF.
+        OsString::from("--no-ext-diff"),
+        OsString::from("--no-color"),
+        OsString::from("--unified=0"),
+    ];
+    if let Some(pattern) = &options.ignore_matching_lines
+        && ignore_matching_lines
+    {
+        diff_args.push(OsString::from(format!("--ignore-matching-lines={pattern}")));
+    }
+    diff_args.extend([
+        OsString::from("--"),
+        left_file.as_os_str().to_os_string(),
+        right_file.as_os_str().to_os_string(),
+    ]);
+    // `--no-index` exits 1 when the files differ.
+    let (_, output) = run_git_with_exit_code(diff_args, repo_root).ok()?;
+    Some(parse_line_highlights_from_patch(&String::from_utf8_lossy(
+        &output,
+    )))
+}
+
+/// Builds the view for two files on disk, the way git's external diff driver hands them
+/// over; `None` is a side that does not exist. Highlights come from `git diff --no-index`.
+pub fn build_file_view_from_files(
+    repo_root: &Path,
+    descriptor: &DiffFileDescriptor,
+    left_file: Option<&Path>,
+    right_file: Option<&Path>,
+    options: &DiffOptions,
+) -> DiffFileView {
+    let left = left_file
+        .map(|path| read_lines_from_file(path, options))
+        .unwrap_or_else(|| SideContent::text(MISSING_LEFT));
+    let right = right_file
+        .map(|path| read_lines_from_file(path, options))
+        .unwrap_or_else(|| SideContent::text(MISSING_RIGHT));
+
+    if let Some(view) = get_structural_view(descriptor, options, &left, &right) {
+        return assemble_file_view(
+            descriptor,
+            options,
+            view.left_lines,
+            view.right_lines,
+            view.highlights,
+            None,
+            Minified::No,
+            false,
+        );
+    }
+
+    let minified = is_minified_pair(descriptor, &left, &right);
+    if minified
+        && let Some((view, hunks)) =
+            get_pretty_printed_view(repo_root, descriptor, options, &left, &right)
+    {
+        return assemble_file_view(
+            descriptor,
+            options,
+            view.left_lines,
+            view.right_lines,
+            view.highlights,
+            hunks,
+            Minified::PrettyPrinted,
+            true,
+        );
+    }
+
+    let local_highlights = get_hex_dump_highlights(descriptor, &left, &right)
+        .or_else(|| get_missing_side_highlights(descriptor, left.lines.len(), right.lines.len()))
+        .or_else(|| get_syntax_tree_highlights(descriptor, options, &left, &right));
+    let (line_highlights, hunks) = match (local_highlights, left_file.zip(right_file)) {
+        (Some(highlights), _) => (highlights, None),
+        (None, Some((left_file, right_file))) => {
+            with_dropped_hunks(options, |ignore_matching_lines| {
+                get_no_index_highlights(
+                    repo_root,
+                    left_file,
+                    right_file,
+                    options,
+                    ignore_matching_lines,
+                )
+                .unwrap_or_else(create_empty_line_highlights)
+            })
+        }
+        (None, None) => (create_empty_line_highlights(), None),
+    };
+
+    let mut view = assemble_file_view(
+        descriptor,
+        options,
+        left.lines,
+        right.lines,
+        line_highlights,
+        hunks,
+        if minified {
+            Minified::AsIs
+        } else {
+            Minified::No
+        },
+        true,
+    );
+    view.left_missing_final_newline = left.missing_final_newline;
+    view.right_missing_final_newline = right.missing_final_newline;
+    view
+}
+
+/// Builds the view of a file known only from a patch, with `left_lines` and
+/// `right_lines` as far as its hunks reach and `highlights` marking the changed lines.
+/// Missing and binary sides get the usual placeholders.
+pub fn build_file_view_from_patch(
+    descriptor: &DiffFileDescriptor,
+    options: &DiffOptions,
+    left_lines: Vec<String>,
+    right_lines: Vec<String>,
+    highlights: FileLineHighlights,
+    binary: bool,
+) -> DiffFileView {
+    let side = |lines: Vec<String>, source: FileContentSource, missing: &str| {
+        if source == FileContentSource::Missing {
+            vec![missing.to_string()]
+        } else if binary {
+            vec![BINARY_PLACEHOLDER.to_string()]
+        } else if lines.is_empty() {
+            vec![String::new()]
+        } else {
+            lines
+        }
+    };
+    let left_lines = side(left_lines, descriptor.base_source, MISSING_LEFT);
+    let right_lines = side(right_lines, descriptor.head_source, MISSING_RIGHT);
+    let highlights = get_missing_side_highlights(descriptor, left_lines.len(), right_lines.len())
+        .unwrap_or(highlights);
+    assemble_file_view(
+        descriptor,
+        options,
+        left_lines,
+        right_lines,
+        highlights,
+        None,
+        Minified::No,
+        true,
+    )
+}
+
+/// Added lines ending in whitespace, like `git diff`'s `blank-at-eol` errors.
+fn get_trailing_whitespace_line_indexes(
+    right_lines: &[String],
+    line_highlights: &FileLineHighlights,
+) -> HashSet<usize> {
+    line_highlights
+        .right_added_line_indexes
+        .iter()
+        .copied()
+        .filter(|index| {
+            right_lines
+                .get(*index)
+                .is_some_and(|line| line.trim_end_matches('\r').ends_with(char::is_whitespace))
+        })
+        .collect()
+}
+
+fn get_syntax_highlighting(
+    options: &DiffOptions,
+    left_lines: &[String],
+    right_lines: &[String],
+) -> SyntaxHighlighting {
+    let content_bytes = |lines: &[String]| lines.iter().map(|line| line.len() + 1).sum::<usize>();
+    if options.no_syntax {
+        SyntaxHighlighting::Off
+    } else if content_bytes(left_lines).max(content_bytes(right_lines)) > MAX_HIGHLIGHTED_BYTES {
+        SyntaxHighlighting::TooLarge
+    } else {
+        SyntaxHighlighting::On
+    }
+}
+
+/// Both sides with filler rows inserted so that unchanged lines face each other.
+struct AlignedSides {
+    left_lines: Vec<String>,
+    right_lines: Vec<String>,
+    left_source_lines: Vec<Option<usize>>,
+    right_source_lines: Vec<Option<usize>>,
+    highlights: FileLineHighlights,
+}
+
+/// Appends one side of a row: line `source_line` of `side_lines`, or filler for `None`.
+fn push_aligned_row(
+    lines: &mut Vec<String>,
+    source_lines: &mut Vec<Option<usize>>,
+    side_lines: &mut [String],
+    source_line: Option<usize>,
+) {
+    lines.push(
+        source_line
+            .map(|index| std::mem::take(&mut side_lines[index]))
+            .unwrap_or_default(),
+    );
+    source_lines.push(source_line);
+}
+
+/// Walks both sides in step: unchanged lines share a row. Within each run of `hunks`
+/// lines the split layout pairs the two sides row by row, the longer run facing filler
+/// rows; the unified layout puts all the base lines first, then the head ones, so no row
+/// holds two changed lines. `highlights` marks the changed lines, which are `hunks`
+/// unless `--ignore-matching-lines` left some hunks unmarked.
+fn align_sides(
+    mut left_lines: Vec<String>,
+    mut right_lines: Vec<String>,
+    highlights: &FileLineHighlights,
+    hunks: &FileLineHighlights,
+    mode: ViewMode,
+) -> AlignedSides {
+    let (left_count, right_count) = (left_lines.len(), right_lines.len());
+    let mut aligned = AlignedSides {
+        left_lines: Vec::with_capacity(left_count.max(right_count)),
+        right_lines: Vec::with_capacity(left_count.max(right_count)),
+        left_source_lines: Vec::with_capacity(left_count.max(right_count)),
+        right_source_lines: Vec::with_capacity(left_count.max(right_count)),
+        highlights: create_empty_line_highlights(),
+    };
+    let run_end = |changed: &HashSet<usize>, start: usize, count: usize| {
+        (start..count)
+            .find(|index| !changed.contains(index))
+            .unwrap_or(count)
+    };
+    let (mut left, mut right) = (0, 0);
+
+    while left < left_count || right < right_count {
+        let deleted_end = run_end(&hunks.left_deleted_line_indexes, left, left_count);
+        let added_end = run_end(&hunks.right_added_line_indexes, right, right_count);
+        let unchanged = deleted_end == left && added_end == right;
+        let (left_rows, right_rows) = if unchanged {
+            (
+                usize::from(left < left_count),
+                usize::from(right < right_count),
+            )
+        } else {
+            (deleted_end - left, added_end - right)
+        };
+        let rows: Vec<(Option<usize>, Option<usize>)> = if unchanged || mode == ViewMode::Split {
+            (0..left_rows.max(right_rows))
+                .map(|offset| {
+                    (
+                        (offset < left_rows).then_some(left + offset),
+                        (offset < right_rows).then_some(right + offset),
+                    )
+                })
+                .collect()
+        } else {
+            (left..left + left_rows)
+                .map(|index| (Some(index), None))
+                .chain((right..right + right_rows).map(|index| (None, Some(index))))
+                .collect()
+        };
+
+        for (left_line, right_line) in rows {
+            let row = aligned.left_lines.len();
+            if left_line.is_some_and(|index| highlights.left_deleted_line_indexes.contains(&index))
+            {
+                aligned.highlights.left_deleted_line_indexes.insert(row);
+            }
+            if right_line.is_some_and(|index| highlights.right_added_line_indexes.contains(&index))
+            {
+                aligned.highlights.right_added_line_indexes.insert(row);
+            }
+            push_aligned_row(
+                &mut aligned.left_lines,
+                &mut aligned.left_source_lines,
+                &mut left_lines,
+                left_line,
+            );
+            push_aligned_row(
+                &mut aligned.right_lines,
+                &mut aligned.right_source_lines,
+                &mut right_lines,
+                right_line,
+            );
+        }
+        left += left_rows;
+        right += right_rows;
+    }
+
+    aligned
+}
+
+/// `hunks` are git's hunks before `--ignore-matching-lines` drops some of them; the
+/// sides are aligned along them, since a dropped hunk still shifts the lines after it.
+#[allow(clippy::too_many_arguments)]
+fn assemble_file_view(
+    descriptor: &DiffFileDescriptor,
+    options: &DiffOptions,
+    left_lines: Vec<String>,
+    right_lines: Vec<String>,
+    line_highlights: FileLineHighlights,
+    hunks: Option<FileLineHighlights>,
+    minified: Minified,
+    align: bool,
+) -> DiffFileView {
+    let (left_language, right_language) = profile::time(Phase::SyntaxDetection, || {
+        (
+            detect_syntax_name(descriptor.base_path.as_deref(), &left_lines),
+            detect_syntax_name(descriptor.head_path.as_deref(), &right_lines),
+        )
+    });
+
+    let syntax_highlighting = get_syntax_highlighting(options, &left_lines, &right_lines);
+    let whitespace_only = descriptor.base_source != FileContentSource::Missing
+        && descriptor.head_source != FileContentSource::Missing
+        && is_whitespace_only_change(&left_lines, &right_lines);
+
+    let review_key = compute_review_key(
+        descriptor,
+        &left_lines,
+        &line_highlights.left_deleted_line_indexes,
+        &right_lines,
+        &line_highlights.right_added_line_indexes,
+    );
+    // A missing side's placeholder has no lines to line up with.
+    let aligned = if align
+        && descriptor.base_source != FileContentSource::Missing
+        && descriptor.head_source != FileContentSource::Missing
+    {
+        align_sides(
+            left_lines,
+            right_lines,
+            &line_highlights,
+            hunks.as_ref().unwrap_or(&line_highlights),
+            ViewMode::Split,
+        )
+    } else {
+        AlignedSides {
+            left_lines,
+            right_lines,
+            left_source_lines: Vec::new(),
+            right_source_lines: Vec::new(),
+            highlights: line_highlights,
+        }
+    };
+
+    let right_trailing_whitespace_line_indexes = if options.flag_trailing_whitespace {
+        get_trailing_whitespace_line_indexes(&aligned.right_lines, &aligned.highlights)
+    } else {
+        HashSet::new()
+    };
+
+    DiffFileView {
+        descriptor: descriptor.clone(),
+        review_key,
+        left_language,
+        right_language,
+        left_deleted_line_indexes: aligned.highlights.left_deleted_line_indexes,
+        right_added_line_indexes: aligned.highlights.right_added_line_indexes,
+        left_max_content_length: get_max_normalized_line_length(&aligned.left_lines),
+        right_max_content_length: get_max_normalized_line_length(&aligned.right_lines),
+        left_lines: aligned.left_lines,
+        right_lines: aligned.right_lines,
+        left_source_lines: aligned.left_source_lines,
+        right_source_lines: aligned.right_source_lines,
+        owners: Vec::new(),
+        right_uncovered_line_indexes: HashSet::new(),
+        right_annotations: Vec::new(),
+        right_trailing_whitespace_line_indexes,
+        left_missing_final_newline: false,
+        right_missing_final_newline: false,
+        whitespace_only,
+        minified,
+        syntax_highlighting,
+        collapsed: false,
+    }
+}
+
+/// Lays the rows of an aligned view out again for `mode`, keeping the marks on each file
+/// line. Views that are not aligned (a missing side, structural, table, and hex dump
+/// views) are left as they are.
+pub fn relayout_file_view(file: &mut DiffFileView, mode: ViewMode) {
+    if file.left_source_lines.is_empty() {
+        return;
+    }
+    // A line was in a hunk when it is marked, or faces filler or a different line; the
+    // unmarked ones are hunks `--ignore-matching-lines` dropped.
+    let hunk_lines = |side: PaneSide| -> HashSet<usize> {
+        let (source_lines, other_source_lines, changed) = match side {
+            PaneSide::Left => (
+                &file.left_source_lines,
+                &file.right_source_lines,
+                &file.left_deleted_line_indexes,
+            ),
+            PaneSide::Right => (
+                &file.right_source_lines,
+                &file.left_source_lines,
+                &file.right_added_line_indexes,
+            ),
+        };
+        (0..source_lines.len())
+            .filter(|row| {
+                changed.contains(row)
+                    || other_source_lines[*row].is_none()
+                    || file.left_lines[*row] != file.right_lines[*row]
+            })
+            .filter_map(|row| source_lines[row])
+            .collect()
+    };
+    let hunks = FileLineHighlights {
+        left_deleted_line_indexes: hunk_lines(PaneSide::Left),
+        right_added_line_indexes: hunk_lines(PaneSide::Right),
+    };
+    let source_side =
+        |lines: &mut Vec<String>, source_lines: &[Option<usize>], changed: &HashSet<usize>| {
+            let mut file_lines = Vec::with_capacity(lines.len());
+            let mut file_changed = HashSet::new();
+            for (row, source_line) in source_lines.iter().enumerate() {
+                if source_line.is_some() {
+                    if changed.contains(&row) {
+                        file_changed.insert(file_lines.len());
+                    }
+                    file_lines.push(std::mem::take(&mut lines[row]));
+                }
+            }
+            (file_lines, file_changed)
+        };
+    let (left_lines, left_deleted_line_indexes) = source_side(
+        &mut file.left_lines,
+        &file.left_source_lines,
+        &file.left_deleted_line_indexes,
+    );
+    let (right_lines, right_added_line_indexes) = source_side(
+        &mut file.right_lines,
+        &file.right_source_lines,
+        &file.right_added_line_indexes,
+    );
+    // Head-side row marks move with their line.
+    let right_file_lines = |file: &DiffFileView, rows: &HashSet<usize>| -> Vec<usize> {
+        rows.iter()
+            .filter_map(|row| file.source_line(PaneSide::Right, *row))
+            .collect()
+    };
+    let uncovered = right_file_lines(file, &file.right_uncovered_line_indexes);
+    let trailing_whitespace = right_file_lines(file, &file.right_trailing_whitespace_line_indexes);
+
+    let highlights = FileLineHighlights {
+        left_deleted_line_indexes,
+        right_added_line_indexes,
+    };
+    let aligned = align_sides(left_lines, right_lines, &highlights, &hunks, mode);
+    file.left_lines = aligned.left_lines;
+    file.right_lines = aligned.right_lines;
+    file.left_source_lines = aligned.left_source_lines;
+    file.right_source_lines = aligned.right_source_lines;
+    file.left_deleted_line_indexes = aligned.highlights.left_deleted_line_indexes;
+    file.right_added_line_indexes = aligned.highlights.right_added_line_indexes;
+    let to_rows = |file: &DiffFileView, lines: Vec<usize>| -> HashSet<usize> {
+        lines
+            .into_iter()
+            .map(|line| file.row_of_source_line(PaneSide::Right, line))
+            .collect()
+    };
+    file.right_uncovered_line_indexes = to_rows(file, uncovered);
+    file.right_trailing_whitespace_line_indexes = to_rows(file, trailing_whitespace);
+}
+
+/// Builds views in order, stopping early and returning `None` once `progress` is cancelled.
+pub fn build_file_views(
+    repo_root: &Path,
+    comparison: &ResolvedComparison,
+    descriptors: &[DiffFileDescriptor],
+    options: &DiffOptions,
+    progress: &LoadProgress,
+) -> Option<Vec<DiffFileView>> {
+    // Build the syntax set up front so its cost is not attributed to the first file.
+    syntax_set();
+    let mut views = Vec::with_capacity(descriptors.len());
+
+    for descriptor in descriptors {
+        if progress.is_cancelled() {
+            return None;
+        }
+        views.push(build_file_view(repo_root, comparison, descriptor, options));
+        progress.advance();
+    }
+
+    Some(views)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::model::{
+        DiffFileDescriptor, DiffOptions, FileContentSource, Minified, SyntaxHighlighting, ViewMode,
+    };
+
+    use super::{
+        MAX_HIGHLIGHTED_BYTES, align_sides, assemble_file_view, detect_syntax_name,
+        get_syntax_highlighting, group_by_top_level_directory, is_whitespace_only_change,
+        parse_diff_raw_output, parse_line_highlights_from_patch, relayout_file_view,
+        split_into_lines,
+    };
+
+    #[test]
+    fn relayout_stacks_changed_runs_for_the_unified_view_and_back() {
+        let descriptor = DiffFileDescriptor {
+            raw_status: "M".to_string(),
+            display_path: "t.txt".to_string(),
+            base_path: Some("t.txt".to_string()),
+            head_path: Some("t.txt".to_string()),
+            base_source: FileContentSource::Commit,
+            head_source: FileContentSource::Commit,
+            base_mode: None,
+            head_mode: None,
+        };
+        let split = assemble_file_view(
+            &descriptor,
+            &DiffOptions::default(),
+            split_into_lines("a\nb\nc\n"),
+            split_into_lines("a\nB\nX\nc\n"),
+            parse_line_highlights_from_patch("@@ -2 +2,2 @@"),
+            None,
+            Minified::No,
+            true,
+        );
+        assert!(!split.has_unified_rows());
+
+        let mut view = split.clone();
+        relayout_file_view(&mut view, ViewMode::Unified);
+        assert!(view.has_unified_rows());
+        assert_eq!(view.left_lines, ["a", "b", "", "", "c"]);
+        assert_eq!(view.right_lines, ["a", "", "B", "X", "c"]);
+        assert_eq!(view.left_deleted_line_indexes, HashSet::from([1]));
+        assert_eq!(view.right_added_line_indexes, HashSet::from([2, 3]));
+
+        relayout_file_view(&mut view, ViewMode::Split);
+        assert_eq!(view.left_lines, split.left_lines);
+        assert_eq!(view.right_source_lines, split.right_source_lines);
+        assert_eq!(
+            view.left_deleted_line_indexes,
+            split.left_deleted_line_indexes
+        );
+    }
+
+    #[test]
+    fn hunks_dropped_by_ignore_matching_lines_keep_the_sides_aligned() {
+        let descriptor = DiffFileDescriptor {
+            raw_status: "M".to_string(),
+            display_path: "t.txt".to_string(),
+            base_path: Some("t.txt".to_string()),
+            head_path: Some("t.txt".to_string()),
+            base_source: FileContentSource::Commit,
+            head_source: FileContentSource::Commit,
+            base_mode: None,
+            head_mode: None,
+        };
+        let options = DiffOptions {
+            ignore_matching_lines: Some("^v".to_string()),
+            ..DiffOptions::default()
+        };
+        // The first hunk only touches `v` lines, so `-I^v` leaves it out of the marks.
+        let split = assemble_file_view(
+            &descriptor,
+            &options,
+            split_into_lines("a\nv1\nv1b\nc\nd\ne\n"),
+            split_into_lines("a\nv2\nc\nD\ne\n"),
+            parse_line_highlights_from_patch("@@ -5 +4 @@"),
+            Some(parse_line_highlights_from_patch(
+                "@@ -2,2 +2 @@\n@@ -5 +4 @@",
+            )),
+            Minified::No,
+            true,
+        );
+        assert_eq!(split.left_lines, ["a", "v1", "v1b", "c", "d", "e"]);
+        assert_eq!(split.right_lines, ["a", "v2", "", "c", "D", "e"]);
+        assert_eq!(split.left_deleted_line_indexes, HashSet::from([4]));
+        assert_eq!(split.right_added_line_indexes, HashSet::from([4]));
+
+        let mut view = split.clone();
+        relayout_file_view(&mut view, ViewMode::Unified);
+        assert_eq!(view.left_lines, ["a", "v1", "v1b", "", "c", "d", "", "e"]);
+        assert_eq!(view.right_lines, ["a", "", "", "v2", "c", "", "D", "e"]);
+        assert_eq!(view.left_deleted_line_indexes, HashSet::from([5]));
+        assert_eq!(view.right_added_line_indexes, HashSet::from([6]));
+
+        relayout_file_view(&mut view, ViewMode::Split);
+        assert_eq!(view.left_lines, split.left_lines);
+        assert_eq!(view.right_lines, split.right_lines);
+    }
+
+    #[test]
+    fn align_sides_pairs_changes_and_pads_the_shorter_run_with_filler() {
+        let highlights = parse_line_highlights_from_patch("@@ -2 +2,3 @@\n@@ -5 +6,0 @@");
+        let left = split_into_lines("a\nb\nc\nd\ne\n");
+        let right = split_into_lines("a\nB\nX\nY\nc\nd\n");
+
+        let aligned = align_sides(left, right, &highlights, &highlights, ViewMode::Split);
+
+        assert_eq!(aligned.left_lines, ["a", "b", "", "", "c", "d", "e"]);
+        assert_eq!(aligned.right_lines, ["a", "B", "X", "Y", "c", "d", ""]);
+        assert_eq!(
+            aligned.left_source_lines,
+            [Some(0), Some(1), None, None, Some(2), Some(3), Some(4)]
+        );
+        assert_eq!(aligned.right_source_lines[6], None);
+        assert_eq!(
+            aligned.highlights.left_deleted_line_indexes,
+            [1, 6].into_iter().collect()
+        );
+        assert_eq!(
+            aligned.highlights.right_added_line_indexes,
+            [1, 2, 3].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn group_by_top_level_directory_counts_root_files_under_dot() {
+        let raw = b":100644 100644 1 2 M\0src/a.rs\0:100644 100644 3 4 M\0src/b/c.rs\0\
+                    :000000 100644 0 5 A\0README.md\0:100644 000000 6 0 D\0docs/x.md\0";
+        let descriptors =
+            parse_diff_raw_output(raw, FileContentSource::Commit, FileContentSource::Commit);
+
+        let groups = group_by_top_level_directory(&descriptors);
+
+        assert_eq!(
+            groups,
+            vec![
+                ("src".to_string(), 2),
+                (".".to_string(), 1),
+                ("docs".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_raw_rename_entry() {
+        let raw = b":100644 100755 1 1 R100\0old.txt\0new.txt\0";
+        let descriptors =
+            parse_diff_raw_output(raw, FileContentSource::Commit, FileContentSource::Commit);
+
+        assert_eq!(descriptors.len(), 1);
+        assert_eq!(descriptors[0].raw_status, "R100");
+        assert_eq!(descriptors[0].display_path, "old.txt -> new.txt");
+        assert_eq!(descriptors[0].base_mode.as_deref(), Some("100644"));
+        assert_eq!(descriptors[0].head_mode.as_deref(), Some("100755"));
+    }
+
+    #[test]
+    fn parse_line_highlights_tracks_deleted_and_added_ranges() {
+        let patch = "@@ -2,2 +5,3 @@";
+        let highlights = parse_line_highlights_from_patch(patch);
+
+        assert!(highlights.left_deleted_line_indexes.contains(&1));
+        assert!(highlights.left_deleted_line_indexes.contains(&2));
+        assert!(highlights.right_added_line_indexes.contains(&4));
+        assert!(highlights.right_added_line_indexes.contains(&6));
+    }
+
+    #[test]
+    fn split_into_lines_trims_trailing_newline() {
+        let lines = split_into_lines("a\nb\n");
+        assert_eq!(lines, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[test]
+    fn detect_syntax_uses_filename_token_when_no_extension() {
+        let lines = vec!["echo hello".to_string()];
+        let detected = detect_syntax_name(Some("bash"), &lines);
+        assert_eq!(detected.as_deref(), Some("Bourne Again Shell (bash)"));
+    }
+
+    #[test]
+    fn detect_syntax_uses_extension_when_available() {
+        let lines = vec!["fn main() {}".to_string()];
+        let detected = detect_syntax_name(Some("src/main.rs"), &lines);
+        assert_eq!(detected.as_deref(), Some("Rust"));
+    }
+
+    #[test]
+    fn detect_syntax_uses_shebang_for_extensionless_files() {
+        let lines = vec!["#!/usr/bin/env bash".to_string(), "echo hello".to_string()];
+        let detected = detect_syntax_name(Some("scripts/release"), &lines);
+        assert_eq!(detected.as_deref(), Some("Bourne Again Shell (bash)"));
+    }
+
+    #[test]
+    fn detect_syntax_uses_bundled_tsx_grammar() {
+        let lines = vec!["export const App = () => <main>Hello</main>;".to_string()];
+        let detected = detect_syntax_name(Some("src/App.tsx"), &lines);
+        assert_eq!(detected.as_deref(), Some("TSX (deff)"));
+    }
+
+    #[test]
+    fn detect_syntax_uses_bundled_jsx_grammar() {
+        let lines = vec!["export default () => <main>Hello</main>;".to_string()];
+        let detected = detect_syntax_name(Some("src/App.jsx"), &lines);
+        assert_eq!(detected.as_deref(), Some("JSX (deff)"));
+    }
+
+    #[test]
+    fn detect_syntax_uses_bundled_typescript_grammar() {
+        let lines = vec!["const answer: number = 42;".to_string()];
+        let detected = detect_syntax_name(Some("src/types.ts"), &lines);
+        assert_eq!(detected.as_deref(), Some("TypeScript (deff)"));
+    }
+
+    #[test]
+    fn detect_syntax_uses_bundled_handlebars_grammar() {
+        let lines = vec!["<h1>{{title}}</h1>".to_string()];
+        let detected = detect_syntax_name(Some("templates/view.hbs"), &lines);
+        assert_eq!(detected.as_deref(), Some("Handlebars (deff)"));
+    }
+
+    #[test]
+    fn detect_syntax_uses_bundled_dotenv_grammar_for_dotenv_file() {
+        let lines = vec!["API_KEY=secret".to_string()];
+        let detected = detect_syntax_name(Some(".env"), &lines);
+        assert_eq!(detected.as_deref(), Some("Dotenv (deff)"));
+    }
+
+    #[test]
+    fn detect_syntax_uses_bundled_dotenv_grammar_for_dotenv_variant_file() {
+        let lines = vec!["NEXT_PUBLIC_URL=https://example.com".to_string()];
+        let detected = detect_syntax_name(Some("config/.env.example"), &lines);
+        assert_eq!(detected.as_deref(), Some("Dotenv (deff)"));
+    }
+
+    #[test]
+    fn detect_syntax_uses_bundled_dotenv_grammar_for_any_dotenv_suffix() {
+        let lines = vec!["NEXT_PUBLIC_URL=https://example.com".to_string()];
+        let detected = detect_syntax_name(Some("config/.env.production.local"), &lines);
+        assert_eq!(detected.as_deref(), Some("Dotenv (deff)"));
+    }
+
+    #[test]
+    fn detect_syntax_uses_bundled_kotlin_grammar() {
+        let lines = vec!["fun main() = println(\"Hello\")".to_string()];
+        let detected = detect_syntax_name(Some("src/main.kt"), &lines);
+        assert_eq!(detected.as_deref(), Some("Kotlin (deff)"));
+    }
+
+    #[test]
+    fn detect_syntax_returns_none_for_unknown_content() {
+        let lines = vec!["this should not match a known first-line rule".to_string()];
+        let detected = detect_syntax_name(Some("notes.customext"), &lines);
+        assert_eq!(detected, None);
+    }
+
+    #[test]
+    fn whitespace_only_change_ignores_indentation_and_blank_lines() {
+        let lines = |values: &[&str]| -> Vec<String> {
+            values.iter().map(|value| value.to_string()).collect()
+        };
+
+        assert!(is_whitespace_only_change(
+            &lines(&["fn a() {", "  x();", "}"]),
+            &lines(&["fn a() {", "", "    x( );", "}"]),
+        ));
+        assert!(!is_whitespace_only_change(
+            &lines(&["x();"]),
+            &lines(&["y();"]),
+        ));
+        assert!(!is_whitespace_only_change(&lines(&["x"]), &lines(&["x"])));
+    }
+
+    #[test]
+    fn syntax_highlighting_is_skipped_for_large_files_and_no_syntax() {
+        let small = vec!["fn main() {}".to_string()];
+        let large = vec!["x".repeat(MAX_HIGHLIGHTED_BYTES)];
+        let options = DiffOptions::default();
+
+        assert_eq!(
+            get_syntax_highlighting(&options, &small, &small),
+            SyntaxHighlighting::On
+        );
+        assert_eq!(
+            get_syntax_highlighting(&options, &small, &large),
+            SyntaxHighlighting::TooLarge
+        );
+        let no_syntax = DiffOptions {
+            no_syntax: true,
+            ..DiffOptions::default()
+        };
+        assert_eq!(
+            get_syntax_highlighting(&no_syntax, &small, &small),
+            SyntaxHighlighting::Off
+        );
+    }
+}
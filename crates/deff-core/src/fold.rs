@@ -0,0 +1,449 @@
+use crate::{model::DiffFileView, text::leading_indent_width};
+
+/// An inclusive range of lines collapsed into a single summary row.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Fold {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Fold {
+    pub fn contains(&self, line_index: usize) -> bool {
+        line_index >= self.start && line_index <= self.end
+    }
+
+    pub fn hidden_line_count(&self) -> usize {
+        self.end - self.start + 1
+    }
+}
+
+/// One visual row of the body: either a plain line or a collapsed fold.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DisplayRow {
+    Line(usize),
+    Fold(Fold),
+}
+
+impl DisplayRow {
+    pub fn first_line(&self) -> usize {
+        match self {
+            DisplayRow::Line(line_index) => *line_index,
+            DisplayRow::Fold(fold) => fold.start,
+        }
+    }
+
+    pub fn last_line(&self) -> usize {
+        match self {
+            DisplayRow::Line(line_index) => *line_index,
+            DisplayRow::Fold(fold) => fold.end,
+        }
+    }
+}
+
+/// Maps file lines to display rows; `folds` must be sorted and non-overlapping.
+pub fn build_display_rows(line_count: usize, folds: &[Fold]) -> Vec<DisplayRow> {
+    let mut rows = Vec::with_capacity(line_count);
+    let mut folds = folds.iter().peekable();
+    let mut line_index = 0;
+
+    while line_index < line_count {
+        if let Some(fold) = folds.next_if(|fold| fold.start == line_index) {
+            rows.push(DisplayRow::Fold(*fold));
+            line_index = fold.end + 1;
+            continue;
+        }
+
+        rows.push(DisplayRow::Line(line_index));
+        line_index += 1;
+    }
+
+    rows
+}
+
+pub fn display_row_for_line(rows: &[DisplayRow], line_index: usize) -> usize {
+    rows.partition_point(|row| row.last_line() < line_index)
+        .min(rows.len().saturating_sub(1))
+}
+
+fn next_non_blank_line(lines: &[String], after: usize) -> Option<usize> {
+    (after + 1..lines.len()).find(|index| !lines[*index].trim().is_empty())
+}
+
+fn block_end(lines: &[String], header: usize) -> Option<usize> {
+    let header_indent = leading_indent_width(&lines[header]);
+    let mut end = None;
+
+    for (index, line) in lines.iter().enumerate().skip(header + 1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if leading_indent_width(line) <= header_indent {
+            break;
+        }
+        end = Some(index);
+    }
+
+    end
+}
+
+fn is_block_header(lines: &[String], line_index: usize) -> bool {
+    if lines[line_index].trim().is_empty() {
+        return false;
+    }
+
+    next_non_blank_line(lines, line_index).is_some_and(|next| {
+        leading_indent_width(&lines[next]) > leading_indent_width(&lines[line_index])
+    })
+}
+
+/// Finds the indentation block that `line_index` opens, or else the innermost block
+/// enclosing it.
+pub fn find_indent_block(lines: &[String], line_index: usize) -> Option<Fold> {
+    if line_index >= lines.len() {
+        return None;
+    }
+
+    if is_block_header(lines, line_index) {
+        return block_end(lines, line_index).map(|end| Fold {
+            start: line_index,
+            end,
+        });
+    }
+
+    let reference_line = if lines[line_index].trim().is_empty() {
+        next_non_blank_line(lines, line_index)?
+    } else {
+        line_index
+    };
+    let reference_indent = leading_indent_width(&lines[reference_line]);
+    let header = (0..line_index).rev().find(|index| {
+        !lines[*index].trim().is_empty() && leading_indent_width(&lines[*index]) < reference_indent
+    })?;
+
+    block_end(lines, header).map(|end| Fold { start: header, end })
+}
+
+/// Finds the outermost indentation blocks that contain no changed lines.
+pub fn find_unchanged_blocks(file: &DiffFileView) -> Vec<Fold> {
+    let lines = file.primary_lines();
+    let mut folds = Vec::new();
+    let mut line_index = 0;
+
+    while line_index < lines.len() {
+        if is_block_header(lines, line_index)
+            && let Some(end) = block_end(lines, line_index)
+            && !(line_index..=end).any(|index| file.is_line_changed(index))
+        {
+            folds.push(Fold {
+                start: line_index,
+                end,
+            });
+            line_index = end + 1;
+            continue;
+        }
+
+        line_index += 1;
+    }
+
+    folds
+}
+
+/// Folds for a changes-only view: every unchanged stretch of a file with more than
+/// `context` lines between it and the nearest change. Single lines stay visible, since
+/// a fold row would take their place anyway.
+pub fn find_context_folds(
+    line_count: usize,
+    is_changed: impl Fn(usize) -> bool,
+    context: usize,
+) -> Vec<Fold> {
+    let mut folds = Vec::new();
+    let mut line_index = 0;
+
+    while line_index < line_count {
+        if is_changed(line_index) {
+            line_index += 1;
+            continue;
+        }
+        let run_start = line_index;
+        while line_index < line_count && !is_changed(line_index) {
+            line_index += 1;
+        }
+        let start = if run_start == 0 {
+            0
+        } else {
+            run_start + context
+        };
+        let end = if line_index == line_count {
+            line_count - 1
+        } else {
+            (line_index - 1).saturating_sub(context)
+        };
+        if start < end {
+            folds.push(Fold { start, end });
+        }
+    }
+
+    folds
+}
+
+/// Adds `fold`, absorbing any folds nested inside it; ignored when already hidden.
+pub fn insert_fold(folds: &mut Vec<Fold>, fold: Fold) {
+    if folds
+        .iter()
+        .any(|existing| existing.start <= fold.start && existing.end >= fold.end)
+    {
+        return;
+    }
+
+    folds.retain(|existing| !(existing.start >= fold.start && existing.end <= fold.end));
+    let position = folds.partition_point(|existing| existing.start < fold.start);
+    folds.insert(position, fold);
+}
+
+fn fold_index_containing(folds: &[Fold], line_index: usize) -> Option<usize> {
+    folds.iter().position(|fold| fold.contains(line_index))
+}
+
+/// Reveals (`expand`) or hides up to `step` more lines of the unchanged context above
+/// and below the hunk `first..=last`, by shrinking or growing the folds next to that
+/// context. Returns whether any fold changed.
+pub fn step_hunk_context(
+    folds: &mut Vec<Fold>,
+    line_count: usize,
+    (first, last): (usize, usize),
+    is_changed: impl Fn(usize) -> bool,
+    step: usize,
+    expand: bool,
+) -> bool {
+    let is_open_context = |folds: &[Fold], index: usize| {
+        !is_changed(index) && fold_index_containing(folds, index).is_none()
+    };
+    let before = folds.clone();
+
+    let mut context_start = first;
+    while context_start > 0 && is_open_context(folds, context_start - 1) {
+        context_start -= 1;
+    }
+    let above = context_start
+        .checked_sub(1)
+        .and_then(|index| fold_index_containing(folds, index));
+    match (above, expand) {
+        (Some(index), true) => {
+            let fold = &mut folds[index];
+            if fold.hidden_line_count() <= step {
+                folds.remove(index);
+            } else {
+                fold.end -= step;
+            }
+        }
+        (above, false) => {
+            let hidden = step.min(first - context_start);
+            match above {
+                Some(index) => folds[index].end += hidden,
+                None if hidden > 0 => insert_fold(
+                    folds,
+                    Fold {
+                        start: context_start,
+                        end: context_start + hidden - 1,
+                    },
+                ),
+                None => {}
+            }
+        }
+        (None, true) => {}
+    }
+
+    let mut context_end = last;
+    while context_end + 1 < line_count && is_open_context(folds, context_end + 1) {
+        context_end += 1;
+    }
+    let below = fold_index_containing(folds, context_end + 1);
+    match (below, expand) {
+        (Some(index), true) => {
+            let fold = &mut folds[index];
+            if fold.hidden_line_count() <= step {
+                folds.remove(index);
+            } else {
+                fold.start += step;
+            }
+        }
+        (below, false) => {
+            let hidden = step.min(context_end - last);
+            match below {
+                Some(index) => folds[index].start -= hidden,
+                None if hidden > 0 => insert_fold(
+                    folds,
+                    Fold {
+                        start: context_end + 1 - hidden,
+                        end: context_end,
+                    },
+                ),
+                None => {}
+            }
+        }
+        (None, true) => {}
+    }
+
+    *folds != before
+}
+
+pub fn remove_fold_containing(folds: &mut Vec<Fold>, line_index: usize) -> bool {
+    let previous_len = folds.len();
+    folds.retain(|fold| !fold.contains(line_index));
+    folds.len() != previous_len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DisplayRow, Fold, build_display_rows, display_row_for_line, find_context_folds,
+        find_indent_block, insert_fold, step_hunk_context,
+    };
+
+    fn to_lines(raw: &[&str]) -> Vec<String> {
+        raw.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn indent_block_from_header_covers_deeper_lines() {
+        let lines = to_lines(&["fn a() {", "    one", "", "    two", "}", "fn b() {}"]);
+        assert_eq!(
+            find_indent_block(&lines, 0),
+            Some(Fold { start: 0, end: 3 })
+        );
+    }
+
+    #[test]
+    fn indent_block_from_body_line_uses_enclosing_header() {
+        let lines = to_lines(&["impl A {", "    fn a() {", "        x", "    }", "}"]);
+        assert_eq!(
+            find_indent_block(&lines, 2),
+            Some(Fold { start: 1, end: 2 })
+        );
+        assert_eq!(
+            find_indent_block(&lines, 3),
+            Some(Fold { start: 0, end: 3 })
+        );
+    }
+
+    #[test]
+    fn display_rows_collapse_folded_ranges() {
+        let rows = build_display_rows(5, &[Fold { start: 1, end: 3 }]);
+        assert_eq!(
+            rows,
+            vec![
+                DisplayRow::Line(0),
+                DisplayRow::Fold(Fold { start: 1, end: 3 }),
+                DisplayRow::Line(4),
+            ]
+        );
+        assert_eq!(display_row_for_line(&rows, 2), 1);
+        assert_eq!(display_row_for_line(&rows, 4), 2);
+    }
+
+    #[test]
+    fn hunk_context_steps_shrink_and_grow_the_neighbouring_folds() {
+        let is_changed = |index: usize| index == 20;
+        let mut folds = vec![Fold { start: 0, end: 17 }, Fold { start: 23, end: 39 }];
+
+        assert!(step_hunk_context(
+            &mut folds,
+            40,
+            (20, 20),
+            is_changed,
+            5,
+            true
+        ));
+        assert_eq!(
+            folds,
+            vec![Fold { start: 0, end: 12 }, Fold { start: 28, end: 39 }]
+        );
+
+        assert!(step_hunk_context(
+            &mut folds,
+            40,
+            (20, 20),
+            is_changed,
+            5,
+            false
+        ));
+        assert!(step_hunk_context(
+            &mut folds,
+            40,
+            (20, 20),
+            is_changed,
+            5,
+            false
+        ));
+        assert_eq!(
+            folds,
+            vec![Fold { start: 0, end: 19 }, Fold { start: 21, end: 39 }]
+        );
+        assert!(!step_hunk_context(
+            &mut folds,
+            40,
+            (20, 20),
+            is_changed,
+            5,
+            false
+        ));
+
+        let mut folds = Vec::new();
+        assert!(step_hunk_context(
+            &mut folds,
+            40,
+            (20, 20),
+            is_changed,
+            5,
+            false
+        ));
+        assert_eq!(
+            folds,
+            vec![Fold { start: 0, end: 4 }, Fold { start: 35, end: 39 }]
+        );
+        assert!(step_hunk_context(
+            &mut folds,
+            40,
+            (20, 20),
+            is_changed,
+            30,
+            true
+        ));
+        assert!(folds.is_empty());
+    }
+
+    #[test]
+    fn insert_fold_absorbs_nested_folds() {
+        let mut folds = vec![Fold { start: 2, end: 3 }, Fold { start: 8, end: 9 }];
+        insert_fold(&mut folds, Fold { start: 1, end: 5 });
+        insert_fold(&mut folds, Fold { start: 2, end: 4 });
+        assert_eq!(
+            folds,
+            vec![Fold { start: 1, end: 5 }, Fold { start: 8, end: 9 }]
+        );
+    }
+
+    #[test]
+    fn context_folds_leave_context_lines_around_each_change() {
+        let changed = [5, 6, 20];
+        let is_changed = |index: usize| changed.contains(&index);
+
+        assert_eq!(
+            find_context_folds(30, is_changed, 2),
+            vec![
+                Fold { start: 0, end: 2 },
+                Fold { start: 9, end: 17 },
+                Fold { start: 23, end: 29 },
+            ]
+        );
+        // Gaps no wider than the context on both sides stay open.
+        assert_eq!(
+            find_context_folds(10, |index| index == 3 || index == 7, 2),
+            Vec::<Fold>::new()
+        );
+        assert_eq!(
+            find_context_folds(4, |_| false, 3),
+            vec![Fold { start: 0, end: 3 }]
+        );
+    }
+}
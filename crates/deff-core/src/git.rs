@@ -0,0 +1,1073 @@
+use std::{
+    collections::HashSet,
+    ffi::{OsStr, OsString},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    time::Instant,
+};
+
+use anyhow::{Context, Result, bail};
+use tempfile::TempDir;
+use tracing::{debug, warn};
+
+use crate::model::{
+    CommitSummary, ComparisonRequest, LoadProgress, ResolvedComparison, StrategyId,
+};
+
+/// Keeps git's output UTF-8 whatever the platform and config: paths are not escaped as
+/// octal, and commit messages are re-encoded from their `i18n.commitEncoding`.
+const GIT_OUTPUT_CONFIG: [&str; 4] = [
+    "-c",
+    "core.quotepath=false",
+    "-c",
+    "i18n.logOutputEncoding=UTF-8",
+];
+
+pub fn run_git<I, S>(args: I, cwd: &Path) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    run_git_with(args, cwd, &[], None)
+}
+
+/// Like `run_git`, with extra environment variables (e.g. `GIT_INDEX_FILE`) and bytes
+/// to feed on stdin.
+pub fn run_git_with<I, S>(
+    args: I,
+    cwd: &Path,
+    envs: &[(&str, &OsStr)],
+    stdin: Option<&[u8]>,
+) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let (command, output, duration_ms) = spawn_git(args, cwd, envs, stdin)?;
+
+    let stderr_text = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if !output.status.success() {
+        warn!(%command, duration_ms, status = %output.status, stderr = %stderr_text, "git command failed");
+
+        let details = if stderr_text.is_empty() {
+            format!("exit status {}", output.status)
+        } else {
+            stderr_text
+        };
+
+        bail!("{command} failed: {details}");
+    }
+
+    debug!(%command, duration_ms, stdout_bytes = output.stdout.len(), "git command finished");
+
+    Ok(output.stdout)
+}
+
+/// For commands whose exit code carries a result instead of signalling failure, such as
+/// `git merge-file` (the number of conflicts). Only a missing exit code is an error.
+pub fn run_git_with_exit_code<I, S>(args: I, cwd: &Path) -> Result<(i32, Vec<u8>)>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let (command, output, duration_ms) = spawn_git(args, cwd, &[], None)?;
+    debug!(%command, duration_ms, status = %output.status, "git command finished");
+    match output.status.code() {
+        Some(code) => Ok((code, output.stdout)),
+        None => bail!("{command} was terminated by a signal"),
+    }
+}
+
+fn spawn_git<I, S>(
+    args: I,
+    cwd: &Path,
+    envs: &[(&str, &OsStr)],
+    stdin: Option<&[u8]>,
+) -> Result<(String, Output, f64)>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let args_vec: Vec<OsString> = args
+        .into_iter()
+        .map(|arg| arg.as_ref().to_os_string())
+        .collect();
+
+    let command = format!(
+        "git {}",
+        args_vec
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    let started_at = Instant::now();
+    let mut child = Command::new("git")
+        .args(GIT_OUTPUT_CONFIG)
+        .args(&args_vec)
+        .envs(envs.iter().copied())
+        .current_dir(cwd)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run git in {}", cwd.display()))?;
+    if let (Some(input), Some(mut child_stdin)) = (stdin, child.stdin.take()) {
+        child_stdin
+            .write_all(input)
+            .with_context(|| format!("failed to write stdin of {command}"))?;
+    }
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("failed to run git in {}", cwd.display()))?;
+    let duration_ms = started_at.elapsed().as_secs_f64() * 1000.0;
+    Ok((command, output, duration_ms))
+}
+
+/// Lets git compile a `-G` or `-I` pattern, which it reads as a POSIX extended regex,
+/// by diffing the empty tree with itself; `option` names the flag in the error.
+pub fn check_diff_regex(repo_root: &Path, git_arg: &str, option: &str) -> Result<()> {
+    let (_, output, _) = spawn_git(
+        ["diff", "--quiet", git_arg, EMPTY_TREE_HASH, EMPTY_TREE_HASH],
+        repo_root,
+        &[],
+        None,
+    )?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches("fatal: ")
+            .trim_start_matches("error: ");
+        bail!("{option}: {reason}");
+    }
+    Ok(())
+}
+
+pub fn run_git_text<I, S>(args: I, cwd: &Path) -> Result<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let output = run_git(args, cwd)?;
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+fn parse_usize_value(raw: &str, context: &str) -> Result<usize> {
+    raw.trim()
+        .parse::<usize>()
+        .with_context(|| format!("unable to parse {context}: {}", raw.trim()))
+}
+
+pub fn get_repository_root(cwd: &Path) -> Result<PathBuf> {
+    let output = run_git_text(["rev-parse", "--show-toplevel"], cwd)?;
+    Ok(PathBuf::from(output.trim()))
+}
+
+pub fn get_git_dir(repo_root: &Path) -> Result<PathBuf> {
+    let git_dir = run_git_text(["rev-parse", "--git-dir"], repo_root)?;
+    let parsed = PathBuf::from(git_dir.trim());
+    if parsed.is_absolute() {
+        Ok(parsed)
+    } else {
+        Ok(repo_root.join(parsed))
+    }
+}
+
+/// Branches tried, in order, when `origin/HEAD` is not set.
+const DEFAULT_BRANCH_CANDIDATES: [&str; 4] = ["origin/main", "origin/master", "main", "master"];
+
+/// `origin/HEAD` when the clone recorded it, otherwise the first candidate that exists.
+fn pick_default_branch(
+    origin_head: Option<&str>,
+    branch_exists: impl Fn(&str) -> bool,
+) -> Option<String> {
+    origin_head
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .or_else(|| {
+            DEFAULT_BRANCH_CANDIDATES
+                .into_iter()
+                .find(|candidate| branch_exists(candidate))
+                .map(str::to_string)
+        })
+}
+
+fn detect_default_branch(repo_root: &Path) -> Option<String> {
+    let origin_head = run_git_text(
+        ["symbolic-ref", "--short", "refs/remotes/origin/HEAD"],
+        repo_root,
+    )
+    .ok();
+    pick_default_branch(origin_head.as_deref(), |candidate| {
+        run_git_text(
+            [
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("{candidate}^{{commit}}"),
+            ],
+            repo_root,
+        )
+        .is_ok()
+    })
+}
+
+/// The base upstream-ahead compares against, and how it was found.
+#[derive(Debug, PartialEq, Eq)]
+struct UpstreamBase {
+    reference: String,
+    label: String,
+}
+
+/// The branch's upstream when it has one. Otherwise, unless `strict`: for a detached HEAD
+/// (CI checkouts, bisects), the upstream of the branch checked out before it, then the
+/// default branch.
+fn choose_upstream_base(
+    upstream: Option<String>,
+    detached: bool,
+    strict: bool,
+    previous_branch_upstream: impl FnOnce() -> Option<(String, String)>,
+    default_branch: impl FnOnce() -> Option<String>,
+) -> Result<UpstreamBase> {
+    if let Some(reference) = upstream {
+        return Ok(UpstreamBase {
+            reference,
+            label: "upstream".to_string(),
+        });
+    }
+    if strict {
+        if detached {
+            bail!(
+                "HEAD is detached, so it has no upstream. Use --strategy range --base <git-ref> instead."
+            );
+        }
+        bail!(
+            "No upstream branch configured for the current branch. Use --strategy range --base <git-ref> instead."
+        );
+    }
+    if detached && let Some((branch, reference)) = previous_branch_upstream() {
+        return Ok(UpstreamBase {
+            reference,
+            label: format!("detached HEAD; upstream of previous branch {branch}"),
+        });
+    }
+    match default_branch() {
+        Some(reference) => Ok(UpstreamBase {
+            reference,
+            label: if detached {
+                "detached HEAD; default branch"
+            } else {
+                "no upstream; default branch"
+            }
+            .to_string(),
+        }),
+        None if detached => bail!(
+            "HEAD is detached and no default branch was found. Use --strategy range --base <git-ref> instead."
+        ),
+        None => bail!(
+            "No upstream branch configured for the current branch. Use --strategy range --base <git-ref> instead."
+        ),
+    }
+}
+
+fn is_head_detached(repo_root: &Path) -> bool {
+    run_git_text(["symbolic-ref", "--quiet", "HEAD"], repo_root).is_err()
+}
+
+fn resolve_upstream_base(repo_root: &Path, detached: bool, strict: bool) -> Result<UpstreamBase> {
+    let upstream_of = |branch: &str| {
+        run_git_text(
+            [
+                "rev-parse",
+                "--abbrev-ref",
+                "--symbolic-full-name",
+                &format!("{branch}@{{upstream}}"),
+            ],
+            repo_root,
+        )
+        .ok()
+        .map(|value| value.trim().to_string())
+    };
+    choose_upstream_base(
+        upstream_of(""),
+        detached,
+        strict,
+        || {
+            let branch = run_git_text(["rev-parse", "--abbrev-ref", "@{-1}"], repo_root).ok()?;
+            let branch = branch.trim().to_string();
+            upstream_of("@{-1}").map(|reference| (branch, reference))
+        },
+        || detect_default_branch(repo_root),
+    )
+}
+
+fn resolve_upstream_ahead_comparison(
+    repo_root: &Path,
+    head_ref: &str,
+    strict: bool,
+) -> Result<ResolvedComparison> {
+    let detached = is_head_detached(repo_root);
+    let UpstreamBase {
+        reference: upstream_ref,
+        label: upstream_label,
+    } = resolve_upstream_base(repo_root, detached, strict)?;
+
+    let current_branch = if detached {
+        let head = run_git_text(["rev-parse", "--short", "HEAD"], repo_root)?;
+        format!("(detached at {})", head.trim())
+    } else {
+        run_git_text(["rev-parse", "--abbrev-ref", "HEAD"], repo_root)?
+            .trim()
+            .to_string()
+    };
+    let base_commit = run_git_text(
+        ["rev-parse", &format!("{upstream_ref}^{{commit}}")],
+        repo_root,
+    )?
+    .trim()
+    .to_string();
+    let head_commit = run_git_text(["rev-parse", &format!("{head_ref}^{{commit}}")], repo_root)?
+        .trim()
+        .to_string();
+    let ahead_count_raw = run_git_text(
+        [
+            "rev-list",
+            "--count",
+            &format!("{upstream_ref}..{head_ref}"),
+        ],
+        repo_root,
+    )?;
+    let behind_count_raw = run_git_text(
+        [
+            "rev-list",
+            "--count",
+            &format!("{head_ref}..{upstream_ref}"),
+        ],
+        repo_root,
+    )?;
+
+    let ahead_count = parse_usize_value(&ahead_count_raw, "ahead count")?;
+    let behind_count = parse_usize_value(&behind_count_raw, "behind count")?;
+
+    Ok(ResolvedComparison {
+        strategy_id: StrategyId::UpstreamAhead,
+        base_ref: upstream_ref.clone(),
+        head_ref: head_ref.to_string(),
+        base_commit,
+        head_commit,
+        summary: format!("{upstream_ref}..{head_ref}"),
+        details: vec![
+            format!("branch: {current_branch}"),
+            format!("{upstream_label}: {upstream_ref}"),
+            format!("ahead: {ahead_count}"),
+            format!("behind: {behind_count}"),
+        ],
+        ahead_count: Some(ahead_count),
+        includes_uncommitted: false,
+        range_commits: Vec::new(),
+    })
+}
+
+/// Holds the private indexes used to add a stash's untracked files to its snapshot,
+/// relative to the git dir.
+const STASH_INDEX_DIR: &str = "deff/stash";
+/// The snapshot commit is never referenced, but `commit-tree` still needs an identity.
+const STASH_SNAPSHOT_IDENT: [(&str, &str); 4] = [
+    ("GIT_AUTHOR_NAME", "deff"),
+    ("GIT_AUTHOR_EMAIL", "deff@localhost"),
+    ("GIT_COMMITTER_NAME", "deff"),
+    ("GIT_COMMITTER_EMAIL", "deff@localhost"),
+];
+
+/// Whether a `core.whitespace` value leaves `blank-at-eol` (trailing whitespace) on, as
+/// it is by default; later entries override earlier ones.
+fn flags_blank_at_eol(core_whitespace: &str) -> bool {
+    core_whitespace
+        .split(',')
+        .map(str::trim)
+        .fold(true, |enabled, rule| match rule {
+            "blank-at-eol" | "trailing-space" => true,
+            "-blank-at-eol" | "-trailing-space" => false,
+            _ => enabled,
+        })
+}
+
+/// Whether git's `core.whitespace` treats trailing whitespace as an error.
+pub fn flags_trailing_whitespace(repo_root: &Path) -> bool {
+    run_git_text(["config", "--get", "core.whitespace"], repo_root)
+        .map(|value| flags_blank_at_eol(&value))
+        .unwrap_or(true)
+}
+
+/// `stash`, `stash@{n}`, and `refs/stash` name stash entries rather than commits on a
+/// branch.
+pub fn is_stash_ref(reference: &str) -> bool {
+    reference == "stash"
+        || reference.starts_with("stash@{")
+        || reference == "refs/stash"
+        || reference.starts_with("refs/stash@{")
+}
+
+/// The files a stash entry would restore. The entry's own commit holds the stashed
+/// working tree of tracked files (staged changes included); entries made with
+/// `--include-untracked` keep untracked files in a third parent, which are added on top
+/// in a dangling commit so they show up as well.
+fn resolve_stash_snapshot(repo_root: &Path, reference: &str) -> Result<String> {
+    let stash_commit = run_git_text(["rev-parse", &format!("{reference}^{{commit}}")], repo_root)?
+        .trim()
+        .to_string();
+    let Ok(untracked_commit) = run_git_text(
+        [
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{stash_commit}^3"),
+        ],
+        repo_root,
+    ) else {
+        return Ok(stash_commit);
+    };
+
+    // git needs the index file to be missing or valid, so each call gets a directory of
+    // its own to create it in, removed again when it is dropped.
+    let index_parent = get_git_dir(repo_root)?.join(STASH_INDEX_DIR);
+    std::fs::create_dir_all(&index_parent)
+        .with_context(|| format!("failed to create {}", index_parent.display()))?;
+    let index_dir = TempDir::new_in(&index_parent)
+        .with_context(|| format!("failed to create a directory in {}", index_parent.display()))?;
+    let index_path = index_dir.path().join("index");
+    let index_env = [("GIT_INDEX_FILE", index_path.as_os_str())];
+    run_git_with(["read-tree", &stash_commit], repo_root, &index_env, None)?;
+    let untracked_entries = run_git(["ls-tree", "-r", "-z", untracked_commit.trim()], repo_root)?;
+    run_git_with(
+        ["update-index", "-z", "--index-info"],
+        repo_root,
+        &index_env,
+        Some(&untracked_entries),
+    )?;
+    let tree = run_git_with(["write-tree"], repo_root, &index_env, None)?;
+    let ident_env = STASH_SNAPSHOT_IDENT.map(|(key, value)| (key, OsStr::new(value)));
+    let snapshot = run_git_with(
+        [
+            "commit-tree",
+            String::from_utf8_lossy(&tree).trim(),
+            "-p",
+            &stash_commit,
+            "-m",
+            &format!("{reference} with untracked files"),
+        ],
+        repo_root,
+        &ident_env,
+        None,
+    )?;
+    Ok(String::from_utf8_lossy(&snapshot).trim().to_string())
+}
+
+fn resolve_range_comparison(
+    repo_root: &Path,
+    base_ref: &str,
+    head_ref: &str,
+) -> Result<ResolvedComparison> {
+    if is_stash_ref(base_ref) || is_stash_ref(head_ref) {
+        return resolve_stash_comparison(repo_root, base_ref, head_ref);
+    }
+
+    let base_commit = run_git_text(["rev-parse", &format!("{base_ref}^{{commit}}")], repo_root)?
+        .trim()
+        .to_string();
+    let head_commit = run_git_text(["rev-parse", &format!("{head_ref}^{{commit}}")], repo_root)?
+        .trim()
+        .to_string();
+    let commit_count_raw = run_git_text(
+        ["rev-list", "--count", &format!("{base_ref}..{head_ref}")],
+        repo_root,
+    )?;
+    let commit_count = parse_usize_value(&commit_count_raw, "commit count")?;
+
+    Ok(ResolvedComparison {
+        strategy_id: StrategyId::Range,
+        base_ref: base_ref.to_string(),
+        head_ref: head_ref.to_string(),
+        base_commit,
+        head_commit,
+        summary: format!("{base_ref}..{head_ref}"),
+        details: vec![format!("commits in range: {commit_count}")],
+        ahead_count: None,
+        includes_uncommitted: false,
+        range_commits: Vec::new(),
+    })
+}
+
+/// A range with a stash entry on either side compares snapshots directly: the stash's
+/// internal index and parent commits are not part of what was stashed, so there is no
+/// commit count and no commit list.
+fn resolve_stash_comparison(
+    repo_root: &Path,
+    base_ref: &str,
+    head_ref: &str,
+) -> Result<ResolvedComparison> {
+    let mut details = Vec::new();
+    let mut resolve_side = |reference: &str| -> Result<String> {
+        if !is_stash_ref(reference) {
+            return Ok(
+                run_git_text(["rev-parse", &format!("{reference}^{{commit}}")], repo_root)?
+                    .trim()
+                    .to_string(),
+            );
+        }
+        let subject = run_git_text(["log", "-1", "--format=%s", reference], repo_root)?;
+        details.push(format!("{reference}: {}", subject.trim()));
+        resolve_stash_snapshot(repo_root, reference)
+    };
+    let base_commit = resolve_side(base_ref)?;
+    let head_commit = resolve_side(head_ref)?;
+
+    Ok(ResolvedComparison {
+        strategy_id: StrategyId::Range,
+        base_ref: base_ref.to_string(),
+        head_ref: head_ref.to_string(),
+        base_commit,
+        head_commit,
+        summary: format!("{base_ref}..{head_ref}"),
+        details,
+        ahead_count: None,
+        includes_uncommitted: false,
+        range_commits: Vec::new(),
+    })
+}
+
+fn resolve_only_uncommitted_comparison(repo_root: &Path) -> Result<ResolvedComparison> {
+    let current_branch = run_git_text(["rev-parse", "--abbrev-ref", "HEAD"], repo_root)?
+        .trim()
+        .to_string();
+    let head_commit = run_git_text(["rev-parse", "HEAD^{commit}"], repo_root)?
+        .trim()
+        .to_string();
+
+    Ok(ResolvedComparison {
+        strategy_id: StrategyId::OnlyUncommitted,
+        base_ref: current_branch.clone(),
+        head_ref: current_branch.clone(),
+        base_commit: head_commit.clone(),
+        head_commit,
+        summary: format!("{current_branch}..WORKTREE"),
+        details: vec![
+            format!("branch: {current_branch}"),
+            "mode: only-uncommitted".to_string(),
+        ],
+        ahead_count: None,
+        includes_uncommitted: true,
+        range_commits: Vec::new(),
+    })
+}
+
+/// `--staged` and `--unstaged`: the two halves of the uncommitted changes, split at the
+/// index. Both sides sit on the checked-out commit, which the staged left side shows.
+fn resolve_index_comparison(
+    repo_root: &Path,
+    strategy_id: StrategyId,
+) -> Result<ResolvedComparison> {
+    let current_branch = run_git_text(["rev-parse", "--abbrev-ref", "HEAD"], repo_root)?
+        .trim()
+        .to_string();
+    let head_commit = run_git_text(["rev-parse", "HEAD^{commit}"], repo_root)?
+        .trim()
+        .to_string();
+    let (base_ref, head_ref) = if strategy_id == StrategyId::Staged {
+        (current_branch.clone(), "INDEX".to_string())
+    } else {
+        ("INDEX".to_string(), "WORKTREE".to_string())
+    };
+
+    Ok(ResolvedComparison {
+        strategy_id,
+        summary: format!("{base_ref}..{head_ref}"),
+        base_ref,
+        head_ref,
+        base_commit: head_commit.clone(),
+        head_commit,
+        details: vec![
+            format!("branch: {current_branch}"),
+            format!("mode: {strategy_id}"),
+        ],
+        ahead_count: None,
+        includes_uncommitted: true,
+        range_commits: Vec::new(),
+    })
+}
+
+/// `--amend`: HEAD's first parent against the index, which is what `git commit --amend`
+/// records, or the empty tree when HEAD is a root commit, so amending the first commit
+/// shows every file as added.
+fn resolve_amend_comparison(repo_root: &Path) -> Result<ResolvedComparison> {
+    let head_commit = run_git_text(["rev-parse", "HEAD^{commit}"], repo_root)?
+        .trim()
+        .to_string();
+    let (base_ref, base_commit) = match list_commit_parents(repo_root, &head_commit)?
+        .into_iter()
+        .next()
+    {
+        Some(parent) => ("HEAD~1".to_string(), parent),
+        None => ("(root)".to_string(), EMPTY_TREE_HASH.to_string()),
+    };
+
+    Ok(ResolvedComparison {
+        strategy_id: StrategyId::Amend,
+        summary: format!("{base_ref}..INDEX"),
+        base_ref,
+        head_ref: "INDEX".to_string(),
+        base_commit,
+        head_commit,
+        details: vec!["mode: amend".to_string()],
+        ahead_count: None,
+        includes_uncommitted: true,
+        range_commits: Vec::new(),
+    })
+}
+
+const EMPTY_TREE_HASH: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+const LOG_FIELD_SEPARATOR: char = '\u{1f}';
+
+fn parse_commit_log_output(output: &str) -> Vec<CommitSummary> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(LOG_FIELD_SEPARATOR);
+            Some(CommitSummary {
+                hash: fields.next()?.to_string(),
+                short_hash: fields.next()?.to_string(),
+                author: fields.next()?.to_string(),
+                date: fields.next()?.to_string(),
+                subject: fields.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+fn run_commit_log(
+    repo_root: &Path,
+    extra_args: &[&str],
+    paths: &[&str],
+) -> Result<Vec<CommitSummary>> {
+    let mut args = vec![
+        "log",
+        "--date=short",
+        "--format=%H%x1f%h%x1f%an%x1f%ad%x1f%s",
+    ];
+    args.extend_from_slice(extra_args);
+    args.push("--");
+    args.extend_from_slice(paths);
+    let output = run_git_text(args, repo_root)?;
+
+    Ok(parse_commit_log_output(&output))
+}
+
+pub fn list_recent_commits(
+    repo_root: &Path,
+    revision: &str,
+    max_count: usize,
+) -> Result<Vec<CommitSummary>> {
+    run_commit_log(
+        repo_root,
+        &[&format!("--max-count={max_count}"), revision],
+        &[],
+    )
+}
+
+/// The latest `max_count` commits reachable from `revision` that touch `path`, newest
+/// first, like `git log -- <path>`.
+pub fn list_path_commits(
+    repo_root: &Path,
+    revision: &str,
+    path: &str,
+    max_count: usize,
+) -> Result<Vec<CommitSummary>> {
+    run_commit_log(
+        repo_root,
+        &[&format!("--max-count={max_count}"), revision],
+        &[path],
+    )
+}
+
+/// The parents of `revision`, first parent first; a merge commit has two or more.
+pub fn list_commit_parents(repo_root: &Path, revision: &str) -> Result<Vec<String>> {
+    let output = run_git_text(["rev-list", "--parents", "-n", "1", revision], repo_root)?;
+    Ok(output
+        .split_whitespace()
+        .skip(1)
+        .map(str::to_string)
+        .collect())
+}
+
+/// Lists the commits between the comparison's base and head, newest first.
+pub fn list_range_commits(
+    repo_root: &Path,
+    comparison: &ResolvedComparison,
+) -> Result<Vec<CommitSummary>> {
+    if comparison.base_commit == comparison.head_commit
+        || is_stash_ref(&comparison.base_ref)
+        || is_stash_ref(&comparison.head_ref)
+    {
+        return Ok(Vec::new());
+    }
+    // A root commit has no parent to exclude: its log is the commit alone.
+    if comparison.base_commit == EMPTY_TREE_HASH {
+        return run_commit_log(repo_root, &[&comparison.head_commit], &[]);
+    }
+
+    run_commit_log(
+        repo_root,
+        &[&format!(
+            "{}..{}",
+            comparison.base_commit, comparison.head_commit
+        )],
+        &[],
+    )
+}
+
+/// Returns every path touched by commits in the comparison range whose author matches
+/// `pattern` (as interpreted by `git log --author`).
+pub fn list_paths_touched_by_author(
+    repo_root: &Path,
+    comparison: &ResolvedComparison,
+    pattern: &str,
+) -> Result<HashSet<String>> {
+    let output = run_git(
+        [
+            "log",
+            &format!("--author={pattern}"),
+            "--name-only",
+            "--format=",
+            "-z",
+            &format!("{}..{}", comparison.base_commit, comparison.head_commit),
+        ],
+        repo_root,
+    )?;
+
+    Ok(output
+        .split(|byte| matches!(byte, b'\0' | b'\n'))
+        .filter(|chunk| !chunk.is_empty())
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect())
+}
+
+/// Builds a comparison between `commit` and its first parent, falling back to the empty
+/// tree for root commits.
+pub fn resolve_commit_comparison(
+    repo_root: &Path,
+    commit: &CommitSummary,
+) -> Result<ResolvedComparison> {
+    let (base_ref, base_commit) =
+        match run_git_text(["rev-parse", &format!("{}^1", commit.hash)], repo_root) {
+            Ok(parent) => (format!("{}^", commit.short_hash), parent.trim().to_string()),
+            Err(_) => ("(root)".to_string(), EMPTY_TREE_HASH.to_string()),
+        };
+
+    Ok(ResolvedComparison {
+        strategy_id: StrategyId::Range,
+        base_ref: base_ref.clone(),
+        head_ref: commit.short_hash.clone(),
+        base_commit,
+        head_commit: commit.hash.clone(),
+        summary: format!("{base_ref}..{}", commit.short_hash),
+        details: vec![
+            format!("author: {}", commit.author),
+            format!("date: {}", commit.date),
+            format!("subject: {}", commit.subject),
+        ],
+        ahead_count: None,
+        includes_uncommitted: false,
+        range_commits: vec![commit.clone()],
+    })
+}
+
+/// The remote whose name prefixes `reference` (`origin` for `origin/main`), preferring
+/// the longest match so `upstream-mirror/main` does not match `upstream`.
+fn remote_for_ref<'a>(reference: &str, remotes: &'a [String]) -> Option<&'a str> {
+    remotes
+        .iter()
+        .filter(|remote| {
+            reference
+                .strip_prefix(remote.as_str())
+                .is_some_and(|rest| rest.starts_with('/'))
+        })
+        .max_by_key(|remote| remote.len())
+        .map(String::as_str)
+}
+
+/// The remote `--fetch` should update: the one behind the upstream (or default branch)
+/// for upstream-ahead, the one named by `--base`/`--head` for ranges, else `origin`.
+pub fn resolve_fetch_remote(repo_root: &Path, request: &ComparisonRequest) -> Result<String> {
+    let remotes: Vec<String> = run_git_text(["remote"], repo_root)?
+        .lines()
+        .map(str::to_string)
+        .collect();
+    if remotes.is_empty() {
+        bail!("--fetch needs a remote, but this repository has none");
+    }
+
+    let references = match request.strategy_id {
+        StrategyId::UpstreamAhead => {
+            resolve_upstream_base(repo_root, is_head_detached(repo_root), false)
+                .map(|base| base.reference)
+                .into_iter()
+                .collect()
+        }
+        StrategyId::Range => request
+            .base_ref
+            .iter()
+            .cloned()
+            .chain([request.head_ref.clone()])
+            .collect(),
+        StrategyId::OnlyUncommitted
+        | StrategyId::Staged
+        | StrategyId::Unstaged
+        | StrategyId::Amend => Vec::new(),
+    };
+    let remote = references
+        .iter()
+        .find_map(|reference| remote_for_ref(reference, &remotes))
+        .or_else(|| {
+            remotes
+                .iter()
+                .find(|remote| *remote == "origin")
+                .map(String::as_str)
+        })
+        .unwrap_or(&remotes[0]);
+    Ok(remote.to_string())
+}
+
+/// Runs `git fetch --progress <remote>`, publishing each progress line as the loading
+/// note. Cancelling through `progress` kills the fetch.
+pub fn fetch_remote(repo_root: &Path, remote: &str, progress: &LoadProgress) -> Result<()> {
+    let command = format!("git fetch --progress {remote}");
+    let started_at = Instant::now();
+    let mut child = Command::new("git")
+        .args(["fetch", "--progress", remote])
+        .current_dir(repo_root)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run git in {}", repo_root.display()))?;
+    progress.set_note(format!("fetching {remote}..."));
+
+    // git redraws progress lines with `\r`, so split on both line endings.
+    let mut stderr = child
+        .stderr
+        .take()
+        .context("git fetch stderr was not piped")?;
+    let mut transcript = Vec::new();
+    let mut line = Vec::new();
+    let mut buffer = [0_u8; 1024];
+    loop {
+        if progress.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("{command} was cancelled");
+        }
+        let read = stderr
+            .read(&mut buffer)
+            .with_context(|| format!("failed to read output of {command}"))?;
+        if read == 0 {
+            break;
+        }
+        for &byte in &buffer[..read] {
+            if byte == b'\r' || byte == b'\n' {
+                let text = String::from_utf8_lossy(&line).trim().to_string();
+                if !text.is_empty() {
+                    progress.set_note(format!("{remote}: {text}"));
+                    if byte == b'\n' {
+                        transcript.push(text);
+                    }
+                }
+                line.clear();
+            } else {
+                line.push(byte);
+            }
+        }
+    }
+
+    let status = child
+        .wait()
+        .with_context(|| format!("failed to run {command}"))?;
+    let duration_ms = started_at.elapsed().as_secs_f64() * 1000.0;
+    if !status.success() {
+        warn!(%command, duration_ms, %status, "git command failed");
+        let details = match transcript.last() {
+            Some(last_line) => last_line.clone(),
+            None => format!("exit status {status}"),
+        };
+        bail!("{command} failed: {details}");
+    }
+    debug!(%command, duration_ms, "git command finished");
+    Ok(())
+}
+
+pub fn resolve_comparison(
+    repo_root: &Path,
+    request: &ComparisonRequest,
+) -> Result<ResolvedComparison> {
+    if request.only_uncommitted {
+        return resolve_only_uncommitted_comparison(repo_root);
+    }
+
+    match request.strategy_id {
+        StrategyId::Range => {
+            let base_ref = request
+                .base_ref
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("missing base reference for range strategy"))?;
+            resolve_range_comparison(repo_root, base_ref, &request.head_ref)
+        }
+        StrategyId::UpstreamAhead => {
+            resolve_upstream_ahead_comparison(repo_root, &request.head_ref, request.strict)
+        }
+        StrategyId::OnlyUncommitted => resolve_only_uncommitted_comparison(repo_root),
+        StrategyId::Staged | StrategyId::Unstaged => {
+            resolve_index_comparison(repo_root, request.strategy_id)
+        }
+        StrategyId::Amend => resolve_amend_comparison(repo_root),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use anyhow::Result;
+
+    use super::{
+        UpstreamBase, check_diff_regex, choose_upstream_base, flags_blank_at_eol, is_stash_ref,
+        parse_commit_log_output, pick_default_branch, remote_for_ref,
+    };
+
+    #[test]
+    fn core_whitespace_rules_toggle_trailing_whitespace() {
+        assert!(flags_blank_at_eol(""));
+        assert!(flags_blank_at_eol("tab-in-indent,cr-at-eol"));
+        assert!(!flags_blank_at_eol("-trailing-space"));
+        assert!(!flags_blank_at_eol("space-before-tab, -blank-at-eol"));
+        assert!(flags_blank_at_eol("-blank-at-eol,trailing-space"));
+    }
+
+    #[test]
+    fn stash_refs_are_recognized_by_name() {
+        for reference in ["stash", "stash@{2}", "refs/stash", "refs/stash@{0}"] {
+            assert!(is_stash_ref(reference), "{reference}");
+        }
+        for reference in ["stashed-work", "origin/stash", "HEAD"] {
+            assert!(!is_stash_ref(reference), "{reference}");
+        }
+    }
+
+    #[test]
+    fn fetch_remote_is_the_longest_remote_prefixing_the_ref() {
+        let remotes = vec!["upstream".to_string(), "upstream-mirror".to_string()];
+        assert_eq!(
+            remote_for_ref("upstream-mirror/main", &remotes),
+            Some("upstream-mirror")
+        );
+        assert_eq!(remote_for_ref("upstream/main", &remotes), Some("upstream"));
+        assert_eq!(remote_for_ref("main", &remotes), None);
+    }
+
+    #[test]
+    fn default_branch_prefers_origin_head_then_candidates() {
+        assert_eq!(
+            pick_default_branch(Some("origin/trunk\n"), |_| false),
+            Some("origin/trunk".to_string())
+        );
+        assert_eq!(
+            pick_default_branch(None, |candidate| candidate == "master"),
+            Some("master".to_string())
+        );
+        assert_eq!(pick_default_branch(None, |_| false), None);
+    }
+
+    #[test]
+    fn detached_heads_fall_back_to_the_previous_branch_upstream_then_default_branch() {
+        let previous = || Some(("feature".to_string(), "origin/feature".to_string()));
+        let default_branch = || Some("origin/main".to_string());
+        let label = |base: Result<UpstreamBase>| base.expect("a base is chosen").label;
+
+        assert_eq!(
+            choose_upstream_base(None, true, false, previous, default_branch)
+                .expect("a base is chosen"),
+            UpstreamBase {
+                reference: "origin/feature".to_string(),
+                label: "detached HEAD; upstream of previous branch feature".to_string(),
+            }
+        );
+        assert_eq!(
+            label(choose_upstream_base(
+                None,
+                true,
+                false,
+                || None,
+                default_branch
+            )),
+            "detached HEAD; default branch"
+        );
+        assert_eq!(
+            label(choose_upstream_base(
+                None,
+                false,
+                false,
+                previous,
+                default_branch
+            )),
+            "no upstream; default branch"
+        );
+        assert_eq!(
+            label(choose_upstream_base(
+                Some("origin/topic".to_string()),
+                true,
+                true,
+                previous,
+                default_branch
+            )),
+            "upstream"
+        );
+        assert!(choose_upstream_base(None, true, true, previous, default_branch).is_err());
+    }
+
+    #[test]
+    fn diff_regexes_are_compiled_by_git() {
+        let repo_root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        assert!(check_diff_regex(repo_root, "-G[[:digit:]]+ms", "--pickaxe-G").is_ok());
+        let error = check_diff_regex(repo_root, "-G(unclosed", "--pickaxe-G")
+            .expect_err("git should reject the pattern");
+        assert!(error.to_string().starts_with("--pickaxe-G: invalid regex"));
+        assert!(
+            check_diff_regex(
+                repo_root,
+                "--ignore-matching-lines=[0-9",
+                "--ignore-matching-lines"
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn parse_commit_log_output_splits_fields() {
+        let output = "abc123\u{1f}abc\u{1f}Jo Doe\u{1f}2024-01-02\u{1f}Fix: a \u{2192} b\n";
+
+        let commits = parse_commit_log_output(output);
+
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].short_hash, "abc");
+        assert_eq!(commits[0].author, "Jo Doe");
+        assert_eq!(commits[0].date, "2024-01-02");
+        assert_eq!(commits[0].subject, "Fix: a \u{2192} b");
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+
+use crate::model::FileLineHighlights;
+
+pub const HEX_DUMP_BYTES_PER_ROW: usize = 16;
+
+fn format_hex_row(offset: usize, bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(HEX_DUMP_BYTES_PER_ROW * 3 + 1);
+    for index in 0..HEX_DUMP_BYTES_PER_ROW {
+        if index == HEX_DUMP_BYTES_PER_ROW / 2 {
+            hex.push(' ');
+        }
+        match bytes.get(index) {
+            Some(byte) => hex.push_str(&format!("{byte:02x} ")),
+            None => hex.push_str("   "),
+        }
+    }
+    let ascii: String = bytes
+        .iter()
+        .map(|byte| {
+            if byte.is_ascii_graphic() || *byte == b' ' {
+                char::from(*byte)
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    format!("{offset:08x}  {hex} |{ascii}|")
+}
+
+/// `xxd`-style rows, one per 16 bytes of the first `limit` bytes, plus a note on what
+/// was left out.
+pub fn hex_dump_lines(content: &[u8], limit: usize) -> Vec<String> {
+    let shown = &content[..content.len().min(limit)];
+    let mut lines: Vec<String> = shown
+        .chunks(HEX_DUMP_BYTES_PER_ROW)
+        .enumerate()
+        .map(|(row, bytes)| format_hex_row(row * HEX_DUMP_BYTES_PER_ROW, bytes))
+        .collect();
+    if lines.is_empty() {
+        lines.push("<empty file>".to_string());
+    }
+    if content.len() > shown.len() {
+        lines.push(format!(
+            "<{} more bytes not shown; raise --hex-dump to see them>",
+            content.len() - shown.len()
+        ));
+    }
+    lines
+}
+
+/// Rows are compared at the same offset rather than aligned like text, since an edited
+/// binary usually keeps its layout; an inserted byte marks every later row.
+pub fn hex_dump_highlights(left_lines: &[String], right_lines: &[String]) -> FileLineHighlights {
+    let differs = |index: usize| left_lines.get(index) != right_lines.get(index);
+    FileLineHighlights {
+        left_deleted_line_indexes: (0..left_lines.len())
+            .filter(|index| differs(*index))
+            .collect::<HashSet<_>>(),
+        right_added_line_indexes: (0..right_lines.len())
+            .filter(|index| differs(*index))
+            .collect::<HashSet<_>>(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{hex_dump_highlights, hex_dump_lines};
+
+    #[test]
+    fn hex_rows_show_offsets_bytes_and_ascii_and_mark_changed_rows() {
+        let left: Vec<u8> = (0..40).collect();
+        let mut right = left.clone();
+        right[20] = b'A';
+
+        let left_lines = hex_dump_lines(&left, 1024);
+        assert_eq!(left_lines.len(), 3);
+        assert_eq!(
+            left_lines[2],
+            format!(
+                "00000020  20 21 22 23 24 25 26 27{}| !\"#$%&'|",
+                " ".repeat(27)
+            )
+        );
+        assert!(left_lines[0].starts_with("00000000  00 01 02 03 04 05 06 07  08 09 "));
+
+        let right_lines = hex_dump_lines(&right, 1024);
+        let highlights = hex_dump_highlights(&left_lines, &right_lines);
+        assert_eq!(highlights.left_deleted_line_indexes, HashSet::from([1]));
+        assert_eq!(highlights.right_added_line_indexes, HashSet::from([1]));
+
+        let truncated = hex_dump_lines(&left, 16);
+        assert_eq!(truncated.len(), 2);
+        assert_eq!(
+            truncated[1],
+            "<24 more bytes not shown; raise --hex-dump to see them>"
+        );
+    }
+}
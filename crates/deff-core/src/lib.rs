@@ -0,0 +1,63 @@
+//! The diff pipeline behind the `deff` TUI: resolve a git comparison, list the changed
+//! files, build side-by-side views, and render them to styled lines.
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! use deff_core::{
+//!     ComparisonRequest, DiffOptions, RenderOptions, StrategyId, build_file_views,
+//!     get_diff_file_descriptors, model::LoadProgress, render_file_lines, resolve_comparison,
+//! };
+//!
+//! let repo_root = Path::new(".");
+//! let comparison = resolve_comparison(
+//!     repo_root,
+//!     &ComparisonRequest {
+//!         strategy_id: StrategyId::Range,
+//!         base_ref: Some("origin/main".to_string()),
+//!         head_ref: "HEAD".to_string(),
+//!         only_uncommitted: false,
+//!         strict: false,
+//!     },
+//! )?;
+//! let descriptors = get_diff_file_descriptors(repo_root, &comparison, None)?;
+//! let views = build_file_views(
+//!     repo_root,
+//!     &comparison,
+//!     &descriptors,
+//!     &DiffOptions::default(),
+//!     &LoadProgress::default(),
+//! )
+//! .unwrap_or_default();
+//! for view in &views {
+//!     let lines = render_file_lines(view, &[], 160, &RenderOptions::default());
+//!     println!("{}: {} rows", view.descriptor.display_path, lines.len());
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod diff;
+pub mod fold;
+pub mod git;
+pub mod hexdump;
+pub mod model;
+pub mod pretty;
+pub mod profile;
+pub mod render;
+pub mod review;
+pub mod structural;
+pub mod syntax;
+pub mod syntax_tree;
+pub mod table;
+pub mod text;
+pub mod view_cache;
+
+pub use diff::{
+    build_file_view, build_file_view_from_files, build_file_views, get_diff_file_descriptors,
+};
+pub use git::{get_repository_root, resolve_comparison};
+pub use model::{
+    ComparisonRequest, DiffFileDescriptor, DiffFileView, DiffOptions, RenderOptions,
+    ResolvedComparison, StrategyId,
+};
+pub use render::render_file_lines;
@@ -0,0 +1,654 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
+
+#[cfg(feature = "clap")]
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+pub enum ThemeMode {
+    #[default]
+    #[cfg_attr(feature = "clap", value(name = "auto"))]
+    Auto,
+    #[cfg_attr(feature = "clap", value(name = "dark"))]
+    Dark,
+    #[cfg_attr(feature = "clap", value(name = "light"))]
+    Light,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+pub enum OutputFormat {
+    #[cfg_attr(feature = "clap", value(name = "plain"))]
+    Plain,
+    #[cfg_attr(feature = "clap", value(name = "ansi"))]
+    Ansi,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+pub enum ExportFormat {
+    #[cfg_attr(feature = "clap", value(name = "markdown"))]
+    Markdown,
+}
+
+/// How the two sides are laid out: in two panes, or interleaved in one.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+pub enum ViewMode {
+    #[default]
+    #[cfg_attr(feature = "clap", value(name = "split"))]
+    Split,
+    #[cfg_attr(feature = "clap", value(name = "unified"))]
+    Unified,
+}
+
+/// Reviewer preferences that shape navigation and drawing, fixed for the session.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ViewSettings {
+    /// Rows kept visible above jump targets and around a focused hunk while scrolling.
+    pub scrolloff: usize,
+    /// Files open at their first hunk rather than line 1.
+    pub open_at_first_change: bool,
+    /// Start in the changes-only view (`--context`).
+    pub changes_only: bool,
+    /// Unchanged lines the changes-only view keeps around each change.
+    pub context_lines: usize,
+    /// The view the review opens in (`--view`).
+    pub view_mode: ViewMode,
+    /// How its frames are drawn.
+    pub render: RenderOptions,
+}
+
+/// How panes are drawn, passed to every render function.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RenderOptions {
+    /// Light or dark syntax colours; `Auto` asks `DEFF_THEME`, then the terminal.
+    pub theme_mode: ThemeMode,
+    /// What the terminal reported as its background, for `Auto`.
+    pub dark_background: Option<bool>,
+    /// Draw a guide after this many characters of every pane line (`--ruler`).
+    pub ruler: Option<usize>,
+    /// Put this between the panes instead of `" | "` (`--separator`).
+    pub separator: Option<String>,
+    /// Draw each pane in a box whose top border names the side's ref (`--bordered`).
+    pub bordered: bool,
+    /// Replace background tints and syntax colours with bold foregrounds and reverse
+    /// video (`--high-contrast`).
+    pub high_contrast: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+pub enum StrategyArg {
+    #[cfg_attr(feature = "clap", value(name = "upstream-ahead"))]
+    UpstreamAhead,
+    #[cfg_attr(feature = "clap", value(name = "range"))]
+    Range,
+    #[cfg_attr(feature = "clap", value(name = "staged"))]
+    Staged,
+    #[cfg_attr(feature = "clap", value(name = "unstaged"))]
+    Unstaged,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StrategyId {
+    UpstreamAhead,
+    Range,
+    OnlyUncommitted,
+    /// HEAD against the index, like `git diff --cached`.
+    Staged,
+    /// The index against the working tree, like `git diff`.
+    Unstaged,
+    /// HEAD's parent against the index: what `git commit --amend` would record.
+    Amend,
+}
+
+impl Display for StrategyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StrategyId::UpstreamAhead => write!(f, "upstream-ahead"),
+            StrategyId::Range => write!(f, "range"),
+            StrategyId::OnlyUncommitted => write!(f, "only-uncommitted"),
+            StrategyId::Staged => write!(f, "staged"),
+            StrategyId::Unstaged => write!(f, "unstaged"),
+            StrategyId::Amend => write!(f, "amend"),
+        }
+    }
+}
+
+impl From<StrategyArg> for StrategyId {
+    fn from(value: StrategyArg) -> Self {
+        match value {
+            StrategyArg::UpstreamAhead => StrategyId::UpstreamAhead,
+            StrategyArg::Range => StrategyId::Range,
+            StrategyArg::Staged => StrategyId::Staged,
+            StrategyArg::Unstaged => StrategyId::Unstaged,
+        }
+    }
+}
+
+/// Restricts the file list to files whose changes add or remove a string (`-S`) or
+/// touch lines matching a regex (`-G`).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Pickaxe {
+    String(String),
+    Regex(String),
+}
+
+impl Pickaxe {
+    pub fn git_arg(&self) -> String {
+        match self {
+            Pickaxe::String(value) => format!("-S{value}"),
+            Pickaxe::Regex(pattern) => format!("-G{pattern}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FileContentSource {
+    Commit,
+    WorkingTree,
+    /// The staged version, read as `:<path>`.
+    Index,
+    Missing,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LineHighlightKind {
+    None,
+    Deleted,
+    Added,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PaneSide {
+    Left,
+    Right,
+}
+
+/// The inputs comparison resolution needs, independent of how they were collected.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ComparisonRequest {
+    pub strategy_id: StrategyId,
+    pub base_ref: Option<String>,
+    pub head_ref: String,
+    pub only_uncommitted: bool,
+    /// Error instead of falling back to the default branch when there is no upstream.
+    pub strict: bool,
+}
+
+/// Knobs for how each file's changed lines are computed.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DiffOptions {
+    /// Drop hunks whose lines all match this regex (git's `--ignore-matching-lines`).
+    pub ignore_matching_lines: Option<String>,
+    /// Show binary files as hex dumps of their first this many bytes instead of a
+    /// placeholder.
+    pub hex_dump_bytes: Option<usize>,
+    /// Compare `.json` / `.yaml` files by key path instead of line by line.
+    pub structural: bool,
+    /// Compare `.csv` / `.tsv` files as tables keyed on this column (a 1-based number or
+    /// a header name).
+    pub csv_key: Option<String>,
+    /// Re-indent minified JS / JSON / CSS on both sides before diffing.
+    pub pretty_minified: bool,
+    /// Mark trailing whitespace on added lines, per git's `core.whitespace`.
+    pub flag_trailing_whitespace: bool,
+    /// Show every file without syntax colours.
+    pub no_syntax: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct ResolvedComparison {
+    pub strategy_id: StrategyId,
+    pub base_ref: String,
+    pub head_ref: String,
+    pub base_commit: String,
+    pub head_commit: String,
+    pub summary: String,
+    pub details: Vec<String>,
+    pub ahead_count: Option<usize>,
+    pub includes_uncommitted: bool,
+    pub range_commits: Vec<CommitSummary>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommitSummary {
+    pub hash: String,
+    pub short_hash: String,
+    pub author: String,
+    pub date: String,
+    pub subject: String,
+}
+
+impl CommitSummary {
+    pub fn format_row(&self) -> String {
+        format!(
+            "{}  {}  {:<16}  {}",
+            self.short_hash,
+            self.date,
+            self.author.chars().take(16).collect::<String>(),
+            self.subject
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DiffFileDescriptor {
+    pub raw_status: String,
+    pub display_path: String,
+    pub base_path: Option<String>,
+    pub head_path: Option<String>,
+    pub base_source: FileContentSource,
+    pub head_source: FileContentSource,
+    /// The base file's mode as git records it, like `100644`; `None` for a missing side
+    /// or when the mode is not known.
+    pub base_mode: Option<String>,
+    /// Like `base_mode`, for the head file.
+    pub head_mode: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum AnnotationSeverity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl AnnotationSeverity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AnnotationSeverity::Error => "error",
+            AnnotationSeverity::Warning => "warning",
+            AnnotationSeverity::Note => "note",
+        }
+    }
+}
+
+/// A lint finding attached to a head-side line.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Annotation {
+    pub line_index: usize,
+    pub severity: AnnotationSeverity,
+    pub message: String,
+    pub rule: Option<String>,
+}
+
+impl Annotation {
+    pub fn format_row(&self) -> String {
+        match &self.rule {
+            Some(rule) => format!(
+                "{:>5}  {:<7}  {} [{rule}]",
+                self.line_index + 1,
+                self.severity.as_str(),
+                self.message
+            ),
+            None => format!(
+                "{:>5}  {:<7}  {}",
+                self.line_index + 1,
+                self.severity.as_str(),
+                self.message
+            ),
+        }
+    }
+}
+
+/// How a minified file (JS, JSON, or CSS on one enormous line) is shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Minified {
+    #[default]
+    No,
+    /// Shown as is; `--pretty` would re-indent it.
+    AsIs,
+    /// Both sides were re-indented before diffing.
+    PrettyPrinted,
+}
+
+impl Minified {
+    /// The file header note, if any.
+    pub fn note(self) -> Option<&'static str> {
+        match self {
+            Self::No => None,
+            Self::AsIs => Some("minified (--pretty to format)"),
+            Self::PrettyPrinted => Some("pretty-printed from minified"),
+        }
+    }
+}
+
+/// Whether a file's content gets syntax colours.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyntaxHighlighting {
+    #[default]
+    On,
+    /// Turned off for every file with `--no-syntax`.
+    Off,
+    /// Skipped for a file too large for colours to be worth their cost.
+    TooLarge,
+}
+
+impl SyntaxHighlighting {
+    /// The file header note, if any.
+    pub fn note(self) -> Option<&'static str> {
+        match self {
+            Self::On | Self::Off => None,
+            Self::TooLarge => Some("large file, no syntax colours"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DiffFileView {
+    pub descriptor: DiffFileDescriptor,
+    pub review_key: String,
+    pub left_lines: Vec<String>,
+    pub right_lines: Vec<String>,
+    /// The zero-based base file line on each row, `None` on the filler rows that keep
+    /// both sides aligned. Empty when each row is the line of the same index, as in
+    /// structural, table, and hex dump views.
+    pub left_source_lines: Vec<Option<usize>>,
+    /// Like `left_source_lines`, for the head file.
+    pub right_source_lines: Vec<Option<usize>>,
+    pub left_language: Option<String>,
+    pub right_language: Option<String>,
+    pub left_deleted_line_indexes: HashSet<usize>,
+    pub right_added_line_indexes: HashSet<usize>,
+    pub left_max_content_length: usize,
+    pub right_max_content_length: usize,
+    pub owners: Vec<String>,
+    pub right_uncovered_line_indexes: HashSet<usize>,
+    pub right_annotations: Vec<Annotation>,
+    /// Added lines that end in whitespace.
+    pub right_trailing_whitespace_line_indexes: HashSet<usize>,
+    /// The base file's last line has no newline, like git's `\ No newline at end of
+    /// file`. Always false for views that do not show the file's own lines.
+    pub left_missing_final_newline: bool,
+    /// Like `left_missing_final_newline`, for the head file.
+    pub right_missing_final_newline: bool,
+    /// The sides differ only in whitespace and blank lines.
+    pub whitespace_only: bool,
+    pub minified: Minified,
+    pub syntax_highlighting: SyntaxHighlighting,
+    /// Shown folded to one line at first (the project's `collapse` paths).
+    pub collapsed: bool,
+}
+
+impl DiffFileView {
+    pub fn line_count(&self) -> usize {
+        self.left_lines.len().max(self.right_lines.len())
+    }
+
+    pub fn is_line_changed(&self, line_index: usize) -> bool {
+        self.left_deleted_line_indexes.contains(&line_index)
+            || self.right_added_line_indexes.contains(&line_index)
+    }
+
+    fn source_lines(&self, side: PaneSide) -> &[Option<usize>] {
+        match side {
+            PaneSide::Left => &self.left_source_lines,
+            PaneSide::Right => &self.right_source_lines,
+        }
+    }
+
+    /// The zero-based line of the `side` file shown on `row`; `None` on a filler row.
+    pub fn source_line(&self, side: PaneSide, row: usize) -> Option<usize> {
+        let source_lines = self.source_lines(side);
+        if source_lines.is_empty() {
+            let lines = match side {
+                PaneSide::Left => &self.left_lines,
+                PaneSide::Right => &self.right_lines,
+            };
+            (row < lines.len()).then_some(row)
+        } else {
+            source_lines.get(row).copied().flatten()
+        }
+    }
+
+    /// The row showing `line` of the `side` file, or the last row when the file has no
+    /// such line.
+    pub fn row_of_source_line(&self, side: PaneSide, line: usize) -> usize {
+        let source_lines = self.source_lines(side);
+        if source_lines.is_empty() {
+            return line;
+        }
+        source_lines
+            .iter()
+            .position(|source_line| *source_line == Some(line))
+            .unwrap_or(source_lines.len().saturating_sub(1))
+    }
+
+    /// The first and last lines of the `side` file shown on rows `first..=last`; `None`
+    /// when those rows are all filler.
+    pub fn source_line_range(
+        &self,
+        side: PaneSide,
+        first: usize,
+        last: usize,
+    ) -> Option<(usize, usize)> {
+        let mut source_lines = (first..=last).filter_map(|row| self.source_line(side, row));
+        let start = source_lines.next()?;
+        Some((start, source_lines.next_back().unwrap_or(start)))
+    }
+
+    /// Whether each row holds at most one changed line, so the unified view can show it as
+    /// one line: aligned rows laid out for it, or a file with a missing side. Structural,
+    /// table, and hex dump views pair their rows by index and stay side by side.
+    pub fn has_unified_rows(&self) -> bool {
+        let aligned = !self.left_source_lines.is_empty()
+            || self.descriptor.base_source == FileContentSource::Missing
+            || self.descriptor.head_source == FileContentSource::Missing;
+        aligned
+            && !self
+                .left_deleted_line_indexes
+                .iter()
+                .any(|row| self.right_added_line_indexes.contains(row))
+    }
+
+    /// The side shown for a file's content: the head, or the base when the file no
+    /// longer exists in the head.
+    pub fn primary_side(&self) -> PaneSide {
+        if self.descriptor.head_source == FileContentSource::Missing {
+            PaneSide::Left
+        } else {
+            PaneSide::Right
+        }
+    }
+
+    /// The lines of the primary side's file, without filler rows.
+    pub fn primary_source_lines(&self) -> Vec<&str> {
+        let side = self.primary_side();
+        self.primary_lines()
+            .iter()
+            .enumerate()
+            .filter(|(row, _)| self.source_line(side, *row).is_some())
+            .map(|(_, line)| line.as_str())
+            .collect()
+    }
+
+    /// Head-side lines, or base-side lines when the file no longer exists in the head.
+    pub fn primary_lines(&self) -> &[String] {
+        if self.descriptor.head_source == FileContentSource::Missing {
+            &self.left_lines
+        } else {
+            &self.right_lines
+        }
+    }
+
+    /// The languages to colour the `(left, right)` sides as, unless highlighting is off.
+    pub fn highlighted_languages(&self) -> (Option<&str>, Option<&str>) {
+        if self.syntax_highlighting == SyntaxHighlighting::On {
+            (
+                self.left_language.as_deref(),
+                self.right_language.as_deref(),
+            )
+        } else {
+            (None, None)
+        }
+    }
+
+    pub fn primary_language(&self) -> Option<&str> {
+        if self.descriptor.head_source == FileContentSource::Missing {
+            self.left_language.as_deref()
+        } else {
+            self.right_language.as_deref()
+        }
+    }
+
+    /// Most severe annotation on the head-side line shown on `row`, if any.
+    pub fn annotation_severity_at(&self, row: usize) -> Option<AnnotationSeverity> {
+        let line_index = self.source_line(PaneSide::Right, row)?;
+        self.right_annotations
+            .iter()
+            .filter(|annotation| annotation.line_index == line_index)
+            .map(|annotation| annotation.severity)
+            .min()
+    }
+}
+
+#[cfg(any(test, feature = "test-support"))]
+impl DiffFileView {
+    /// An empty view of `path`, modified between two commits and keyed on its path, for
+    /// tests to fill in the fields they exercise with struct update syntax.
+    pub fn for_test(path: &str) -> Self {
+        DiffFileView {
+            descriptor: DiffFileDescriptor {
+                raw_status: "M".to_string(),
+                display_path: path.to_string(),
+                base_path: Some(path.to_string()),
+                head_path: Some(path.to_string()),
+                base_source: FileContentSource::Commit,
+                head_source: FileContentSource::Commit,
+                base_mode: None,
+                head_mode: None,
+            },
+            review_key: path.to_string(),
+            left_lines: Vec::new(),
+            right_lines: Vec::new(),
+            left_source_lines: Vec::new(),
+            right_source_lines: Vec::new(),
+            left_language: None,
+            right_language: None,
+            left_deleted_line_indexes: HashSet::new(),
+            right_added_line_indexes: HashSet::new(),
+            left_max_content_length: 0,
+            right_max_content_length: 0,
+            owners: Vec::new(),
+            right_uncovered_line_indexes: HashSet::new(),
+            right_annotations: Vec::new(),
+            right_trailing_whitespace_line_indexes: HashSet::new(),
+            left_missing_final_newline: false,
+            right_missing_final_newline: false,
+            whitespace_only: false,
+            minified: Minified::No,
+            syntax_highlighting: SyntaxHighlighting::On,
+            collapsed: false,
+        }
+    }
+
+    /// `path` with its one line changed from `old` to `new`.
+    pub fn for_test_change(path: &str) -> Self {
+        DiffFileView {
+            left_lines: vec!["old".to_string()],
+            right_lines: vec!["new".to_string()],
+            left_deleted_line_indexes: HashSet::from([0]),
+            right_added_line_indexes: HashSet::from([0]),
+            left_max_content_length: 3,
+            right_max_content_length: 3,
+            ..Self::for_test(path)
+        }
+    }
+}
+
+#[cfg(any(test, feature = "test-support"))]
+impl ResolvedComparison {
+    /// `main..HEAD` between two made-up commits, for tests to adjust with struct update
+    /// syntax.
+    pub fn for_test() -> Self {
+        ResolvedComparison {
+            strategy_id: StrategyId::Range,
+            base_ref: "main".to_string(),
+            head_ref: "HEAD".to_string(),
+            base_commit: "a".repeat(40),
+            head_commit: "b".repeat(40),
+            summary: "main..HEAD".to_string(),
+            details: Vec::new(),
+            ahead_count: None,
+            includes_uncommitted: false,
+            range_commits: Vec::new(),
+        }
+    }
+}
+
+/// Shared between the loading thread and the UI: completed-file count plus a
+/// cancellation flag checked between files, and an optional note replacing the count
+/// for work that is not per file (such as `--fetch`).
+#[derive(Debug, Default)]
+pub struct LoadProgress {
+    completed: AtomicUsize,
+    cancelled: AtomicBool,
+    note: Mutex<Option<String>>,
+}
+
+impl LoadProgress {
+    pub fn set_note(&self, note: String) {
+        if let Ok(mut current) = self.note.lock() {
+            *current = Some(note);
+        }
+    }
+
+    pub fn note(&self) -> Option<String> {
+        self.note.lock().ok().and_then(|note| note.clone())
+    }
+
+    pub fn advance(&self) {
+        self.completed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn completed(&self) -> usize {
+        self.completed.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PaneOffsets {
+    pub left: usize,
+    pub right: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct FileLineHighlights {
+    pub left_deleted_line_indexes: HashSet<usize>,
+    pub right_added_line_indexes: HashSet<usize>,
+}
+
+/// Reviewed badge of a file; `Stale` means an older version of it was reviewed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReviewState {
+    Unreviewed,
+    Reviewed,
+    Stale,
+}
+
+impl ReviewState {
+    pub fn label(self) -> &'static str {
+        match self {
+            ReviewState::Unreviewed => "unreviewed",
+            ReviewState::Reviewed => "reviewed",
+            ReviewState::Stale => "stale: changed since last reviewed",
+        }
+    }
+}
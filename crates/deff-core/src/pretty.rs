@@ -0,0 +1,185 @@
+/// A line this long in a file of only a few lines is taken for minified output.
+const MINIFIED_LINE_LENGTH: usize = 1000;
+const MINIFIED_MAX_LINES: usize = 10;
+const INDENT: &str = "  ";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SourceKind {
+    Json,
+    Script,
+    Css,
+}
+
+fn source_kind(path: &str) -> Option<SourceKind> {
+    let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+    match extension.as_str() {
+        "json" | "map" => Some(SourceKind::Json),
+        "js" | "mjs" | "cjs" => Some(SourceKind::Script),
+        "css" => Some(SourceKind::Css),
+        _ => None,
+    }
+}
+
+/// JS, JSON, or CSS squeezed onto one enormous line.
+pub fn is_minified(path: &str, lines: &[String]) -> bool {
+    source_kind(path).is_some()
+        && lines.len() <= MINIFIED_MAX_LINES
+        && lines
+            .iter()
+            .any(|line| line.chars().count() >= MINIFIED_LINE_LENGTH)
+}
+
+struct Printer {
+    lines: Vec<String>,
+    current: String,
+    depth: usize,
+}
+
+impl Printer {
+    fn push(&mut self, character: char) {
+        if self.current.is_empty() {
+            if character.is_whitespace() {
+                return;
+            }
+            self.current.push_str(&INDENT.repeat(self.depth));
+        }
+        self.current.push(character);
+    }
+
+    fn push_str(&mut self, text: &str) {
+        text.chars().for_each(|character| self.push(character));
+    }
+
+    fn break_line(&mut self) {
+        let line = self.current.trim_end();
+        if !line.is_empty() {
+            self.lines.push(line.to_string());
+        }
+        self.current.clear();
+    }
+}
+
+/// Re-indents by brackets: a line break after `{`, `;`, and top-level `,` (and `[` in
+/// JSON), never inside strings, comments, or parentheses. It does not parse the
+/// language, so the output is for reading and diffing only. `None` for other files.
+pub fn pretty_print(path: &str, text: &str) -> Option<Vec<String>> {
+    let kind = source_kind(path)?;
+    let breaks_brackets = kind == SourceKind::Json;
+    let mut printer = Printer {
+        lines: Vec::new(),
+        current: String::new(),
+        depth: 0,
+    };
+    let mut parens = 0usize;
+    let mut characters = text.chars().peekable();
+    while let Some(character) = characters.next() {
+        match character {
+            '"' | '\'' | '`' if kind != SourceKind::Json || character == '"' => {
+                printer.push(character);
+                while let Some(next) = characters.next() {
+                    printer.push(next);
+                    if next == '\\' {
+                        if let Some(escaped) = characters.next() {
+                            printer.push(escaped);
+                        }
+                    } else if next == character {
+                        break;
+                    }
+                }
+            }
+            '/' if kind != SourceKind::Json && characters.peek() == Some(&'*') => {
+                printer.push_str("/*");
+                characters.next();
+                let mut previous = ' ';
+                for next in characters.by_ref() {
+                    printer.push(next);
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+                printer.break_line();
+            }
+            '/' if kind == SourceKind::Script && characters.peek() == Some(&'/') => {
+                for next in characters.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                    printer.push(next);
+                }
+                printer.break_line();
+            }
+            '(' => {
+                parens += 1;
+                printer.push(character);
+            }
+            ')' => {
+                parens = parens.saturating_sub(1);
+                printer.push(character);
+            }
+            '{' | '[' if parens == 0 && (character == '{' || breaks_brackets) => {
+                printer.push(character);
+                printer.depth += 1;
+                printer.break_line();
+            }
+            '}' | ']' if parens == 0 && (character == '}' || breaks_brackets) => {
+                printer.break_line();
+                printer.depth = printer.depth.saturating_sub(1);
+                printer.push(character);
+                if !matches!(characters.peek(), Some(',' | ';' | ')')) {
+                    printer.break_line();
+                }
+            }
+            ',' | ';' if parens == 0 => {
+                printer.push(character);
+                printer.break_line();
+            }
+            ':' if kind == SourceKind::Json => printer.push_str(": "),
+            '\n' | '\r' => printer.push(' '),
+            character => printer.push(character),
+        }
+    }
+    printer.break_line();
+    Some(printer.lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_minified, pretty_print};
+
+    #[test]
+    fn minified_sources_are_reindented_by_brackets() {
+        assert_eq!(
+            pretty_print("a.json", r#"{"a":[1,2],"b":{"c":"x,{y}"}}"#).expect("json prints"),
+            vec![
+                "{",
+                r#"  "a": ["#,
+                "    1,",
+                "    2",
+                "  ],",
+                r#"  "b": {"#,
+                r#"    "c": "x,{y}""#,
+                "  }",
+                "}",
+            ]
+        );
+        assert_eq!(
+            pretty_print("a.min.js", "function f(a,b){for(;;){g('}');}}/* c */x=1;")
+                .expect("js prints"),
+            vec![
+                "function f(a,b){",
+                "  for(;;){",
+                "    g('}');",
+                "  }",
+                "}",
+                "/* c */",
+                "x=1;",
+            ]
+        );
+        assert!(pretty_print("a.rs", "fn main() {}").is_none());
+
+        let minified = vec!["a{color:red}".repeat(100)];
+        assert!(is_minified("site.min.css", &minified));
+        assert!(!is_minified("site.css", &["a {}".to_string()]));
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::OnceCell;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Phase {
+    ComparisonResolution,
+    DescriptorListing,
+    ContentReads,
+    HighlightDiffs,
+    SyntaxSetBuild,
+    SyntaxDetection,
+}
+
+const PHASES: [Phase; 6] = [
+    Phase::ComparisonResolution,
+    Phase::DescriptorListing,
+    Phase::ContentReads,
+    Phase::HighlightDiffs,
+    Phase::SyntaxSetBuild,
+    Phase::SyntaxDetection,
+];
+
+impl Phase {
+    fn label(self) -> &'static str {
+        match self {
+            Phase::ComparisonResolution => "comparison resolution",
+            Phase::DescriptorListing => "descriptor listing",
+            Phase::ContentReads => "content reads",
+            Phase::HighlightDiffs => "highlight diffs",
+            Phase::SyntaxSetBuild => "syntax set build",
+            Phase::SyntaxDetection => "syntax detection",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct PhaseTiming {
+    total: Duration,
+    calls: usize,
+}
+
+/// Per-phase timings, only collected when `--profile` is passed.
+static PROFILE: OnceCell<Mutex<Vec<(Phase, PhaseTiming)>>> = OnceCell::new();
+
+pub fn enable() {
+    let _ = PROFILE.set(Mutex::new(Vec::new()));
+}
+
+pub fn time<T>(phase: Phase, body: impl FnOnce() -> T) -> T {
+    let Some(profile) = PROFILE.get() else {
+        return body();
+    };
+
+    let started_at = Instant::now();
+    let result = body();
+    let elapsed = started_at.elapsed();
+
+    if let Ok(mut timings) = profile.lock() {
+        match timings
+            .iter_mut()
+            .find(|(entry_phase, _)| *entry_phase == phase)
+        {
+            Some((_, timing)) => {
+                timing.total += elapsed;
+                timing.calls += 1;
+            }
+            None => timings.push((
+                phase,
+                PhaseTiming {
+                    total: elapsed,
+                    calls: 1,
+                },
+            )),
+        }
+    }
+    result
+}
+
+fn format_report(timings: &[(Phase, PhaseTiming)]) -> String {
+    let mut lines = vec!["deff profile:".to_string()];
+    for phase in PHASES {
+        let timing = timings
+            .iter()
+            .find(|(entry_phase, _)| *entry_phase == phase)
+            .map(|(_, timing)| *timing)
+            .unwrap_or_default();
+        lines.push(format!(
+            "  {:<22} {:>10.1} ms  ({} calls)",
+            phase.label(),
+            timing.total.as_secs_f64() * 1000.0,
+            timing.calls
+        ));
+    }
+    lines.join("\n")
+}
+
+pub fn report() -> Option<String> {
+    let timings = PROFILE.get()?.lock().ok()?;
+    Some(format_report(&timings))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Phase, PhaseTiming, format_report};
+
+    #[test]
+    fn report_lists_every_phase_including_unmeasured_ones() {
+        let report = format_report(&[(
+            Phase::ContentReads,
+            PhaseTiming {
+                total: Duration::from_millis(12),
+                calls: 3,
+            },
+        )]);
+
+        assert!(report.contains("content reads"));
+        assert!(report.contains("12.0 ms  (3 calls)"));
+        assert!(report.contains("syntax set build"));
+        assert_eq!(report.lines().count(), 7);
+    }
+}
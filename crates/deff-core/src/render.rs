@@ -0,0 +1,1447 @@
+use std::{
+    collections::HashSet,
+    hash::{DefaultHasher, Hash, Hasher},
+    ops::Range,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    symbols::line,
+    text::{Line, Span},
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Theme, ThemeSet},
+    parsing::SyntaxReference,
+};
+
+use crate::{
+    fold::{DisplayRow, Fold, build_display_rows},
+    model::{
+        AnnotationSeverity, DiffFileView, LineHighlightKind, PaneOffsets, PaneSide, RenderOptions,
+        ResolvedComparison, ReviewState, StrategyId, ThemeMode, ViewMode,
+    },
+    syntax::syntax_set,
+    text::{fit_line, normalize_content, normalized_char_count, pad_to_width, slice_chars},
+};
+
+const HEADER_LINE_COUNT: usize = 4;
+const FOOTER_LINE_COUNT: usize = 2;
+const FRAME_DIVIDER_LINE_COUNT: usize = 2;
+const MIN_BODY_LINE_COUNT: usize = 3;
+const PANE_SEPARATOR: &str = " | ";
+const LIST_HEADER_LINE_COUNT: usize = 2;
+const LIST_FOOTER_LINE_COUNT: usize = 2;
+const POPUP_MARGIN_PERCENT: u16 = 10;
+const POPUP_BORDER_LINE_COUNT: usize = 2;
+
+const COLOR_BG_DELETED: Color = Color::Rgb(48, 24, 24);
+const COLOR_BG_ADDED: Color = Color::Rgb(22, 34, 24);
+const COLOR_BG_DELETED_FOCUSED: Color = Color::Rgb(72, 32, 32);
+const COLOR_BG_ADDED_FOCUSED: Color = Color::Rgb(32, 52, 32);
+const COLOR_BG_WHITESPACE_ERROR: Color = Color::Rgb(150, 30, 30);
+const COLOR_RULER: Color = Color::Rgb(90, 90, 110);
+const COLOR_BG_FOLD: Color = Color::Rgb(36, 38, 52);
+const COLOR_BG_SELECTED: Color = Color::Rgb(60, 70, 120);
+const COLOR_BG_GUTTER_UNCOVERED: Color = Color::Rgb(120, 84, 0);
+const COLOR_BG_GUTTER_ERROR: Color = Color::Rgb(140, 30, 30);
+const COLOR_BG_GUTTER_WARNING: Color = Color::Rgb(150, 120, 20);
+const COLOR_BG_GUTTER_NOTE: Color = Color::Rgb(40, 80, 140);
+/// Lines longer than this (minified bundles, base64 blobs) are never tokenized.
+const MAX_HIGHLIGHTED_LINE_CHARS: usize = 2_000;
+/// A line whose tokenizing takes longer than this is shown unstyled from then on.
+const LINE_HIGHLIGHT_BUDGET: Duration = Duration::from_millis(20);
+const DARK_THEME_CANDIDATES: &[&str] = &[
+    "base16-ocean.dark",
+    "base16-eighties.dark",
+    "base16-mocha.dark",
+    "Solarized (dark)",
+];
+const LIGHT_THEME_CANDIDATES: &[&str] =
+    &["InspiredGitHub", "Solarized (light)", "base16-ocean.light"];
+
+static THEME_SET: Lazy<ThemeSet> = Lazy::new(ThemeSet::load_defaults);
+/// Hashes of the `(language, content)` pairs that went over `LINE_HIGHLIGHT_BUDGET`.
+static SLOW_LINES: Lazy<Mutex<HashSet<u64>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+static DARK_THEME: Lazy<Theme> = Lazy::new(|| load_theme(true));
+static LIGHT_THEME: Lazy<Theme> = Lazy::new(|| load_theme(false));
+
+fn load_theme(prefer_dark_theme: bool) -> Theme {
+    let candidates = if prefer_dark_theme {
+        DARK_THEME_CANDIDATES
+    } else {
+        LIGHT_THEME_CANDIDATES
+    };
+
+    candidates
+        .iter()
+        .find_map(|name| THEME_SET.themes.get(*name).cloned())
+        .or_else(|| {
+            if prefer_dark_theme {
+                LIGHT_THEME_CANDIDATES
+                    .iter()
+                    .find_map(|name| THEME_SET.themes.get(*name).cloned())
+            } else {
+                DARK_THEME_CANDIDATES
+                    .iter()
+                    .find_map(|name| THEME_SET.themes.get(*name).cloned())
+            }
+        })
+        .or_else(|| THEME_SET.themes.values().next().cloned())
+        .expect("syntect should always provide at least one default theme")
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FrameLayout<'a> {
+    pub columns: usize,
+    pub body_line_count: usize,
+    pub separator: &'a str,
+    /// The panes are boxed: a border column on the outside of each, with the separator
+    /// as their shared border.
+    pub bordered: bool,
+    pub options: &'a RenderOptions,
+    theme: &'static Theme,
+    pub left_pane_width: usize,
+    pub right_pane_width: usize,
+    pub left_content_width: usize,
+    pub right_content_width: usize,
+    pub line_number_width: usize,
+    pub body_start_row: usize,
+    pub body_end_row: usize,
+    pub left_pane_start_column: usize,
+    pub left_pane_end_column: usize,
+    pub right_pane_start_column: usize,
+    pub right_pane_end_column: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct RenderFrameOutput {
+    pub lines: Vec<Line<'static>>,
+    pub max_scroll: usize,
+    pub clamped_pane_offsets: PaneOffsets,
+}
+
+fn parse_terminal_palette_index(value: &str) -> Option<usize> {
+    value.trim().parse::<usize>().ok()
+}
+
+/// High-contrast changed lines: a bright bold foreground, underlined in the focused hunk.
+fn high_contrast_change_style(kind: LineHighlightKind, focused: bool) -> Style {
+    let color = match kind {
+        LineHighlightKind::Deleted => Color::LightRed,
+        LineHighlightKind::Added => Color::LightGreen,
+        LineHighlightKind::None => return Style::default(),
+    };
+    let style = Style::default().fg(color).add_modifier(Modifier::BOLD);
+    if focused {
+        style.add_modifier(Modifier::UNDERLINED)
+    } else {
+        style
+    }
+}
+
+/// The focused pane's title in the divider or border.
+fn focused_title_style(high_contrast: bool) -> Style {
+    if high_contrast {
+        Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+    } else {
+        Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD)
+    }
+}
+
+fn should_prefer_dark_theme(options: &RenderOptions) -> bool {
+    match options.theme_mode {
+        ThemeMode::Dark => return true,
+        ThemeMode::Light => return false,
+        ThemeMode::Auto => {}
+    }
+
+    if let Ok(value) = std::env::var("DEFF_THEME") {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "dark" => return true,
+            "light" => return false,
+            _ => {}
+        }
+    }
+
+    if let Some(dark) = options.dark_background {
+        return dark;
+    }
+
+    if let Ok(value) = std::env::var("COLORFGBG") {
+        let background_index = value
+            .split([';', ':'])
+            .next_back()
+            .and_then(parse_terminal_palette_index);
+
+        if let Some(index) = background_index {
+            return index <= 6;
+        }
+    }
+
+    true
+}
+
+fn syntax_for_language(language: &str) -> Option<&'static SyntaxReference> {
+    let syntaxes = syntax_set();
+
+    syntaxes
+        .find_syntax_by_name(language)
+        .or_else(|| syntaxes.find_syntax_by_token(language))
+        .or_else(|| syntaxes.find_syntax_by_extension(language))
+}
+
+fn base_style(tint_background: Option<Color>) -> Style {
+    let mut style = Style::default();
+    if let Some(color) = tint_background {
+        style = style.bg(color);
+    }
+    style
+}
+
+fn syntect_style_to_ratatui(
+    style: syntect::highlighting::Style,
+    tint_background: Option<Color>,
+) -> Style {
+    let mut mapped = Style::default().fg(Color::Rgb(
+        style.foreground.r,
+        style.foreground.g,
+        style.foreground.b,
+    ));
+
+    if let Some(color) = tint_background {
+        mapped = mapped.bg(color);
+    }
+
+    if style.font_style.contains(FontStyle::BOLD) {
+        mapped = mapped.add_modifier(Modifier::BOLD);
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        mapped = mapped.add_modifier(Modifier::ITALIC);
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        mapped = mapped.add_modifier(Modifier::UNDERLINED);
+    }
+
+    mapped
+}
+
+fn highlight_visible_content(
+    value: &str,
+    language: Option<&str>,
+    tint_background: Option<Color>,
+    theme: &Theme,
+) -> Vec<Span<'static>> {
+    let default_span = || vec![Span::styled(value.to_string(), base_style(tint_background))];
+
+    let Some(language_name) = language else {
+        return default_span();
+    };
+
+    if value.trim().is_empty() {
+        return default_span();
+    }
+
+    let Some(syntax) = syntax_for_language(language_name) else {
+        return default_span();
+    };
+
+    let mut hasher = DefaultHasher::new();
+    (language_name, value).hash(&mut hasher);
+    let line_hash = hasher.finish();
+    if SLOW_LINES
+        .lock()
+        .is_ok_and(|slow_lines| slow_lines.contains(&line_hash))
+    {
+        return default_span();
+    }
+
+    let syntaxes = syntax_set();
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let started_at = Instant::now();
+    let highlighted = match highlighter.highlight_line(value, syntaxes) {
+        Ok(ranges) => ranges,
+        Err(_) => return default_span(),
+    };
+    // syntect cannot be interrupted, so a slow line is paid for once and then skipped,
+    // keeping scrolling past it responsive.
+    if started_at.elapsed() > LINE_HIGHLIGHT_BUDGET
+        && let Ok(mut slow_lines) = SLOW_LINES.lock()
+    {
+        slow_lines.insert(line_hash);
+    }
+
+    if highlighted.is_empty() {
+        return default_span();
+    }
+
+    highlighted
+        .into_iter()
+        .map(|(style, text)| {
+            Span::styled(
+                text.to_string(),
+                syntect_style_to_ratatui(style, tint_background),
+            )
+        })
+        .collect()
+}
+
+/// Restyles the characters in `range` (counted across all of `spans`), splitting spans
+/// at its edges.
+fn restyle_char_range(
+    spans: Vec<Span<'static>>,
+    range: Range<usize>,
+    restyle: impl Fn(String, Style) -> Span<'static>,
+) -> Vec<Span<'static>> {
+    let mut restyled = Vec::with_capacity(spans.len() + 2);
+    let mut start = 0;
+    for span in spans {
+        let length = normalized_char_count(&span.content);
+        let (from, to) = (
+            range.start.clamp(start, start + length) - start,
+            range.end.clamp(start, start + length) - start,
+        );
+        start += length;
+        if from == to {
+            restyled.push(span);
+            continue;
+        }
+        let characters: Vec<char> = span.content.chars().collect();
+        let piece = |from: usize, to: usize| characters[from..to].iter().collect::<String>();
+        if from > 0 {
+            restyled.push(Span::styled(piece(0, from), span.style));
+        }
+        restyled.push(restyle(piece(from, to), span.style));
+        if to < length {
+            restyled.push(Span::styled(piece(to, length), span.style));
+        }
+    }
+    restyled
+}
+
+/// The ruler shows through blanks and tints the character it crosses.
+fn overlay_ruler(text: String, style: Style, high_contrast: bool) -> Span<'static> {
+    match (text.trim().is_empty(), high_contrast) {
+        (true, false) => Span::styled("│", style.fg(COLOR_RULER)),
+        (true, true) => Span::styled("│", style.add_modifier(Modifier::BOLD)),
+        (false, false) => Span::styled(text, style.bg(COLOR_RULER)),
+        (false, true) => Span::styled(text, style.add_modifier(Modifier::REVERSED)),
+    }
+}
+
+/// How a body row relates to the line cursor; shown on the line-number gutter.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RowMarker {
+    Cursor,
+    Selected,
+}
+
+impl RowMarker {
+    fn for_row(
+        cursor_line: usize,
+        selection: Option<(usize, usize)>,
+        display_row: DisplayRow,
+    ) -> Option<Self> {
+        let (first, last) = (display_row.first_line(), display_row.last_line());
+        if (first..=last).contains(&cursor_line) {
+            return Some(RowMarker::Cursor);
+        }
+        selection
+            .filter(|&(start, end)| start <= last && first <= end)
+            .map(|_| RowMarker::Selected)
+    }
+
+    fn apply(marker: Option<Self>, style: Style, high_contrast: bool) -> Style {
+        match marker {
+            Some(RowMarker::Cursor) => style.add_modifier(Modifier::REVERSED | Modifier::BOLD),
+            Some(RowMarker::Selected) if high_contrast => {
+                style.add_modifier(Modifier::UNDERLINED | Modifier::BOLD)
+            }
+            Some(RowMarker::Selected) => style.bg(COLOR_BG_SELECTED),
+            None => style,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn format_pane_line(
+    layout: &FrameLayout,
+    line_value: Option<&str>,
+    source_line: Option<usize>,
+    pane_width: usize,
+    line_highlight_kind: LineHighlightKind,
+    horizontal_offset: usize,
+    language: Option<&str>,
+    focused: bool,
+    gutter_background: Option<Color>,
+    marker: Option<RowMarker>,
+    trailing_whitespace: bool,
+) -> Vec<Span<'static>> {
+    let line_number_text = match source_line.filter(|_| line_value.is_some()) {
+        Some(index) => format!("{:>width$}", index + 1, width = layout.line_number_width),
+        None => " ".repeat(layout.line_number_width),
+    };
+    let prefix = format!("{line_number_text} ");
+    let prefix_width = normalized_char_count(&prefix);
+    let high_contrast = layout.options.high_contrast;
+    let tint_background = match (line_highlight_kind, focused) {
+        _ if high_contrast => None,
+        (LineHighlightKind::Deleted, true) => Some(COLOR_BG_DELETED_FOCUSED),
+        (LineHighlightKind::Deleted, false) => Some(COLOR_BG_DELETED),
+        (LineHighlightKind::Added, true) => Some(COLOR_BG_ADDED_FOCUSED),
+        (LineHighlightKind::Added, false) => Some(COLOR_BG_ADDED),
+        (LineHighlightKind::None, _) => None,
+    };
+
+    if pane_width <= prefix_width {
+        return vec![Span::styled(
+            fit_line(&prefix, pane_width),
+            RowMarker::apply(marker, base_style(tint_background), high_contrast),
+        )];
+    }
+
+    let content_width = pane_width - prefix_width;
+    let content_text = line_value.map(normalize_content).unwrap_or_default();
+    let visible_content = slice_chars(&content_text, horizontal_offset, content_width);
+    let padded_visible_content = pad_to_width(visible_content, content_width);
+
+    // High contrast marks changed lines with a reverse-video line number instead.
+    let change_style = high_contrast_change_style(line_highlight_kind, focused);
+    let gutter_style = match gutter_background {
+        None if high_contrast && line_highlight_kind != LineHighlightKind::None => {
+            change_style.add_modifier(Modifier::REVERSED)
+        }
+        _ => base_style(gutter_background.or(tint_background)),
+    };
+    let mut spans = vec![Span::styled(
+        prefix,
+        RowMarker::apply(marker, gutter_style, high_contrast),
+    )];
+    let mut content_spans = if high_contrast {
+        vec![Span::styled(padded_visible_content, change_style)]
+    } else {
+        let language =
+            language.filter(|_| normalized_char_count(&content_text) <= MAX_HIGHLIGHTED_LINE_CHARS);
+        highlight_visible_content(
+            &padded_visible_content,
+            language,
+            tint_background,
+            layout.theme,
+        )
+    };
+    if trailing_whitespace {
+        let whitespace_start = normalized_char_count(content_text.trim_end());
+        let whitespace_end = normalized_char_count(&content_text);
+        content_spans = restyle_char_range(
+            content_spans,
+            whitespace_start.saturating_sub(horizontal_offset)
+                ..whitespace_end.saturating_sub(horizontal_offset),
+            |text, style| Span::styled(text, style.bg(COLOR_BG_WHITESPACE_ERROR)),
+        );
+    }
+    if let Some(column) = layout.options.ruler
+        && column >= horizontal_offset
+    {
+        let position = column - horizontal_offset;
+        content_spans = restyle_char_range(content_spans, position..position + 1, |text, style| {
+            overlay_ruler(text, style, high_contrast)
+        });
+    }
+    // The last column says the line goes on past the pane; `e` shows it whole.
+    if normalized_char_count(&content_text) > horizontal_offset + content_width {
+        content_spans = restyle_char_range(
+            content_spans,
+            content_width - 1..content_width,
+            |_, style| Span::styled("…", style.add_modifier(Modifier::BOLD)),
+        );
+    }
+    spans.extend(content_spans);
+    spans
+}
+
+#[allow(clippy::too_many_arguments)]
+fn format_fold_line(
+    layout: &FrameLayout,
+    line_value: Option<&str>,
+    source_line: Option<usize>,
+    fold: Fold,
+    fold_contains_changes: bool,
+    pane_width: usize,
+    horizontal_offset: usize,
+    marker: Option<RowMarker>,
+) -> Vec<Span<'static>> {
+    let line_number_text = match source_line.filter(|_| line_value.is_some()) {
+        Some(index) => format!("{:>width$}", index + 1, width = layout.line_number_width),
+        None => " ".repeat(layout.line_number_width),
+    };
+    let summary = format!(
+        "  [+{} lines{}]",
+        fold.hidden_line_count(),
+        if fold_contains_changes {
+            ", changed"
+        } else {
+            ""
+        }
+    );
+    let content_text = line_value.map(normalize_content).unwrap_or_default();
+    let visible_content = slice_chars(&content_text, horizontal_offset, usize::MAX);
+    let line_text = format!("{line_number_text} {visible_content}{summary}");
+
+    vec![Span::styled(
+        fit_line(&line_text, pane_width),
+        RowMarker::apply(
+            marker,
+            if layout.options.high_contrast {
+                Style::default().add_modifier(Modifier::BOLD | Modifier::ITALIC)
+            } else {
+                Style::default()
+                    .bg(COLOR_BG_FOLD)
+                    .add_modifier(Modifier::ITALIC)
+            },
+            layout.options.high_contrast,
+        ),
+    )]
+}
+
+fn get_right_gutter_background(file: &DiffFileView, line_index: usize) -> Option<Color> {
+    match file.annotation_severity_at(line_index) {
+        Some(AnnotationSeverity::Error) => Some(COLOR_BG_GUTTER_ERROR),
+        Some(AnnotationSeverity::Warning) => Some(COLOR_BG_GUTTER_WARNING),
+        Some(AnnotationSeverity::Note) => Some(COLOR_BG_GUTTER_NOTE),
+        None => file
+            .right_uncovered_line_indexes
+            .contains(&line_index)
+            .then_some(COLOR_BG_GUTTER_UNCOVERED),
+    }
+}
+
+pub fn short_commit(commit: &str) -> String {
+    commit.chars().take(8).collect()
+}
+
+pub fn get_body_line_count(rows: usize) -> usize {
+    rows.saturating_sub(HEADER_LINE_COUNT + FOOTER_LINE_COUNT + FRAME_DIVIDER_LINE_COUNT)
+        .max(MIN_BODY_LINE_COUNT)
+}
+
+pub fn create_frame_layout(
+    columns: u16,
+    rows: u16,
+    max_lines: usize,
+    options: &RenderOptions,
+) -> FrameLayout<'_> {
+    build_frame_layout(columns, rows, max_lines, options, options.bordered)
+}
+
+fn build_frame_layout(
+    columns: u16,
+    rows: u16,
+    max_lines: usize,
+    options: &RenderOptions,
+    bordered: bool,
+) -> FrameLayout<'_> {
+    let columns = columns as usize;
+    let rows = rows as usize;
+    let body_line_count = get_body_line_count(rows);
+    let separator = if bordered {
+        line::VERTICAL
+    } else {
+        options.separator.as_deref().unwrap_or(PANE_SEPARATOR)
+    };
+    let separator_width = normalized_char_count(separator);
+    let border_width = if bordered { 1 } else { 0 };
+    let available_pane_width = columns
+        .saturating_sub(separator_width + 2 * border_width)
+        .max(2);
+    let left_pane_width = (available_pane_width / 2).max(1);
+    let right_pane_width = available_pane_width.saturating_sub(left_pane_width).max(1);
+    let line_number_width = max_lines.to_string().len().max(3);
+    let left_content_width = left_pane_width.saturating_sub(line_number_width + 1);
+    let right_content_width = right_pane_width.saturating_sub(line_number_width + 1);
+    let body_start_row = HEADER_LINE_COUNT + 1;
+    let body_end_row = body_start_row + body_line_count.saturating_sub(1);
+    let left_pane_start_column = border_width;
+    let left_pane_end_column = left_pane_start_column + left_pane_width.saturating_sub(1);
+    let right_pane_start_column = left_pane_start_column + left_pane_width + separator_width;
+    let right_pane_end_column = right_pane_start_column + right_pane_width.saturating_sub(1);
+
+    FrameLayout {
+        columns,
+        body_line_count,
+        separator,
+        bordered,
+        options,
+        theme: if should_prefer_dark_theme(options) {
+            &DARK_THEME
+        } else {
+            &LIGHT_THEME
+        },
+        left_pane_width,
+        right_pane_width,
+        left_content_width,
+        right_content_width,
+        line_number_width,
+        body_start_row,
+        body_end_row,
+        left_pane_start_column,
+        left_pane_end_column,
+        right_pane_start_column,
+        right_pane_end_column,
+    }
+}
+
+fn get_max_pane_offset(max_content_length: usize, content_width: usize) -> usize {
+    if content_width == 0 {
+        0
+    } else {
+        max_content_length.saturating_sub(content_width)
+    }
+}
+
+pub fn get_max_pane_offsets(file: &DiffFileView, layout: &FrameLayout) -> PaneOffsets {
+    PaneOffsets {
+        left: get_max_pane_offset(file.left_max_content_length, layout.left_content_width),
+        right: get_max_pane_offset(file.right_max_content_length, layout.right_content_width),
+    }
+}
+
+pub fn get_pane_for_column(column: usize, layout: &FrameLayout) -> Option<PaneSide> {
+    if column >= layout.left_pane_start_column && column <= layout.left_pane_end_column {
+        return Some(PaneSide::Left);
+    }
+
+    if column >= layout.right_pane_start_column && column <= layout.right_pane_end_column {
+        return Some(PaneSide::Right);
+    }
+
+    None
+}
+
+/// Renders one side-by-side body row; rows past the end of the file render as blank
+/// panes.
+pub fn render_display_row(
+    file: &DiffFileView,
+    display_row: DisplayRow,
+    layout: &FrameLayout,
+    pane_offsets: PaneOffsets,
+    focused_hunk_lines: Option<&HashSet<usize>>,
+    marker: Option<RowMarker>,
+) -> Line<'static> {
+    let line_number = match display_row {
+        DisplayRow::Fold(fold) => {
+            let fold_contains_changes =
+                (fold.start..=fold.end).any(|line_index| file.is_line_changed(line_index));
+            let mut spans = format_fold_line(
+                layout,
+                file.left_lines.get(fold.start).map(String::as_str),
+                file.source_line_range(PaneSide::Left, fold.start, fold.end)
+                    .map(|(first, _)| first),
+                fold,
+                fold_contains_changes,
+                layout.left_pane_width,
+                pane_offsets.left,
+                marker,
+            );
+            spans.push(Span::raw(layout.separator.to_string()));
+            spans.extend(format_fold_line(
+                layout,
+                file.right_lines.get(fold.start).map(String::as_str),
+                file.source_line_range(PaneSide::Right, fold.start, fold.end)
+                    .map(|(first, _)| first),
+                fold,
+                fold_contains_changes,
+                layout.right_pane_width,
+                pane_offsets.right,
+                marker,
+            ));
+            return frame_pane_row(layout, spans);
+        }
+        DisplayRow::Line(line_number) => line_number,
+    };
+
+    // Filler rows render like rows past the end of the file.
+    let left_source_line = file.source_line(PaneSide::Left, line_number);
+    let right_source_line = file.source_line(PaneSide::Right, line_number);
+    let left_line = left_source_line
+        .and(file.left_lines.get(line_number))
+        .map(String::as_str);
+    let right_line = right_source_line
+        .and(file.right_lines.get(line_number))
+        .map(String::as_str);
+    let left_highlight_kind = if file.left_deleted_line_indexes.contains(&line_number) {
+        LineHighlightKind::Deleted
+    } else {
+        LineHighlightKind::None
+    };
+    let right_highlight_kind = if file.right_added_line_indexes.contains(&line_number) {
+        LineHighlightKind::Added
+    } else {
+        LineHighlightKind::None
+    };
+
+    let focused = focused_hunk_lines
+        .map(|lines| lines.contains(&line_number))
+        .unwrap_or(false);
+
+    let (left_language, right_language) = file.highlighted_languages();
+    let left_rendered = format_pane_line(
+        layout,
+        left_line,
+        left_source_line,
+        layout.left_pane_width,
+        left_highlight_kind,
+        pane_offsets.left,
+        left_language,
+        focused,
+        None,
+        marker,
+        false,
+    );
+    let right_rendered = format_pane_line(
+        layout,
+        right_line,
+        right_source_line,
+        layout.right_pane_width,
+        right_highlight_kind,
+        pane_offsets.right,
+        right_language,
+        focused,
+        get_right_gutter_background(file, line_number),
+        marker,
+        file.right_trailing_whitespace_line_indexes
+            .contains(&line_number),
+    );
+
+    let mut spans = Vec::with_capacity(left_rendered.len() + right_rendered.len() + 1);
+    spans.extend(left_rendered);
+    spans.push(Span::raw(layout.separator.to_string()));
+    spans.extend(right_rendered);
+    frame_pane_row(layout, spans)
+}
+
+/// Renders one row of the unified view across both panes: the base and head line numbers,
+/// a `-`/`+` gutter, and the one line the row holds (see `has_unified_rows`).
+fn render_unified_row(
+    file: &DiffFileView,
+    display_row: DisplayRow,
+    layout: &FrameLayout,
+    horizontal_offset: usize,
+    focused_hunk_lines: Option<&HashSet<usize>>,
+    marker: Option<RowMarker>,
+) -> Line<'static> {
+    let width =
+        layout.left_pane_width + normalized_char_count(layout.separator) + layout.right_pane_width;
+    let base_number_width = layout.line_number_width + 1;
+    let pane_width = width.saturating_sub(base_number_width + 2);
+    let number = |line: Option<usize>| match line {
+        Some(index) => format!("{:>width$} ", index + 1, width = layout.line_number_width),
+        None => " ".repeat(base_number_width),
+    };
+
+    let row = match display_row {
+        DisplayRow::Fold(fold) => {
+            let side = file.primary_side();
+            let lines = match side {
+                PaneSide::Left => &file.left_lines,
+                PaneSide::Right => &file.right_lines,
+            };
+            let first_line = |side| {
+                file.source_line_range(side, fold.start, fold.end)
+                    .map(|(first, _)| first)
+            };
+            let mut spans = vec![
+                Span::raw(number(first_line(PaneSide::Left))),
+                Span::raw("  "),
+            ];
+            spans.extend(format_fold_line(
+                layout,
+                lines.get(fold.start).map(String::as_str),
+                first_line(side),
+                fold,
+                (fold.start..=fold.end).any(|line_index| file.is_line_changed(line_index)),
+                pane_width,
+                horizontal_offset,
+                marker,
+            ));
+            return frame_pane_row(layout, spans);
+        }
+        DisplayRow::Line(row) => row,
+    };
+
+    let left_source_line = file.source_line(PaneSide::Left, row);
+    let right_source_line = file.source_line(PaneSide::Right, row);
+    let (sign, kind, side, line) = if file.right_added_line_indexes.contains(&row) {
+        (
+            "+",
+            LineHighlightKind::Added,
+            PaneSide::Right,
+            &file.right_lines,
+        )
+    } else if file.left_deleted_line_indexes.contains(&row) {
+        (
+            "-",
+            LineHighlightKind::Deleted,
+            PaneSide::Left,
+            &file.left_lines,
+        )
+    } else if right_source_line.is_some() {
+        (
+            " ",
+            LineHighlightKind::None,
+            PaneSide::Right,
+            &file.right_lines,
+        )
+    } else {
+        (
+            " ",
+            LineHighlightKind::None,
+            PaneSide::Left,
+            &file.left_lines,
+        )
+    };
+    let source_line = file.source_line(side, row);
+    let line = source_line.and(line.get(row)).map(String::as_str);
+    let (left_language, right_language) = file.highlighted_languages();
+    let (language, gutter_background, trailing_whitespace) = match side {
+        PaneSide::Left => (left_language, None, false),
+        PaneSide::Right => (
+            right_language,
+            get_right_gutter_background(file, row),
+            file.right_trailing_whitespace_line_indexes.contains(&row),
+        ),
+    };
+    let base_number = match kind {
+        LineHighlightKind::Added => None,
+        _ => left_source_line.filter(|_| line.is_some()),
+    };
+    let mut pane = format_pane_line(
+        layout,
+        line,
+        match kind {
+            LineHighlightKind::Deleted => None,
+            _ => right_source_line,
+        },
+        pane_width,
+        kind,
+        horizontal_offset,
+        language,
+        focused_hunk_lines.is_some_and(|lines| lines.contains(&row)),
+        gutter_background,
+        marker,
+        trailing_whitespace,
+    );
+    // The gutter takes the style of the line number before it.
+    let gutter_style = pane.first().map(|span| span.style).unwrap_or_default();
+    let mut spans = vec![Span::styled(number(base_number), gutter_style)];
+    if !pane.is_empty() {
+        let head_number = pane.remove(0);
+        spans.push(head_number);
+    }
+    spans.push(Span::styled(format!("{sign} "), gutter_style));
+    spans.extend(pane);
+    frame_pane_row(layout, spans)
+}
+
+fn frame_pane_row(layout: &FrameLayout, mut spans: Vec<Span<'static>>) -> Line<'static> {
+    if layout.bordered {
+        spans.insert(0, Span::raw(line::VERTICAL));
+        spans.push(Span::raw(line::VERTICAL));
+    }
+    Line::from(spans)
+}
+
+/// Renders a whole file as side-by-side lines without the TUI header and footer,
+/// collapsing the given folds.
+pub fn render_file_lines(
+    file: &DiffFileView,
+    folds: &[Fold],
+    columns: u16,
+    options: &RenderOptions,
+) -> Vec<Line<'static>> {
+    let layout = build_frame_layout(columns, 0, file.line_count(), options, false);
+    build_display_rows(file.line_count(), folds)
+        .into_iter()
+        .map(|display_row| {
+            render_display_row(
+                file,
+                display_row,
+                &layout,
+                PaneOffsets::default(),
+                None,
+                None,
+            )
+        })
+        .collect()
+}
+
+/// The rule above the body; the focused pane's half is drawn as a titled, bold `=` bar.
+fn render_pane_divider(layout: &FrameLayout, focused_pane: Option<PaneSide>) -> Line<'static> {
+    let Some(focused_pane) = focused_pane else {
+        return Line::from(fit_line(&"-".repeat(layout.columns.max(1)), layout.columns));
+    };
+
+    let focused_style = focused_title_style(layout.options.high_contrast);
+    let segment = |side: PaneSide, width: usize| {
+        if side == focused_pane {
+            let title = match side {
+                PaneSide::Left => "= base (focused) ",
+                PaneSide::Right => "= head (focused) ",
+            };
+            let bar = format!("{title}{}", "=".repeat(width));
+            Span::styled(slice_chars(&bar, 0, width), focused_style)
+        } else {
+            Span::raw("-".repeat(width))
+        }
+    };
+
+    Line::from(vec![
+        segment(PaneSide::Left, layout.left_pane_width),
+        Span::raw("-".repeat(normalized_char_count(layout.separator))),
+        segment(PaneSide::Right, layout.right_pane_width),
+    ])
+}
+
+/// The top border of boxed panes, titled with each side's ref; the focused pane's title
+/// is bold.
+fn render_pane_top_border(
+    layout: &FrameLayout,
+    comparison: &ResolvedComparison,
+    focused_pane: Option<PaneSide>,
+) -> Line<'static> {
+    let base_title = if comparison.strategy_id == StrategyId::Unstaged {
+        "base: index".to_string()
+    } else {
+        format!(
+            "base: {} ({})",
+            comparison.base_ref,
+            short_commit(&comparison.base_commit)
+        )
+    };
+    let head_title = match comparison.strategy_id {
+        StrategyId::Staged | StrategyId::Amend => "head: index".to_string(),
+        _ if comparison.includes_uncommitted => "head: working tree".to_string(),
+        _ => format!(
+            "head: {} ({})",
+            comparison.head_ref,
+            short_commit(&comparison.head_commit)
+        ),
+    };
+    let titles = [
+        (PaneSide::Left, base_title, layout.left_pane_width),
+        (PaneSide::Right, head_title, layout.right_pane_width),
+    ];
+    let mut spans = vec![Span::raw(line::TOP_LEFT)];
+    for (index, (side, title, width)) in titles.into_iter().enumerate() {
+        if index > 0 {
+            spans.push(Span::raw(line::HORIZONTAL_DOWN));
+        }
+        let focused = focused_pane == Some(side);
+        let title = slice_chars(
+            &format!(
+                "{} {title}{} ",
+                line::HORIZONTAL,
+                if focused { " (focused)" } else { "" }
+            ),
+            0,
+            width,
+        );
+        let fill = line::HORIZONTAL.repeat(width - normalized_char_count(&title));
+        let title_style = if focused {
+            focused_title_style(layout.options.high_contrast)
+        } else {
+            Style::default()
+        };
+        spans.push(Span::styled(title, title_style));
+        spans.push(Span::raw(fill));
+    }
+    spans.push(Span::raw(line::TOP_RIGHT));
+    Line::from(spans)
+}
+
+fn render_pane_bottom_border(layout: &FrameLayout) -> Line<'static> {
+    Line::from(format!(
+        "{}{}{}{}{}",
+        line::BOTTOM_LEFT,
+        line::HORIZONTAL.repeat(layout.left_pane_width),
+        line::HORIZONTAL_UP,
+        line::HORIZONTAL.repeat(layout.right_pane_width),
+        line::BOTTOM_RIGHT
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn render_frame(
+    files: &[DiffFileView],
+    comparison: &ResolvedComparison,
+    file_index: usize,
+    scroll_offset: usize,
+    pane_offsets: PaneOffsets,
+    reviewed_count: usize,
+    current_file_review: ReviewState,
+    hide_whitespace_only: bool,
+    focused_pane: Option<PaneSide>,
+    cursor_line: usize,
+    selection: Option<(usize, usize)>,
+    status_text: String,
+    focused_hunk_lines: Option<&HashSet<usize>>,
+    display_rows: &[DisplayRow],
+    view_mode: ViewMode,
+    columns: u16,
+    rows: u16,
+    options: &RenderOptions,
+) -> RenderFrameOutput {
+    let current_file = &files[file_index];
+    let max_lines = current_file.line_count();
+    let layout = create_frame_layout(columns, rows, max_lines, options);
+    let max_scroll = display_rows.len().saturating_sub(layout.body_line_count);
+    let clamped_scroll_offset = scroll_offset.min(max_scroll);
+    let max_pane_offsets = get_max_pane_offsets(current_file, &layout);
+    let clamped_pane_offsets = PaneOffsets {
+        left: pane_offsets.left.min(max_pane_offsets.left),
+        right: pane_offsets.right.min(max_pane_offsets.right),
+    };
+
+    let mut body_lines: Vec<Line<'static>> = Vec::with_capacity(layout.body_line_count);
+    for row in 0..layout.body_line_count {
+        let display_row = display_rows
+            .get(clamped_scroll_offset + row)
+            .copied()
+            .unwrap_or(DisplayRow::Line(max_lines + row));
+        let marker = RowMarker::for_row(cursor_line, selection, display_row);
+        body_lines.push(
+            if view_mode == ViewMode::Unified && current_file.has_unified_rows() {
+                render_unified_row(
+                    current_file,
+                    display_row,
+                    &layout,
+                    clamped_pane_offsets.right,
+                    focused_hunk_lines,
+                    marker,
+                )
+            } else {
+                render_display_row(
+                    current_file,
+                    display_row,
+                    &layout,
+                    clamped_pane_offsets,
+                    focused_hunk_lines,
+                    marker,
+                )
+            },
+        );
+    }
+
+    let first_visible_line = display_rows
+        .get(clamped_scroll_offset)
+        .map(|display_row| display_row.first_line() + 1)
+        .unwrap_or(0);
+    let visible_row_end = (clamped_scroll_offset + layout.body_line_count).min(display_rows.len());
+    let last_visible_line = display_rows
+        .get(clamped_scroll_offset..visible_row_end)
+        .and_then(<[DisplayRow]>::last)
+        .map(|display_row| display_row.last_line() + 1)
+        .unwrap_or(0);
+
+    let mut lines = Vec::new();
+    let side_summary = match comparison.strategy_id {
+        StrategyId::Staged | StrategyId::Amend => format!(
+            "left: {} ({})  right: index",
+            comparison.base_ref,
+            short_commit(&comparison.base_commit)
+        ),
+        StrategyId::Unstaged => "left: index  right: working tree".to_string(),
+        _ if comparison.includes_uncommitted => format!(
+            "left: {} ({})  right: working tree ({} + local changes)",
+            comparison.base_ref,
+            short_commit(&comparison.base_commit),
+            comparison.head_ref
+        ),
+        _ => format!(
+            "left: {} ({})  right: {} ({})",
+            comparison.base_ref,
+            short_commit(&comparison.base_commit),
+            comparison.head_ref,
+            short_commit(&comparison.head_commit)
+        ),
+    };
+
+    let mut filename_line = format!("filename: {}", current_file.descriptor.display_path);
+    if !current_file.owners.is_empty() {
+        filename_line.push_str(&format!("  owners: {}", current_file.owners.join(" ")));
+    }
+    let mut file_marker_summary = match current_file.right_uncovered_line_indexes.len() {
+        0 => String::new(),
+        count => format!(" uncovered: {count}"),
+    };
+    if !current_file.right_annotations.is_empty() {
+        file_marker_summary.push_str(&format!(
+            " annotations: {}",
+            current_file.right_annotations.len()
+        ));
+    }
+    for note in [
+        current_file.minified.note(),
+        current_file.syntax_highlighting.note(),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        file_marker_summary.push_str(&format!(" [{note}]"));
+    }
+    let file_meta_line = format!(
+        "file {}/{} [{}] [{}]{} reviewed: {}/{}  {}",
+        file_index + 1,
+        files.len(),
+        current_file.descriptor.raw_status,
+        current_file_review.label(),
+        file_marker_summary,
+        reviewed_count,
+        files.len(),
+        side_summary
+    );
+
+    let mut title_line = format!(
+        "deff review ({})  {}",
+        comparison.strategy_id, comparison.summary
+    );
+    match files.iter().filter(|file| file.whitespace_only).count() {
+        0 => {}
+        count if hide_whitespace_only => {
+            title_line.push_str(&format!("  whitespace-only: {count} hidden"));
+        }
+        count => title_line.push_str(&format!("  whitespace-only: {count}")),
+    }
+    lines.push(Line::from(fit_line(&title_line, layout.columns)));
+    lines.push(Line::styled(
+        fit_line(&filename_line, layout.columns),
+        Style::default()
+            .add_modifier(Modifier::BOLD)
+            .add_modifier(Modifier::UNDERLINED),
+    ));
+    lines.push(Line::from(fit_line(&file_meta_line, layout.columns)));
+    lines.push(Line::from(fit_line(
+        &comparison.details.join(" | "),
+        layout.columns,
+    )));
+
+    if layout.bordered {
+        lines.push(render_pane_top_border(&layout, comparison, focused_pane));
+        lines.extend(body_lines);
+        lines.push(render_pane_bottom_border(&layout));
+    } else {
+        lines.push(render_pane_divider(&layout, focused_pane));
+        lines.extend(body_lines);
+        lines.push(Line::from(fit_line(
+            &"-".repeat(layout.columns.max(1)),
+            layout.columns,
+        )));
+    }
+    lines.push(Line::from(fit_line(
+        "h/l: file  j/k: cursor  V: select  ctrl-u/d: page  g/G: top/bottom  /: search  n/N: match  }/{: hunk  o: outline  i: commits  zc/zo: fold  |: pipe  r: reviewed  R: next unreviewed  q: quit",
+        layout.columns,
+    )));
+    lines.push(Line::from(fit_line(
+        &format!(
+            "lines {first_visible_line}-{last_visible_line}/{max_lines}  ln {}{}  v {clamped_scroll_offset}/{max_scroll}  xL {}/{}  xR {}/{}  {}",
+            cursor_line + 1,
+            match selection {
+                Some((first, last)) => format!(" (V {}-{})", first + 1, last + 1),
+                None => String::new(),
+            },
+            clamped_pane_offsets.left,
+            max_pane_offsets.left,
+            clamped_pane_offsets.right,
+            max_pane_offsets.right,
+            status_text,
+        ),
+        layout.columns,
+    )));
+
+    RenderFrameOutput {
+        lines,
+        max_scroll,
+        clamped_pane_offsets,
+    }
+}
+
+/// The title row when several `--tab` comparisons are open: one numbered label per tab,
+/// the active one reversed. Tabs before the active one are left off when it would not
+/// fit otherwise.
+pub fn render_tab_bar(labels: &[String], active: usize, columns: u16) -> Line<'static> {
+    let columns = columns as usize;
+    let labels: Vec<String> = labels
+        .iter()
+        .enumerate()
+        .map(|(index, label)| format!(" {} {label} ", index + 1))
+        .collect();
+    let mut first = 0;
+    while first < active
+        && labels[first..=active]
+            .iter()
+            .map(|label| label.chars().count())
+            .sum::<usize>()
+            > columns
+    {
+        first += 1;
+    }
+    let segments = labels
+        .into_iter()
+        .enumerate()
+        .skip(first)
+        .map(|(index, label)| (label, index == active))
+        .chain(std::iter::once(("  [/]: switch tab".to_string(), false)));
+    let mut spans = Vec::new();
+    let mut width = 0;
+    for (text, is_active) in segments {
+        let remaining = columns.saturating_sub(width);
+        if remaining == 0 {
+            break;
+        }
+        let text = fit_line(&text, text.chars().count().min(remaining));
+        width += text.chars().count();
+        let style = if is_active {
+            Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        spans.push(Span::styled(text, style));
+    }
+    spans.push(Span::raw(" ".repeat(columns.saturating_sub(width))));
+    Line::from(spans)
+}
+
+/// The centered area popups are drawn over.
+pub fn get_popup_area(area: Rect) -> Rect {
+    let horizontal_margin = area.width * POPUP_MARGIN_PERCENT / 100;
+    let vertical_margin = area.height * POPUP_MARGIN_PERCENT / 100;
+    Rect {
+        x: area.x + horizontal_margin,
+        y: area.y + vertical_margin,
+        width: area.width.saturating_sub(horizontal_margin * 2),
+        height: area.height.saturating_sub(vertical_margin * 2),
+    }
+}
+
+pub fn get_popup_body_line_count(rows: usize) -> usize {
+    let vertical_margin = rows * POPUP_MARGIN_PERCENT as usize / 100;
+    rows.saturating_sub(vertical_margin * 2 + POPUP_BORDER_LINE_COUNT)
+}
+
+/// Columns inside the popup's border, for content wrapped to fit it.
+pub fn get_popup_body_width(columns: usize) -> usize {
+    let horizontal_margin = columns * POPUP_MARGIN_PERCENT as usize / 100;
+    columns.saturating_sub(horizontal_margin * 2 + POPUP_BORDER_LINE_COUNT)
+}
+
+pub fn get_list_body_line_count(rows: usize) -> usize {
+    rows.saturating_sub(LIST_HEADER_LINE_COUNT + LIST_FOOTER_LINE_COUNT)
+        .max(1)
+}
+
+pub fn render_list_frame(
+    title: &str,
+    items: &[String],
+    selected: usize,
+    scroll: usize,
+    columns: u16,
+    rows: u16,
+) -> Vec<Line<'static>> {
+    let columns = columns as usize;
+    let body_line_count = get_list_body_line_count(rows as usize);
+    let mut lines = Vec::with_capacity(body_line_count + LIST_HEADER_LINE_COUNT + 2);
+
+    lines.push(Line::styled(
+        fit_line(title, columns),
+        Style::default().add_modifier(Modifier::BOLD),
+    ));
+    lines.push(Line::from(fit_line(&"-".repeat(columns.max(1)), columns)));
+    for row in 0..body_line_count {
+        let index = scroll + row;
+        let Some(item) = items.get(index) else {
+            lines.push(Line::from(String::new()));
+            continue;
+        };
+
+        let content = fit_line(&normalize_content(item), columns);
+        if index == selected {
+            lines.push(Line::styled(
+                content,
+                Style::default().add_modifier(Modifier::REVERSED),
+            ));
+        } else {
+            lines.push(Line::from(content));
+        }
+    }
+    lines.push(Line::from(fit_line(&"-".repeat(columns.max(1)), columns)));
+    lines.push(Line::from(fit_line(
+        &format!(
+            "{}/{}  j/k: move  ctrl-u/d: page  g/G: first/last  enter: open  q: back",
+            if items.is_empty() { 0 } else { selected + 1 },
+            items.len()
+        ),
+        columns,
+    )));
+
+    lines
+}
+
+/// Columns the file list takes on the left of the review frame, its border included.
+pub fn get_file_list_width(columns: u16) -> u16 {
+    (columns / 4).clamp(16, 40).min(columns.saturating_sub(20))
+}
+
+/// The file list sidebar, one line per terminal row: each changed file with its status
+/// letter and a mark for reviewed (`✓`) or stale (`~`). The current file is bold, and
+/// `selected` is shown reversed while the list has the keyboard.
+#[allow(clippy::too_many_arguments)]
+pub fn render_file_list(
+    files: &[DiffFileView],
+    review_states: &[ReviewState],
+    file_index: usize,
+    selected: Option<usize>,
+    scroll: usize,
+    width: u16,
+    rows: u16,
+) -> Vec<Line<'static>> {
+    let content_width = (width as usize).saturating_sub(1);
+    let mut lines = Vec::with_capacity(rows as usize);
+    let title = format!("files {}/{}", file_index + 1, files.len());
+    lines.push(Line::from(vec![
+        Span::styled(
+            fit_line(&title, content_width),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Span::raw("|"),
+    ]));
+    for row in 1..rows as usize {
+        let index = scroll + row - 1;
+        let Some(file) = files.get(index) else {
+            lines.push(Line::from(vec![
+                Span::raw(" ".repeat(content_width)),
+                Span::raw("|"),
+            ]));
+            continue;
+        };
+        let mark = match review_states.get(index) {
+            Some(ReviewState::Reviewed) => "✓",
+            Some(ReviewState::Stale) => "~",
+            _ => " ",
+        };
+        let status = file.descriptor.raw_status.get(..1).unwrap_or("?");
+        let item = format!("{mark} {status} {}", file.descriptor.display_path);
+        let mut style = Style::default();
+        if index == file_index {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        if selected == Some(index) {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        lines.push(Line::from(vec![
+            Span::styled(fit_line(&normalize_content(&item), content_width), style),
+            Span::raw("|"),
+        ]));
+    }
+    lines
+}
+
+/// Puts `sidebar` lines to the left of `frame` lines, row by row.
+pub fn place_beside(sidebar: Vec<Line<'static>>, frame: Vec<Line<'static>>) -> Vec<Line<'static>> {
+    sidebar
+        .into_iter()
+        .zip(frame)
+        .map(|(mut row, frame_row)| {
+            // The frame row's own style must not spill over the sidebar.
+            row.spans
+                .extend(frame_row.spans.into_iter().map(|span| Span {
+                    style: frame_row.style.patch(span.style),
+                    ..span
+                }));
+            row
+        })
+        .collect()
+}
+
+pub fn render_message_frame(
+    title: &str,
+    message: &[String],
+    choices: &[String],
+    selected: usize,
+    columns: u16,
+    rows: u16,
+) -> Vec<Line<'static>> {
+    let columns = columns as usize;
+    let mut lines = vec![
+        Line::styled(
+            fit_line(title, columns),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Line::from(fit_line(&"-".repeat(columns.max(1)), columns)),
+    ];
+    // Errors from git can be long; wrap them rather than cutting off the cause.
+    let wrap_width = columns.saturating_sub(2).max(1);
+    for message_line in message {
+        let content = normalize_content(message_line);
+        let char_count = normalized_char_count(&content);
+        for start in (0..char_count.max(1)).step_by(wrap_width) {
+            lines.push(Line::from(fit_line(
+                &format!("  {}", slice_chars(&content, start, wrap_width)),
+                columns,
+            )));
+        }
+    }
+    lines.push(Line::from(String::new()));
+    for (index, choice) in choices.iter().enumerate() {
+        let content = fit_line(&format!("  {choice}"), columns);
+        if index == selected {
+            lines.push(Line::styled(
+                content,
+                Style::default().add_modifier(Modifier::REVERSED),
+            ));
+        } else {
+            lines.push(Line::from(content));
+        }
+    }
+
+    let footer_row = (rows as usize).saturating_sub(1);
+    lines.truncate(footer_row);
+    lines.resize(footer_row, Line::from(String::new()));
+    lines.push(Line::from(fit_line(
+        "j/k: move  enter: choose  q: quit",
+        columns,
+    )));
+    lines
+}
+
+pub fn render_loading_frame(
+    title: &str,
+    completed: usize,
+    total: usize,
+    note: Option<&str>,
+    cancelling: bool,
+    columns: u16,
+    rows: u16,
+) -> Vec<Line<'static>> {
+    let columns = columns as usize;
+    let status = match note {
+        _ if cancelling => "cancelling...".to_string(),
+        Some(note) => format!("{note}  (q / ctrl-c: cancel)"),
+        None => format!("loading {completed}/{total} files  (q / ctrl-c: cancel)"),
+    };
+
+    let mut lines = vec![
+        Line::styled(
+            fit_line(title, columns),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Line::from(fit_line(&"-".repeat(columns.max(1)), columns)),
+        Line::from(fit_line(&status, columns)),
+    ];
+    lines.resize(rows as usize, Line::from(String::new()));
+    lines
+}
@@ -0,0 +1,626 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    git::{get_git_dir, run_git_with, run_git_with_exit_code},
+    model::{DiffFileDescriptor, DiffFileView, ResolvedComparison},
+};
+
+/// Under the git dir; `ReviewStore::load` accepts any other directory, e.g. a tracked one.
+const DEFAULT_STATE_DIRECTORY: &str = "deff";
+const REVIEW_DIRECTORY: &str = "reviewed";
+/// Notes ref for `ReviewStore::attach_notes`; `git log --notes=deff` shows it.
+pub const REVIEW_NOTES_REF: &str = "refs/notes/deff";
+/// Each review note section starts with one of these; review sections are replaced on
+/// every persist, comment sections only accumulate.
+const NOTE_REVIEW_HEADER: &str = "deff-review:";
+const NOTE_COMMENT_HEADER: &str = "deff-comment:";
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+pub(crate) struct StableHasher {
+    state: u64,
+}
+
+impl StableHasher {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: FNV_OFFSET_BASIS,
+        }
+    }
+
+    pub(crate) fn write_str(&mut self, value: &str) {
+        self.write_bytes(value.as_bytes());
+        self.write_bytes(&[0]);
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= u64::from(*byte);
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub(crate) fn finish_hex(&self) -> String {
+        format!("{:016x}", self.state)
+    }
+}
+
+fn comparison_scope_key(comparison: &ResolvedComparison) -> String {
+    let mut hasher = StableHasher::new();
+    hasher.write_str(&comparison.strategy_id.to_string());
+    hasher.write_str(&comparison.base_ref);
+    hasher.write_str(&comparison.head_ref);
+    hasher.write_str(if comparison.includes_uncommitted {
+        "uncommitted"
+    } else {
+        "committed"
+    });
+    hasher.finish_hex()
+}
+
+/// One reviewed key per line; file marks append a tab and the path they were made for,
+/// which is what lets a later key for the same path show up as stale.
+fn parse_reviewed_hashes(raw: &str) -> HashMap<String, Option<String>> {
+    raw.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.split_once('\t') {
+            Some((key, path)) => (key.to_string(), Some(path.to_string())),
+            None => (line.to_string(), None),
+        })
+        .collect()
+}
+
+fn format_reviewed_hashes(reviewed_hashes: &HashMap<String, Option<String>>) -> String {
+    let mut entries: Vec<String> = reviewed_hashes
+        .iter()
+        .map(|(key, file_path)| match file_path {
+            Some(file_path) => format!("{key}\t{file_path}"),
+            None => key.clone(),
+        })
+        .collect();
+    entries.sort_unstable();
+
+    let mut output = entries.join("\n");
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    output
+}
+
+fn persist_reviewed_hashes(
+    path: &Path,
+    reviewed_hashes: &HashMap<String, Option<String>>,
+) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create directory {}", parent.display()))?;
+    }
+
+    fs::write(path, format_reviewed_hashes(reviewed_hashes))
+        .with_context(|| format!("failed to write review state {}", path.display()))
+}
+
+/// Splits a review note into its sections, each starting at a header line.
+fn split_note_sections(note: &str) -> Vec<String> {
+    let mut sections: Vec<String> = Vec::new();
+    for line in note.lines() {
+        let starts_section =
+            line.starts_with(NOTE_REVIEW_HEADER) || line.starts_with(NOTE_COMMENT_HEADER);
+        match sections.last_mut() {
+            Some(section) if !starts_section => {
+                section.push_str(line);
+                section.push('\n');
+            }
+            _ => sections.push(format!("{line}\n")),
+        }
+    }
+    sections
+        .into_iter()
+        .map(|section| section.trim_end().to_string())
+        .filter(|section| !section.is_empty())
+        .collect()
+}
+
+/// `note` with the review section headed `header` replaced by `section`, or dropped when
+/// there is none; other reviewers' and comparisons' sections are kept as they are.
+fn replace_note_section(note: &str, header: &str, section: Option<&str>) -> String {
+    let mut sections: Vec<String> = split_note_sections(note)
+        .into_iter()
+        .filter(|existing| existing.lines().next() != Some(header))
+        .collect();
+    sections.extend(section.map(str::to_string));
+    sections.join("\n\n")
+}
+
+fn read_review_note(repo_root: &Path, commit: &str) -> Result<String> {
+    let (code, output) = run_git_with_exit_code(
+        ["notes", "--ref", REVIEW_NOTES_REF, "show", commit],
+        repo_root,
+    )?;
+    // `git notes show` fails when the commit has no note yet.
+    Ok(if code == 0 {
+        String::from_utf8_lossy(&output).into_owned()
+    } else {
+        String::new()
+    })
+}
+
+fn write_review_note(repo_root: &Path, commit: &str, note: &str) -> Result<()> {
+    if note.trim().is_empty() {
+        run_git_with(
+            [
+                "notes",
+                "--ref",
+                REVIEW_NOTES_REF,
+                "remove",
+                "--ignore-missing",
+                commit,
+            ],
+            repo_root,
+            &[],
+            None,
+        )?;
+    } else {
+        run_git_with(
+            [
+                "notes",
+                "--ref",
+                REVIEW_NOTES_REF,
+                "add",
+                "--force",
+                "--file",
+                "-",
+                commit,
+            ],
+            repo_root,
+            &[],
+            Some(format!("{note}\n").as_bytes()),
+        )?;
+    }
+    Ok(())
+}
+
+/// Hashes the file identity and its changed lines only, grouped into runs but without
+/// line numbers, so rebases and edits to unchanged context keep the key stable. Only the
+/// status letter is hashed, since a rename's similarity score moves with the context.
+/// Files without line changes (mode changes, binaries) fall back to the full contents.
+pub fn compute_review_key(
+    descriptor: &DiffFileDescriptor,
+    left_lines: &[String],
+    left_changed: &HashSet<usize>,
+    right_lines: &[String],
+    right_changed: &HashSet<usize>,
+) -> String {
+    let mut hasher = StableHasher::new();
+
+    hasher.write_str(descriptor.raw_status.get(..1).unwrap_or(""));
+    hasher.write_str(&descriptor.display_path);
+    hasher.write_str(descriptor.base_path.as_deref().unwrap_or(""));
+    hasher.write_str(descriptor.head_path.as_deref().unwrap_or(""));
+
+    let all_lines = |lines: &[String]| (0..lines.len()).collect::<HashSet<_>>();
+    let (left_changed, right_changed) = if left_changed.is_empty() && right_changed.is_empty() {
+        (&all_lines(left_lines), &all_lines(right_lines))
+    } else {
+        (left_changed, right_changed)
+    };
+    for (marker, lines, changed) in [
+        ("L", left_lines, left_changed),
+        ("R", right_lines, right_changed),
+    ] {
+        let mut indexes: Vec<usize> = changed.iter().copied().collect();
+        indexes.sort_unstable();
+        let mut previous: Option<usize> = None;
+        for index in indexes {
+            if previous.is_some_and(|previous| previous + 1 != index) {
+                hasher.write_str("-");
+            }
+            hasher.write_str(marker);
+            hasher.write_str(lines.get(index).map_or("", String::as_str));
+            previous = Some(index);
+        }
+    }
+
+    hasher.finish_hex()
+}
+
+/// Reviewer names become a directory under the review state, so keep them to a safe
+/// set of characters.
+pub fn validate_reviewer_name(reviewer: &str) -> Result<()> {
+    let allowed =
+        |character: char| character.is_ascii_alphanumeric() || matches!(character, '-' | '_' | '.');
+    if reviewer.is_empty() || reviewer.starts_with('.') || !reviewer.chars().all(allowed) {
+        bail!(
+            "invalid reviewer name {reviewer:?}: use letters, digits, `-`, `_`, and `.` (not leading)"
+        );
+    }
+    Ok(())
+}
+
+/// `.git/deff`, the private per-clone location for reviewed state and comments.
+pub fn default_review_state_dir(repo_root: &Path) -> Result<PathBuf> {
+    Ok(get_git_dir(repo_root)?.join(DEFAULT_STATE_DIRECTORY))
+}
+
+/// The commit whose `REVIEW_NOTES_REF` note mirrors the review state.
+struct NotesTarget {
+    repo_root: PathBuf,
+    commit: String,
+}
+
+pub struct ReviewStore {
+    state_dir: PathBuf,
+    /// Where the marks are written; `None` once the store is kept in memory.
+    path: Option<PathBuf>,
+    /// Reviewed keys, with the path for file-level marks.
+    reviewed_hashes: HashMap<String, Option<String>>,
+    /// First line of this store's section in the review note.
+    note_header: String,
+    comparison_summary: String,
+    notes: Option<NotesTarget>,
+}
+
+impl ReviewStore {
+    /// Reads the state kept in `state_dir` (see `default_review_state_dir`). Each named
+    /// reviewer gets separate state; `None` keeps the shared default store.
+    pub fn load(
+        state_dir: &Path,
+        comparison: &ResolvedComparison,
+        reviewer: Option<&str>,
+    ) -> Result<Self> {
+        let mut directory = state_dir.join(REVIEW_DIRECTORY);
+        if let Some(reviewer) = reviewer {
+            validate_reviewer_name(reviewer)?;
+            directory = directory.join(reviewer);
+        }
+        let scope_key = comparison_scope_key(comparison);
+        let path = directory.join(format!("{scope_key}.txt"));
+        let note_header = match reviewer {
+            Some(reviewer) => format!("{NOTE_REVIEW_HEADER} {scope_key} {reviewer}"),
+            None => format!("{NOTE_REVIEW_HEADER} {scope_key}"),
+        };
+
+        let reviewed_hashes = match fs::read_to_string(&path) {
+            Ok(raw) => parse_reviewed_hashes(&raw),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("failed to read review state {}", path.display()));
+            }
+        };
+
+        Ok(Self {
+            state_dir: state_dir.to_path_buf(),
+            path: Some(path),
+            reviewed_hashes,
+            note_header,
+            comparison_summary: comparison.summary.clone(),
+            notes: None,
+        })
+    }
+
+    /// Also records reviewed keys and comments as a `REVIEW_NOTES_REF` note on
+    /// `commit`, so the review travels with the repository when the notes are pushed.
+    pub fn attach_notes(&mut self, repo_root: &Path, commit: &str) {
+        self.notes = Some(NotesTarget {
+            repo_root: repo_root.to_path_buf(),
+            commit: commit.to_string(),
+        });
+    }
+
+    /// Keeps later marks in memory only: `persist` writes neither the state file nor the
+    /// review note, and comments are not recorded.
+    pub fn keep_in_memory(&mut self) {
+        self.path = None;
+        self.notes = None;
+    }
+
+    /// Directory holding the reviewed state; review comments are written next to it.
+    pub fn state_dir(&self) -> &Path {
+        &self.state_dir
+    }
+
+    pub fn reviewed_flags_for_files(&self, files: &[DiffFileView]) -> Vec<bool> {
+        files
+            .iter()
+            .map(|file| self.reviewed_hashes.contains_key(&file.review_key))
+            .collect()
+    }
+
+    /// Unreviewed files whose path was reviewed under an older key, i.e. that changed
+    /// since the last review.
+    pub fn stale_flags_for_files(&self, files: &[DiffFileView]) -> Vec<bool> {
+        files.iter().map(|file| self.is_stale(file)).collect()
+    }
+
+    pub fn is_stale(&self, file: &DiffFileView) -> bool {
+        !self.reviewed_hashes.contains_key(&file.review_key)
+            && self
+                .reviewed_hashes
+                .values()
+                .any(|path| path.as_deref() == Some(file.descriptor.display_path.as_str()))
+    }
+
+    pub fn is_reviewed(&self, review_key: &str) -> bool {
+        self.reviewed_hashes.contains_key(review_key)
+    }
+
+    /// Marks a hunk (or any other key without a path).
+    pub fn set_reviewed(&mut self, review_key: &str, reviewed: bool) {
+        if reviewed {
+            self.reviewed_hashes.insert(review_key.to_string(), None);
+        } else {
+            self.reviewed_hashes.remove(review_key);
+        }
+    }
+
+    /// Marks a whole file, replacing keys from earlier reviews of the same path.
+    pub fn set_file_reviewed(&mut self, file: &DiffFileView, reviewed: bool) {
+        if !reviewed {
+            self.reviewed_hashes.remove(&file.review_key);
+            return;
+        }
+        let path = &file.descriptor.display_path;
+        self.reviewed_hashes
+            .retain(|_, reviewed_path| reviewed_path.as_ref() != Some(path));
+        self.reviewed_hashes
+            .insert(file.review_key.clone(), Some(path.clone()));
+    }
+
+    pub fn persist(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        persist_reviewed_hashes(path, &self.reviewed_hashes)?;
+        let Some(notes) = &self.notes else {
+            return Ok(());
+        };
+        let section = (!self.reviewed_hashes.is_empty()).then(|| {
+            format!(
+                "{}\ncomparison: {}\n{}",
+                self.note_header,
+                self.comparison_summary,
+                format_reviewed_hashes(&self.reviewed_hashes).trim_end()
+            )
+        });
+        let note = read_review_note(&notes.repo_root, &notes.commit)?;
+        write_review_note(
+            &notes.repo_root,
+            &notes.commit,
+            &replace_note_section(&note, &self.note_header, section.as_deref()),
+        )
+    }
+
+    /// Appends a review comment to the note, when notes are attached.
+    pub fn record_comment(&self, comment: &str) -> Result<()> {
+        let Some(notes) = &self.notes else {
+            return Ok(());
+        };
+        let mut note = read_review_note(&notes.repo_root, &notes.commit)?
+            .trim_end()
+            .to_string();
+        if !note.is_empty() {
+            note.push_str("\n\n");
+        }
+        note.push_str(&format!(
+            "{NOTE_COMMENT_HEADER} {}\n{}",
+            self.comparison_summary,
+            comment.trim_end()
+        ));
+        write_review_note(&notes.repo_root, &notes.commit, &note)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ReviewStore, compute_review_key, parse_reviewed_hashes, persist_reviewed_hashes,
+        replace_note_section, validate_reviewer_name,
+    };
+    use crate::model::{
+        DiffFileDescriptor, DiffFileView, FileContentSource, ResolvedComparison, StrategyId,
+    };
+    use std::{
+        collections::{HashMap, HashSet},
+        fs,
+        path::PathBuf,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    fn unique_temp_file_path() -> PathBuf {
+        let now_nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time should be after unix epoch")
+            .as_nanos();
+        std::env::temp_dir().join(format!("deff-review-test-{now_nanos}.txt"))
+    }
+
+    #[test]
+    fn parse_reviewed_hashes_ignores_empty_lines() {
+        let parsed = parse_reviewed_hashes("abc\n\n  \ndef\tsrc/lib.rs\n");
+        assert_eq!(parsed.get("abc"), Some(&None));
+        assert_eq!(parsed.get("def"), Some(&Some("src/lib.rs".to_string())));
+        assert_eq!(parsed.len(), 2);
+    }
+
+    #[test]
+    fn note_sections_are_replaced_per_reviewer_and_comments_kept() {
+        let note = "deff-review: abc alice\ncomparison: main..HEAD\nk1\ta.rs\n\n\
+                    deff-comment: main..HEAD\n### `a.rs` line 1\n\nwhy?\n\n\
+                    deff-review: abc bob\ncomparison: main..HEAD\nk2";
+
+        let replaced = replace_note_section(
+            note,
+            "deff-review: abc alice",
+            Some("deff-review: abc alice\ncomparison: main..HEAD\nk3"),
+        );
+        assert_eq!(
+            replaced,
+            "deff-comment: main..HEAD\n### `a.rs` line 1\n\nwhy?\n\n\
+             deff-review: abc bob\ncomparison: main..HEAD\nk2\n\n\
+             deff-review: abc alice\ncomparison: main..HEAD\nk3"
+        );
+        assert_eq!(
+            replace_note_section(&replaced, "deff-review: abc bob", None),
+            "deff-comment: main..HEAD\n### `a.rs` line 1\n\nwhy?\n\n\
+             deff-review: abc alice\ncomparison: main..HEAD\nk3"
+        );
+    }
+
+    #[test]
+    fn reviewer_names_must_be_safe_directory_names() {
+        assert!(validate_reviewer_name("alice").is_ok());
+        assert!(validate_reviewer_name("author.hat_2-b").is_ok());
+        for name in ["", ".hidden", "..", "a/b", "a b"] {
+            assert!(validate_reviewer_name(name).is_err(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn stores_in_a_custom_state_dir_are_kept_per_reviewer() {
+        let state_dir = unique_temp_file_path().with_extension("");
+        let comparison = ResolvedComparison::for_test();
+
+        let mut alice =
+            ReviewStore::load(&state_dir, &comparison, Some("alice")).expect("store loads");
+        alice.set_reviewed("abc", true);
+        alice.persist().expect("persist should succeed");
+
+        let reloaded =
+            ReviewStore::load(&state_dir, &comparison, Some("alice")).expect("store loads");
+        let mut bob = ReviewStore::load(&state_dir, &comparison, Some("bob")).expect("store loads");
+        assert!(reloaded.is_reviewed("abc"));
+        assert!(!bob.is_reviewed("abc"));
+        assert!(state_dir.join("reviewed").join("alice").is_dir());
+
+        bob.keep_in_memory();
+        bob.set_reviewed("def", true);
+        bob.persist().expect("persist should succeed");
+        assert!(bob.is_reviewed("def"));
+        assert!(!state_dir.join("reviewed").join("bob").exists());
+
+        let _ = fs::remove_dir_all(state_dir);
+    }
+
+    #[test]
+    fn files_reviewed_under_an_older_key_are_stale() {
+        let state_dir = unique_temp_file_path().with_extension("");
+        let comparison = ResolvedComparison {
+            strategy_id: StrategyId::OnlyUncommitted,
+            base_ref: "HEAD".to_string(),
+            head_ref: "WORKTREE".to_string(),
+            head_commit: "a".repeat(40),
+            summary: "HEAD..WORKTREE".to_string(),
+            includes_uncommitted: true,
+            ..ResolvedComparison::for_test()
+        };
+        let file = |review_key: &str| {
+            let view = DiffFileView::for_test("src/lib.rs");
+            DiffFileView {
+                descriptor: DiffFileDescriptor {
+                    head_source: FileContentSource::WorkingTree,
+                    base_mode: None,
+                    head_mode: None,
+                    ..view.descriptor.clone()
+                },
+                review_key: review_key.to_string(),
+                ..view
+            }
+        };
+
+        let mut store = ReviewStore::load(&state_dir, &comparison, None).expect("store loads");
+        store.set_file_reviewed(&file("old"), true);
+        store.persist().expect("persist should succeed");
+
+        let mut store = ReviewStore::load(&state_dir, &comparison, None).expect("store loads");
+        assert!(!store.is_stale(&file("old")));
+        assert!(store.is_stale(&file("new")));
+
+        store.set_file_reviewed(&file("new"), true);
+        assert!(!store.is_reviewed("old"));
+        store.set_file_reviewed(&file("new"), false);
+        assert!(!store.is_stale(&file("new")));
+
+        let _ = fs::remove_dir_all(state_dir);
+    }
+
+    #[test]
+    fn persist_round_trip_writes_sorted_lines() {
+        let path = unique_temp_file_path();
+        let mut hashes = HashMap::new();
+        hashes.insert("bbb".to_string(), None);
+        hashes.insert("aaa".to_string(), Some("a.rs".to_string()));
+
+        persist_reviewed_hashes(&path, &hashes).expect("persist should succeed");
+        let raw = fs::read_to_string(&path).expect("saved file should be readable");
+        assert_eq!(raw, "aaa\ta.rs\nbbb\n");
+
+        let _ = fs::remove_file(path);
+    }
+
+    fn lines(values: &[&str]) -> Vec<String> {
+        values.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn review_key_tracks_changed_lines_but_not_context() {
+        let descriptor = DiffFileDescriptor {
+            raw_status: "M".to_string(),
+            display_path: "src/main.rs".to_string(),
+            base_path: Some("src/main.rs".to_string()),
+            head_path: Some("src/main.rs".to_string()),
+            base_source: FileContentSource::Commit,
+            head_source: FileContentSource::Commit,
+            base_mode: None,
+            head_mode: None,
+        };
+        let key =
+            |left: &[&str], left_changed: &[usize], right: &[&str], right_changed: &[usize]| {
+                compute_review_key(
+                    &descriptor,
+                    &lines(left),
+                    &left_changed.iter().copied().collect(),
+                    &lines(right),
+                    &right_changed.iter().copied().collect(),
+                )
+            };
+
+        let original = key(&["ctx", "a"], &[1], &["ctx", "b"], &[1]);
+        // The same change after a rebase added context above it.
+        let rebased = key(&["new", "ctx", "a"], &[2], &["new", "ctx", "b"], &[2]);
+        let edited = key(&["ctx", "a"], &[1], &["ctx", "c"], &[1]);
+
+        let renamed = DiffFileDescriptor {
+            raw_status: "R097".to_string(),
+            ..descriptor.clone()
+        };
+        let rescored = DiffFileDescriptor {
+            raw_status: "R096".to_string(),
+            ..descriptor.clone()
+        };
+        let rename_key = |descriptor: &DiffFileDescriptor| {
+            compute_review_key(
+                descriptor,
+                &lines(&["a"]),
+                &HashSet::from([0]),
+                &lines(&["b"]),
+                &HashSet::from([0]),
+            )
+        };
+
+        assert_eq!(original, rebased);
+        assert_ne!(original, edited);
+        assert_eq!(rename_key(&renamed), rename_key(&rescored));
+        assert_ne!(
+            key(&["x", "y"], &[], &["x", "z"], &[]),
+            key(&["x", "y"], &[], &["x", "w"], &[])
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use std::{cmp::Ordering, collections::BTreeMap, collections::HashSet};
+
+use yaml_rust2::{Yaml, YamlLoader};
+
+use crate::model::FileLineHighlights;
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// A parsed document with map keys sorted, so key order and formatting drop out.
+enum Node {
+    Scalar(String),
+    List(Vec<Node>),
+    Map(BTreeMap<String, Node>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StructuredFormat {
+    Json,
+    Yaml,
+}
+
+fn structured_format(path: &str) -> Option<StructuredFormat> {
+    let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+    match extension.as_str() {
+        "json" => Some(StructuredFormat::Json),
+        "yaml" | "yml" => Some(StructuredFormat::Yaml),
+        _ => None,
+    }
+}
+
+fn quote(value: &str) -> String {
+    serde_json::Value::String(value.to_string()).to_string()
+}
+
+fn json_node(value: serde_json::Value) -> Node {
+    match value {
+        serde_json::Value::Array(items) => Node::List(items.into_iter().map(json_node).collect()),
+        serde_json::Value::Object(entries) => Node::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key, json_node(value)))
+                .collect(),
+        ),
+        scalar => Node::Scalar(scalar.to_string()),
+    }
+}
+
+fn yaml_scalar(value: &Yaml) -> String {
+    match value {
+        Yaml::Real(value) => value.clone(),
+        Yaml::Integer(value) => value.to_string(),
+        Yaml::String(value) => quote(value),
+        Yaml::Boolean(value) => value.to_string(),
+        Yaml::Null => "null".to_string(),
+        Yaml::Alias(_) => "*alias".to_string(),
+        Yaml::Array(_) | Yaml::Hash(_) | Yaml::BadValue => "?".to_string(),
+    }
+}
+
+fn yaml_node(value: &Yaml) -> Node {
+    match value {
+        Yaml::Array(items) => Node::List(items.iter().map(yaml_node).collect()),
+        Yaml::Hash(entries) => Node::Map(
+            entries
+                .iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        Yaml::String(key) => key.clone(),
+                        key => yaml_scalar(key),
+                    };
+                    (key, yaml_node(value))
+                })
+                .collect(),
+        ),
+        scalar => Node::Scalar(yaml_scalar(scalar)),
+    }
+}
+
+/// Several YAML documents in one file become a list of documents.
+fn parse(format: StructuredFormat, text: &str) -> Option<Node> {
+    match format {
+        StructuredFormat::Json => serde_json::from_str(text).ok().map(json_node),
+        StructuredFormat::Yaml => {
+            let mut documents = YamlLoader::load_from_str(text).ok()?;
+            match documents.len() {
+                0 => Some(Node::Scalar("null".to_string())),
+                1 => Some(yaml_node(&documents.remove(0))),
+                _ => Some(Node::List(documents.iter().map(yaml_node).collect())),
+            }
+        }
+    }
+}
+
+fn flatten(node: &Node, path: &mut Vec<Segment>, leaves: &mut Vec<(Vec<Segment>, String)>) {
+    match node {
+        Node::Scalar(value) => leaves.push((path.clone(), value.clone())),
+        Node::List(items) if items.is_empty() => leaves.push((path.clone(), "[]".to_string())),
+        Node::Map(entries) if entries.is_empty() => leaves.push((path.clone(), "{}".to_string())),
+        Node::List(items) => {
+            for (index, item) in items.iter().enumerate() {
+                path.push(Segment::Index(index));
+                flatten(item, path, leaves);
+                path.pop();
+            }
+        }
+        Node::Map(entries) => {
+            for (key, value) in entries {
+                path.push(Segment::Key(key.clone()));
+                flatten(value, path, leaves);
+                path.pop();
+            }
+        }
+    }
+}
+
+fn is_plain_key(key: &str) -> bool {
+    let mut characters = key.chars();
+    characters
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && characters.all(|character| {
+            character.is_ascii_alphanumeric() || character == '_' || character == '-'
+        })
+}
+
+/// `.dependencies.serde`, `.servers[0].host`, `["key with spaces"]`; `.` is the root.
+fn format_path(path: &[Segment]) -> String {
+    if path.is_empty() {
+        return ".".to_string();
+    }
+    path.iter()
+        .map(|segment| match segment {
+            Segment::Key(key) if is_plain_key(key) => format!(".{key}"),
+            Segment::Key(key) => format!("[{}]", quote(key)),
+            Segment::Index(index) => format!("[{index}]"),
+        })
+        .collect()
+}
+
+/// Both sides of a structural view: one `path = value` row per leaf, aligned by key path
+/// so a key added on one side faces an empty row on the other.
+pub struct StructuralView {
+    pub left_lines: Vec<String>,
+    pub right_lines: Vec<String>,
+    pub highlights: FileLineHighlights,
+}
+
+/// Parses both sides as JSON or YAML (by `path`'s extension) and pairs their leaves by
+/// key path; `None` when the file is neither or either side does not parse.
+pub fn build_structural_view(
+    path: &str,
+    left_text: &str,
+    right_text: &str,
+) -> Option<StructuralView> {
+    let format = structured_format(path)?;
+    let leaves = |text: &str| -> Option<Vec<(Vec<Segment>, String)>> {
+        let mut leaves = Vec::new();
+        flatten(&parse(format, text)?, &mut Vec::new(), &mut leaves);
+        Some(leaves)
+    };
+    let left = leaves(left_text)?;
+    let right = leaves(right_text)?;
+
+    let mut view = StructuralView {
+        left_lines: Vec::new(),
+        right_lines: Vec::new(),
+        highlights: FileLineHighlights {
+            left_deleted_line_indexes: HashSet::new(),
+            right_added_line_indexes: HashSet::new(),
+        },
+    };
+    let (mut left_index, mut right_index) = (0, 0);
+    while left_index < left.len() || right_index < right.len() {
+        let row = view.left_lines.len();
+        let order = match (left.get(left_index), right.get(right_index)) {
+            (Some((left_path, _)), Some((right_path, _))) => left_path.cmp(right_path),
+            (Some(_), None) => Ordering::Less,
+            (None, _) => Ordering::Greater,
+        };
+        let line =
+            |(path, value): &(Vec<Segment>, String)| format!("{} = {value}", format_path(path));
+        match order {
+            Ordering::Less => {
+                view.left_lines.push(line(&left[left_index]));
+                view.right_lines.push(String::new());
+                view.highlights.left_deleted_line_indexes.insert(row);
+                left_index += 1;
+            }
+            Ordering::Greater => {
+                view.left_lines.push(String::new());
+                view.right_lines.push(line(&right[right_index]));
+                view.highlights.right_added_line_indexes.insert(row);
+                right_index += 1;
+            }
+            Ordering::Equal => {
+                if left[left_index].1 != right[right_index].1 {
+                    view.highlights.left_deleted_line_indexes.insert(row);
+                    view.highlights.right_added_line_indexes.insert(row);
+                }
+                view.left_lines.push(line(&left[left_index]));
+                view.right_lines.push(line(&right[right_index]));
+                left_index += 1;
+                right_index += 1;
+            }
+        }
+    }
+    Some(view)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::build_structural_view;
+
+    #[test]
+    fn reordered_keys_are_unchanged_and_edits_pair_up_by_key_path() {
+        let left = r#"{"name": "deff", "deps": {"serde": "1.0", "regex": "1.11"}, "tags": ["a"]}"#;
+        let right = "name: deff\ntags: [a, b]\ndeps:\n  regex: '1.12'\n  serde: '1.0'\n";
+
+        assert!(build_structural_view("config.json", left, right).is_none());
+        assert!(build_structural_view("config.toml", left, left).is_none());
+
+        // JSON is YAML too, so a .yml file can hold either.
+        let view = build_structural_view("config.yml", left, right).expect("both sides parse");
+        assert_eq!(
+            view.left_lines,
+            vec![
+                r#".deps.regex = "1.11""#,
+                r#".deps.serde = "1.0""#,
+                r#".name = "deff""#,
+                r#".tags[0] = "a""#,
+                "",
+            ]
+        );
+        assert_eq!(view.right_lines[0], r#".deps.regex = "1.12""#);
+        assert_eq!(view.right_lines[4], r#".tags[1] = "b""#);
+        assert_eq!(
+            view.highlights.left_deleted_line_indexes,
+            HashSet::from([0])
+        );
+        assert_eq!(
+            view.highlights.right_added_line_indexes,
+            HashSet::from([0, 4])
+        );
+    }
+}
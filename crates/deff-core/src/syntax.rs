@@ -0,0 +1,191 @@
+use std::{
+    collections::HashSet,
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+use once_cell::sync::Lazy;
+use syntect::{
+    dumps::{dump_to_file, from_binary, from_dump_file},
+    parsing::SyntaxSet,
+};
+use tracing::warn;
+
+use crate::profile::{self, Phase};
+
+const DEFAULT_RELATIVE_SYNTAX_DIRS: &[&str] = &["assets/syntaxes", ".deff/syntaxes"];
+/// Sets that include syntax directories are cached here, under the user cache dir.
+const SYNTAX_CACHE_DIRECTORY: &str = "deff";
+const SYNTAX_CACHE_PREFIX: &str = "syntaxes-";
+const SYNTAX_CACHE_EXTENSION: &str = "packdump";
+
+/// syntect's defaults linked with the bundled grammars by the build script.
+static BUNDLED_SYNTAX_PACK: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/syntaxes.packdump"));
+
+static SYNTAX_SET: Lazy<SyntaxSet> =
+    Lazy::new(|| profile::time(Phase::SyntaxSetBuild, load_syntax_set));
+
+pub fn syntax_set() -> &'static SyntaxSet {
+    &SYNTAX_SET
+}
+
+/// The prebuilt set, plus any syntax directories. Linking those in is the slow part, so
+/// the result is cached and only rebuilt when a grammar in them (or deff) changes.
+fn load_syntax_set() -> SyntaxSet {
+    let bundled: SyntaxSet = from_binary(BUNDLED_SYNTAX_PACK);
+    let directories = syntax_directories();
+    if directories.is_empty() {
+        return bundled;
+    }
+
+    let cache_path = syntax_cache_path(&directories);
+    if let Some(set) = cache_path
+        .as_deref()
+        .and_then(|path| from_dump_file::<SyntaxSet, _>(path).ok())
+    {
+        return set;
+    }
+
+    let mut builder = bundled.into_builder();
+    for directory in &directories {
+        if let Err(error) = builder.add_from_folder(directory, true) {
+            warn!(directory = %directory.display(), %error, "ignoring syntax directory");
+        }
+    }
+    let set = builder.build();
+    if let Some(cache_path) = cache_path {
+        write_syntax_cache(&cache_path, &set);
+    }
+    set
+}
+
+fn collect_syntax_sources(directory: &Path, sources: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            collect_syntax_sources(&path, sources);
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "sublime-syntax")
+        {
+            sources.push(path);
+        }
+    }
+}
+
+/// Keyed on the deff build and the path and contents of every grammar in `directories`.
+fn syntax_cache_path(directories: &[PathBuf]) -> Option<PathBuf> {
+    let cache_root = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        // Windows has no HOME unless a Unix-like shell set one.
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    BUNDLED_SYNTAX_PACK.len().hash(&mut hasher);
+    for directory in directories {
+        let mut sources = Vec::new();
+        collect_syntax_sources(directory, &mut sources);
+        sources.sort();
+        for source in sources {
+            source.hash(&mut hasher);
+            fs::read(&source).ok()?.hash(&mut hasher);
+        }
+    }
+    Some(cache_root.join(SYNTAX_CACHE_DIRECTORY).join(format!(
+        "{SYNTAX_CACHE_PREFIX}{:016x}.{SYNTAX_CACHE_EXTENSION}",
+        hasher.finish()
+    )))
+}
+
+/// Best effort: replaces older cached sets, and a failed write only costs the next
+/// start another build.
+fn write_syntax_cache(cache_path: &Path, set: &SyntaxSet) {
+    let Some(directory) = cache_path.parent() else {
+        return;
+    };
+    if fs::create_dir_all(directory).is_err() {
+        return;
+    }
+    if let Ok(entries) = fs::read_dir(directory) {
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let is_cached_set =
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| {
+                        name.starts_with(SYNTAX_CACHE_PREFIX)
+                            && name.ends_with(SYNTAX_CACHE_EXTENSION)
+                    });
+            if is_cached_set && path != cache_path {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+    let _ = dump_to_file(set, cache_path);
+}
+
+fn syntax_directories() -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    candidates.extend(DEFAULT_RELATIVE_SYNTAX_DIRS.iter().map(PathBuf::from));
+
+    let cwd = std::env::current_dir().ok();
+    let mut unique = HashSet::new();
+    let mut resolved = Vec::new();
+    for candidate in candidates {
+        let absolute = if candidate.is_relative() {
+            match cwd.as_ref() {
+                Some(directory) => directory.join(candidate),
+                None => candidate,
+            }
+        } else {
+            candidate
+        };
+
+        if !absolute.is_dir() {
+            continue;
+        }
+
+        if unique.insert(absolute.clone()) {
+            resolved.push(absolute);
+        }
+    }
+
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use syntect::parsing::SyntaxDefinition;
+
+    use super::load_syntax_set;
+
+    include!(concat!(env!("OUT_DIR"), "/bundled_syntaxes.rs"));
+
+    #[test]
+    fn every_bundled_syntax_file_is_loaded() {
+        let syntaxes = load_syntax_set();
+
+        for (file_name, source) in BUNDLED_SYNTAXES {
+            let fallback_name = Path::new(file_name)
+                .file_stem()
+                .and_then(|stem| stem.to_str());
+            let definition = SyntaxDefinition::load_from_str(source, true, fallback_name)
+                .unwrap_or_else(|error| {
+                    panic!("failed to parse bundled syntax {file_name}: {error}")
+                });
+
+            assert!(
+                syntaxes.find_syntax_by_name(&definition.name).is_some(),
+                "expected bundled syntax {} from {file_name}",
+                definition.name
+            );
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+
+use tree_sitter::{Language, Node, Parser};
+
+use crate::model::FileLineHighlights;
+
+/// Sides with more tokens than this keep the line diff; the token diff is quadratic in
+/// the worst case.
+const MAX_TOKENS: usize = 50_000;
+/// Token edits beyond which the sides count as rewritten and keep the line diff.
+const MAX_EDIT_DISTANCE: usize = 2_000;
+
+fn tree_sitter_language(path: &str) -> Option<Language> {
+    let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+    match extension.as_str() {
+        "rs" => Some(tree_sitter_rust::LANGUAGE.into()),
+        "go" => Some(tree_sitter_go::LANGUAGE.into()),
+        _ => None,
+    }
+}
+
+/// One leaf of the syntax tree and the lines it covers.
+struct Token<'a> {
+    text: &'a str,
+    first_line: usize,
+    last_line: usize,
+}
+
+/// Comments and string literals are compared whole, even where the grammar splits them
+/// into parts.
+fn is_atomic(node: Node) -> bool {
+    let kind = node.kind();
+    kind.contains("comment") || kind.ends_with("string_literal")
+}
+
+fn collect_tokens<'a>(root: Node, source: &'a str) -> Vec<Token<'a>> {
+    let mut tokens = Vec::new();
+    let mut cursor = root.walk();
+    loop {
+        let node = cursor.node();
+        if node.child_count() > 0 && !is_atomic(node) && cursor.goto_first_child() {
+            continue;
+        }
+        if !node.is_missing() && node.start_byte() < node.end_byte() {
+            tokens.push(Token {
+                text: &source[node.byte_range()],
+                first_line: node.start_position().row,
+                last_line: node.end_position().row,
+            });
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return tokens;
+            }
+        }
+    }
+}
+
+/// The `(left, right)` index pairs of tokens a shortest edit script keeps, by Myers'
+/// greedy algorithm; `None` past `MAX_EDIT_DISTANCE` edits.
+fn common_tokens(left: &[&str], right: &[&str]) -> Option<Vec<(usize, usize)>> {
+    let (n, m) = (left.len() as isize, right.len() as isize);
+    let max_distance = (left.len() + right.len()).min(MAX_EDIT_DISTANCE) as isize;
+    let offset = max_distance + 1;
+    let mut furthest = vec![0isize; 2 * offset as usize + 1];
+    // The furthest x of each diagonal before step d, for diagonals -d..=d.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let mut distance = None;
+    'steps: for d in 0..=max_distance {
+        trace.push(furthest[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let index = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && furthest[index - 1] < furthest[index + 1]) {
+                furthest[index + 1]
+            } else {
+                furthest[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && left[x as usize] == right[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            furthest[index] = x;
+            if x >= n && y >= m {
+                distance = Some(d);
+                break 'steps;
+            }
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..=distance?).rev() {
+        let before = &trace[d as usize];
+        let at = |k: isize| before[(k + d) as usize];
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = at(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            pairs.push((x as usize, y as usize));
+        }
+        (x, y) = (previous_x, previous_y);
+    }
+    while x > 0 && y > 0 {
+        x -= 1;
+        y -= 1;
+        pairs.push((x as usize, y as usize));
+    }
+    Some(pairs)
+}
+
+fn token_texts<'a>(tokens: &[Token<'a>]) -> Vec<&'a str> {
+    tokens.iter().map(|token| token.text).collect()
+}
+
+fn parse_tokens<'a>(parser: &mut Parser, source: &'a str) -> Option<Vec<Token<'a>>> {
+    let tree = parser.parse(source, None)?;
+    if tree.root_node().has_error() {
+        return None;
+    }
+    Some(collect_tokens(tree.root_node(), source)).filter(|tokens| tokens.len() <= MAX_TOKENS)
+}
+
+/// Changed lines from a diff of the syntax tree leaves of both sides (Rust and Go), so
+/// reformatting that keeps every token in place leaves no changed line. `None` for
+/// other languages, sides that do not parse, and rewrites too large to pair up, which
+/// keep the line diff.
+pub fn build_syntax_tree_highlights(
+    path: &str,
+    left_lines: &[String],
+    right_lines: &[String],
+) -> Option<FileLineHighlights> {
+    let language = tree_sitter_language(path)?;
+    let mut parser = Parser::new();
+    parser.set_language(&language).ok()?;
+    let (left_source, right_source) = (left_lines.join("\n"), right_lines.join("\n"));
+    let left_tokens = parse_tokens(&mut parser, &left_source)?;
+    let right_tokens = parse_tokens(&mut parser, &right_source)?;
+
+    let pairs = common_tokens(&token_texts(&left_tokens), &token_texts(&right_tokens))?;
+    let (left_kept, right_kept): (HashSet<usize>, HashSet<usize>) = pairs.into_iter().unzip();
+    let changed_lines = |tokens: &[Token], kept: &HashSet<usize>| {
+        tokens
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !kept.contains(index))
+            .flat_map(|(_, token)| token.first_line..=token.last_line)
+            .collect::<HashSet<_>>()
+    };
+    Some(FileLineHighlights {
+        left_deleted_line_indexes: changed_lines(&left_tokens, &left_kept),
+        right_added_line_indexes: changed_lines(&right_tokens, &right_kept),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::build_syntax_tree_highlights;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn reformatting_is_ignored_and_token_changes_are_kept() {
+        let left = lines("fn main() {\n    let total = add(1, 2);\n    println!(\"{total}\");\n}");
+        let right = lines(
+            "fn main() {\n    let total = add(\n        1,\n        3,\n    );\n    println!(\"{total}\");\n}",
+        );
+
+        let highlights =
+            build_syntax_tree_highlights("src/main.rs", &left, &right).expect("both sides parse");
+        assert_eq!(highlights.left_deleted_line_indexes, HashSet::from([1]));
+        assert_eq!(highlights.right_added_line_indexes, HashSet::from([3]));
+
+        assert!(build_syntax_tree_highlights("notes.txt", &left, &right).is_none());
+        assert!(build_syntax_tree_highlights("src/main.rs", &left, &lines("fn main( {")).is_none());
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    model::FileLineHighlights,
+    structural::StructuralView,
+    text::{normalized_char_count, pad_to_width},
+};
+
+/// Put in front of cells that differ from the paired row's cell.
+const CHANGED_CELL_MARKER: char = '*';
+const COLUMN_SEPARATOR: &str = " | ";
+
+fn delimiter_for_path(path: &str) -> Option<char> {
+    let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+    match extension.as_str() {
+        "csv" => Some(','),
+        "tsv" => Some('\t'),
+        _ => None,
+    }
+}
+
+/// RFC 4180 records: quoted fields may hold delimiters, `""`, and line breaks (shown as
+/// `\n` so each record stays on one row).
+fn parse_records(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut characters = text.chars().peekable();
+    while let Some(character) = characters.next() {
+        match character {
+            '"' if in_quotes && characters.peek() == Some(&'"') => {
+                field.push('"');
+                characters.next();
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if field.is_empty() => in_quotes = true,
+            '\n' if in_quotes => field.push_str("\\n"),
+            '\r' => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            character if character == delimiter && !in_quotes => {
+                record.push(std::mem::take(&mut field));
+            }
+            character => field.push(character),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+/// `key` is a 1-based column number or a header name.
+fn resolve_key_column(key: &str, headers: &[&[String]]) -> Option<usize> {
+    if let Ok(number) = key.parse::<usize>() {
+        return number.checked_sub(1);
+    }
+    headers
+        .iter()
+        .find_map(|header| header.iter().position(|name| name == key))
+}
+
+/// One record's cells as displayed.
+type Cells = Vec<String>;
+
+fn row_key(record: &[String], column: usize) -> &str {
+    record.get(column).map(String::as_str).unwrap_or("")
+}
+
+enum RowPair {
+    Both(usize, usize),
+    Left(usize),
+    Right(usize),
+}
+
+/// Pairs rows by key while keeping left's order; rows only on the right come in where
+/// they sat between their neighbours, and a key moved elsewhere is paired anyway.
+fn pair_rows(left_keys: &[&str], right_keys: &[&str]) -> Vec<RowPair> {
+    let left_key_set: HashSet<&str> = left_keys.iter().copied().collect();
+    let mut right_by_key: HashMap<&str, usize> = HashMap::new();
+    for (index, key) in right_keys.iter().enumerate() {
+        right_by_key.entry(key).or_insert(index);
+    }
+
+    let mut used = vec![false; right_keys.len()];
+    let mut next_right = 0;
+    let mut pairs = Vec::new();
+    for (left_index, key) in left_keys.iter().enumerate() {
+        while next_right < right_keys.len()
+            && (used[next_right] || !left_key_set.contains(right_keys[next_right]))
+        {
+            if !used[next_right] {
+                used[next_right] = true;
+                pairs.push(RowPair::Right(next_right));
+            }
+            next_right += 1;
+        }
+        match right_by_key.get(key) {
+            Some(&right_index) if !used[right_index] => {
+                used[right_index] = true;
+                pairs.push(RowPair::Both(left_index, right_index));
+            }
+            _ => pairs.push(RowPair::Left(left_index)),
+        }
+    }
+    pairs.extend(
+        (0..right_keys.len())
+            .filter(|index| !used[*index])
+            .map(RowPair::Right),
+    );
+    pairs
+}
+
+/// `.csv` / `.tsv` files as aligned columns, with rows paired on the `key` column and
+/// changed cells marked `*`; the header rows are always paired. `None` for other files.
+pub fn build_table_view(
+    path: &str,
+    key: &str,
+    left_text: &str,
+    right_text: &str,
+) -> Option<StructuralView> {
+    let delimiter = delimiter_for_path(path)?;
+    let left = parse_records(left_text, delimiter);
+    let right = parse_records(right_text, delimiter);
+    let headers: Vec<&[String]> = [left.first(), right.first()]
+        .into_iter()
+        .flatten()
+        .map(Vec::as_slice)
+        .collect();
+    let key_column = resolve_key_column(key, &headers)?;
+
+    let left_keys: Vec<&str> = left
+        .iter()
+        .skip(1)
+        .map(|record| row_key(record, key_column))
+        .collect();
+    let right_keys: Vec<&str> = right
+        .iter()
+        .skip(1)
+        .map(|record| row_key(record, key_column))
+        .collect();
+    let mut pairs = vec![RowPair::Both(0, 0)];
+    if left.is_empty() || right.is_empty() {
+        pairs.clear();
+    }
+    pairs.extend(
+        pair_rows(&left_keys, &right_keys)
+            .into_iter()
+            .map(|pair| match pair {
+                RowPair::Both(left_index, right_index) => {
+                    RowPair::Both(left_index + 1, right_index + 1)
+                }
+                RowPair::Left(index) => RowPair::Left(index + 1),
+                RowPair::Right(index) => RowPair::Right(index + 1),
+            }),
+    );
+
+    // Cells as shown, with change markers, so widths can be shared by both sides.
+    let mut rows: Vec<(Option<Cells>, Option<Cells>)> = Vec::new();
+    for pair in &pairs {
+        let (left_record, right_record) = match *pair {
+            RowPair::Both(left_index, right_index) => {
+                (Some(&left[left_index]), Some(&right[right_index]))
+            }
+            RowPair::Left(index) => (Some(&left[index]), None),
+            RowPair::Right(index) => (None, Some(&right[index])),
+        };
+        // A row without a partner is tinted whole, so its cells stay unmarked.
+        let mark = |record: &Vec<String>, other: Option<&Vec<String>>| -> Vec<String> {
+            record
+                .iter()
+                .enumerate()
+                .map(|(column, cell)| {
+                    if other.is_some_and(|other| other.get(column) != Some(cell)) {
+                        format!("{CHANGED_CELL_MARKER}{cell}")
+                    } else {
+                        cell.clone()
+                    }
+                })
+                .collect()
+        };
+        rows.push((
+            left_record.map(|record| mark(record, right_record)),
+            right_record.map(|record| mark(record, left_record)),
+        ));
+    }
+    let mut widths: Vec<usize> = Vec::new();
+    for cells in rows
+        .iter()
+        .flat_map(|(left, right)| [left, right])
+        .flatten()
+    {
+        for (column, cell) in cells.iter().enumerate() {
+            if widths.len() <= column {
+                widths.push(0);
+            }
+            widths[column] = widths[column].max(normalized_char_count(cell));
+        }
+    }
+    let format_row = |cells: &Vec<String>| -> String {
+        cells
+            .iter()
+            .enumerate()
+            .map(|(column, cell)| pad_to_width(cell.clone(), widths[column]))
+            .collect::<Vec<_>>()
+            .join(COLUMN_SEPARATOR)
+            .trim_end()
+            .to_string()
+    };
+
+    let mut view = StructuralView {
+        left_lines: Vec::with_capacity(rows.len()),
+        right_lines: Vec::with_capacity(rows.len()),
+        highlights: FileLineHighlights {
+            left_deleted_line_indexes: HashSet::new(),
+            right_added_line_indexes: HashSet::new(),
+        },
+    };
+    for (row, (left_cells, right_cells)) in rows.iter().enumerate() {
+        let changed = match (left_cells, right_cells) {
+            (Some(left_cells), Some(right_cells)) => left_cells
+                .iter()
+                .chain(right_cells)
+                .any(|cell| cell.starts_with(CHANGED_CELL_MARKER)),
+            _ => true,
+        };
+        if changed && left_cells.is_some() {
+            view.highlights.left_deleted_line_indexes.insert(row);
+        }
+        if changed && right_cells.is_some() {
+            view.highlights.right_added_line_indexes.insert(row);
+        }
+        view.left_lines
+            .push(left_cells.as_ref().map(format_row).unwrap_or_default());
+        view.right_lines
+            .push(right_cells.as_ref().map(format_row).unwrap_or_default());
+    }
+    Some(view)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::build_table_view;
+
+    #[test]
+    fn rows_pair_on_the_key_column_and_changed_cells_are_marked() {
+        let left = "id,name,qty\n1,apple,3\n2,pear,5\n3,\"fig, dried\",1\n";
+        let right = "id,name,qty\n2,pear,6\n1,apple,3\n4,kiwi,2\n";
+
+        let view = build_table_view("stock.csv", "id", left, right).expect("csv files tabulate");
+        assert_eq!(
+            view.left_lines,
+            vec![
+                "id | name       | qty",
+                "1  | apple      | 3",
+                "2  | pear       | *5",
+                "",
+                "3  | fig, dried | 1",
+            ]
+        );
+        assert_eq!(
+            view.right_lines,
+            vec![
+                "id | name       | qty",
+                "1  | apple      | 3",
+                "2  | pear       | *6",
+                "4  | kiwi       | 2",
+                "",
+            ]
+        );
+        assert_eq!(
+            view.highlights.left_deleted_line_indexes,
+            HashSet::from([2, 4])
+        );
+        assert_eq!(
+            view.highlights.right_added_line_indexes,
+            HashSet::from([2, 3])
+        );
+
+        assert!(build_table_view("stock.txt", "1", left, right).is_none());
+        assert!(build_table_view("stock.csv", "missing", left, right).is_none());
+    }
+}
@@ -1,8 +1,8 @@
-pub(crate) fn normalized_char_count(value: &str) -> usize {
+pub fn normalized_char_count(value: &str) -> usize {
     value.chars().count()
 }
 
-pub(crate) fn slice_chars(value: &str, start: usize, len: usize) -> String {
+pub fn slice_chars(value: &str, start: usize, len: usize) -> String {
     if len == 0 {
         return String::new();
     }
@@ -10,7 +10,7 @@ pub(crate) fn slice_chars(value: &str, start: usize, len: usize) -> String {
     value.chars().skip(start).take(len).collect()
 }
 
-pub(crate) fn truncate_to_width(value: &str, width: usize) -> String {
+pub fn truncate_to_width(value: &str, width: usize) -> String {
     if width == 0 {
         return String::new();
     }
@@ -28,7 +28,7 @@ pub(crate) fn truncate_to_width(value: &str, width: usize) -> String {
     truncated
 }
 
-pub(crate) fn pad_to_width(value: String, width: usize) -> String {
+pub fn pad_to_width(value: String, width: usize) -> String {
     let len = normalized_char_count(&value);
     if len >= width {
         value.chars().take(width).collect()
@@ -37,16 +37,23 @@ pub(crate) fn pad_to_width(value: String, width: usize) -> String {
     }
 }
 
-pub(crate) fn fit_line(value: &str, width: usize) -> String {
+pub fn fit_line(value: &str, width: usize) -> String {
     let truncated = truncate_to_width(value, width);
     pad_to_width(truncated, width)
 }
 
-pub(crate) fn normalize_content(value: &str) -> String {
+pub fn normalize_content(value: &str) -> String {
     value.replace('\t', "  ").replace('\r', "")
 }
 
-pub(crate) fn get_max_normalized_line_length(lines: &[String]) -> usize {
+pub fn leading_indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|ch| ch.is_whitespace())
+        .map(|ch| if ch == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+pub fn get_max_normalized_line_length(lines: &[String]) -> usize {
     lines
         .iter()
         .map(|line| normalized_char_count(&normalize_content(line)))
@@ -0,0 +1,408 @@
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use serde_json::{Value, json};
+use tracing::debug;
+
+use crate::{
+    diff::build_file_view,
+    git::{get_git_dir, run_git_with},
+    model::{
+        DiffFileDescriptor, DiffFileView, DiffOptions, FileContentSource, LoadProgress, Minified,
+        ResolvedComparison, SyntaxHighlighting,
+    },
+    review::StableHasher,
+    syntax::syntax_set,
+    text::get_max_normalized_line_length,
+};
+
+/// Under the git dir, next to the review state.
+const CACHE_DIRECTORY: &str = "deff/views";
+/// Entries not read or written for this long are removed when a cache is opened.
+const MAX_ENTRY_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// Past this total size, the least recently used entries are removed when a cache is
+/// opened.
+const MAX_CACHE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Built views of committed content, stored by the blob hashes of both sides, so opening
+/// the same comparison again reads each file's view back instead of diffing it. Sides
+/// read from the working tree are never cached.
+#[derive(Clone, Debug)]
+pub struct ViewCache {
+    dir: PathBuf,
+}
+
+impl ViewCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// `.git/deff/views`, with entries past `MAX_ENTRY_AGE` cleared out and the rest
+    /// trimmed to `MAX_CACHE_BYTES`.
+    pub fn open(repo_root: &Path) -> Result<Self> {
+        let cache = Self::new(get_git_dir(repo_root)?.join(CACHE_DIRECTORY));
+        cache.remove_stale_entries(MAX_ENTRY_AGE, MAX_CACHE_BYTES);
+        Ok(cache)
+    }
+
+    /// Removes entries older than `max_age`, then the least recently used ones until the
+    /// rest fit in `max_bytes`. Reads touch an entry's modification time, so it orders
+    /// entries by last use.
+    fn remove_stale_entries(&self, max_age: Duration, max_bytes: u64) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let now = SystemTime::now();
+        let mut kept = Vec::new();
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            let is_old = now.duration_since(modified).is_ok_and(|age| age > max_age);
+            if is_old {
+                let _ = fs::remove_file(entry.path());
+            } else {
+                kept.push((modified, metadata.len(), entry.path()));
+            }
+        }
+
+        kept.sort_unstable_by_key(|(modified, _, _)| Reverse(*modified));
+        let mut total_bytes = 0;
+        for (_, len, path) in kept {
+            total_bytes += len;
+            if total_bytes > max_bytes {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+
+    fn read(&self, key: &str, descriptor: &DiffFileDescriptor) -> Option<DiffFileView> {
+        let path = self.entry_path(key);
+        let content = fs::read_to_string(&path).ok()?;
+        let view = decode_view(&serde_json::from_str(&content).ok()?, descriptor)?;
+        // Keeps entries in use from aging out.
+        if let Ok(file) = File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(view)
+    }
+
+    fn write(&self, key: &str, view: &DiffFileView) {
+        let written = fs::create_dir_all(&self.dir)
+            .and_then(|()| fs::write(self.entry_path(key), encode_view(view).to_string()));
+        if let Err(error) = written {
+            debug!(%error, "failed to write cached view");
+        }
+    }
+
+    /// Like `build_file_views`, reading cached views where there are any and caching the
+    /// ones it builds.
+    pub fn build_file_views(
+        &self,
+        repo_root: &Path,
+        comparison: &ResolvedComparison,
+        descriptors: &[DiffFileDescriptor],
+        options: &DiffOptions,
+        progress: &LoadProgress,
+    ) -> Option<Vec<DiffFileView>> {
+        syntax_set();
+        let keys = cache_keys(repo_root, comparison, descriptors, options);
+        let mut views = Vec::with_capacity(descriptors.len());
+
+        for (descriptor, key) in descriptors.iter().zip(&keys) {
+            if progress.is_cancelled() {
+                return None;
+            }
+            let cached = key.as_deref().and_then(|key| self.read(key, descriptor));
+            let view = cached.unwrap_or_else(|| {
+                let view = build_file_view(repo_root, comparison, descriptor, options);
+                if let Some(key) = key {
+                    self.write(key, &view);
+                }
+                view
+            });
+            views.push(view);
+            progress.advance();
+        }
+
+        Some(views)
+    }
+}
+
+/// The `<commit>:<path>` object names of a descriptor's committed sides, or `:<path>`
+/// for staged ones; `None` when a side comes from the working tree.
+fn side_object_names(
+    comparison: &ResolvedComparison,
+    descriptor: &DiffFileDescriptor,
+) -> Option<[Option<String>; 2]> {
+    let side = |source: FileContentSource, commit: &str, path: Option<&str>| match source {
+        FileContentSource::Missing => Some(None),
+        FileContentSource::WorkingTree => None,
+        FileContentSource::Commit => path
+            .filter(|path| !path.contains('\n'))
+            .map(|path| Some(format!("{commit}:{path}"))),
+        FileContentSource::Index => path
+            .filter(|path| !path.contains('\n'))
+            .map(|path| Some(format!(":{path}"))),
+    };
+    Some([
+        side(
+            descriptor.base_source,
+            &comparison.base_commit,
+            descriptor.base_path.as_deref(),
+        )?,
+        side(
+            descriptor.head_source,
+            &comparison.head_commit,
+            descriptor.head_path.as_deref(),
+        )?,
+    ])
+}
+
+/// One key per descriptor, from both sides' blob hashes and everything else the view is
+/// built from; `None` for descriptors that cannot be cached. All blobs are looked up in
+/// one `git cat-file --batch-check`.
+fn cache_keys(
+    repo_root: &Path,
+    comparison: &ResolvedComparison,
+    descriptors: &[DiffFileDescriptor],
+    options: &DiffOptions,
+) -> Vec<Option<String>> {
+    let object_names: Vec<Option<[Option<String>; 2]>> = descriptors
+        .iter()
+        .map(|descriptor| side_object_names(comparison, descriptor))
+        .collect();
+    let query: String = object_names
+        .iter()
+        .flatten()
+        .flatten()
+        .flatten()
+        .map(|name| format!("{name}\n"))
+        .collect();
+    if query.is_empty() {
+        return vec![None; descriptors.len()];
+    }
+    let Ok(output) = run_git_with(
+        ["cat-file", "--batch-check=%(objectname)"],
+        repo_root,
+        &[],
+        Some(query.as_bytes()),
+    ) else {
+        return vec![None; descriptors.len()];
+    };
+    let output = String::from_utf8_lossy(&output);
+    let mut blobs = output.lines();
+
+    descriptors
+        .iter()
+        .zip(object_names)
+        .map(|(descriptor, sides)| {
+            let mut hasher = StableHasher::new();
+            hasher.write_str(env!("CARGO_PKG_VERSION"));
+            hasher.write_str(&format!("{options:?}"));
+            hasher.write_str(if comparison.includes_uncommitted {
+                "uncommitted"
+            } else {
+                "committed"
+            });
+            hasher.write_str(&descriptor.raw_status);
+            hasher.write_str(&format!("{:?}", descriptor.base_path));
+            hasher.write_str(&format!("{:?}", descriptor.head_path));
+            let mut cacheable = true;
+            for _ in sides?.iter().flatten() {
+                // Every name takes one output line; `<name> missing` for unknown objects.
+                let blob = blobs.next()?;
+                cacheable &= !blob.ends_with(" missing");
+                hasher.write_str(blob);
+            }
+            cacheable.then(|| hasher.finish_hex())
+        })
+        .collect()
+}
+
+fn sorted_indexes(indexes: &HashSet<usize>) -> Vec<usize> {
+    let mut sorted: Vec<usize> = indexes.iter().copied().collect();
+    sorted.sort_unstable();
+    sorted
+}
+
+/// What `build_file_view` computed; owners, coverage, annotations, and the collapsed
+/// flag are attached after loading and not stored.
+fn encode_view(view: &DiffFileView) -> Value {
+    json!({
+        "review_key": view.review_key,
+        "left_lines": view.left_lines,
+        "right_lines": view.right_lines,
+        "left_source_lines": view.left_source_lines,
+        "right_source_lines": view.right_source_lines,
+        "left_language": view.left_language,
+        "right_language": view.right_language,
+        "left_deleted": sorted_indexes(&view.left_deleted_line_indexes),
+        "right_added": sorted_indexes(&view.right_added_line_indexes),
+        "right_trailing_whitespace": sorted_indexes(&view.right_trailing_whitespace_line_indexes),
+        "left_missing_final_newline": view.left_missing_final_newline,
+        "right_missing_final_newline": view.right_missing_final_newline,
+        "whitespace_only": view.whitespace_only,
+        "minified": match view.minified {
+            Minified::No => "no",
+            Minified::AsIs => "as-is",
+            Minified::PrettyPrinted => "pretty-printed",
+        },
+        "syntax_highlighting": match view.syntax_highlighting {
+            SyntaxHighlighting::On => "on",
+            SyntaxHighlighting::Off => "off",
+            SyntaxHighlighting::TooLarge => "too-large",
+        },
+    })
+}
+
+fn decode_view(entry: &Value, descriptor: &DiffFileDescriptor) -> Option<DiffFileView> {
+    let strings = |field: &str| -> Option<Vec<String>> {
+        entry[field]
+            .as_array()?
+            .iter()
+            .map(|value| value.as_str().map(str::to_string))
+            .collect()
+    };
+    let indexes = |field: &str| -> Option<HashSet<usize>> {
+        entry[field]
+            .as_array()?
+            .iter()
+            .map(|value| value.as_u64().map(|index| index as usize))
+            .collect()
+    };
+    let source_lines = |field: &str| -> Option<Vec<Option<usize>>> {
+        entry[field]
+            .as_array()?
+            .iter()
+            .map(|value| match value {
+                Value::Null => Some(None),
+                value => value.as_u64().map(|line| Some(line as usize)),
+            })
+            .collect()
+    };
+    let language = |field: &str| match &entry[field] {
+        Value::Null => Some(None),
+        value => value.as_str().map(|name| Some(name.to_string())),
+    };
+
+    let left_lines = strings("left_lines")?;
+    let right_lines = strings("right_lines")?;
+    Some(DiffFileView {
+        descriptor: descriptor.clone(),
+        review_key: entry["review_key"].as_str()?.to_string(),
+        left_language: language("left_language")?,
+        right_language: language("right_language")?,
+        left_deleted_line_indexes: indexes("left_deleted")?,
+        right_added_line_indexes: indexes("right_added")?,
+        left_max_content_length: get_max_normalized_line_length(&left_lines),
+        right_max_content_length: get_max_normalized_line_length(&right_lines),
+        left_lines,
+        right_lines,
+        left_source_lines: source_lines("left_source_lines")?,
+        right_source_lines: source_lines("right_source_lines")?,
+        owners: Vec::new(),
+        right_uncovered_line_indexes: HashSet::new(),
+        right_annotations: Vec::new(),
+        right_trailing_whitespace_line_indexes: indexes("right_trailing_whitespace")?,
+        left_missing_final_newline: entry["left_missing_final_newline"].as_bool()?,
+        right_missing_final_newline: entry["right_missing_final_newline"].as_bool()?,
+        whitespace_only: entry["whitespace_only"].as_bool()?,
+        minified: match entry["minified"].as_str()? {
+            "no" => Minified::No,
+            "as-is" => Minified::AsIs,
+            "pretty-printed" => Minified::PrettyPrinted,
+            _ => return None,
+        },
+        syntax_highlighting: match entry["syntax_highlighting"].as_str()? {
+            "on" => SyntaxHighlighting::On,
+            "off" => SyntaxHighlighting::Off,
+            "too-large" => SyntaxHighlighting::TooLarge,
+            _ => return None,
+        },
+        collapsed: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        fs::{self, File},
+        time::{Duration, SystemTime},
+    };
+
+    use super::{ViewCache, decode_view, encode_view};
+    use crate::model::{DiffFileView, Minified, SyntaxHighlighting};
+
+    #[test]
+    fn encoded_views_decode_to_the_same_view() {
+        let view = DiffFileView {
+            review_key: "0123456789abcdef".to_string(),
+            left_lines: vec!["fn main() {".to_string(), "}".to_string()],
+            right_lines: vec!["fn main() {".to_string(), "\tok(); ".to_string()],
+            left_source_lines: vec![Some(0), Some(1)],
+            right_source_lines: vec![Some(0), None],
+            left_language: Some("Rust".to_string()),
+            left_deleted_line_indexes: HashSet::from([1]),
+            right_added_line_indexes: HashSet::from([1]),
+            left_max_content_length: 11,
+            right_max_content_length: 11,
+            right_trailing_whitespace_line_indexes: HashSet::from([1]),
+            right_missing_final_newline: true,
+            minified: Minified::AsIs,
+            syntax_highlighting: SyntaxHighlighting::TooLarge,
+            ..DiffFileView::for_test("src/main.rs")
+        };
+        let descriptor = view.descriptor.clone();
+
+        let entry = serde_json::from_str(&encode_view(&view).to_string()).expect("valid JSON");
+        let decoded = decode_view(&entry, &descriptor).expect("entry decodes");
+        assert_eq!(format!("{decoded:?}"), format!("{view:?}"));
+
+        assert!(decode_view(&serde_json::json!({}), &descriptor).is_none());
+    }
+
+    #[test]
+    fn stale_entries_are_removed_least_recently_used_first() {
+        let dir = tempfile::tempdir().expect("temp dir should be created");
+        let cache = ViewCache::new(dir.path().to_path_buf());
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+        for (key, age) in [("recent", 0), ("older", 1), ("oldest", 2), ("expired", 40)] {
+            let path = cache.entry_path(key);
+            fs::write(&path, "0123456789").expect("entry should be written");
+            File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(now - day * age))
+                .expect("entry should be touched");
+        }
+
+        cache.remove_stale_entries(day * 30, 20);
+
+        let mut remaining: Vec<String> = fs::read_dir(dir.path())
+            .expect("cache dir should be readable")
+            .map(|entry| {
+                entry
+                    .expect("entry should be readable")
+                    .file_name()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        remaining.sort_unstable();
+        assert_eq!(remaining, vec!["older.json", "recent.json"]);
+    }
+}
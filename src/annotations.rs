@@ -0,0 +1,244 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use crate::model::{Annotation, AnnotationSeverity, DiffFileView};
+
+/// Lint findings keyed by repository-relative path, loaded from clippy/cargo JSON
+/// messages, eslint JSON, or SARIF.
+#[derive(Debug, Default)]
+pub(crate) struct AnnotationSet {
+    annotations_by_path: HashMap<String, Vec<Annotation>>,
+}
+
+fn normalize_path(raw_path: &str, repo_root: &Path) -> String {
+    let raw_path = raw_path.strip_prefix("file://").unwrap_or(raw_path);
+    let path = Path::new(raw_path);
+    let relative = path.strip_prefix(repo_root).unwrap_or(path);
+    let normalized = relative.to_string_lossy().replace('\\', "/");
+    normalized.trim_start_matches("./").to_string()
+}
+
+fn parse_severity(level: &str) -> AnnotationSeverity {
+    match level {
+        "error" | "error: internal compiler error" => AnnotationSeverity::Error,
+        "warning" => AnnotationSeverity::Warning,
+        _ => AnnotationSeverity::Note,
+    }
+}
+
+fn value_str<'a>(value: &'a Value, pointer: &str) -> Option<&'a str> {
+    value.pointer(pointer).and_then(Value::as_str)
+}
+
+fn value_line(value: &Value, pointer: &str) -> Option<usize> {
+    value
+        .pointer(pointer)
+        .and_then(Value::as_u64)
+        .and_then(|line| (line as usize).checked_sub(1))
+}
+
+impl AnnotationSet {
+    pub(crate) fn push(&mut self, path: String, annotation: Annotation) {
+        self.annotations_by_path
+            .entry(path)
+            .or_default()
+            .push(annotation);
+    }
+
+    fn add_cargo_message(&mut self, message: &Value, repo_root: &Path) {
+        // `cargo --message-format=json` wraps diagnostics; bare rustc JSON does not.
+        let diagnostic = message.get("message").unwrap_or(message);
+        let Some(text) = value_str(diagnostic, "/message") else {
+            return;
+        };
+        let severity = parse_severity(value_str(diagnostic, "/level").unwrap_or_default());
+        let rule = value_str(diagnostic, "/code/code").map(str::to_string);
+        let Some(spans) = diagnostic.get("spans").and_then(Value::as_array) else {
+            return;
+        };
+
+        for span in spans
+            .iter()
+            .filter(|span| span.get("is_primary").and_then(Value::as_bool) == Some(true))
+        {
+            let (Some(file_name), Some(line_index)) = (
+                value_str(span, "/file_name"),
+                value_line(span, "/line_start"),
+            ) else {
+                continue;
+            };
+            self.push(
+                normalize_path(file_name, repo_root),
+                Annotation {
+                    line_index,
+                    severity,
+                    message: text.to_string(),
+                    rule: rule.clone(),
+                },
+            );
+        }
+    }
+
+    fn add_eslint_results(&mut self, results: &[Value], repo_root: &Path) {
+        for result in results {
+            let Some(file_path) = value_str(result, "/filePath") else {
+                continue;
+            };
+            let path = normalize_path(file_path, repo_root);
+            let Some(messages) = result.get("messages").and_then(Value::as_array) else {
+                continue;
+            };
+
+            for message in messages {
+                let (Some(line_index), Some(text)) =
+                    (value_line(message, "/line"), value_str(message, "/message"))
+                else {
+                    continue;
+                };
+                let severity = match message.get("severity").and_then(Value::as_u64) {
+                    Some(2) => AnnotationSeverity::Error,
+                    Some(1) => AnnotationSeverity::Warning,
+                    _ => AnnotationSeverity::Note,
+                };
+                self.push(
+                    path.clone(),
+                    Annotation {
+                        line_index,
+                        severity,
+                        message: text.to_string(),
+                        rule: value_str(message, "/ruleId").map(str::to_string),
+                    },
+                );
+            }
+        }
+    }
+
+    fn add_sarif_log(&mut self, log: &Value, repo_root: &Path) {
+        let runs = log.get("runs").and_then(Value::as_array);
+        for run in runs.into_iter().flatten() {
+            let results = run.get("results").and_then(Value::as_array);
+            for result in results.into_iter().flatten() {
+                let Some(text) = value_str(result, "/message/text") else {
+                    continue;
+                };
+                let severity = parse_severity(value_str(result, "/level").unwrap_or("warning"));
+                let locations = result.get("locations").and_then(Value::as_array);
+                for location in locations.into_iter().flatten() {
+                    let (Some(uri), Some(line_index)) = (
+                        value_str(location, "/physicalLocation/artifactLocation/uri"),
+                        value_line(location, "/physicalLocation/region/startLine"),
+                    ) else {
+                        continue;
+                    };
+                    self.push(
+                        normalize_path(uri, repo_root),
+                        Annotation {
+                            line_index,
+                            severity,
+                            message: text.to_string(),
+                            rule: value_str(result, "/ruleId").map(str::to_string),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    pub(crate) fn parse(content: &str, repo_root: &Path) -> Result<Self> {
+        let mut set = Self::default();
+
+        match serde_json::from_str::<Value>(content) {
+            Ok(Value::Array(results)) => set.add_eslint_results(&results, repo_root),
+            Ok(log) if log.get("runs").is_some() => set.add_sarif_log(&log, repo_root),
+            Ok(message) => set.add_cargo_message(&message, repo_root),
+            // Cargo emits one JSON object per line.
+            Err(_) => {
+                for line in content.lines().filter(|line| !line.trim().is_empty()) {
+                    let message: Value = serde_json::from_str(line)
+                        .context("annotations must be clippy/cargo JSON, eslint JSON, or SARIF")?;
+                    if matches!(
+                        message.get("reason").and_then(Value::as_str),
+                        None | Some("compiler-message")
+                    ) {
+                        set.add_cargo_message(&message, repo_root);
+                    }
+                }
+            }
+        }
+
+        Ok(set)
+    }
+
+    pub(crate) fn load(path: &Path, repo_root: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read annotations file {}", path.display()))?;
+        Self::parse(&content, repo_root)
+            .with_context(|| format!("failed to parse annotations file {}", path.display()))
+    }
+
+    pub(crate) fn extend(&mut self, other: AnnotationSet) {
+        for (path, annotations) in other.annotations_by_path {
+            self.annotations_by_path
+                .entry(path)
+                .or_default()
+                .extend(annotations);
+        }
+    }
+
+    pub(crate) fn assign_annotations(&self, files: &mut [DiffFileView]) {
+        for file in files {
+            let Some(annotations) = file
+                .descriptor
+                .head_path
+                .as_deref()
+                .and_then(|path| self.annotations_by_path.get(path))
+            else {
+                continue;
+            };
+            let mut annotations = annotations.clone();
+            annotations.sort_by_key(|annotation| (annotation.line_index, annotation.severity));
+            file.right_annotations = annotations;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::AnnotationSet;
+    use crate::model::AnnotationSeverity;
+
+    #[test]
+    fn parses_cargo_json_lines() {
+        let content = r#"{"reason":"compiler-artifact","target":{}}
+{"reason":"compiler-message","message":{"message":"unused variable: `x`","level":"warning","code":{"code":"unused_variables"},"spans":[{"file_name":"src/lib.rs","line_start":3,"is_primary":true}]}}"#;
+
+        let set = AnnotationSet::parse(content, Path::new("/repo")).expect("cargo json parses");
+
+        let annotations = &set.annotations_by_path["src/lib.rs"];
+        assert_eq!(annotations[0].line_index, 2);
+        assert_eq!(annotations[0].severity, AnnotationSeverity::Warning);
+        assert_eq!(annotations[0].rule.as_deref(), Some("unused_variables"));
+    }
+
+    #[test]
+    fn parses_eslint_and_sarif() {
+        let eslint = r#"[{"filePath":"/repo/web/app.js","messages":[{"line":7,"severity":2,"message":"no-undef","ruleId":"no-undef"}]}]"#;
+        let sarif = r#"{"runs":[{"results":[{"level":"note","message":{"text":"consider"},"locations":[{"physicalLocation":{"artifactLocation":{"uri":"src/a.py"},"region":{"startLine":1}}}]}]}]}"#;
+
+        let eslint_set = AnnotationSet::parse(eslint, Path::new("/repo")).expect("eslint parses");
+        let sarif_set = AnnotationSet::parse(sarif, Path::new("/repo")).expect("sarif parses");
+
+        assert_eq!(
+            eslint_set.annotations_by_path["web/app.js"][0].severity,
+            AnnotationSeverity::Error
+        );
+        assert_eq!(
+            sarif_set.annotations_by_path["src/a.py"][0].severity,
+            AnnotationSeverity::Note
+        );
+    }
+}
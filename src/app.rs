@@ -1,35 +1,303 @@
 use std::collections::HashSet;
 
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 
 use crate::{
-    model::{DiffFileView, PaneOffsets, PaneSide},
-    render::{create_frame_layout, get_body_line_count, get_max_pane_offsets, get_pane_for_column},
+    diff::relayout_file_view,
+    fold::{
+        DisplayRow, Fold, build_display_rows, display_row_for_line, find_context_folds,
+        find_indent_block, find_unchanged_blocks, insert_fold, remove_fold_containing,
+        step_hunk_context,
+    },
+    forge::{BlobLocation, ForgeAction, ForgeRequest},
+    hunk::{HunkOperation, HunkRequest},
+    keymap::{Action, key_map},
+    model::{
+        Annotation, CommitSummary, DiffFileView, FileContentSource, PaneOffsets, PaneSide,
+        ResolvedComparison, ReviewState, ViewMode, ViewSettings,
+    },
+    outline::{build_outline, format_outline_symbol},
+    patch::build_selection_patch,
+    pipe::{PipeRequest, PipeScope},
+    render::{
+        FrameLayout, create_frame_layout, get_body_line_count, get_file_list_width,
+        get_list_body_line_count, get_max_pane_offsets, get_pane_for_column,
+        get_popup_body_line_count, get_popup_body_width, short_commit,
+    },
+    review::compute_review_key,
+    stats::build_diff_summary_lines,
+    summary::pluralize,
+    text::normalize_content,
 };
 
 const MOUSE_WHEEL_SCROLL_LINES: usize = 3;
 const MOUSE_WHEEL_HORIZONTAL_COLUMNS: usize = 8;
+const KEYBOARD_HORIZONTAL_COLUMNS: usize = 8;
+/// Unchanged lines `+` reveals and `-` hides on each side of the current hunk.
+const CONTEXT_STEP_LINES: usize = 10;
+/// Review edits `u` can step back through.
+const REVIEW_HISTORY_LIMIT: usize = 100;
+
+/// The cursor line and, in visual mode, the line where the selection started.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct LineCursor {
+    pub(crate) line: usize,
+    pub(crate) anchor: Option<usize>,
+}
+
+impl LineCursor {
+    /// The selected lines as an inclusive `(first, last)` range, if visual mode is on.
+    pub(crate) fn selection(&self) -> Option<(usize, usize)> {
+        self.anchor
+            .map(|anchor| (anchor.min(self.line), anchor.max(self.line)))
+    }
+}
+
+/// Entries of the per-hunk action menu.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum HunkAction {
+    Copy,
+    Stage,
+    Discard,
+    ToggleReviewed,
+    Comment,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum PopupKind {
+    Info,
+    Outline {
+        line_indexes: Vec<usize>,
+    },
+    CommitSummary,
+    DiffSummary,
+    Annotations {
+        line_indexes: Vec<usize>,
+    },
+    HunkActions {
+        first_line: usize,
+        last_line: usize,
+        actions: Vec<HunkAction>,
+    },
+    /// Confirms marking every file reviewed (or clearing every mark).
+    ConfirmBulkReview {
+        reviewed: bool,
+    },
+    /// Commits to compare the current file from, one per row; the first is the
+    /// comparison's own base.
+    FileBases {
+        commits: Vec<String>,
+    },
+}
 
+#[derive(Clone, Debug)]
+pub(crate) struct Popup {
+    pub(crate) title: String,
+    pub(crate) lines: Vec<String>,
+    pub(crate) scroll: usize,
+    pub(crate) selected: Option<usize>,
+    pub(crate) kind: PopupKind,
+}
+
+impl Popup {
+    pub(crate) fn new(title: impl Into<String>, lines: Vec<String>) -> Self {
+        Self {
+            title: title.into(),
+            lines,
+            scroll: 0,
+            selected: None,
+            kind: PopupKind::Info,
+        }
+    }
+
+    pub(crate) fn list(title: impl Into<String>, lines: Vec<String>, kind: PopupKind) -> Self {
+        let selected = if lines.is_empty() { None } else { Some(0) };
+        Self {
+            title: title.into(),
+            lines,
+            scroll: 0,
+            selected,
+            kind,
+        }
+    }
+}
 #[derive(Clone, Debug, Default)]
 pub(crate) struct KeypressOutcome {
     pub(crate) should_quit: bool,
-    pub(crate) review_toggled: Option<(usize, bool)>,
+    pub(crate) review_updates: Vec<ReviewMark>,
+    pub(crate) pipe_request: Option<PipeRequest>,
+    pub(crate) forge_request: Option<ForgeRequest>,
+    pub(crate) selection_patch: Option<String>,
+    pub(crate) hunk_request: Option<HunkRequest>,
+    pub(crate) switch_tab: Option<TabSwitch>,
+    /// `m`: commit the staged changes with this message.
+    pub(crate) commit_message: Option<String>,
+    /// `L`: read the current file again and rebuild its view.
+    pub(crate) reload_file: bool,
+    /// `B`: list the commits touching the current file to pick its base from.
+    pub(crate) pick_file_base: bool,
+    /// The commit picked as the current file's base.
+    pub(crate) file_base: Option<String>,
+    /// `U`: switch between the split and unified views.
+    pub(crate) toggle_view: bool,
+}
+
+/// `--goto`: the file and line the review opens on.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct StartPosition {
+    pub(crate) file_index: usize,
+    pub(crate) line_index: usize,
+}
+
+/// `]` / `[`: move to the next or previous comparison tab.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum TabSwitch {
+    Next,
+    Previous,
+}
+
+/// A reviewed flag set on a file or on one of its hunks (by hunk review key).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum ReviewMark {
+    File {
+        file_index: usize,
+        reviewed: bool,
+    },
+    Hunk {
+        file_index: usize,
+        key: String,
+        reviewed: bool,
+    },
+}
+
+impl ReviewMark {
+    fn file_index(&self) -> usize {
+        match self {
+            ReviewMark::File { file_index, .. } | ReviewMark::Hunk { file_index, .. } => {
+                *file_index
+            }
+        }
+    }
+
+    fn reviewed(&self) -> bool {
+        match self {
+            ReviewMark::File { reviewed, .. } | ReviewMark::Hunk { reviewed, .. } => *reviewed,
+        }
+    }
+
+    fn inverted(&self) -> Self {
+        match self.clone() {
+            ReviewMark::File {
+                file_index,
+                reviewed,
+            } => ReviewMark::File {
+                file_index,
+                reviewed: !reviewed,
+            },
+            ReviewMark::Hunk {
+                file_index,
+                key,
+                reviewed,
+            } => ReviewMark::Hunk {
+                file_index,
+                key,
+                reviewed: !reviewed,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ViewportAnchor {
+    Top,
+    Center,
+    Bottom,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum InputMode {
+    Search,
+    Pipe,
+    Comment,
+    CommitMessage,
+}
+
+/// Where the view stood when search input started, so Esc can put it back.
+#[derive(Clone, Debug)]
+struct SearchOrigin {
+    scroll_offset: usize,
+    cursor_line: usize,
+    folds: Vec<Fold>,
+    search_query: String,
+    search_changed_only: bool,
+}
+
+/// Presentation state remembered separately for every file, so switching away and
+/// back restores where the reviewer left off.
+#[derive(Clone, Debug, Default)]
+struct FileViewState {
+    scroll_offset: usize,
+    pane_offsets: PaneOffsets,
+    hunk_anchor: Option<usize>,
+    folds: Vec<Fold>,
+    cursor_line: usize,
+    /// Set once the file was shown, so `--first-change` only places a fresh view.
+    opened: bool,
+}
+
+/// `t`: the file list beside the panes. While it has the keyboard, `j`/`k` move the
+/// selection and enter opens the selected file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct FileListState {
+    pub(crate) selected: usize,
+    pub(crate) scroll: usize,
+    pub(crate) focused: bool,
 }
 
 #[derive(Clone, Debug)]
 pub(crate) struct AppState {
     pub(crate) file_index: usize,
     pub(crate) scroll_offset: usize,
-    pane_offsets_by_file: Vec<PaneOffsets>,
-    hunk_anchor_by_file: Vec<Option<usize>>,
+    view_state_by_file: Vec<FileViewState>,
+    pending_z_prefix: bool,
     reviewed_by_file: Vec<bool>,
     reviewed_count: usize,
-    search_input_mode: bool,
+    /// Files reviewed in an earlier version that changed since; cleared by a new mark.
+    pub(crate) stale_by_file: Vec<bool>,
+    input_mode: Option<InputMode>,
+    input: String,
+    search_origin: Option<SearchOrigin>,
+    /// Where `V` started the line selection in the current file.
+    visual_anchor: Option<usize>,
     search_query: String,
-    search_input: String,
+    search_changed_only: bool,
     search_match_line_indexes: Vec<usize>,
     search_match_index: Option<usize>,
     pub(crate) focused_hunk_lines: Option<HashSet<usize>>,
+    pub(crate) popup: Option<Popup>,
+    hover_message: Option<String>,
+    notice: Option<String>,
+    /// Review keys of hunks marked reviewed from the hunk menu.
+    pub(crate) reviewed_hunk_keys: HashSet<String>,
+    /// The `(first, last)` lines of the hunk a comment is being typed for.
+    comment_target: Option<(usize, usize)>,
+    /// Undoable review edits, oldest first; each entry is one keypress worth of marks.
+    review_history: Vec<Vec<ReviewMark>>,
+    review_redo: Vec<Vec<ReviewMark>>,
+    pub(crate) hide_whitespace_only: bool,
+    /// Pane targeted by keyboard horizontal scroll, search, and copy; `None` means both.
+    pub(crate) focused_pane: Option<PaneSide>,
+    /// `c`: unchanged stretches away from the changes are folded in every file.
+    changes_only: bool,
+    pub(crate) file_list: Option<FileListState>,
+    /// Whether rows are shown in two panes or as one unified pane (`U`).
+    pub(crate) view_mode: ViewMode,
+    pub(crate) settings: ViewSettings,
+    /// Named shell commands (`[pipe]` in the user's config) the `|` prompt runs by
+    /// name; later entries win.
+    pub(crate) pipe_commands: Vec<(String, String)>,
+    /// Files still shown by a placeholder while their views are built in the background.
+    pub(crate) loading_by_file: Vec<bool>,
 }
 
 impl AppState {
@@ -47,25 +315,833 @@ impl AppState {
         Self {
             file_index: 0,
             scroll_offset: 0,
-            pane_offsets_by_file: vec![PaneOffsets::default(); file_count],
-            hunk_anchor_by_file: vec![None; file_count],
+            view_state_by_file: vec![FileViewState::default(); file_count],
+            pending_z_prefix: false,
             reviewed_by_file,
             reviewed_count,
-            search_input_mode: false,
+            stale_by_file: vec![false; file_count],
+            input_mode: None,
+            input: String::new(),
+            search_origin: None,
+            visual_anchor: None,
             search_query: String::new(),
-            search_input: String::new(),
+            search_changed_only: false,
             search_match_line_indexes: Vec::new(),
             search_match_index: None,
             focused_hunk_lines: None,
+            popup: None,
+            hover_message: None,
+            notice: None,
+            reviewed_hunk_keys: HashSet::new(),
+            comment_target: None,
+            review_history: Vec::new(),
+            review_redo: Vec::new(),
+            hide_whitespace_only: false,
+            focused_pane: None,
+            changes_only: false,
+            file_list: None,
+            view_mode: ViewMode::Split,
+            settings: ViewSettings::default(),
+            pipe_commands: Vec::new(),
+            loading_by_file: vec![false; file_count],
+        }
+    }
+
+    /// Folds every file the project marks `collapsed` to a single row; `zo` or `zR`
+    /// opens it like any other fold.
+    pub(crate) fn fold_collapsed_files(&mut self, files: &[DiffFileView]) {
+        for (file, view) in files.iter().zip(&mut self.view_state_by_file) {
+            if file.collapsed && file.line_count() > 0 {
+                insert_fold(
+                    &mut view.folds,
+                    Fold {
+                        start: 0,
+                        end: file.line_count() - 1,
+                    },
+                );
+            }
+        }
+    }
+
+    /// The folds the changes-only view puts on `file`, none when it is off.
+    fn context_folds(&self, file: &DiffFileView) -> Vec<Fold> {
+        if !self.changes_only {
+            return Vec::new();
+        }
+        find_context_folds(
+            file.line_count(),
+            |index| file.is_line_changed(index),
+            self.settings.context_lines,
+        )
+    }
+
+    /// `c`: folds every unchanged stretch more than `context_lines` from a change, in
+    /// every file, or opens all folds again. Each file keeps its top line in view.
+    pub(crate) fn set_changes_only(
+        &mut self,
+        files: &[DiffFileView],
+        rows: u16,
+        changes_only: bool,
+    ) {
+        self.current_view_mut().scroll_offset = self.scroll_offset;
+        let top_lines: Vec<usize> = files
+            .iter()
+            .zip(&self.view_state_by_file)
+            .map(|(file, view)| {
+                build_display_rows(file.line_count(), &view.folds)
+                    .get(view.scroll_offset)
+                    .map_or(0, DisplayRow::first_line)
+            })
+            .collect();
+
+        self.changes_only = changes_only;
+        for (index, file) in files.iter().enumerate() {
+            let context_folds = self.context_folds(file);
+            let folds = &mut self.view_state_by_file[index].folds;
+            if !changes_only {
+                folds.clear();
+            }
+            for fold in context_folds {
+                insert_fold(folds, fold);
+            }
+        }
+        if !changes_only {
+            self.fold_collapsed_files(files);
+        }
+        for ((file, view), top_line) in files
+            .iter()
+            .zip(&mut self.view_state_by_file)
+            .zip(top_lines)
+        {
+            let display_rows = build_display_rows(file.line_count(), &view.folds);
+            view.scroll_offset = display_row_for_line(&display_rows, top_line);
+        }
+        self.scroll_offset = self
+            .current_view()
+            .scroll_offset
+            .min(max_scroll_for_current_file(files, self, rows));
+        self.keep_cursor_in_view(files, rows);
+    }
+
+    /// Adopts the rebuilt view of the current file: the cursor and scroll stay where
+    /// they still fit, folds and the selection are dropped since the lines moved, and
+    /// the review state is the one the store has for the new contents.
+    pub(crate) fn reload_current_file(
+        &mut self,
+        files: &[DiffFileView],
+        rows: u16,
+        reviewed: bool,
+        stale: bool,
+    ) {
+        let last_line = files[self.file_index].line_count().saturating_sub(1);
+        let context_folds = self.context_folds(&files[self.file_index]);
+        let view = self.current_view_mut();
+        view.folds = context_folds;
+        view.hunk_anchor = None;
+        view.cursor_line = view.cursor_line.min(last_line);
+        self.focused_hunk_lines = None;
+        self.visual_anchor = None;
+        self.set_file_reviewed(self.file_index, reviewed);
+        if let Some(current) = self.stale_by_file.get_mut(self.file_index) {
+            *current = stale;
+        }
+        if let Some(loading) = self.loading_by_file.get_mut(self.file_index) {
+            *loading = false;
+        }
+        self.scroll_offset = self
+            .scroll_offset
+            .min(max_scroll_for_current_file(files, self, rows));
+        self.refresh_search_matches_for_current_file(files);
+        self.keep_cursor_in_view(files, rows);
+    }
+
+    /// Takes in the view of a file that finished loading in the background: it gets the
+    /// folds of a fresh file and the review state the store has for it, and the current
+    /// file is placed like one just opened.
+    pub(crate) fn adopt_loaded_file(
+        &mut self,
+        files: &[DiffFileView],
+        file_index: usize,
+        rows: u16,
+        reviewed: bool,
+        stale: bool,
+    ) {
+        if file_index == self.file_index {
+            self.reload_current_file(files, rows, reviewed, stale);
+        } else {
+            self.view_state_by_file[file_index].folds = self.context_folds(&files[file_index]);
+            self.set_file_reviewed(file_index, reviewed);
+            self.stale_by_file[file_index] = stale;
+            self.loading_by_file[file_index] = false;
+        }
+        let file = &files[file_index];
+        let view = &mut self.view_state_by_file[file_index];
+        view.opened = false;
+        if file.collapsed && file.line_count() > 0 {
+            insert_fold(
+                &mut view.folds,
+                Fold {
+                    start: 0,
+                    end: file.line_count() - 1,
+                },
+            );
+        }
+        if file_index == self.file_index {
+            self.place_new_file(files, rows);
+        }
+    }
+
+    fn is_file_loading(&self, file_index: usize) -> bool {
+        self.loading_by_file.get(file_index) == Some(&true)
+    }
+
+    /// Lays every file's rows out for `view_mode`. Each file keeps its cursor and
+    /// top line, and its folds go back to those of the changes-only view.
+    pub(crate) fn set_view_mode(
+        &mut self,
+        files: &mut [DiffFileView],
+        view_mode: ViewMode,
+        rows: u16,
+    ) {
+        self.current_view_mut().scroll_offset = self.scroll_offset;
+        for (file, view) in files.iter_mut().zip(&mut self.view_state_by_file) {
+            let top_row = build_display_rows(file.line_count(), &view.folds)
+                .get(view.scroll_offset)
+                .map_or(0, DisplayRow::first_line);
+            let (cursor, top) = (
+                row_anchor(file, view.cursor_line),
+                row_anchor(file, top_row),
+            );
+            relayout_file_view(file, view_mode);
+            view.cursor_line = file.row_of_source_line(cursor.0, cursor.1);
+            view.scroll_offset = file.row_of_source_line(top.0, top.1);
+            view.hunk_anchor = None;
+        }
+        self.view_mode = view_mode;
+        for (file, view) in files.iter().zip(&mut self.view_state_by_file) {
+            view.folds = Vec::new();
+            if self.changes_only {
+                view.folds = find_context_folds(
+                    file.line_count(),
+                    |index| file.is_line_changed(index),
+                    self.settings.context_lines,
+                );
+            }
+            view.scroll_offset = display_row_for_line(
+                &build_display_rows(file.line_count(), &view.folds),
+                view.scroll_offset,
+            );
+        }
+        self.scroll_offset = self.current_view().scroll_offset;
+        self.focused_hunk_lines = None;
+        self.visual_anchor = None;
+        self.refresh_search_matches_for_current_file(files);
+        self.keep_cursor_in_view(files, rows);
+    }
+
+    /// `U`: switches between the split and unified views and says which is on.
+    pub(crate) fn toggle_view_mode(&mut self, files: &mut [DiffFileView], rows: u16) {
+        let view_mode = match self.view_mode {
+            ViewMode::Split => ViewMode::Unified,
+            ViewMode::Unified => ViewMode::Split,
+        };
+        self.set_view_mode(files, view_mode, rows);
+        let file = &files[self.file_index];
+        self.set_notice(match view_mode {
+            ViewMode::Split => "split view".to_string(),
+            ViewMode::Unified if file.has_unified_rows() => "unified view".to_string(),
+            ViewMode::Unified => format!(
+                "unified view ({} stays side by side)",
+                file.descriptor.display_path
+            ),
+        });
+    }
+
+    fn current_view(&self) -> &FileViewState {
+        &self.view_state_by_file[self.file_index]
+    }
+
+    fn current_view_mut(&mut self) -> &mut FileViewState {
+        &mut self.view_state_by_file[self.file_index]
+    }
+
+    /// Stores the live scroll position with the file being left and restores the
+    /// remembered one for the file being entered.
+    fn switch_to_file(&mut self, file_index: usize) {
+        self.current_view_mut().scroll_offset = self.scroll_offset;
+        self.file_index = file_index;
+        self.scroll_offset = self.current_view().scroll_offset;
+        self.focused_hunk_lines = None;
+        self.visual_anchor = None;
+    }
+
+    fn is_file_hidden(&self, files: &[DiffFileView], file_index: usize) -> bool {
+        self.hide_whitespace_only && files[file_index].whitespace_only
+    }
+
+    /// Hides files whose changes are whitespace-only from navigation, moving off the
+    /// current file if it becomes hidden. Ignored when every file would be hidden.
+    fn toggle_hide_whitespace_only(&mut self, files: &[DiffFileView]) {
+        if !self.hide_whitespace_only && files.iter().all(|file| file.whitespace_only) {
+            return;
+        }
+
+        self.hide_whitespace_only = !self.hide_whitespace_only;
+        if self.is_file_hidden(files, self.file_index)
+            && (move_file(1, files, self) || move_file(-1, files, self))
+        {
+            self.refresh_search_matches_for_current_file(files);
         }
     }
 
     pub(crate) fn current_offsets(&self) -> PaneOffsets {
-        self.pane_offsets_by_file[self.file_index]
+        self.current_view().pane_offsets
     }
 
     pub(crate) fn set_current_offsets(&mut self, pane_offsets: PaneOffsets) {
-        self.pane_offsets_by_file[self.file_index] = pane_offsets;
+        self.current_view_mut().pane_offsets = pane_offsets;
+    }
+
+    pub(crate) fn display_rows(&self, files: &[DiffFileView]) -> Vec<DisplayRow> {
+        build_display_rows(
+            files[self.file_index].line_count(),
+            &self.current_view().folds,
+        )
+    }
+
+    fn top_line(&self, files: &[DiffFileView]) -> usize {
+        self.display_rows(files)
+            .get(self.scroll_offset)
+            .map(DisplayRow::first_line)
+            .unwrap_or(0)
+    }
+
+    /// Scrolls so `line_index` sits `scrolloff` rows below the top of the body, opening
+    /// any fold that hides it, and puts the cursor there.
+    fn scroll_to_line(&mut self, files: &[DiffFileView], rows: u16, line_index: usize) {
+        remove_fold_containing(&mut self.current_view_mut().folds, line_index);
+        let display_row = display_row_for_line(&self.display_rows(files), line_index);
+        let max_scroll = max_scroll_for_current_file(files, self, rows);
+        self.scroll_offset = display_row
+            .saturating_sub(self.scrolloff(rows))
+            .min(max_scroll);
+        self.current_view_mut().cursor_line = line_index;
+    }
+
+    /// The configured scrolloff, capped so both margins still leave a row in between.
+    fn scrolloff(&self, rows: u16) -> usize {
+        let body_line_count = get_body_line_count(rows as usize).max(1);
+        self.settings.scrolloff.min((body_line_count - 1) / 2)
+    }
+
+    fn align_scroll_to_line(&mut self, files: &[DiffFileView], rows: u16, line_index: usize) {
+        let display_rows = self.display_rows(files);
+        let max_scroll = max_scroll_for_current_file(files, self, rows);
+        self.scroll_offset = display_row_for_line(&display_rows, line_index).min(max_scroll);
+    }
+
+    pub(crate) fn cursor(&self) -> LineCursor {
+        LineCursor {
+            line: self.current_view().cursor_line,
+            anchor: self.visual_anchor,
+        }
+    }
+
+    fn cursor_line(&self) -> usize {
+        self.current_view().cursor_line
+    }
+
+    /// The first and last body rows the cursor may occupy without scrolling, keeping
+    /// `scrolloff` rows of margin except at the ends of the file.
+    fn cursor_row_bounds(&self, files: &[DiffFileView], rows: u16) -> (usize, usize) {
+        let display_row_count = self.display_rows(files).len();
+        let body_line_count = get_body_line_count(rows as usize).max(1);
+        let scrolloff = self.scrolloff(rows);
+        let first = if self.scroll_offset == 0 {
+            0
+        } else {
+            self.scroll_offset + scrolloff
+        };
+        let bottom = self.scroll_offset + body_line_count;
+        let last = if bottom >= display_row_count {
+            display_row_count.saturating_sub(1)
+        } else {
+            bottom - 1 - scrolloff
+        };
+        (first, last.max(first))
+    }
+
+    /// Moves the cursor `delta` display rows (a fold counts as one) and scrolls just
+    /// enough to keep it inside the scrolloff margins. Leaving the focused hunk drops
+    /// the focus.
+    fn move_cursor(&mut self, files: &[DiffFileView], rows: u16, delta: isize) {
+        let display_rows = self.display_rows(files);
+        let Some(last_row) = display_rows.len().checked_sub(1) else {
+            return;
+        };
+        let current_row = display_row_for_line(&display_rows, self.cursor_line());
+        let next_row = (current_row as isize + delta).clamp(0, last_row as isize) as usize;
+        self.current_view_mut().cursor_line = display_rows[next_row].first_line();
+
+        let body_line_count = get_body_line_count(rows as usize).max(1);
+        let scrolloff = self.scrolloff(rows);
+        let max_scroll = max_scroll_for_current_file(files, self, rows);
+        if next_row < self.scroll_offset + scrolloff {
+            self.scroll_offset = next_row.saturating_sub(scrolloff);
+        } else if next_row + scrolloff >= self.scroll_offset + body_line_count {
+            self.scroll_offset = (next_row + scrolloff + 1).saturating_sub(body_line_count);
+        }
+        self.scroll_offset = self.scroll_offset.min(max_scroll);
+
+        let cursor_line = self.cursor_line();
+        if self
+            .focused_hunk_lines
+            .as_ref()
+            .is_some_and(|lines| !lines.contains(&cursor_line))
+        {
+            self.focused_hunk_lines = None;
+            self.current_view_mut().hunk_anchor = None;
+        }
+    }
+
+    /// Pulls the cursor back inside the viewport after the view scrolled without it.
+    fn keep_cursor_in_view(&mut self, files: &[DiffFileView], rows: u16) {
+        let display_rows = self.display_rows(files);
+        if display_rows.is_empty() {
+            return;
+        }
+        let (first, last) = self.cursor_row_bounds(files, rows);
+        let cursor_row = display_row_for_line(&display_rows, self.cursor_line());
+        let clamped_row = cursor_row.clamp(first, last).min(display_rows.len() - 1);
+        if clamped_row != cursor_row {
+            self.current_view_mut().cursor_line = display_rows[clamped_row].first_line();
+        }
+    }
+
+    fn move_cursor_to_first_line(&mut self) {
+        self.current_view_mut().cursor_line = 0;
+    }
+
+    fn move_cursor_to_last_line(&mut self, files: &[DiffFileView]) {
+        let last_line = self
+            .display_rows(files)
+            .last()
+            .map(DisplayRow::first_line)
+            .unwrap_or(0);
+        self.current_view_mut().cursor_line = last_line;
+    }
+
+    /// `V` starts a line selection at the cursor, or ends the current one.
+    fn toggle_visual_mode(&mut self) {
+        self.visual_anchor = match self.visual_anchor {
+            Some(_) => None,
+            None => Some(self.cursor_line()),
+        };
+    }
+
+    /// The `(first, last)` lines of the hunk under the cursor.
+    fn hunk_at_cursor(&self, files: &[DiffFileView]) -> Option<(usize, usize)> {
+        let file = &files[self.file_index];
+        let cursor_line = self.cursor_line();
+        if !file.is_line_changed(cursor_line) {
+            return None;
+        }
+        let first = build_hunk_start_lines(file)
+            .into_iter()
+            .rev()
+            .find(|&start| start <= cursor_line)?;
+        let last = build_hunk_line_range(file, first).into_iter().max()?;
+        Some((first, last))
+    }
+
+    /// Enter: the action menu for the hunk under the cursor. Staging and discarding
+    /// are offered only when the head side is the working tree.
+    fn show_hunk_menu(&mut self, files: &[DiffFileView]) {
+        let Some((first_line, last_line)) = self.hunk_at_cursor(files) else {
+            self.set_notice("no hunk at the cursor".to_string());
+            return;
+        };
+        let file = &files[self.file_index];
+        let mut actions = vec![HunkAction::Copy];
+        if file.descriptor.head_source == FileContentSource::WorkingTree {
+            actions.extend([HunkAction::Stage, HunkAction::Discard]);
+        }
+        actions.extend([HunkAction::ToggleReviewed, HunkAction::Comment]);
+
+        let reviewed = self
+            .reviewed_hunk_keys
+            .contains(&hunk_review_key(file, first_line, last_line));
+        let lines = actions
+            .iter()
+            .map(|action| format_hunk_action(*action, reviewed).to_string())
+            .collect();
+        let (first_source_line, last_source_line) = hunk_source_lines(file, first_line, last_line);
+        let title = format!(
+            "hunk {}-{}: {}",
+            first_source_line + 1,
+            last_source_line + 1,
+            file.descriptor.display_path
+        );
+        self.show_popup(Popup::list(
+            title,
+            lines,
+            PopupKind::HunkActions {
+                first_line,
+                last_line,
+                actions,
+            },
+        ));
+    }
+
+    fn build_hunk_request(
+        &self,
+        files: &[DiffFileView],
+        operation: HunkOperation,
+        (first_line, last_line): (usize, usize),
+    ) -> Option<HunkRequest> {
+        let file = &files[self.file_index];
+        Some(HunkRequest {
+            operation,
+            path: file.descriptor.display_path.clone(),
+            line: hunk_source_lines(file, first_line, last_line).0 + 1,
+            patch: build_selection_patch(file, first_line, last_line)?,
+        })
+    }
+
+    /// Marks the hunk reviewed (or not); reviewing a file's last open hunk also marks
+    /// the file reviewed.
+    fn toggle_hunk_reviewed(
+        &mut self,
+        files: &[DiffFileView],
+        (first_line, last_line): (usize, usize),
+    ) -> KeypressOutcome {
+        let file = &files[self.file_index];
+        let key = hunk_review_key(file, first_line, last_line);
+        let reviewed = !self.reviewed_hunk_keys.contains(&key);
+        let mut marks = vec![ReviewMark::Hunk {
+            file_index: self.file_index,
+            key: key.clone(),
+            reviewed,
+        }];
+        let every_other_hunk_reviewed = hunk_review_keys(file)
+            .iter()
+            .all(|other| *other == key || self.reviewed_hunk_keys.contains(other));
+        if reviewed && every_other_hunk_reviewed && !self.is_current_file_reviewed() {
+            marks.push(ReviewMark::File {
+                file_index: self.file_index,
+                reviewed: true,
+            });
+        }
+        self.record_review_change(marks)
+    }
+
+    /// The marks `A` (every file reviewed) or `X` (every file and hunk mark cleared)
+    /// would apply.
+    fn bulk_review_marks(&self, files: &[DiffFileView], reviewed: bool) -> Vec<ReviewMark> {
+        let file_marks = (0..files.len())
+            .filter(|&file_index| self.reviewed_by_file[file_index] != reviewed)
+            .map(|file_index| ReviewMark::File {
+                file_index,
+                reviewed,
+            });
+        if reviewed {
+            return file_marks.collect();
+        }
+        let hunk_marks = files.iter().enumerate().flat_map(|(file_index, file)| {
+            hunk_review_keys(file)
+                .into_iter()
+                .filter(|key| self.reviewed_hunk_keys.contains(key))
+                .map(move |key| ReviewMark::Hunk {
+                    file_index,
+                    key,
+                    reviewed: false,
+                })
+        });
+        file_marks.chain(hunk_marks).collect()
+    }
+
+    /// `A` / `X`: asks before marking every file reviewed or clearing every mark.
+    fn confirm_bulk_review(&mut self, files: &[DiffFileView], reviewed: bool) {
+        let marks = self.bulk_review_marks(files, reviewed);
+        if marks.is_empty() {
+            self.set_notice(if reviewed {
+                "every file is already reviewed".to_string()
+            } else {
+                "no reviewed marks to clear".to_string()
+            });
+            return;
+        }
+        let (title, action) = if reviewed {
+            (
+                "mark all files reviewed?".to_string(),
+                format!(
+                    "mark {} unreviewed {} reviewed",
+                    marks.len(),
+                    pluralize(marks.len(), "file", "files")
+                ),
+            )
+        } else {
+            (
+                "clear all reviewed marks?".to_string(),
+                format!(
+                    "clear {} reviewed {}",
+                    marks.len(),
+                    pluralize(marks.len(), "mark", "marks")
+                ),
+            )
+        };
+        self.show_popup(Popup::list(
+            title,
+            vec![action, "cancel".to_string()],
+            PopupKind::ConfirmBulkReview { reviewed },
+        ));
+    }
+
+    fn apply_bulk_review_confirmation(&mut self, files: &[DiffFileView]) -> KeypressOutcome {
+        let Some(Popup {
+            kind: PopupKind::ConfirmBulkReview { reviewed },
+            selected: Some(0),
+            ..
+        }) = self.popup.take()
+        else {
+            return KeypressOutcome::default();
+        };
+        let marks = self.bulk_review_marks(files, reviewed);
+        self.set_notice(describe_review_marks(files, &marks));
+        self.record_review_change(marks)
+    }
+
+    /// Lists `commits` (touching the current file) below the comparison's base, with
+    /// the one the left pane shows now marked `*`.
+    pub(crate) fn show_file_base_picker(
+        &mut self,
+        files: &[DiffFileView],
+        comparison: &ResolvedComparison,
+        commits: &[CommitSummary],
+        current_base: &str,
+    ) {
+        let marker = |commit: &str| if commit == current_base { "* " } else { "  " };
+        let mut lines = vec![format!(
+            "{}comparison base: {} ({})",
+            marker(&comparison.base_commit),
+            comparison.base_ref,
+            short_commit(&comparison.base_commit)
+        )];
+        lines.extend(
+            commits
+                .iter()
+                .map(|commit| format!("{}{}", marker(&commit.hash), commit.format_row())),
+        );
+        let commits = std::iter::once(comparison.base_commit.clone())
+            .chain(commits.iter().map(|commit| commit.hash.clone()))
+            .collect();
+        self.show_popup(Popup::list(
+            format!(
+                "compare from: {}",
+                files[self.file_index].descriptor.display_path
+            ),
+            lines,
+            PopupKind::FileBases { commits },
+        ));
+    }
+
+    fn apply_file_base_selection(&mut self) -> KeypressOutcome {
+        let Some(Popup {
+            kind: PopupKind::FileBases { commits },
+            selected: Some(selected),
+            ..
+        }) = self.popup.take()
+        else {
+            return KeypressOutcome::default();
+        };
+        KeypressOutcome {
+            file_base: commits.get(selected).cloned(),
+            ..KeypressOutcome::default()
+        }
+    }
+
+    fn apply_hunk_menu_selection(&mut self, files: &[DiffFileView]) -> KeypressOutcome {
+        let Some(popup) = self.popup.take() else {
+            return KeypressOutcome::default();
+        };
+        let (
+            PopupKind::HunkActions {
+                first_line,
+                last_line,
+                actions,
+            },
+            Some(selected),
+        ) = (popup.kind, popup.selected)
+        else {
+            return KeypressOutcome::default();
+        };
+        let hunk = (first_line, last_line);
+        let operation = match actions.get(selected) {
+            Some(HunkAction::Copy) => HunkOperation::Copy,
+            Some(HunkAction::Stage) => HunkOperation::Stage,
+            Some(HunkAction::Discard) => HunkOperation::Discard,
+            Some(HunkAction::ToggleReviewed) => return self.toggle_hunk_reviewed(files, hunk),
+            Some(HunkAction::Comment) => {
+                self.enter_input_mode(InputMode::Comment);
+                self.comment_target = Some(hunk);
+                return KeypressOutcome::default();
+            }
+            None => return KeypressOutcome::default(),
+        };
+        KeypressOutcome {
+            hunk_request: self.build_hunk_request(files, operation, hunk),
+            ..KeypressOutcome::default()
+        }
+    }
+
+    fn apply_comment_input(&mut self, files: &[DiffFileView]) -> Option<HunkRequest> {
+        let text = self.input.trim().to_string();
+        let target = self.comment_target;
+        self.exit_input_mode();
+        if text.is_empty() {
+            return None;
+        }
+        self.build_hunk_request(files, HunkOperation::Comment(text), target?)
+    }
+
+    /// The changed lines of the `V` selection (or the cursor line) as a patch; ends
+    /// visual mode once exported.
+    fn take_selection_patch(&mut self, files: &[DiffFileView]) -> Option<String> {
+        let cursor = self.cursor();
+        let (first, last) = cursor.selection().unwrap_or((cursor.line, cursor.line));
+        let patch = build_selection_patch(&files[self.file_index], first, last);
+        if patch.is_some() {
+            self.visual_anchor = None;
+        } else {
+            self.set_notice("patch: no changed lines selected".to_string());
+        }
+        patch
+    }
+
+    fn fold_at_cursor(&mut self, files: &[DiffFileView], rows: u16) {
+        let line_index = self.cursor_line();
+        if let Some(fold) = find_indent_block(files[self.file_index].primary_lines(), line_index) {
+            insert_fold(&mut self.current_view_mut().folds, fold);
+            self.current_view_mut().cursor_line = fold.start;
+            let fold_row = display_row_for_line(&self.display_rows(files), fold.start);
+            let (first, last) = self.cursor_row_bounds(files, rows);
+            if !(first..=last).contains(&fold_row) {
+                self.align_scroll_to_line(files, rows, fold.start);
+            }
+        }
+    }
+
+    fn unfold_at_cursor(&mut self) {
+        let line_index = self.cursor_line();
+        remove_fold_containing(&mut self.current_view_mut().folds, line_index);
+    }
+
+    fn toggle_fold_at_cursor(&mut self, files: &[DiffFileView], rows: u16) {
+        let display_rows = self.display_rows(files);
+        let cursor_row = display_row_for_line(&display_rows, self.cursor_line());
+        if matches!(display_rows.get(cursor_row), Some(DisplayRow::Fold(_))) {
+            self.unfold_at_cursor();
+        } else {
+            self.fold_at_cursor(files, rows);
+        }
+    }
+
+    fn fold_unchanged_blocks(&mut self, files: &[DiffFileView], rows: u16) {
+        let line_index = self.top_line(files);
+        let unchanged_blocks = find_unchanged_blocks(&files[self.file_index]);
+        let folds = &mut self.current_view_mut().folds;
+        for fold in unchanged_blocks {
+            insert_fold(folds, fold);
+        }
+        self.align_scroll_to_line(files, rows, line_index);
+    }
+
+    /// `+` / `-`: shows or hides `CONTEXT_STEP_LINES` more lines of context on both
+    /// sides of the hunk at the cursor, like a forge's expand arrows.
+    fn step_context(&mut self, files: &[DiffFileView], rows: u16, expand: bool) {
+        let Some(hunk) = self.hunk_at_cursor(files) else {
+            self.set_notice("no hunk at the cursor".to_string());
+            return;
+        };
+        let file = &files[self.file_index];
+        // The cursor keeps its screen row while the context around it changes.
+        let cursor_line = self.cursor_line();
+        let cursor_row = display_row_for_line(&self.display_rows(files), cursor_line)
+            .saturating_sub(self.scroll_offset);
+        let changed = step_hunk_context(
+            &mut self.current_view_mut().folds,
+            file.line_count(),
+            hunk,
+            |index| file.is_line_changed(index),
+            CONTEXT_STEP_LINES,
+            expand,
+        );
+        if !changed {
+            self.set_notice(if expand {
+                "no folded context next to this hunk".to_string()
+            } else {
+                "no context left to hide around this hunk".to_string()
+            });
+        }
+        let display_row = display_row_for_line(&self.display_rows(files), cursor_line);
+        self.scroll_offset = display_row
+            .saturating_sub(cursor_row)
+            .min(max_scroll_for_current_file(files, self, rows));
+    }
+
+    fn unfold_all(&mut self, files: &[DiffFileView], rows: u16) {
+        let line_index = self.top_line(files);
+        self.current_view_mut().folds.clear();
+        self.align_scroll_to_line(files, rows, line_index);
+    }
+
+    fn focused_hunk_within_scrolloff(&self, files: &[DiffFileView], rows: u16) -> bool {
+        let scrolloff = self.scrolloff(rows);
+        let Some(lines) = self.focused_hunk_lines.as_ref() else {
+            return false;
+        };
+        let (Some(&first), Some(&last)) = (lines.iter().min(), lines.iter().max()) else {
+            return false;
+        };
+        if scrolloff == 0 {
+            return false;
+        }
+
+        let display_rows = self.display_rows(files);
+        let body_line_count = get_body_line_count(rows as usize);
+        let top = display_row_for_line(&display_rows, first);
+        let bottom = display_row_for_line(&display_rows, last);
+        top >= self.scroll_offset + scrolloff
+            && bottom + scrolloff < self.scroll_offset + body_line_count
+    }
+
+    /// `zz` / `zt` / `zb`: scrolls so the cursor line sits at the given place.
+    fn reposition_viewport(&mut self, files: &[DiffFileView], rows: u16, anchor: ViewportAnchor) {
+        let line_index = self.cursor_line();
+        remove_fold_containing(&mut self.current_view_mut().folds, line_index);
+        let display_row = display_row_for_line(&self.display_rows(files), line_index);
+        let body_line_count = get_body_line_count(rows as usize).max(1);
+        let scrolloff = self.scrolloff(rows);
+        let offset = match anchor {
+            ViewportAnchor::Top => display_row.saturating_sub(scrolloff),
+            ViewportAnchor::Center => display_row.saturating_sub(body_line_count / 2),
+            ViewportAnchor::Bottom => display_row.saturating_sub(body_line_count - 1 - scrolloff),
+        };
+        self.scroll_offset = offset.min(max_scroll_for_current_file(files, self, rows));
+    }
+
+    fn handle_z_command(&mut self, code: KeyCode, files: &[DiffFileView], rows: u16) {
+        match code {
+            KeyCode::Char('c') => self.fold_at_cursor(files, rows),
+            KeyCode::Char('o') => self.unfold_at_cursor(),
+            KeyCode::Char('a') => self.toggle_fold_at_cursor(files, rows),
+            KeyCode::Char('M') => self.fold_unchanged_blocks(files, rows),
+            KeyCode::Char('R') => self.unfold_all(files, rows),
+            KeyCode::Char('z') => self.reposition_viewport(files, rows, ViewportAnchor::Center),
+            KeyCode::Char('t') => self.reposition_viewport(files, rows, ViewportAnchor::Top),
+            KeyCode::Char('b') => self.reposition_viewport(files, rows, ViewportAnchor::Bottom),
+            _ => {}
+        }
     }
 
     pub(crate) fn reviewed_count(&self) -> usize {
@@ -76,227 +1152,970 @@ impl AppState {
         self.reviewed_by_file[self.file_index]
     }
 
-    pub(crate) fn toggle_current_file_reviewed(&mut self) -> bool {
-        let reviewed = &mut self.reviewed_by_file[self.file_index];
-        if *reviewed {
-            *reviewed = false;
-            self.reviewed_count = self.reviewed_count.saturating_sub(1);
-        } else {
-            *reviewed = true;
-            self.reviewed_count = self.reviewed_count.saturating_add(1);
+    pub(crate) fn current_file_review_state(&self) -> ReviewState {
+        self.file_review_state(self.file_index)
+    }
+
+    fn file_review_state(&self, file_index: usize) -> ReviewState {
+        if self.reviewed_by_file[file_index] {
+            ReviewState::Reviewed
+        } else if self.stale_by_file.get(file_index) == Some(&true) {
+            ReviewState::Stale
+        } else {
+            ReviewState::Unreviewed
+        }
+    }
+
+    pub(crate) fn file_review_states(&self) -> Vec<ReviewState> {
+        (0..self.reviewed_by_file.len())
+            .map(|file_index| self.file_review_state(file_index))
+            .collect()
+    }
+
+    /// Moves to the next file not marked reviewed, wrapping past the last file and
+    /// skipping hidden ones.
+    fn jump_to_next_unreviewed_file(&mut self, files: &[DiffFileView]) {
+        let file_count = files.len();
+        let next_index = (1..file_count)
+            .map(|step| (self.file_index + step) % file_count)
+            .find(|&file_index| {
+                !self.reviewed_by_file[file_index] && !self.is_file_hidden(files, file_index)
+            });
+        match next_index {
+            Some(next_index) => {
+                self.switch_to_file(next_index);
+                self.refresh_search_matches_for_current_file(files);
+            }
+            None if self.is_current_file_reviewed() => {
+                self.set_notice("nothing left to review".to_string())
+            }
+            None => self.set_notice("no other file left to review".to_string()),
+        }
+    }
+
+    /// The columns left for the review frame beside the file list.
+    pub(crate) fn frame_columns(&self, columns: u16) -> u16 {
+        match self.file_list {
+            Some(_) => columns - get_file_list_width(columns),
+            None => columns,
+        }
+    }
+
+    /// `t`: opens the file list with the keyboard on it, gives it the keyboard back, or
+    /// closes it when it already has the keyboard.
+    fn toggle_file_list(&mut self, rows: u16) {
+        self.file_list = match self.file_list {
+            Some(list) if list.focused => None,
+            list => Some(FileListState {
+                selected: self.file_index,
+                scroll: list.map_or(0, |list| list.scroll),
+                focused: true,
+            }),
+        };
+        self.keep_file_list_selection_visible(rows);
+    }
+
+    fn keep_file_list_selection_visible(&mut self, rows: u16) {
+        let Some(list) = self.file_list.as_mut() else {
+            return;
+        };
+        // The first row holds the list's title.
+        let item_rows = (rows as usize).saturating_sub(1).max(1);
+        if list.selected < list.scroll {
+            list.scroll = list.selected;
+        } else if list.selected >= list.scroll + item_rows {
+            list.scroll = list.selected + 1 - item_rows;
+        }
+    }
+
+    /// Keys for the file list while it has the keyboard; `false` leaves the key to the
+    /// panes.
+    fn handle_file_list_key(&mut self, key: KeyEvent, files: &[DiffFileView], rows: u16) -> bool {
+        let Some(list) = self.file_list.as_mut().filter(|list| list.focused) else {
+            return false;
+        };
+        let page_size = (rows as usize).saturating_sub(1).max(1) as isize;
+        let delta = match (key.code, key_map().action(key)) {
+            (KeyCode::Enter, _) => {
+                let selected = list.selected;
+                list.focused = false;
+                self.open_file(files, selected);
+                return true;
+            }
+            (KeyCode::Esc, _) => {
+                list.focused = false;
+                return true;
+            }
+            (_, Some(Action::CursorUp)) => -1,
+            (_, Some(Action::CursorDown)) => 1,
+            (_, Some(Action::PageUp)) => -page_size,
+            (_, Some(Action::PageDown)) => page_size,
+            (_, Some(Action::Top)) => -(files.len() as isize),
+            (_, Some(Action::Bottom)) => files.len() as isize,
+            (_, Some(Action::FileList | Action::Quit)) => {
+                self.file_list = None;
+                return true;
+            }
+            _ => return false,
+        };
+        let max_index = files.len().saturating_sub(1) as isize;
+        list.selected = (list.selected as isize + delta).clamp(0, max_index) as usize;
+        self.keep_file_list_selection_visible(rows);
+        true
+    }
+
+    /// Clicks and the wheel over the file list: a click opens the clicked file.
+    fn handle_file_list_mouse(&mut self, mouse: MouseEvent, files: &[DiffFileView], rows: u16) {
+        let Some(list) = self.file_list.as_mut() else {
+            return;
+        };
+        let item_rows = (rows as usize).saturating_sub(1).max(1);
+        let max_scroll = files.len().saturating_sub(item_rows);
+        match mouse.kind {
+            MouseEventKind::ScrollUp => {
+                list.scroll = list.scroll.saturating_sub(MOUSE_WHEEL_SCROLL_LINES);
+            }
+            MouseEventKind::ScrollDown => {
+                list.scroll = (list.scroll + MOUSE_WHEEL_SCROLL_LINES).min(max_scroll);
+            }
+            MouseEventKind::Down(MouseButton::Left) if mouse.row > 0 => {
+                let file_index = list.scroll + mouse.row as usize - 1;
+                if file_index < files.len() {
+                    list.selected = file_index;
+                    self.open_file(files, file_index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn open_file(&mut self, files: &[DiffFileView], file_index: usize) {
+        if file_index != self.file_index {
+            self.switch_to_file(file_index);
+            self.refresh_search_matches_for_current_file(files);
+        }
+    }
+
+    fn set_file_reviewed(&mut self, file_index: usize, reviewed: bool) {
+        if reviewed && let Some(stale) = self.stale_by_file.get_mut(file_index) {
+            // The store drops the older reviewed key once the file is marked again.
+            *stale = false;
+        }
+        let current = &mut self.reviewed_by_file[file_index];
+        if *current == reviewed {
+            return;
+        }
+        *current = reviewed;
+        if reviewed {
+            self.reviewed_count = self.reviewed_count.saturating_add(1);
+        } else {
+            self.reviewed_count = self.reviewed_count.saturating_sub(1);
+        }
+    }
+
+    fn apply_review_mark(&mut self, mark: &ReviewMark) {
+        match mark {
+            ReviewMark::File {
+                file_index,
+                reviewed,
+            } => self.set_file_reviewed(*file_index, *reviewed),
+            ReviewMark::Hunk { key, reviewed, .. } => {
+                if *reviewed {
+                    self.reviewed_hunk_keys.insert(key.clone());
+                } else {
+                    self.reviewed_hunk_keys.remove(key);
+                }
+            }
+        }
+    }
+
+    /// Applies a new review edit, making it the latest undo step.
+    fn record_review_change(&mut self, mut marks: Vec<ReviewMark>) -> KeypressOutcome {
+        // A placeholder's review key is not the file's.
+        let loading = marks
+            .iter()
+            .filter(|mark| self.is_file_loading(mark.file_index()))
+            .count();
+        if loading > 0 {
+            marks.retain(|mark| !self.is_file_loading(mark.file_index()));
+            self.set_notice(format!(
+                "review: skipped {loading} marks on files still loading"
+            ));
+            if marks.is_empty() {
+                return KeypressOutcome::default();
+            }
+        }
+        for mark in &marks {
+            self.apply_review_mark(mark);
+        }
+        self.review_history.push(marks.clone());
+        if self.review_history.len() > REVIEW_HISTORY_LIMIT {
+            self.review_history.remove(0);
+        }
+        self.review_redo.clear();
+        KeypressOutcome {
+            review_updates: marks,
+            ..KeypressOutcome::default()
+        }
+    }
+
+    /// `u` reverts the latest review edit; `ctrl-r` replays the latest reverted one.
+    fn step_review_history(&mut self, files: &[DiffFileView], undo: bool) -> KeypressOutcome {
+        let (verb, entry) = if undo {
+            ("undo", self.review_history.pop())
+        } else {
+            ("redo", self.review_redo.pop())
+        };
+        let Some(marks) = entry else {
+            self.set_notice(format!("nothing to {verb}"));
+            return KeypressOutcome::default();
+        };
+
+        let applied: Vec<ReviewMark> = if undo {
+            marks.iter().rev().map(ReviewMark::inverted).collect()
+        } else {
+            marks.clone()
+        };
+        for mark in &applied {
+            self.apply_review_mark(mark);
+        }
+        if undo {
+            self.review_redo.push(marks);
+        } else {
+            self.review_history.push(marks);
+        }
+
+        self.set_notice(format!(
+            "{verb}: {}",
+            describe_review_marks(files, &applied)
+        ));
+        KeypressOutcome {
+            review_updates: applied,
+            ..KeypressOutcome::default()
+        }
+    }
+
+    pub(crate) fn status_text(&self) -> String {
+        let status = self.unfocused_status_text();
+        match self.focused_pane {
+            Some(PaneSide::Left) => format!("[left] {status}"),
+            Some(PaneSide::Right) => format!("[right] {status}"),
+            None => status,
+        }
+    }
+
+    fn unfocused_status_text(&self) -> String {
+        match self.input_mode {
+            Some(InputMode::Search) => {
+                return format!("search: {}{}", self.search_prompt(), self.input);
+            }
+            Some(InputMode::Pipe) => return format!("pipe: |{}", self.input),
+            Some(InputMode::Comment) => return format!("comment: {}", self.input),
+            Some(InputMode::CommitMessage) => {
+                return format!("commit message: {}", self.input);
+            }
+            None => {}
+        }
+
+        if let Some(notice) = &self.notice {
+            return notice.clone();
+        }
+
+        if let Some(message) = &self.hover_message {
+            return message.clone();
+        }
+
+        let prompt = self.search_prompt();
+        if self.search_query.is_empty() {
+            return format!("search: {prompt}");
+        }
+
+        if self.search_match_line_indexes.is_empty() {
+            return format!("search: {prompt}{} (no matches)", self.search_query);
+        }
+
+        let current_match = self.search_match_index.unwrap_or(0).saturating_add(1);
+        format!(
+            "search: {prompt}{} ({}/{})",
+            self.search_query,
+            current_match,
+            self.search_match_line_indexes.len()
+        )
+    }
+
+    /// `?` searches only added/deleted lines, `/` searches everything.
+    fn search_prompt(&self) -> char {
+        if self.search_changed_only { '?' } else { '/' }
+    }
+
+    fn begin_search(&mut self, changed_only: bool) {
+        self.enter_input_mode(InputMode::Search);
+        self.search_changed_only = changed_only;
+    }
+
+    /// Cycles the focus through both panes, left, and right, re-scoping search.
+    fn cycle_focused_pane(&mut self, files: &[DiffFileView]) {
+        self.focused_pane = match self.focused_pane {
+            None => Some(PaneSide::Left),
+            Some(PaneSide::Left) => Some(PaneSide::Right),
+            Some(PaneSide::Right) => None,
+        };
+        self.refresh_search_matches_for_current_file(files);
+    }
+
+    /// Shows a one-off message in the footer until the next keypress.
+    pub(crate) fn set_notice(&mut self, notice: String) {
+        self.notice = Some(notice);
+    }
+
+    /// The cursor line (or the `V` selection) on the head side, or on the base side
+    /// when the left pane is focused or the file was deleted.
+    fn current_blob_location(
+        &self,
+        files: &[DiffFileView],
+        comparison: &ResolvedComparison,
+    ) -> Option<BlobLocation> {
+        let file = &files[self.file_index];
+        let cursor = self.cursor();
+        let (first_line, last_line) = cursor.selection().unwrap_or((cursor.line, cursor.line));
+        let use_base = match self.focused_pane {
+            Some(PaneSide::Left) => file.descriptor.base_source != FileContentSource::Missing,
+            _ => file.descriptor.head_source == FileContentSource::Missing,
+        };
+        let (commit, path, side) = if use_base {
+            (
+                &comparison.base_commit,
+                file.descriptor.base_path.as_ref()?,
+                PaneSide::Left,
+            )
+        } else {
+            (
+                &comparison.head_commit,
+                file.descriptor.head_path.as_ref()?,
+                PaneSide::Right,
+            )
+        };
+
+        let lines = file.source_line_range(side, first_line, last_line);
+        Some(BlobLocation {
+            commit: commit.clone(),
+            path: path.clone(),
+            line: lines.map(|(first, _)| first + 1),
+            end_line: lines
+                .filter(|(first, last)| last > first)
+                .map(|(_, last)| last + 1),
+        })
+    }
+
+    fn refresh_search_matches_for_current_file(&mut self, files: &[DiffFileView]) {
+        if self.search_query.is_empty() {
+            self.search_match_line_indexes.clear();
+            self.search_match_index = None;
+            return;
+        }
+
+        let current_file = &files[self.file_index];
+        self.search_match_line_indexes = build_search_match_line_indexes(
+            current_file,
+            &self.search_query,
+            self.focused_pane,
+            self.search_changed_only,
+        );
+        self.search_match_index = if self.search_match_line_indexes.is_empty() {
+            None
+        } else {
+            Some(0)
+        };
+    }
+
+    fn jump_to_search_match(&mut self, files: &[DiffFileView], rows: u16, forward: bool) {
+        if self.search_match_line_indexes.is_empty() {
+            self.search_match_index = None;
+            return;
+        }
+
+        let next_match_index = next_match_index(
+            self.search_match_line_indexes.len(),
+            self.search_match_index,
+            forward,
+        );
+
+        if let Some(match_index) = next_match_index {
+            self.search_match_index = Some(match_index);
+            let target_line = self.search_match_line_indexes[match_index];
+            self.scroll_to_line(files, rows, target_line);
+        }
+    }
+
+    fn jump_to_hunk(&mut self, files: &[DiffFileView], rows: u16, forward: bool) {
+        let current_anchor = self
+            .focused_hunk_lines
+            .as_ref()
+            .and_then(|lines| {
+                if forward {
+                    lines.iter().max()
+                } else {
+                    lines.iter().min()
+                }
+            })
+            .copied()
+            .or(self.current_view().hunk_anchor)
+            .unwrap_or_else(|| self.cursor_line());
+        let hunk_starts = build_hunk_start_lines(&files[self.file_index]);
+
+        let target = if forward {
+            hunk_starts.iter().find(|&&line| line > current_anchor)
+        } else {
+            hunk_starts
+                .iter()
+                .rev()
+                .find(|&&line| line < current_anchor)
+        };
+
+        if let Some(&line) = target {
+            self.scroll_to_line(files, rows, line);
+            self.focused_hunk_lines = Some(build_hunk_line_range(&files[self.file_index], line));
+            self.current_view_mut().hunk_anchor = Some(line);
+            return;
+        }
+
+        // Cross-file wrap: cycle through files until we find the next/prev hunk.
+        let file_count = files.len();
+        if file_count <= 1 {
+            return;
+        }
+
+        for step in 1..file_count {
+            let next_index = if forward {
+                (self.file_index + step) % file_count
+            } else {
+                (self.file_index + file_count - step) % file_count
+            };
+            if self.is_file_hidden(files, next_index) {
+                continue;
+            }
+            let next_hunk_starts = build_hunk_start_lines(&files[next_index]);
+            let wrap_target = if forward {
+                next_hunk_starts.first()
+            } else {
+                next_hunk_starts.last()
+            };
+
+            if let Some(&line) = wrap_target {
+                self.switch_to_file(next_index);
+                self.refresh_search_matches_for_current_file(files);
+                self.scroll_to_line(files, rows, line);
+                self.focused_hunk_lines =
+                    Some(build_hunk_line_range(&files[self.file_index], line));
+                self.current_view_mut().hunk_anchor = Some(line);
+                return;
+            }
+        }
+    }
+
+    fn enter_input_mode(&mut self, mode: InputMode) {
+        self.input_mode = Some(mode);
+        self.input.clear();
+        if mode == InputMode::Search {
+            self.search_origin = Some(SearchOrigin {
+                scroll_offset: self.scroll_offset,
+                cursor_line: self.cursor_line(),
+                folds: self.current_view().folds.clone(),
+                search_query: self.search_query.clone(),
+                search_changed_only: self.search_changed_only,
+            });
+        }
+    }
+
+    fn exit_input_mode(&mut self) {
+        self.input_mode = None;
+        self.input.clear();
+        self.search_origin = None;
+        self.comment_target = None;
+    }
+
+    /// Puts the view and query back to how they were when search input started.
+    fn restore_search_origin(&mut self, files: &[DiffFileView]) {
+        if let Some(origin) = self.search_origin.clone() {
+            self.scroll_offset = origin.scroll_offset;
+            self.current_view_mut().cursor_line = origin.cursor_line;
+            self.current_view_mut().folds = origin.folds;
+            self.search_query = origin.search_query;
+            self.search_changed_only = origin.search_changed_only;
+            self.refresh_search_matches_for_current_file(files);
+        }
+    }
+
+    fn cancel_search_input(&mut self, files: &[DiffFileView]) {
+        self.restore_search_origin(files);
+        self.exit_input_mode();
+    }
+
+    /// Live search: re-runs the typed query from the starting position on every edit.
+    fn preview_search_input(&mut self, files: &[DiffFileView], rows: u16) {
+        self.restore_search_origin(files);
+        if self.input.is_empty() {
+            return;
+        }
+
+        self.search_query = self.input.clone();
+        self.refresh_search_matches_for_current_file(files);
+        let cursor_line = self.cursor_line();
+        if let Some(start_index) =
+            first_match_index_from_line(&self.search_match_line_indexes, cursor_line, true)
+        {
+            self.search_match_index = Some(start_index);
+            let target_line = self.search_match_line_indexes[start_index];
+            self.scroll_to_line(files, rows, target_line);
+        }
+    }
+
+    pub(crate) fn show_popup(&mut self, popup: Popup) {
+        self.popup = Some(popup);
+    }
+
+    fn scroll_popup(&mut self, delta: isize, rows: u16) {
+        let Some(popup) = self.popup.as_mut() else {
+            return;
+        };
+
+        let max_scroll = popup
+            .lines
+            .len()
+            .saturating_sub(get_popup_body_line_count(rows as usize));
+        popup.scroll = (popup.scroll as isize + delta).clamp(0, max_scroll as isize) as usize;
+    }
+
+    fn move_popup_selection(&mut self, delta: isize, rows: u16) {
+        let Some(popup) = self.popup.as_mut() else {
+            return;
+        };
+        let Some(selected) = popup.selected else {
+            return;
+        };
+
+        let max_index = popup.lines.len().saturating_sub(1);
+        let next_selected = (selected as isize + delta).clamp(0, max_index as isize) as usize;
+        let body_line_count = get_popup_body_line_count(rows as usize).max(1);
+        popup.selected = Some(next_selected);
+        if next_selected < popup.scroll {
+            popup.scroll = next_selected;
+        } else if next_selected >= popup.scroll + body_line_count {
+            popup.scroll = next_selected + 1 - body_line_count;
+        }
+    }
+
+    /// The cursor line of the focused pane (or of both) whole, wrapped to the popup width.
+    fn show_expanded_line(&mut self, files: &[DiffFileView], columns: u16) {
+        let current_file = &files[self.file_index];
+        let line_index = self.cursor_line();
+        let width = get_popup_body_width(columns as usize).max(1);
+        let mut lines = Vec::new();
+        for (pane, label, side_lines) in [
+            (PaneSide::Left, "left", &current_file.left_lines),
+            (PaneSide::Right, "right", &current_file.right_lines),
+        ] {
+            if self.focused_pane.is_some_and(|focused| focused != pane) {
+                continue;
+            }
+            let Some(line) = side_lines.get(line_index) else {
+                continue;
+            };
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(format!("{label}:"));
+            let characters: Vec<char> = normalize_content(line).chars().collect();
+            lines.extend(
+                characters
+                    .chunks(width)
+                    .map(|chunk| chunk.iter().collect::<String>()),
+            );
+        }
+        if lines.is_empty() {
+            lines.push("<no line at the cursor>".to_string());
+        }
+        let side = self.focused_pane.unwrap_or(current_file.primary_side());
+        let title = match current_file.source_line(side, line_index) {
+            Some(source_line) => format!(
+                "line {}: {}",
+                source_line + 1,
+                current_file.descriptor.display_path
+            ),
+            None => current_file.descriptor.display_path.clone(),
+        };
+        self.show_popup(Popup::new(title, lines));
+    }
+
+    /// Scrolls the focused pane, or both, so the first character where the two sides of
+    /// the cursor line differ is in view, a quarter of the pane in from its left edge.
+    fn scroll_to_first_difference(&mut self, files: &[DiffFileView], columns: u16, rows: u16) {
+        let current_file = &files[self.file_index];
+        let line_index = self.cursor_line();
+        let side_chars = |lines: &[String]| -> Vec<char> {
+            lines
+                .get(line_index)
+                .map(|line| normalize_content(line).chars().collect())
+                .unwrap_or_default()
+        };
+        let (left, right) = (
+            side_chars(&current_file.left_lines),
+            side_chars(&current_file.right_lines),
+        );
+        if left == right {
+            self.set_notice("no difference on this line".to_string());
+            return;
+        }
+        let first_difference = left
+            .iter()
+            .zip(&right)
+            .take_while(|(left, right)| left == right)
+            .count();
+
+        let layout = create_frame_layout(
+            self.frame_columns(columns),
+            rows,
+            current_file.line_count(),
+            &self.settings.render,
+        );
+        let max_offsets = get_max_pane_offsets(current_file, &layout);
+        let mut offsets = self.current_offsets();
+        for (pane, offset, max_offset, width) in [
+            (
+                PaneSide::Left,
+                &mut offsets.left,
+                max_offsets.left,
+                layout.left_content_width,
+            ),
+            (
+                PaneSide::Right,
+                &mut offsets.right,
+                max_offsets.right,
+                layout.right_content_width,
+            ),
+        ] {
+            if self.focused_pane.is_some_and(|focused| focused != pane) {
+                continue;
+            }
+            if !(*offset..*offset + width).contains(&first_difference) {
+                *offset = first_difference.saturating_sub(width / 4).min(max_offset);
+            }
+        }
+        self.set_current_offsets(offsets);
+    }
+
+    fn show_outline(&mut self, files: &[DiffFileView]) {
+        let current_file = &files[self.file_index];
+        let symbols = build_outline(current_file);
+        let title = format!("outline: {}", current_file.descriptor.display_path);
+        let line_indexes = symbols.iter().map(|symbol| symbol.line_index).collect();
+        let lines = symbols
+            .iter()
+            .map(|symbol| format_outline_symbol(current_file, symbol))
+            .collect();
+        let mut popup = Popup::list(title, lines, PopupKind::Outline { line_indexes });
+        if popup.lines.is_empty() {
+            popup
+                .lines
+                .push("<no symbols detected for this file>".to_string());
+        }
+        self.show_popup(popup);
+    }
+
+    pub(crate) fn show_commit_summary(&mut self, comparison: &ResolvedComparison) {
+        let title = format!(
+            "commits: {} ({})",
+            comparison.summary,
+            comparison.range_commits.len()
+        );
+        let mut lines: Vec<String> = comparison
+            .range_commits
+            .iter()
+            .map(CommitSummary::format_row)
+            .collect();
+        if lines.is_empty() {
+            lines.push("<no commits in this comparison>".to_string());
         }
-
-        *reviewed
+        self.show_popup(Popup {
+            kind: PopupKind::CommitSummary,
+            ..Popup::new(title, lines)
+        });
     }
 
-    pub(crate) fn search_status_text(&self) -> String {
-        if self.search_input_mode {
-            return format!("search: /{}", self.search_input);
+    fn show_annotations(&mut self, files: &[DiffFileView]) {
+        let current_file = &files[self.file_index];
+        let title = format!("annotations: {}", current_file.descriptor.display_path);
+        let annotations = &current_file.right_annotations;
+        let line_indexes = annotations
+            .iter()
+            .map(|annotation| {
+                current_file.row_of_source_line(PaneSide::Right, annotation.line_index)
+            })
+            .collect();
+        let mut popup = Popup::list(
+            title,
+            annotations.iter().map(Annotation::format_row).collect(),
+            PopupKind::Annotations { line_indexes },
+        );
+        if popup.lines.is_empty() {
+            popup
+                .lines
+                .push("<no annotations for this file>".to_string());
         }
+        self.show_popup(popup);
+    }
 
-        if self.search_query.is_empty() {
-            return "search: /".to_string();
+    fn update_hover_message(
+        &mut self,
+        files: &[DiffFileView],
+        layout: &FrameLayout,
+        hovered_pane: Option<PaneSide>,
+        row: usize,
+    ) {
+        self.hover_message = None;
+        if hovered_pane != Some(PaneSide::Right) || row < layout.body_start_row {
+            return;
         }
 
-        if self.search_match_line_indexes.is_empty() {
-            return format!("search: /{} (no matches)", self.search_query);
+        let display_rows = self.display_rows(files);
+        let Some(DisplayRow::Line(line_index)) =
+            display_rows.get(self.scroll_offset + row - layout.body_start_row)
+        else {
+            return;
+        };
+        let file = &files[self.file_index];
+        let source_line = file.source_line(PaneSide::Right, *line_index);
+        let messages: Vec<String> = file
+            .right_annotations
+            .iter()
+            .filter(|annotation| Some(annotation.line_index) == source_line)
+            .map(|annotation| format!("{}: {}", annotation.severity.as_str(), annotation.message))
+            .collect();
+        if !messages.is_empty() {
+            self.hover_message = Some(messages.join(" | "));
         }
+    }
 
-        let current_match = self.search_match_index.unwrap_or(0).saturating_add(1);
-        format!(
-            "search: /{} ({}/{})",
-            self.search_query,
-            current_match,
-            self.search_match_line_indexes.len()
-        )
+    fn show_diff_summary(&mut self, files: &[DiffFileView]) {
+        self.show_popup(Popup {
+            kind: PopupKind::DiffSummary,
+            ..Popup::new("diff summary".to_string(), build_diff_summary_lines(files))
+        });
     }
 
-    fn is_search_input_mode(&self) -> bool {
-        self.search_input_mode
+    /// Opens the start file with the cursor on the start line, clamped to the file.
+    pub(crate) fn open_at(&mut self, files: &[DiffFileView], rows: u16, start: StartPosition) {
+        self.switch_to_file(start.file_index);
+        self.current_view_mut().opened = true;
+        let last_line = files[start.file_index].line_count().saturating_sub(1);
+        self.jump_to_line(files, rows, start.line_index.min(last_line));
     }
 
-    fn refresh_search_matches_for_current_file(&mut self, files: &[DiffFileView]) {
-        if self.search_query.is_empty() {
-            self.search_match_line_indexes.clear();
-            self.search_match_index = None;
+    /// `--first-change`: a file shown for the first time, and not already moved to a
+    /// line (e.g. by a hunk jump from the previous file) or collapsed by the project,
+    /// opens at its first hunk.
+    pub(crate) fn place_new_file(&mut self, files: &[DiffFileView], rows: u16) {
+        let view = self.current_view_mut();
+        if std::mem::replace(&mut view.opened, true) {
             return;
         }
+        let untouched = view.cursor_line == 0 && self.scroll_offset == 0;
+        if self.settings.open_at_first_change
+            && untouched
+            && !files[self.file_index].collapsed
+            && let Some(&line_index) = build_hunk_start_lines(&files[self.file_index]).first()
+        {
+            self.jump_to_line(files, rows, line_index);
+        }
+    }
 
-        let current_file = &files[self.file_index];
-        self.search_match_line_indexes =
-            build_search_match_line_indexes(current_file, &self.search_query);
-        self.search_match_index = if self.search_match_line_indexes.is_empty() {
-            None
-        } else {
-            Some(0)
-        };
+    fn jump_to_line(&mut self, files: &[DiffFileView], rows: u16, line_index: usize) {
+        self.scroll_to_line(files, rows, line_index);
+        self.focused_hunk_lines = None;
+        self.current_view_mut().hunk_anchor = Some(line_index);
     }
 
-    fn jump_to_search_match(&mut self, files: &[DiffFileView], rows: u16, forward: bool) {
-        if self.search_match_line_indexes.is_empty() {
-            self.search_match_index = None;
+    fn apply_popup_selection(&mut self, files: &[DiffFileView], rows: u16) {
+        let Some(popup) = self.popup.take() else {
             return;
-        }
-
-        let next_match_index = next_match_index(
-            self.search_match_line_indexes.len(),
-            self.search_match_index,
-            forward,
-        );
+        };
 
-        if let Some(match_index) = next_match_index {
-            self.search_match_index = Some(match_index);
-            let target_line = self.search_match_line_indexes[match_index];
-            let max_scroll = max_scroll_for_current_file(files, self, rows);
-            self.scroll_offset = target_line.min(max_scroll);
+        if let (
+            PopupKind::Outline { line_indexes } | PopupKind::Annotations { line_indexes },
+            Some(selected),
+        ) = (&popup.kind, popup.selected)
+            && let Some(&line_index) = line_indexes.get(selected)
+        {
+            self.jump_to_line(files, rows, line_index);
         }
     }
 
-    fn jump_to_hunk(&mut self, files: &[DiffFileView], rows: u16, forward: bool) {
-        let current_anchor = self
-            .focused_hunk_lines
-            .as_ref()
-            .and_then(|lines| {
-                if forward {
-                    lines.iter().max()
-                } else {
-                    lines.iter().min()
-                }
-            })
-            .copied()
-            .or(self.hunk_anchor_by_file[self.file_index])
-            .unwrap_or(self.scroll_offset);
-        let hunk_starts = build_hunk_start_lines(&files[self.file_index]);
+    /// A `[pipe]` command's name runs the command it names.
+    fn apply_pipe_input(&mut self, files: &[DiffFileView]) -> Option<PipeRequest> {
+        let input = self.input.trim().to_string();
+        self.exit_input_mode();
+        if input.is_empty() {
+            return None;
+        }
+        let command = self
+            .pipe_commands
+            .iter()
+            .rev()
+            .find(|(name, _)| *name == input)
+            .map_or(input, |(_, command)| command.clone());
 
-        let target = if forward {
-            hunk_starts.iter().find(|&&line| line > current_anchor)
-        } else {
-            hunk_starts
-                .iter()
-                .rev()
-                .find(|&&line| line < current_anchor)
-        };
+        let current_file = &files[self.file_index];
+        let (scope, input) = build_pipe_input(current_file, self.focused_hunk_lines.as_ref());
+        Some(PipeRequest {
+            command,
+            scope,
+            path: current_file.descriptor.display_path.clone(),
+            input,
+        })
+    }
 
-        if let Some(&line) = target {
-            let max_scroll = max_scroll_for_current_file(files, self, rows);
-            self.scroll_offset = line.min(max_scroll);
-            self.focused_hunk_lines = Some(build_hunk_line_range(&files[self.file_index], line));
-            self.hunk_anchor_by_file[self.file_index] = Some(line);
-            return;
+    fn apply_search_input(&mut self, files: &[DiffFileView], rows: u16) {
+        self.preview_search_input(files, rows);
+        // An empty query clears the search rather than restoring the previous one.
+        if self.input.is_empty() {
+            self.search_query.clear();
+            self.refresh_search_matches_for_current_file(files);
         }
+        self.exit_input_mode();
+    }
+}
 
-        // Cross-file wrap: cycle through files until we find the next/prev hunk.
-        let file_count = files.len();
-        if file_count <= 1 {
-            return;
-        }
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum ListPickerOutcome {
+    Continue,
+    Select(usize),
+    Cancel,
+}
 
-        for step in 1..file_count {
-            let next_index = if forward {
-                (self.file_index + step) % file_count
-            } else {
-                (self.file_index + file_count - step) % file_count
-            };
-            let next_hunk_starts = build_hunk_start_lines(&files[next_index]);
-            let wrap_target = if forward {
-                next_hunk_starts.first()
-            } else {
-                next_hunk_starts.last()
-            };
+#[derive(Clone, Debug)]
+pub(crate) struct ListPickerState {
+    pub(crate) selected: usize,
+    pub(crate) scroll: usize,
+    item_count: usize,
+}
 
-            if let Some(&line) = wrap_target {
-                self.file_index = next_index;
-                self.refresh_search_matches_for_current_file(files);
-                let max_scroll = max_scroll_for_current_file(files, self, rows);
-                self.scroll_offset = line.min(max_scroll);
-                self.focused_hunk_lines =
-                    Some(build_hunk_line_range(&files[self.file_index], line));
-                self.hunk_anchor_by_file[self.file_index] = Some(line);
-                return;
-            }
+impl ListPickerState {
+    pub(crate) fn new(item_count: usize, selected: usize) -> Self {
+        Self {
+            selected: selected.min(item_count.saturating_sub(1)),
+            scroll: 0,
+            item_count,
         }
     }
 
-    fn enter_search_input_mode(&mut self) {
-        self.search_input_mode = true;
-        self.search_input.clear();
+    pub(crate) fn keep_selection_visible(&mut self, rows: u16) {
+        let body_line_count = get_list_body_line_count(rows as usize);
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + body_line_count {
+            self.scroll = self.selected + 1 - body_line_count;
+        }
     }
 
-    fn exit_search_input_mode(&mut self) {
-        self.search_input_mode = false;
-        self.search_input.clear();
+    fn move_selection(&mut self, delta: isize) {
+        let max_index = self.item_count.saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, max_index) as usize;
     }
 
-    fn apply_search_input(&mut self, files: &[DiffFileView], rows: u16) {
-        self.search_query = self.search_input.clone();
-        self.search_input_mode = false;
-        self.search_input.clear();
-        self.refresh_search_matches_for_current_file(files);
+    pub(crate) fn handle_key(&mut self, key: KeyEvent, rows: u16) -> ListPickerOutcome {
+        let page_size = get_list_body_line_count(rows as usize).max(1) as isize;
+        let is_control = key.modifiers.contains(KeyModifiers::CONTROL);
 
-        if self.search_match_line_indexes.is_empty() {
-            return;
+        match key.code {
+            KeyCode::Char('c') if is_control => return ListPickerOutcome::Cancel,
+            KeyCode::Char('q') | KeyCode::Esc => return ListPickerOutcome::Cancel,
+            KeyCode::Enter if self.item_count > 0 => {
+                return ListPickerOutcome::Select(self.selected);
+            }
+            KeyCode::Char('u') if is_control => self.move_selection(-page_size),
+            KeyCode::Char('d') if is_control => self.move_selection(page_size),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-page_size),
+            KeyCode::PageDown => self.move_selection(page_size),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => {
+                self.selected = self.item_count.saturating_sub(1);
+            }
+            _ => {}
         }
 
-        if let Some(start_index) =
-            first_match_index_from_line(&self.search_match_line_indexes, self.scroll_offset, true)
-        {
-            self.search_match_index = Some(start_index);
-            let target_line = self.search_match_line_indexes[start_index];
-            let max_scroll = max_scroll_for_current_file(files, self, rows);
-            self.scroll_offset = target_line.min(max_scroll);
-        }
+        self.keep_selection_visible(rows);
+        ListPickerOutcome::Continue
     }
 }
 
 fn max_scroll_for_current_file(files: &[DiffFileView], app: &AppState, rows: u16) -> usize {
-    let current_file = &files[app.file_index];
-    let max_lines = current_file
-        .left_lines
-        .len()
-        .max(current_file.right_lines.len());
+    let display_row_count = app.display_rows(files).len();
     let body_line_count = get_body_line_count(rows as usize);
-    max_lines.saturating_sub(body_line_count)
+    display_row_count.saturating_sub(body_line_count)
 }
 
+/// Moves `delta` files away, skipping hidden ones; stays put at either end.
 fn move_file(delta: isize, files: &[DiffFileView], app: &mut AppState) -> bool {
-    let max_index = files.len().saturating_sub(1) as isize;
-    let next_index = (app.file_index as isize + delta).clamp(0, max_index) as usize;
-    if next_index != app.file_index {
-        app.file_index = next_index;
-        app.scroll_offset = 0;
-        app.focused_hunk_lines = None;
-        app.hunk_anchor_by_file[app.file_index] = None;
-        return true;
+    let mut next_index = app.file_index as isize;
+    let mut remaining = delta.unsigned_abs();
+    let mut target = None;
+    while remaining > 0 {
+        next_index += delta.signum();
+        if next_index < 0 || next_index >= files.len() as isize {
+            break;
+        }
+        if !app.is_file_hidden(files, next_index as usize) {
+            target = Some(next_index as usize);
+            remaining -= 1;
+        }
     }
 
-    false
+    match target {
+        Some(next_index) => {
+            app.switch_to_file(next_index);
+            true
+        }
+        None => false,
+    }
 }
 
+/// Scrolls by `delta` rows. With a scrolloff, a focused hunk stays focused while it
+/// remains at least that many rows inside the body; otherwise any move drops the focus.
 fn move_scroll(delta: isize, files: &[DiffFileView], app: &mut AppState, rows: u16) {
     let max_scroll = max_scroll_for_current_file(files, app, rows);
     let previous_offset = app.scroll_offset;
     let next_offset = (app.scroll_offset as isize + delta).clamp(0, max_scroll as isize) as usize;
     app.scroll_offset = next_offset;
-    if next_offset != previous_offset {
+    if next_offset != previous_offset && !app.focused_hunk_within_scrolloff(files, rows) {
         app.focused_hunk_lines = None;
-        app.hunk_anchor_by_file[app.file_index] = None;
+        app.current_view_mut().hunk_anchor = None;
     }
 }
 
+/// Scrolls a page and carries the cursor along by the same number of rows.
+fn move_page(delta: isize, files: &[DiffFileView], app: &mut AppState, rows: u16) {
+    move_scroll(delta, files, app, rows);
+    app.move_cursor(files, rows, delta);
+}
+
 fn scroll_to_top(app: &mut AppState) {
+    app.move_cursor_to_first_line();
     if app.scroll_offset != 0 {
         app.scroll_offset = 0;
         app.focused_hunk_lines = None;
-        app.hunk_anchor_by_file[app.file_index] = None;
+        app.current_view_mut().hunk_anchor = None;
     }
 }
 
 fn scroll_to_bottom(files: &[DiffFileView], app: &mut AppState, rows: u16) {
+    app.move_cursor_to_last_line(files);
     let next_offset = max_scroll_for_current_file(files, app, rows);
     if next_offset != app.scroll_offset {
         app.scroll_offset = next_offset;
         app.focused_hunk_lines = None;
-        app.hunk_anchor_by_file[app.file_index] = None;
+        app.current_view_mut().hunk_anchor = None;
     }
 }
 
@@ -309,13 +2128,14 @@ fn move_horizontal(
     rows: u16,
 ) {
     let current_file = &files[app.file_index];
-    let max_lines = current_file
-        .left_lines
-        .len()
-        .max(current_file.right_lines.len());
-    let layout = create_frame_layout(columns, rows, max_lines);
+    let layout = create_frame_layout(
+        app.frame_columns(columns),
+        rows,
+        current_file.line_count(),
+        &app.settings.render,
+    );
     let max_offsets = get_max_pane_offsets(current_file, &layout);
-    let current_offsets = &mut app.pane_offsets_by_file[app.file_index];
+    let current_offsets = &mut app.current_view_mut().pane_offsets;
 
     match pane {
         PaneSide::Left => {
@@ -348,12 +2168,10 @@ fn build_hunk_start_lines(file: &DiffFileView) -> Vec<usize> {
 
 fn build_hunk_line_range(file: &DiffFileView, hunk_start: usize) -> HashSet<usize> {
     let mut range = HashSet::new();
-    let max_lines = file.left_lines.len().max(file.right_lines.len());
+    let max_lines = file.line_count();
     let mut line = hunk_start;
     while line < max_lines {
-        let is_changed = file.left_deleted_line_indexes.contains(&line)
-            || file.right_added_line_indexes.contains(&line);
-        if !is_changed {
+        if !file.is_line_changed(line) {
             break;
         }
         range.insert(line);
@@ -362,22 +2180,111 @@ fn build_hunk_line_range(file: &DiffFileView, hunk_start: usize) -> HashSet<usiz
     range
 }
 
-fn build_search_match_line_indexes(file: &DiffFileView, query: &str) -> Vec<usize> {
+/// The file line a row shows, head side first, to find the row again after a relayout.
+fn row_anchor(file: &DiffFileView, row: usize) -> (PaneSide, usize) {
+    [PaneSide::Right, PaneSide::Left]
+        .into_iter()
+        .find_map(|side| Some((side, file.source_line(side, row)?)))
+        .unwrap_or((PaneSide::Right, row))
+}
+
+/// The file lines a hunk on rows `first_line..=last_line` covers: head lines, or base
+/// lines for a pure deletion, or the rows themselves when both sides are filler.
+fn hunk_source_lines(file: &DiffFileView, first_line: usize, last_line: usize) -> (usize, usize) {
+    file.source_line_range(PaneSide::Right, first_line, last_line)
+        .or_else(|| file.source_line_range(PaneSide::Left, first_line, last_line))
+        .unwrap_or((first_line, last_line))
+}
+
+/// Identifies a hunk by its file and changed lines, so its reviewed mark survives
+/// unrelated edits elsewhere in the file. The prefix keeps it distinct from the key of
+/// a file whose only hunk this is.
+fn hunk_review_key(file: &DiffFileView, first_line: usize, last_line: usize) -> String {
+    let in_hunk = |changed: &HashSet<usize>| -> HashSet<usize> {
+        changed
+            .iter()
+            .copied()
+            .filter(|index| (first_line..=last_line).contains(index))
+            .collect()
+    };
+    format!(
+        "hunk-{}",
+        compute_review_key(
+            &file.descriptor,
+            &file.left_lines,
+            &in_hunk(&file.left_deleted_line_indexes),
+            &file.right_lines,
+            &in_hunk(&file.right_added_line_indexes),
+        )
+    )
+}
+
+pub(crate) fn hunk_review_keys(file: &DiffFileView) -> Vec<String> {
+    build_hunk_start_lines(file)
+        .into_iter()
+        .filter_map(|start| {
+            let last = build_hunk_line_range(file, start).into_iter().max()?;
+            Some(hunk_review_key(file, start, last))
+        })
+        .collect()
+}
+
+fn describe_review_marks(files: &[DiffFileView], marks: &[ReviewMark]) -> String {
+    let Some(mark) = marks.first() else {
+        return String::new();
+    };
+    let state = if mark.reviewed() {
+        "reviewed"
+    } else {
+        "not reviewed"
+    };
+    let path = &files[mark.file_index()].descriptor.display_path;
+    let subject = match mark {
+        ReviewMark::File { .. } => path.clone(),
+        ReviewMark::Hunk { .. } => format!("hunk in {path}"),
+    };
+    match marks.len() {
+        1 => format!("{subject} {state}"),
+        count => format!("{subject} {state} (+{} more)", count - 1),
+    }
+}
+
+fn format_hunk_action(action: HunkAction, reviewed: bool) -> &'static str {
+    match action {
+        HunkAction::Copy => "copy hunk",
+        HunkAction::Stage => "stage hunk",
+        HunkAction::Discard => "discard hunk",
+        HunkAction::ToggleReviewed if reviewed => "unmark hunk reviewed",
+        HunkAction::ToggleReviewed => "mark hunk reviewed",
+        HunkAction::Comment => "comment on hunk",
+    }
+}
+
+fn build_search_match_line_indexes(
+    file: &DiffFileView,
+    query: &str,
+    scope: Option<PaneSide>,
+    changed_only: bool,
+) -> Vec<usize> {
     if query.is_empty() {
         return Vec::new();
     }
 
-    let max_lines = file.left_lines.len().max(file.right_lines.len());
+    let max_lines = file.line_count();
     let mut match_indexes = Vec::new();
     for line_index in 0..max_lines {
-        let left_matches = file
-            .left_lines
-            .get(line_index)
-            .is_some_and(|line| line.contains(query));
-        let right_matches = file
-            .right_lines
-            .get(line_index)
-            .is_some_and(|line| line.contains(query));
+        let left_matches = scope != Some(PaneSide::Right)
+            && (!changed_only || file.left_deleted_line_indexes.contains(&line_index))
+            && file
+                .left_lines
+                .get(line_index)
+                .is_some_and(|line| line.contains(query));
+        let right_matches = scope != Some(PaneSide::Left)
+            && (!changed_only || file.right_added_line_indexes.contains(&line_index))
+            && file
+                .right_lines
+                .get(line_index)
+                .is_some_and(|line| line.contains(query));
 
         if left_matches || right_matches {
             match_indexes.push(line_index);
@@ -387,6 +2294,38 @@ fn build_search_match_line_indexes(file: &DiffFileView, query: &str) -> Vec<usiz
     match_indexes
 }
 
+fn build_pipe_input(
+    file: &DiffFileView,
+    hunk_lines: Option<&HashSet<usize>>,
+) -> (PipeScope, String) {
+    if let Some(hunk_lines) = hunk_lines {
+        let mut line_indexes: Vec<usize> = hunk_lines.iter().copied().collect();
+        line_indexes.sort_unstable();
+
+        let mut output = String::new();
+        for line_index in &line_indexes {
+            if file.left_deleted_line_indexes.contains(line_index)
+                && let Some(line) = file.left_lines.get(*line_index)
+            {
+                output.push_str(&format!("-{line}\n"));
+            }
+        }
+        for line_index in &line_indexes {
+            if file.right_added_line_indexes.contains(line_index)
+                && let Some(line) = file.right_lines.get(*line_index)
+            {
+                output.push_str(&format!("+{line}\n"));
+            }
+        }
+
+        return (PipeScope::Hunk, output);
+    }
+
+    let mut output = file.primary_source_lines().join("\n");
+    output.push('\n');
+    (PipeScope::File, output)
+}
+
 fn first_match_index_from_line(
     matches: &[usize],
     line_index: usize,
@@ -439,7 +2378,45 @@ fn next_match_index(
 pub(crate) fn handle_keypress(
     key: KeyEvent,
     files: &[DiffFileView],
+    comparison: &ResolvedComparison,
+    app: &mut AppState,
+    columns: u16,
+    rows: u16,
+) -> KeypressOutcome {
+    let outcome = dispatch_keypress(key, files, comparison, app, columns, rows);
+    app.place_new_file(files, rows);
+    app.keep_cursor_in_view(files, rows);
+    outcome
+}
+
+/// Bracketed paste: inserted into the active input line (with line breaks flattened to
+/// spaces) and ignored otherwise, so pasted text is never replayed as commands.
+pub(crate) fn handle_paste(text: &str, files: &[DiffFileView], app: &mut AppState, rows: u16) {
+    let Some(mode) = app.input_mode else {
+        return;
+    };
+    if app.popup.is_some() {
+        return;
+    }
+    let pasted: String = text
+        .replace("\r\n", " ")
+        .chars()
+        .map(|ch| if ch == '\n' || ch == '\r' { ' ' } else { ch })
+        .filter(|ch| !ch.is_control())
+        .collect();
+    app.input.push_str(&pasted);
+    if mode == InputMode::Search {
+        app.preview_search_input(files, rows);
+    }
+    app.keep_cursor_in_view(files, rows);
+}
+
+fn dispatch_keypress(
+    key: KeyEvent,
+    files: &[DiffFileView],
+    comparison: &ResolvedComparison,
     app: &mut AppState,
+    columns: u16,
     rows: u16,
 ) -> KeypressOutcome {
     if key.modifiers.contains(KeyModifiers::CONTROL)
@@ -447,22 +2424,94 @@ pub(crate) fn handle_keypress(
     {
         return KeypressOutcome {
             should_quit: true,
-            review_toggled: None,
+            ..KeypressOutcome::default()
         };
     }
+    app.notice = None;
+
+    if let Some(popup) = app.popup.as_ref() {
+        let selectable = popup.selected.is_some();
+        let is_outline = matches!(popup.kind, PopupKind::Outline { .. });
+        let is_commit_summary = popup.kind == PopupKind::CommitSummary;
+        let is_diff_summary = popup.kind == PopupKind::DiffSummary;
+        let is_annotations = matches!(popup.kind, PopupKind::Annotations { .. });
+        let is_hunk_menu = matches!(popup.kind, PopupKind::HunkActions { .. });
+        let is_bulk_review = matches!(popup.kind, PopupKind::ConfirmBulkReview { .. });
+        let is_file_bases = matches!(popup.kind, PopupKind::FileBases { .. });
+        let page_size = get_popup_body_line_count(rows as usize).max(1) as isize;
+        let action = key_map().action(key);
+        let delta = match action {
+            Some(Action::CursorUp) => -1,
+            Some(Action::CursorDown) => 1,
+            Some(Action::PageUp) => -page_size,
+            Some(Action::PageDown) => page_size,
+            _ => 0,
+        };
+
+        match key.code {
+            KeyCode::Esc => app.popup = None,
+            _ if action == Some(Action::Quit) => app.popup = None,
+            _ if is_outline && action == Some(Action::Outline) => app.popup = None,
+            _ if is_commit_summary && action == Some(Action::CommitSummary) => app.popup = None,
+            _ if is_diff_summary && action == Some(Action::DiffSummary) => app.popup = None,
+            _ if is_annotations && action == Some(Action::Annotations) => app.popup = None,
+            KeyCode::Enter if is_hunk_menu => return app.apply_hunk_menu_selection(files),
+            KeyCode::Enter if is_bulk_review => return app.apply_bulk_review_confirmation(files),
+            KeyCode::Enter if is_file_bases => return app.apply_file_base_selection(),
+            KeyCode::Enter => app.apply_popup_selection(files, rows),
+            _ if delta != 0 && selectable => app.move_popup_selection(delta, rows),
+            _ if delta != 0 => app.scroll_popup(delta, rows),
+            _ => {}
+        }
+
+        return KeypressOutcome::default();
+    }
 
-    if app.is_search_input_mode() {
+    if let Some(mode) = app.input_mode {
         match key.code {
-            KeyCode::Enter => app.apply_search_input(files, rows),
-            KeyCode::Esc => app.exit_search_input_mode(),
+            KeyCode::Enter => match mode {
+                InputMode::Search => app.apply_search_input(files, rows),
+                InputMode::Pipe => {
+                    return KeypressOutcome {
+                        pipe_request: app.apply_pipe_input(files),
+                        ..KeypressOutcome::default()
+                    };
+                }
+                InputMode::Comment => {
+                    return KeypressOutcome {
+                        hunk_request: app.apply_comment_input(files),
+                        ..KeypressOutcome::default()
+                    };
+                }
+                InputMode::CommitMessage => {
+                    let message = app.input.trim().to_string();
+                    app.exit_input_mode();
+                    return KeypressOutcome {
+                        commit_message: (!message.is_empty()).then_some(message),
+                        ..KeypressOutcome::default()
+                    };
+                }
+            },
+            KeyCode::Esc => match mode {
+                InputMode::Search => app.cancel_search_input(files),
+                InputMode::Pipe | InputMode::Comment | InputMode::CommitMessage => {
+                    app.exit_input_mode()
+                }
+            },
             KeyCode::Backspace => {
-                let _ = app.search_input.pop();
+                let _ = app.input.pop();
+                if mode == InputMode::Search {
+                    app.preview_search_input(files, rows);
+                }
             }
             KeyCode::Char(ch)
                 if !key.modifiers.contains(KeyModifiers::CONTROL)
                     && !key.modifiers.contains(KeyModifiers::ALT) =>
             {
-                app.search_input.push(ch);
+                app.input.push(ch);
+                if mode == InputMode::Search {
+                    app.preview_search_input(files, rows);
+                }
             }
             _ => {}
         }
@@ -470,119 +2519,257 @@ pub(crate) fn handle_keypress(
         return KeypressOutcome::default();
     }
 
-    match key.code {
-        KeyCode::Char('q') | KeyCode::Char('Q') => KeypressOutcome {
+    if app.handle_file_list_key(key, files, rows) {
+        return KeypressOutcome::default();
+    }
+
+    if app.pending_z_prefix {
+        app.pending_z_prefix = false;
+        app.handle_z_command(key.code, files, rows);
+        return KeypressOutcome::default();
+    }
+
+    let Some(action) = key_map().action(key) else {
+        return KeypressOutcome::default();
+    };
+    match action {
+        Action::Quit => KeypressOutcome {
             should_quit: true,
-            review_toggled: None,
+            ..KeypressOutcome::default()
         },
-        KeyCode::Left => {
+        Action::PreviousFile => {
             if move_file(-1, files, app) {
                 app.refresh_search_matches_for_current_file(files);
             }
             KeypressOutcome::default()
         }
-        KeyCode::Right => {
+        Action::NextFile => {
             if move_file(1, files, app) {
                 app.refresh_search_matches_for_current_file(files);
             }
             KeypressOutcome::default()
         }
-        KeyCode::Up => {
-            move_scroll(-1, files, app, rows);
+        Action::NextUnreviewedFile => {
+            app.jump_to_next_unreviewed_file(files);
             KeypressOutcome::default()
         }
-        KeyCode::Down => {
-            move_scroll(1, files, app, rows);
+        Action::CursorUp => {
+            app.move_cursor(files, rows, -1);
             KeypressOutcome::default()
         }
-        KeyCode::Char('h') => {
-            if move_file(-1, files, app) {
-                app.refresh_search_matches_for_current_file(files);
-            }
+        Action::CursorDown => {
+            app.move_cursor(files, rows, 1);
             KeypressOutcome::default()
         }
-        KeyCode::Char('l') => {
-            if move_file(1, files, app) {
-                app.refresh_search_matches_for_current_file(files);
-            }
+        Action::PageUp => {
+            let page_size = get_body_line_count(rows as usize).max(1) as isize;
+            move_page(-page_size, files, app, rows);
             KeypressOutcome::default()
         }
-        KeyCode::Char('k') => {
-            move_scroll(-1, files, app, rows);
+        Action::PageDown => {
+            let page_size = get_body_line_count(rows as usize).max(1) as isize;
+            move_page(page_size, files, app, rows);
             KeypressOutcome::default()
         }
-        KeyCode::Char('j') => {
-            move_scroll(1, files, app, rows);
+        Action::Bottom => {
+            scroll_to_bottom(files, app, rows);
             KeypressOutcome::default()
         }
-        KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            let page_size = get_body_line_count(rows as usize).max(1) as isize;
-            move_scroll(-page_size, files, app, rows);
+        Action::Top => {
+            scroll_to_top(app);
             KeypressOutcome::default()
         }
-        KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            let page_size = get_body_line_count(rows as usize).max(1) as isize;
-            move_scroll(page_size, files, app, rows);
+        Action::NextTab => KeypressOutcome {
+            switch_tab: Some(TabSwitch::Next),
+            ..KeypressOutcome::default()
+        },
+        Action::PreviousTab => KeypressOutcome {
+            switch_tab: Some(TabSwitch::Previous),
+            ..KeypressOutcome::default()
+        },
+        Action::HunkMenu => {
+            app.show_hunk_menu(files);
             KeypressOutcome::default()
         }
-        KeyCode::PageUp => {
-            let page_size = get_body_line_count(rows as usize).max(1) as isize;
-            move_scroll(-page_size, files, app, rows);
+        Action::VisualSelect => {
+            app.toggle_visual_mode();
             KeypressOutcome::default()
         }
-        KeyCode::PageDown => {
-            let page_size = get_body_line_count(rows as usize).max(1) as isize;
-            move_scroll(page_size, files, app, rows);
+        Action::ClearSelection => {
+            app.visual_anchor = None;
             KeypressOutcome::default()
         }
-        KeyCode::Home => {
-            scroll_to_top(app);
+        Action::Search => {
+            app.begin_search(false);
             KeypressOutcome::default()
         }
-        KeyCode::End => {
-            scroll_to_bottom(files, app, rows);
+        Action::SearchBackward => {
+            app.begin_search(true);
+            KeypressOutcome::default()
+        }
+        Action::Pipe => {
+            app.enter_input_mode(InputMode::Pipe);
+            KeypressOutcome::default()
+        }
+        // Commits record the index, so the comparison has to show it or the working tree
+        // it is staged from; committed ranges have nothing left to commit.
+        Action::Commit if comparison.includes_uncommitted => {
+            app.enter_input_mode(InputMode::CommitMessage);
+            KeypressOutcome::default()
+        }
+        Action::Commit => {
+            app.set_notice(
+                "commit: needs uncommitted changes in the comparison (e.g. --staged or --amend)"
+                    .to_string(),
+            );
+            KeypressOutcome::default()
+        }
+        Action::ExpandLine => {
+            app.show_expanded_line(files, columns);
+            KeypressOutcome::default()
+        }
+        Action::ToggleView => KeypressOutcome {
+            toggle_view: true,
+            ..KeypressOutcome::default()
+        },
+        Action::ReloadFile => KeypressOutcome {
+            reload_file: true,
+            ..KeypressOutcome::default()
+        },
+        Action::PickFileBase => KeypressOutcome {
+            pick_file_base: true,
+            ..KeypressOutcome::default()
+        },
+        Action::CyclePane => {
+            app.cycle_focused_pane(files);
+            KeypressOutcome::default()
+        }
+        Action::ScrollLeft | Action::ScrollRight => {
+            let delta = if action == Action::ScrollLeft {
+                -(KEYBOARD_HORIZONTAL_COLUMNS as isize)
+            } else {
+                KEYBOARD_HORIZONTAL_COLUMNS as isize
+            };
+            let panes = match app.focused_pane {
+                Some(pane) => vec![pane],
+                None => vec![PaneSide::Left, PaneSide::Right],
+            };
+            for pane in panes {
+                move_horizontal(pane, delta, files, app, columns, rows);
+            }
+            KeypressOutcome::default()
+        }
+        Action::FirstDifference => {
+            app.scroll_to_first_difference(files, columns, rows);
+            KeypressOutcome::default()
+        }
+        Action::ChangesOnly => {
+            app.set_changes_only(files, rows, !app.changes_only);
+            app.set_notice(if app.changes_only {
+                format!(
+                    "changes only, {} lines of context",
+                    app.settings.context_lines
+                )
+            } else {
+                "whole files".to_string()
+            });
+            KeypressOutcome::default()
+        }
+        Action::CopyPermalink => KeypressOutcome {
+            forge_request: app
+                .current_blob_location(files, comparison)
+                .map(|location| ForgeRequest {
+                    action: ForgeAction::CopyPermalink,
+                    location,
+                }),
+            ..KeypressOutcome::default()
+        },
+        Action::OpenInBrowser => KeypressOutcome {
+            forge_request: app
+                .current_blob_location(files, comparison)
+                .map(|location| ForgeRequest {
+                    action: ForgeAction::OpenInBrowser,
+                    location: BlobLocation {
+                        line: None,
+                        end_line: None,
+                        ..location
+                    },
+                }),
+            ..KeypressOutcome::default()
+        },
+        Action::SelectionPatch => KeypressOutcome {
+            selection_patch: app.take_selection_patch(files),
+            ..KeypressOutcome::default()
+        },
+        Action::Outline => {
+            app.show_outline(files);
+            KeypressOutcome::default()
+        }
+        Action::CommitSummary => {
+            app.show_commit_summary(comparison);
             KeypressOutcome::default()
         }
-        KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::SHIFT) => {
-            scroll_to_bottom(files, app, rows);
+        Action::DiffSummary => {
+            app.show_diff_summary(files);
             KeypressOutcome::default()
         }
-        KeyCode::Char('G') => {
-            scroll_to_bottom(files, app, rows);
+        Action::Annotations => {
+            app.show_annotations(files);
             KeypressOutcome::default()
         }
-        KeyCode::Char('g') => {
-            scroll_to_top(app);
+        Action::HideWhitespaceOnly => {
+            app.toggle_hide_whitespace_only(files);
             KeypressOutcome::default()
         }
-        KeyCode::Char('/') => {
-            app.enter_search_input_mode();
+        Action::FoldPrefix => {
+            app.pending_z_prefix = true;
             KeypressOutcome::default()
         }
-        KeyCode::Char('n') => {
+        Action::NextMatch => {
             app.jump_to_search_match(files, rows, true);
             KeypressOutcome::default()
         }
-        KeyCode::Char('N') => {
+        Action::PreviousMatch => {
             app.jump_to_search_match(files, rows, false);
             KeypressOutcome::default()
         }
-        KeyCode::Char('}') => {
+        Action::NextHunk => {
             app.jump_to_hunk(files, rows, true);
             KeypressOutcome::default()
         }
-        KeyCode::Char('{') => {
+        Action::PreviousHunk => {
             app.jump_to_hunk(files, rows, false);
             KeypressOutcome::default()
         }
-        KeyCode::Char('r') => {
-            let reviewed = app.toggle_current_file_reviewed();
-            KeypressOutcome {
-                should_quit: false,
-                review_toggled: Some((app.file_index, reviewed)),
-            }
+        Action::RedoReview => app.step_review_history(files, false),
+        Action::UndoReview => app.step_review_history(files, true),
+        Action::FileList => {
+            app.toggle_file_list(rows);
+            KeypressOutcome::default()
+        }
+        Action::MoreContext => {
+            app.step_context(files, rows, true);
+            KeypressOutcome::default()
+        }
+        Action::LessContext => {
+            app.step_context(files, rows, false);
+            KeypressOutcome::default()
+        }
+        Action::ReviewAll => {
+            app.confirm_bulk_review(files, true);
+            KeypressOutcome::default()
+        }
+        Action::UnreviewAll => {
+            app.confirm_bulk_review(files, false);
+            KeypressOutcome::default()
+        }
+        Action::ToggleReviewed => {
+            let marks = vec![ReviewMark::File {
+                file_index: app.file_index,
+                reviewed: !app.is_current_file_reviewed(),
+            }];
+            app.record_review_change(marks)
         }
-        _ => KeypressOutcome::default(),
     }
 }
 
@@ -593,20 +2780,38 @@ pub(crate) fn handle_mouse(
     columns: u16,
     rows: u16,
 ) {
-    let current_file = &files[app.file_index];
-    let max_lines = current_file
-        .left_lines
-        .len()
-        .max(current_file.right_lines.len());
-    let layout = create_frame_layout(columns, rows, max_lines);
+    let list_width = columns - app.frame_columns(columns);
+    if mouse.column < list_width {
+        app.hover_message = None;
+        app.handle_file_list_mouse(mouse, files, rows);
+        return;
+    }
+    let options = app.settings.render.clone();
+    let layout = create_frame_layout(
+        columns - list_width,
+        rows,
+        files[app.file_index].line_count(),
+        &options,
+    );
 
     let row = mouse.row as usize;
-    if row < layout.body_start_row || row > layout.body_end_row {
+    let column = (mouse.column - list_width) as usize;
+    // The unified view's one pane shows the head side's marks.
+    let hovered_pane =
+        if app.view_mode == ViewMode::Unified && files[app.file_index].has_unified_rows() {
+            get_pane_for_column(column, &layout).map(|_| PaneSide::Right)
+        } else {
+            get_pane_for_column(column, &layout)
+        };
+    if mouse.kind == MouseEventKind::Moved {
+        let hovered_pane = hovered_pane.filter(|_| row <= layout.body_end_row);
+        app.update_hover_message(files, &layout, hovered_pane, row);
         return;
     }
 
-    let column = mouse.column as usize;
-    let hovered_pane = get_pane_for_column(column, &layout);
+    if row < layout.body_start_row || row > layout.body_end_row {
+        return;
+    }
 
     match mouse.kind {
         MouseEventKind::ScrollUp => {
@@ -665,35 +2870,45 @@ pub(crate) fn handle_mouse(
                 );
             }
         }
+        MouseEventKind::Down(MouseButton::Left) => {
+            let clicked_row = app.scroll_offset + (row - layout.body_start_row);
+            if let Some(display_row) = app.display_rows(files).get(clicked_row) {
+                app.current_view_mut().cursor_line = display_row.first_line();
+            }
+        }
         _ => {}
     }
+    app.keep_cursor_in_view(files, rows);
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{AppState, build_search_match_line_indexes, next_match_index};
-    use crate::model::{DiffFileDescriptor, DiffFileView, FileContentSource, PaneOffsets};
+    use super::{
+        AppState, FileListState, Fold, ListPickerOutcome, ListPickerState, PopupKind, ReviewMark,
+        build_pipe_input, build_search_match_line_indexes, handle_keypress, handle_mouse,
+        handle_paste, move_file, move_scroll, next_match_index,
+    };
+    use crate::{
+        model::{
+            Annotation, AnnotationSeverity, CommitSummary, DiffFileView, PaneSide, RenderOptions,
+            ResolvedComparison, ReviewState, ViewSettings,
+        },
+        pipe::PipeScope,
+        render::{create_frame_layout, get_body_line_count},
+    };
+    use crossterm::event::{
+        KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    };
     use std::collections::HashSet;
 
     fn create_test_file(left_lines: &[&str], right_lines: &[&str]) -> DiffFileView {
         DiffFileView {
-            descriptor: DiffFileDescriptor {
-                raw_status: "M".to_string(),
-                display_path: "src/main.rs".to_string(),
-                base_path: Some("src/main.rs".to_string()),
-                head_path: Some("src/main.rs".to_string()),
-                base_source: FileContentSource::Commit,
-                head_source: FileContentSource::Commit,
-            },
             review_key: "key".to_string(),
             left_lines: left_lines.iter().map(|line| line.to_string()).collect(),
             right_lines: right_lines.iter().map(|line| line.to_string()).collect(),
             left_language: Some("rust".to_string()),
             right_language: Some("rust".to_string()),
-            left_deleted_line_indexes: HashSet::new(),
-            right_added_line_indexes: HashSet::new(),
-            left_max_content_length: 0,
-            right_max_content_length: 0,
+            ..DiffFileView::for_test("src/main.rs")
         }
     }
 
@@ -716,13 +2931,46 @@ mod tests {
             &["one", "two", "right-hit"],
         );
 
-        let left_matches = build_search_match_line_indexes(&file, "left");
-        let right_matches = build_search_match_line_indexes(&file, "right");
+        let left_matches = build_search_match_line_indexes(&file, "left", None, false);
+        let right_matches = build_search_match_line_indexes(&file, "right", None, false);
 
         assert_eq!(left_matches, vec![1]);
         assert_eq!(right_matches, vec![2]);
     }
 
+    #[test]
+    fn changed_only_search_skips_unchanged_context() {
+        let file = create_test_file_with_hunks(
+            &["use foo;", "foo()", "bar()"],
+            &["use foo;", "foo(1)", "foo()"],
+            &[1],
+            &[1, 2],
+        );
+
+        assert_eq!(
+            build_search_match_line_indexes(&file, "foo", None, false),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            build_search_match_line_indexes(&file, "foo", None, true),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn focused_pane_scopes_search_matches() {
+        let file = create_test_file(&["hit", "miss"], &["miss", "hit"]);
+
+        assert_eq!(
+            build_search_match_line_indexes(&file, "hit", Some(PaneSide::Left), false),
+            vec![0]
+        );
+        assert_eq!(
+            build_search_match_line_indexes(&file, "hit", Some(PaneSide::Right), false),
+            vec![1]
+        );
+    }
+
     #[test]
     fn next_match_index_wraps_both_directions() {
         assert_eq!(next_match_index(3, Some(2), true), Some(0));
@@ -733,27 +2981,206 @@ mod tests {
 
     #[test]
     fn reviewed_toggle_updates_reviewed_count() {
-        let mut app = AppState {
-            file_index: 1,
-            scroll_offset: 0,
-            pane_offsets_by_file: vec![PaneOffsets::default(), PaneOffsets::default()],
-            hunk_anchor_by_file: vec![None, None],
-            reviewed_by_file: vec![false, false],
-            reviewed_count: 0,
-            search_input_mode: false,
-            search_query: String::new(),
-            search_input: String::new(),
-            search_match_line_indexes: Vec::new(),
-            search_match_index: None,
-            focused_hunk_lines: None,
+        let mut app = AppState::new(2, vec![false, false]);
+
+        app.set_file_reviewed(1, true);
+        app.set_file_reviewed(1, true);
+        assert_eq!(app.reviewed_count(), 1);
+
+        app.set_file_reviewed(1, false);
+        assert_eq!(app.reviewed_count(), 0);
+    }
+
+    #[test]
+    fn undo_and_redo_step_through_review_marks() {
+        let files = vec![
+            create_test_file(&["a"], &["b"]),
+            create_test_file(&["c"], &["d"]),
+        ];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(files.len(), vec![false, false]);
+        let press = |app: &mut AppState, code: KeyCode, modifiers: KeyModifiers| {
+            handle_keypress(
+                KeyEvent::new(code, modifiers),
+                &files,
+                &comparison,
+                app,
+                120,
+                40,
+            )
+        };
+
+        press(&mut app, KeyCode::Char('r'), KeyModifiers::NONE);
+        press(&mut app, KeyCode::Char('l'), KeyModifiers::NONE);
+        press(&mut app, KeyCode::Char('r'), KeyModifiers::NONE);
+        assert_eq!(app.reviewed_count(), 2);
+
+        let outcome = press(&mut app, KeyCode::Char('u'), KeyModifiers::NONE);
+        assert_eq!(
+            outcome.review_updates,
+            vec![ReviewMark::File {
+                file_index: 1,
+                reviewed: false
+            }]
+        );
+        press(&mut app, KeyCode::Char('u'), KeyModifiers::NONE);
+        assert_eq!(app.reviewed_count(), 0);
+        press(&mut app, KeyCode::Char('u'), KeyModifiers::NONE);
+        assert_eq!(app.status_text(), "nothing to undo");
+
+        press(&mut app, KeyCode::Char('r'), KeyModifiers::CONTROL);
+        assert_eq!(app.reviewed_count(), 1);
+        assert_eq!(app.status_text(), "redo: src/main.rs reviewed");
+    }
+
+    #[test]
+    fn commit_messages_are_only_taken_for_uncommitted_comparisons() {
+        let files = vec![create_test_file(&["a"], &["b"])];
+        let mut comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(files.len(), vec![false]);
+        let press = |app: &mut AppState, comparison: &ResolvedComparison, code: KeyCode| {
+            handle_keypress(
+                KeyEvent::new(code, KeyModifiers::NONE),
+                &files,
+                comparison,
+                app,
+                120,
+                40,
+            )
         };
 
-        let first = app.toggle_current_file_reviewed();
-        let second = app.toggle_current_file_reviewed();
+        press(&mut app, &comparison, KeyCode::Char('m'));
+        assert!(app.input_mode.is_none());
+        assert_eq!(
+            app.notice.as_deref(),
+            Some("commit: needs uncommitted changes in the comparison (e.g. --staged or --amend)")
+        );
+
+        comparison.includes_uncommitted = true;
+        press(&mut app, &comparison, KeyCode::Char('m'));
+        for ch in "Fix it".chars() {
+            press(&mut app, &comparison, KeyCode::Char(ch));
+        }
+        let outcome = press(&mut app, &comparison, KeyCode::Enter);
+        assert_eq!(outcome.commit_message.as_deref(), Some("Fix it"));
+        assert!(app.input_mode.is_none());
+    }
+
+    #[test]
+    fn bulk_review_marks_ask_first_and_undo_in_one_step() {
+        let files = vec![
+            create_test_file(&["a"], &["b"]),
+            create_test_file(&["c"], &["d"]),
+            create_test_file(&["e"], &["f"]),
+        ];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(files.len(), vec![false, true, false]);
+        let press = |app: &mut AppState, code: KeyCode| {
+            handle_keypress(
+                KeyEvent::new(code, KeyModifiers::NONE),
+                &files,
+                &comparison,
+                app,
+                120,
+                40,
+            )
+        };
 
-        assert!(first);
-        assert!(!second);
+        press(&mut app, KeyCode::Char('A'));
+        let outcome = press(&mut app, KeyCode::Esc);
+        assert!(outcome.review_updates.is_empty());
+        assert_eq!(app.reviewed_count(), 1);
+
+        press(&mut app, KeyCode::Char('A'));
+        let outcome = press(&mut app, KeyCode::Enter);
+        assert_eq!(outcome.review_updates.len(), 2);
+        assert_eq!(app.reviewed_count(), 3);
+
+        press(&mut app, KeyCode::Char('X'));
+        press(&mut app, KeyCode::Char('j'));
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.reviewed_count(), 3);
+        press(&mut app, KeyCode::Char('X'));
+        press(&mut app, KeyCode::Enter);
         assert_eq!(app.reviewed_count(), 0);
+
+        press(&mut app, KeyCode::Char('u'));
+        assert_eq!(app.reviewed_count(), 3);
+    }
+
+    #[test]
+    fn next_unreviewed_file_skips_reviewed_ones_and_wraps() {
+        let files = vec![
+            create_test_file(&["a"], &["b"]),
+            create_test_file(&["c"], &["d"]),
+            create_test_file(&["e"], &["f"]),
+            create_test_file(&["g"], &["h"]),
+        ];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(files.len(), vec![false, true, false, true]);
+        let press = |app: &mut AppState, code: KeyCode| {
+            handle_keypress(
+                KeyEvent::new(code, KeyModifiers::NONE),
+                &files,
+                &comparison,
+                app,
+                120,
+                40,
+            )
+        };
+
+        press(&mut app, KeyCode::Char('R'));
+        assert_eq!(app.file_index, 2);
+        press(&mut app, KeyCode::Char('R'));
+        assert_eq!(app.file_index, 0);
+
+        press(&mut app, KeyCode::Char('r'));
+        press(&mut app, KeyCode::Char('R'));
+        assert_eq!(app.file_index, 2);
+        press(&mut app, KeyCode::Char('r'));
+        press(&mut app, KeyCode::Char('R'));
+        assert_eq!(app.file_index, 2);
+        assert_eq!(app.notice.as_deref(), Some("nothing left to review"));
+    }
+
+    #[test]
+    fn files_still_loading_take_no_review_marks_until_their_view_arrives() {
+        let mut files = vec![
+            create_test_file(&["a"], &["b"]),
+            create_test_file(&["<loading...>"], &["<loading...>"]),
+        ];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(files.len(), vec![false, false]);
+        app.loading_by_file = vec![false, true];
+        let press = |app: &mut AppState, files: &[DiffFileView], code: KeyCode| {
+            handle_keypress(
+                KeyEvent::new(code, KeyModifiers::NONE),
+                files,
+                &comparison,
+                app,
+                120,
+                40,
+            )
+        };
+
+        press(&mut app, &files, KeyCode::Char('A'));
+        let outcome = press(&mut app, &files, KeyCode::Enter);
+        assert_eq!(
+            outcome.review_updates,
+            vec![ReviewMark::File {
+                file_index: 0,
+                reviewed: true,
+            }]
+        );
+        assert_eq!(app.reviewed_count(), 1);
+
+        files[1] = create_test_file(&["c", "d"], &["c", "D"]);
+        app.adopt_loaded_file(&files, 1, 40, true, false);
+        assert!(!app.loading_by_file[1]);
+        assert_eq!(app.reviewed_count(), 2);
+        press(&mut app, &files, KeyCode::Char('l'));
+        let outcome = press(&mut app, &files, KeyCode::Char('r'));
+        assert_eq!(outcome.review_updates.len(), 1);
     }
 
     #[test]
@@ -771,5 +3198,684 @@ mod tests {
 
         app.jump_to_hunk(&files, 40, true);
         assert_eq!(app.file_index, 1);
+
+        app.jump_to_hunk(&files, 40, false);
+        assert_eq!(app.file_index, 0);
+        assert_eq!(app.current_view().hunk_anchor, Some(1));
+    }
+
+    #[test]
+    fn hunk_menu_marks_the_last_open_hunk_and_its_file_reviewed() {
+        let files = vec![create_test_file_with_hunks(
+            &["a", "b", "c"],
+            &["a", "B", "c"],
+            &[1],
+            &[1],
+        )];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(1, vec![false]);
+        let press = |app: &mut AppState, code: KeyCode| {
+            let key = KeyEvent::new(code, KeyModifiers::NONE);
+            handle_keypress(key, &files, &comparison, app, 120, 40)
+        };
+
+        press(&mut app, KeyCode::Enter);
+        assert!(app.popup.is_none());
+        assert_eq!(app.status_text(), "no hunk at the cursor");
+
+        press(&mut app, KeyCode::Char('}'));
+        press(&mut app, KeyCode::Enter);
+        let popup = app.popup.as_ref().expect("hunk menu");
+        assert_eq!(
+            popup.lines,
+            vec!["copy hunk", "mark hunk reviewed", "comment on hunk"]
+        );
+
+        press(&mut app, KeyCode::Char('j'));
+        let outcome = press(&mut app, KeyCode::Enter);
+        assert!(matches!(
+            outcome.review_updates.as_slice(),
+            [
+                ReviewMark::Hunk { reviewed: true, .. },
+                ReviewMark::File {
+                    file_index: 0,
+                    reviewed: true
+                }
+            ]
+        ));
+        assert!(app.is_current_file_reviewed());
+
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(
+            app.popup.as_ref().map(|popup| popup.lines[1].as_str()),
+            Some("unmark hunk reviewed")
+        );
+    }
+
+    #[test]
+    fn pipe_input_uses_hunk_lines_when_a_hunk_is_focused() {
+        let file = create_test_file_with_hunks(&["a", "b", "c"], &["a", "B", "c"], &[1], &[1]);
+        let hunk_lines: HashSet<usize> = [1].into_iter().collect();
+
+        let (scope, input) = build_pipe_input(&file, Some(&hunk_lines));
+
+        assert_eq!(scope, PipeScope::Hunk);
+        assert_eq!(input, "-b\n+B\n");
+    }
+
+    #[test]
+    fn pipe_input_falls_back_to_head_file_content() {
+        let file = create_test_file(&["old"], &["new", "lines"]);
+
+        let (scope, input) = build_pipe_input(&file, None);
+
+        assert_eq!(scope, PipeScope::File);
+        assert_eq!(input, "new\nlines\n");
+    }
+
+    #[test]
+    fn pipe_prompt_runs_named_commands_by_name() {
+        let files = vec![create_test_file(&["old"], &["new"])];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(files.len(), vec![false]);
+        app.pipe_commands = vec![
+            ("fmt".to_string(), "rustfmt".to_string()),
+            ("fmt".to_string(), "rustfmt --edition 2024".to_string()),
+        ];
+        let pipe = |app: &mut AppState, typed: &str| {
+            for code in std::iter::once(KeyCode::Char('|'))
+                .chain(typed.chars().map(KeyCode::Char))
+                .chain([KeyCode::Enter])
+            {
+                let outcome = handle_keypress(
+                    KeyEvent::new(code, KeyModifiers::NONE),
+                    &files,
+                    &comparison,
+                    app,
+                    120,
+                    40,
+                );
+                if outcome.pipe_request.is_some() {
+                    return outcome.pipe_request.map(|request| request.command);
+                }
+            }
+            None
+        };
+
+        assert_eq!(
+            pipe(&mut app, "fmt").as_deref(),
+            Some("rustfmt --edition 2024")
+        );
+        assert_eq!(pipe(&mut app, "wc -l").as_deref(), Some("wc -l"));
+    }
+
+    #[test]
+    fn fold_commands_collapse_and_restore_rows() {
+        let files = vec![create_test_file(
+            &[
+                "fn a() {",
+                "    one",
+                "    two",
+                "}",
+                "fn b() {",
+                "    three",
+                "}",
+            ],
+            &[
+                "fn a() {",
+                "    one",
+                "    two",
+                "}",
+                "fn b() {",
+                "    three",
+                "}",
+            ],
+        )];
+        let mut app = AppState::new(files.len(), vec![false]);
+
+        app.handle_z_command(KeyCode::Char('M'), &files, 40);
+        assert_eq!(app.display_rows(&files).len(), 4);
+
+        app.handle_z_command(KeyCode::Char('R'), &files, 40);
+        assert_eq!(app.display_rows(&files).len(), 7);
+    }
+
+    #[test]
+    fn reloading_a_file_keeps_the_cursor_in_range_and_takes_its_review_state() {
+        let lines: Vec<String> = (0..10).map(|index| format!("line {index}")).collect();
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let mut files = vec![create_test_file(&lines, &lines)];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(files.len(), vec![true]);
+        app.current_view_mut().cursor_line = 8;
+        app.handle_z_command(KeyCode::Char('M'), &files, 40);
+
+        let outcome = handle_keypress(
+            KeyEvent::new(KeyCode::Char('L'), KeyModifiers::NONE),
+            &files,
+            &comparison,
+            &mut app,
+            120,
+            40,
+        );
+        assert!(outcome.reload_file);
+
+        files[0] = create_test_file(&["a", "b"], &["a", "c", "d"]);
+        app.reload_current_file(&files, 40, false, true);
+        assert_eq!(app.cursor_line(), 2);
+        assert_eq!(app.display_rows(&files).len(), 3);
+        assert_eq!(app.reviewed_count(), 0);
+        assert_eq!(app.current_file_review_state(), ReviewState::Stale);
+    }
+
+    #[test]
+    fn file_base_picker_marks_the_current_base_and_returns_the_chosen_commit() {
+        let files = vec![create_test_file(&["a"], &["b"])];
+        let comparison = ResolvedComparison::for_test();
+        let commits = vec![CommitSummary {
+            hash: "c".repeat(40),
+            short_hash: "ccccccc".to_string(),
+            author: "Ada".to_string(),
+            date: "2026-10-16".to_string(),
+            subject: "Tweak main".to_string(),
+        }];
+        let mut app = AppState::new(files.len(), vec![false]);
+        let press = |app: &mut AppState, code: KeyCode| {
+            handle_keypress(
+                KeyEvent::new(code, KeyModifiers::NONE),
+                &files,
+                &comparison,
+                app,
+                120,
+                40,
+            )
+        };
+
+        assert!(press(&mut app, KeyCode::Char('B')).pick_file_base);
+        app.show_file_base_picker(&files, &comparison, &commits, &"c".repeat(40));
+        let lines = &app.popup.as_ref().expect("picker is open").lines;
+        assert_eq!(lines[0], "  comparison base: main (aaaaaaaa)");
+        assert!(lines[1].starts_with("* ccccccc"));
+
+        press(&mut app, KeyCode::Char('j'));
+        let outcome = press(&mut app, KeyCode::Enter);
+        assert_eq!(outcome.file_base, Some("c".repeat(40)));
+        assert!(app.popup.is_none());
+    }
+
+    #[test]
+    fn first_change_setting_opens_new_files_at_their_first_hunk_once() {
+        let lines: Vec<String> = (0..60).map(|index| format!("line {index}")).collect();
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let files = vec![
+            create_test_file_with_hunks(&lines, &lines, &[], &[40]),
+            create_test_file_with_hunks(&lines, &lines, &[], &[30]),
+        ];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(files.len(), vec![false, false]);
+        app.settings.open_at_first_change = true;
+        let press = |app: &mut AppState, code: KeyCode| {
+            handle_keypress(
+                KeyEvent::new(code, KeyModifiers::NONE),
+                &files,
+                &comparison,
+                app,
+                120,
+                20,
+            );
+        };
+
+        app.place_new_file(&files, 20);
+        assert_eq!(app.cursor_line(), 40);
+        press(&mut app, KeyCode::Char('l'));
+        assert_eq!(app.cursor_line(), 30);
+        press(&mut app, KeyCode::Char('g'));
+        press(&mut app, KeyCode::Char('h'));
+        press(&mut app, KeyCode::Char('l'));
+        assert_eq!(app.cursor_line(), 0);
+    }
+
+    #[test]
+    fn jumping_into_a_fold_opens_it() {
+        let files = vec![create_test_file_with_hunks(
+            &["fn a() {", "    one", "    two", "}"],
+            &["fn a() {", "    one", "    TWO", "}"],
+            &[2],
+            &[2],
+        )];
+        let mut app = AppState::new(files.len(), vec![false]);
+        app.handle_z_command(KeyCode::Char('c'), &files, 40);
+        assert_eq!(app.display_rows(&files).len(), 2);
+
+        app.jump_to_hunk(&files, 40, true);
+
+        assert_eq!(app.display_rows(&files).len(), 4);
+    }
+
+    #[test]
+    fn hiding_whitespace_only_files_skips_them_in_navigation() {
+        let mut whitespace_file = create_test_file(&["a"], &["  a"]);
+        whitespace_file.whitespace_only = true;
+        let files = vec![
+            create_test_file(&["x"], &["y"]),
+            whitespace_file,
+            create_test_file(&["x"], &["z"]),
+        ];
+        let mut app = AppState::new(files.len(), vec![false; 3]);
+        app.file_index = 1;
+
+        app.toggle_hide_whitespace_only(&files);
+        assert!(app.hide_whitespace_only);
+        assert_eq!(app.file_index, 2);
+
+        assert!(move_file(-1, &files, &mut app));
+        assert_eq!(app.file_index, 0);
+        assert!(!move_file(-1, &files, &mut app));
+
+        app.toggle_hide_whitespace_only(&files);
+        assert!(move_file(1, &files, &mut app));
+        assert_eq!(app.file_index, 1);
+    }
+
+    #[test]
+    fn pasting_into_search_inserts_the_text_and_searches() {
+        let files = vec![create_test_file(
+            &["alpha", "beta"],
+            &["alpha", "gamma_delta"],
+        )];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(1, vec![false]);
+
+        handle_paste("gamma", &files, &mut app, 40);
+        assert_eq!(app.status_text(), "search: /");
+
+        let key = KeyEvent::new(KeyCode::Char('/'), KeyModifiers::NONE);
+        handle_keypress(key, &files, &comparison, &mut app, 120, 40);
+        handle_paste("gamma_\r\n", &files, &mut app, 40);
+        assert_eq!(app.status_text(), "search: /gamma_ ");
+        let key = KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE);
+        handle_keypress(key, &files, &comparison, &mut app, 120, 40);
+        assert_eq!(app.search_query, "gamma_");
+    }
+
+    #[test]
+    fn z_commands_place_the_search_match_in_the_viewport() {
+        let lines: Vec<String> = (0..100).map(|index| format!("line {index}")).collect();
+        let refs: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let files = vec![create_test_file(&refs, &refs)];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(1, vec![false]);
+        let press = |app: &mut AppState, code: KeyCode| {
+            let key = KeyEvent::new(code, KeyModifiers::NONE);
+            handle_keypress(key, &files, &comparison, app, 120, 40);
+        };
+        for code in [KeyCode::Char('/'), KeyCode::Char('5'), KeyCode::Char('0')] {
+            press(&mut app, code);
+        }
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Char('n'));
+        assert_eq!(app.scroll_offset, 50);
+
+        let body_line_count = get_body_line_count(40);
+        for (command, expected) in [
+            ('z', 50 - body_line_count / 2),
+            ('b', 50 - (body_line_count - 1)),
+            ('t', 50),
+        ] {
+            press(&mut app, KeyCode::Char('z'));
+            press(&mut app, KeyCode::Char(command));
+            assert_eq!(app.scroll_offset, expected, "z{command}");
+        }
+    }
+
+    #[test]
+    fn scrolloff_keeps_context_above_hunk_jumps_and_focus_while_scrolling() {
+        let lines: Vec<String> = (0..100).map(|index| format!("line {index}")).collect();
+        let refs: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let files = vec![create_test_file_with_hunks(&refs, &refs, &[60], &[60])];
+        let mut app = AppState::new(1, vec![false]);
+        app.settings = ViewSettings {
+            scrolloff: 5,
+            ..ViewSettings::default()
+        };
+
+        app.jump_to_hunk(&files, 40, true);
+        assert_eq!(app.scroll_offset, 55);
+
+        move_scroll(-2, &files, &mut app, 40);
+        assert!(app.focused_hunk_lines.is_some());
+        move_scroll(10, &files, &mut app, 40);
+        assert!(app.focused_hunk_lines.is_none());
+    }
+
+    #[test]
+    fn search_jumps_while_typing_and_esc_restores_the_view() {
+        let lines: Vec<String> = (0..100).map(|index| format!("line {index}")).collect();
+        let refs: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let files = vec![create_test_file(&refs, &refs)];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(1, vec![false]);
+        app.scroll_offset = 10;
+        app.keep_cursor_in_view(&files, 40);
+        let press = |app: &mut AppState, code: KeyCode| {
+            let key = KeyEvent::new(code, KeyModifiers::NONE);
+            handle_keypress(key, &files, &comparison, app, 120, 40);
+        };
+
+        press(&mut app, KeyCode::Char('/'));
+        press(&mut app, KeyCode::Char('4'));
+        assert_eq!(app.scroll_offset, 14);
+        press(&mut app, KeyCode::Char('2'));
+        assert_eq!(app.scroll_offset, 42);
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.scroll_offset, 10);
+        assert!(app.search_match_line_indexes.is_empty());
+
+        press(&mut app, KeyCode::Char('/'));
+        press(&mut app, KeyCode::Char('4'));
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.scroll_offset, 14);
+        assert_eq!(app.status_text(), "search: /4 (2/19)");
+    }
+
+    #[test]
+    fn cursor_moves_scroll_the_view_and_v_selects_a_range() {
+        let lines: Vec<String> = (0..100).map(|index| format!("line {index}")).collect();
+        let refs: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let files = vec![create_test_file(&refs, &refs)];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(1, vec![false]);
+        app.settings = ViewSettings {
+            scrolloff: 2,
+            ..ViewSettings::default()
+        };
+        let press = |app: &mut AppState, code: KeyCode| {
+            let key = KeyEvent::new(code, KeyModifiers::NONE);
+            handle_keypress(key, &files, &comparison, app, 120, 40)
+        };
+        let body_line_count = get_body_line_count(40);
+
+        for _ in 0..body_line_count {
+            press(&mut app, KeyCode::Char('j'));
+        }
+        assert_eq!(app.cursor().line, body_line_count);
+        assert_eq!(app.scroll_offset, 3);
+
+        press(&mut app, KeyCode::Char('V'));
+        press(&mut app, KeyCode::Char('k'));
+        press(&mut app, KeyCode::Char('k'));
+        assert_eq!(
+            app.cursor().selection(),
+            Some((body_line_count - 2, body_line_count))
+        );
+        let outcome = press(&mut app, KeyCode::Char('y'));
+        let location = outcome.forge_request.expect("permalink request").location;
+        assert_eq!(location.line, Some(body_line_count - 1));
+        assert_eq!(location.end_line, Some(body_line_count + 1));
+
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.cursor().selection(), None);
+        press(&mut app, KeyCode::Char('G'));
+        assert_eq!(app.cursor().line, 99);
+        app.scroll_offset = 0;
+        app.keep_cursor_in_view(&files, 40);
+        assert_eq!(app.cursor().line, body_line_count - 3);
+    }
+
+    #[test]
+    fn switching_files_restores_remembered_view_state() {
+        let long_lines: Vec<String> = (0..100).map(|index| format!("line {index}")).collect();
+        let long_refs: Vec<&str> = long_lines.iter().map(String::as_str).collect();
+        let files = vec![
+            create_test_file(&long_refs, &long_refs),
+            create_test_file(&["x"], &["y"]),
+        ];
+        let mut app = AppState::new(files.len(), vec![false, false]);
+        app.scroll_offset = 42;
+
+        assert!(move_file(1, &files, &mut app));
+        assert_eq!(app.scroll_offset, 0);
+
+        assert!(move_file(-1, &files, &mut app));
+        assert_eq!(app.scroll_offset, 42);
+    }
+
+    #[test]
+    fn list_picker_clamps_selection_and_selects_on_enter() {
+        let mut picker = ListPickerState::new(3, 0);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+        assert_eq!(
+            picker.handle_key(key(KeyCode::Char('k')), 40),
+            ListPickerOutcome::Continue
+        );
+        assert_eq!(picker.selected, 0);
+
+        picker.handle_key(key(KeyCode::Char('G')), 40);
+        picker.handle_key(key(KeyCode::Char('j')), 40);
+        assert_eq!(
+            picker.handle_key(key(KeyCode::Enter), 40),
+            ListPickerOutcome::Select(2)
+        );
+        assert_eq!(
+            picker.handle_key(key(KeyCode::Esc), 40),
+            ListPickerOutcome::Cancel
+        );
+    }
+
+    #[test]
+    fn commit_summary_popup_toggles_with_i() {
+        let files = vec![create_test_file(&["a"], &["b"])];
+        let mut comparison = ResolvedComparison::for_test();
+        comparison.range_commits = vec![CommitSummary {
+            hash: "b".repeat(40),
+            short_hash: "bbbbbbb".to_string(),
+            author: "Jo".to_string(),
+            date: "2024-01-02".to_string(),
+            subject: "Add feature".to_string(),
+        }];
+        let mut app = AppState::new(1, vec![false]);
+        let key = KeyEvent::new(KeyCode::Char('i'), KeyModifiers::NONE);
+
+        handle_keypress(key, &files, &comparison, &mut app, 120, 40);
+        let popup = app.popup.as_ref().expect("summary popup should open");
+        assert_eq!(popup.kind, PopupKind::CommitSummary);
+        assert!(popup.lines[0].contains("Add feature"));
+
+        handle_keypress(key, &files, &comparison, &mut app, 120, 40);
+        assert!(app.popup.is_none());
+    }
+
+    #[test]
+    fn expanded_line_is_wrapped_to_the_popup_for_the_focused_pane() {
+        let long_line = "x".repeat(80);
+        let files = vec![create_test_file(&["short"], &[long_line.as_str()])];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(1, vec![false]);
+        let key = KeyEvent::new(KeyCode::Char('e'), KeyModifiers::NONE);
+
+        handle_keypress(key, &files, &comparison, &mut app, 50, 20);
+        let popup = app.popup.as_ref().expect("expanded line popup should open");
+        assert_eq!(popup.title, "line 1: src/main.rs");
+        assert_eq!(popup.lines[..3], ["left:", "short", ""]);
+        assert_eq!(popup.lines[3], "right:");
+        assert_eq!(
+            popup.lines[4..].iter().map(String::len).collect::<Vec<_>>(),
+            vec![38, 38, 4]
+        );
+
+        app.popup = None;
+        app.focused_pane = Some(PaneSide::Right);
+        handle_keypress(key, &files, &comparison, &mut app, 50, 20);
+        let popup = app.popup.as_ref().expect("expanded line popup should open");
+        assert_eq!(popup.lines[0], "right:");
+        assert_eq!(popup.lines.len(), 4);
+    }
+
+    #[test]
+    fn f_scrolls_the_panes_to_the_first_difference_on_the_cursor_line() {
+        let left_line = format!("{}old tail", "x".repeat(200));
+        let right_line = format!("{}new tail", "x".repeat(200));
+        let mut file = create_test_file(&[left_line.as_str()], &[right_line.as_str()]);
+        file.left_max_content_length = left_line.len();
+        file.right_max_content_length = right_line.len();
+        let files = vec![file];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(1, vec![false]);
+        let key = KeyEvent::new(KeyCode::Char('f'), KeyModifiers::NONE);
+
+        handle_keypress(key, &files, &comparison, &mut app, 80, 20);
+        let options = RenderOptions::default();
+        let layout = create_frame_layout(80, 20, 1, &options);
+        let offsets = app.current_offsets();
+        assert!((offsets.left..offsets.left + layout.left_content_width).contains(&200));
+        assert!((offsets.right..offsets.right + layout.right_content_width).contains(&200));
+
+        app.set_current_offsets(Default::default());
+        app.focused_pane = Some(PaneSide::Right);
+        handle_keypress(key, &files, &comparison, &mut app, 80, 20);
+        assert_eq!(app.current_offsets().left, 0);
+        assert!(app.current_offsets().right > 0);
+
+        let same = vec![create_test_file(&["same"], &["same"])];
+        let mut app = AppState::new(1, vec![false]);
+        handle_keypress(key, &same, &comparison, &mut app, 80, 20);
+        assert_eq!(app.notice.as_deref(), Some("no difference on this line"));
+    }
+
+    #[test]
+    fn c_folds_unchanged_lines_away_from_the_hunks_in_every_file() {
+        let lines: Vec<String> = (0..30).map(|index| format!("line {index}")).collect();
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let files = vec![
+            create_test_file_with_hunks(&lines, &lines, &[10], &[10]),
+            create_test_file_with_hunks(&lines, &lines, &[], &[0]),
+        ];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(files.len(), vec![false; files.len()]);
+        app.settings = ViewSettings {
+            context_lines: 2,
+            ..ViewSettings::default()
+        };
+        let press = |app: &mut AppState, code: KeyCode| {
+            let key = KeyEvent::new(code, KeyModifiers::NONE);
+            handle_keypress(key, &files, &comparison, app, 120, 40)
+        };
+
+        press(&mut app, KeyCode::Char('c'));
+        assert_eq!(app.status_text(), "changes only, 2 lines of context");
+        assert_eq!(
+            app.current_view().folds,
+            vec![Fold { start: 0, end: 7 }, Fold { start: 13, end: 29 }]
+        );
+        assert_eq!(
+            app.view_state_by_file[1].folds,
+            vec![Fold { start: 3, end: 29 }]
+        );
+
+        press(&mut app, KeyCode::Char('c'));
+        assert_eq!(app.status_text(), "whole files");
+        assert!(
+            app.view_state_by_file
+                .iter()
+                .all(|view| view.folds.is_empty())
+        );
+    }
+
+    #[test]
+    fn toggling_the_unified_view_keeps_the_cursor_on_its_line() {
+        let mut file =
+            create_test_file_with_hunks(&["a", "b", "", "c"], &["a", "B", "X", "c"], &[1], &[1, 2]);
+        file.left_source_lines = vec![Some(0), Some(1), None, Some(2)];
+        file.right_source_lines = vec![Some(0), Some(1), Some(2), Some(3)];
+        let mut files = vec![file];
+        let mut app = AppState::new(1, vec![false]);
+        app.current_view_mut().cursor_line = 3;
+
+        app.toggle_view_mode(&mut files, 40);
+        assert_eq!(app.status_text(), "unified view");
+        assert_eq!(files[0].right_lines, ["a", "", "B", "X", "c"]);
+        assert_eq!(app.cursor_line(), 4);
+
+        app.toggle_view_mode(&mut files, 40);
+        assert_eq!(app.status_text(), "split view");
+        assert_eq!(files[0].left_lines, ["a", "b", "", "c"]);
+        assert_eq!(app.cursor_line(), 3);
+    }
+
+    #[test]
+    fn t_opens_a_file_list_that_picks_files_with_the_keyboard_and_mouse() {
+        let files = vec![
+            create_test_file(&["a"], &["b"]),
+            create_test_file(&["c"], &["d"]),
+            create_test_file(&["e"], &["f"]),
+        ];
+        let comparison = ResolvedComparison::for_test();
+        let mut app = AppState::new(files.len(), vec![false; files.len()]);
+        let press = |app: &mut AppState, code: KeyCode| {
+            let key = KeyEvent::new(code, KeyModifiers::NONE);
+            handle_keypress(key, &files, &comparison, app, 120, 40)
+        };
+
+        press(&mut app, KeyCode::Char('t'));
+        press(&mut app, KeyCode::Char('j'));
+        press(&mut app, KeyCode::Char('j'));
+        assert_eq!(app.file_index, 0);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.file_index, 2);
+        assert_eq!(
+            app.file_list,
+            Some(FileListState {
+                selected: 2,
+                scroll: 0,
+                focused: false,
+            })
+        );
+        assert!(app.frame_columns(120) < 120);
+
+        // With the list unfocused, j moves the cursor again rather than the selection.
+        press(&mut app, KeyCode::Char('j'));
+        assert_eq!(app.file_list.map(|list| list.selected), Some(2));
+
+        let click = MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column: 2,
+            row: 1,
+            modifiers: KeyModifiers::NONE,
+        };
+        handle_mouse(click, &files, &mut app, 120, 40);
+        assert_eq!(app.file_index, 0);
+
+        press(&mut app, KeyCode::Char('t'));
+        press(&mut app, KeyCode::Char('t'));
+        assert_eq!(app.file_list, None);
+        assert_eq!(app.frame_columns(120), 120);
+    }
+
+    #[test]
+    fn hovering_annotated_head_line_shows_message_in_status() {
+        let mut file = create_test_file(&["a", "b"], &["a", "b"]);
+        file.right_annotations = vec![Annotation {
+            line_index: 1,
+            severity: AnnotationSeverity::Warning,
+            message: "unused".to_string(),
+            rule: None,
+        }];
+        let files = vec![file];
+        let mut app = AppState::new(1, vec![false]);
+        let options = RenderOptions::default();
+        let layout = create_frame_layout(100, 30, 2, &options);
+        let hover = |row: usize| MouseEvent {
+            kind: MouseEventKind::Moved,
+            column: layout.right_pane_start_column as u16 + 2,
+            row: row as u16,
+            modifiers: KeyModifiers::NONE,
+        };
+
+        handle_mouse(hover(layout.body_start_row + 1), &files, &mut app, 100, 30);
+        assert_eq!(app.status_text(), "warning: unused");
+
+        handle_mouse(hover(layout.body_start_row), &files, &mut app, 100, 30);
+        assert_eq!(app.status_text(), "search: /");
     }
 }
@@ -0,0 +1,119 @@
+/// How long to wait for the terminal to answer; terminals that do answer take a few
+/// milliseconds, even over ssh.
+#[cfg(unix)]
+const QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(200);
+/// OSC 11 asks for the background colour; the primary device attributes query after it
+/// is answered by every terminal, so its reply marks the end of the wait.
+#[cfg(unix)]
+const BACKGROUND_QUERY: &[u8] = b"\x1b]11;?\x07\x1b[c";
+
+/// One `rgb:` component of 1 to 4 hex digits, scaled to `0.0..=1.0`.
+fn parse_component(digits: &[u8]) -> Option<f64> {
+    if digits.is_empty() || digits.len() > 4 {
+        return None;
+    }
+    let value = u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    Some(f64::from(value) / f64::from((1u32 << (4 * digits.len())) - 1))
+}
+
+/// Reads an OSC 11 reply (`ESC ] 11 ; rgb:RRRR/GGGG/BBBB` ended by BEL or ST) and says
+/// whether that background is dark.
+pub(crate) fn parse_background_reply(reply: &[u8]) -> Option<bool> {
+    let start = reply
+        .windows(4)
+        .position(|window| window == b"]11;")
+        .map(|index| index + 4)?;
+    let body = reply[start..].strip_prefix(b"rgb:")?;
+    let end = body
+        .iter()
+        .position(|byte| matches!(byte, b'\x07' | b'\x1b'))?;
+    let components: Vec<f64> = body[..end]
+        .split(|byte| *byte == b'/')
+        .map(parse_component)
+        .collect::<Option<_>>()?;
+    let [red, green, blue] = components[..] else {
+        return None;
+    };
+    Some(0.299 * red + 0.587 * green + 0.114 * blue < 0.5)
+}
+
+/// The device attributes reply is `ESC [ ? ... c`.
+#[cfg(unix)]
+fn has_device_attributes_reply(response: &[u8]) -> bool {
+    response
+        .windows(3)
+        .position(|window| window == b"\x1b[?")
+        .is_some_and(|start| response[start..].contains(&b'c'))
+}
+
+/// Asks the terminal for its background colour; `None` when it does not say in time.
+#[cfg(unix)]
+pub(crate) fn query_dark_background() -> Option<bool> {
+    use std::{
+        fs::OpenOptions,
+        io::{Read, Write},
+        os::fd::AsRawFd,
+        time::Instant,
+    };
+
+    use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+
+    let mut tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    enable_raw_mode().ok()?;
+    let response = (|| {
+        tty.write_all(BACKGROUND_QUERY).ok()?;
+        tty.flush().ok()?;
+        let deadline = Instant::now() + QUERY_TIMEOUT;
+        let mut response = Vec::new();
+        while !has_device_attributes_reply(&response) {
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            let mut poll_fd = libc::pollfd {
+                fd: tty.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: `poll_fd` is one valid pollfd that outlives the call.
+            let ready = unsafe { libc::poll(&mut poll_fd, 1, remaining.as_millis() as i32) };
+            if ready <= 0 {
+                return None;
+            }
+            let mut buffer = [0u8; 64];
+            let count = tty.read(&mut buffer).ok()?;
+            if count == 0 {
+                return None;
+            }
+            response.extend_from_slice(&buffer[..count]);
+        }
+        Some(response)
+    })();
+    let _ = disable_raw_mode();
+    parse_background_reply(&response?)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn query_dark_background() -> Option<bool> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_background_reply;
+
+    #[test]
+    fn background_replies_are_classified_by_luminance() {
+        assert_eq!(
+            parse_background_reply(b"\x1b]11;rgb:ffff/ffff/dddd\x07\x1b[?62;22c"),
+            Some(false)
+        );
+        assert_eq!(
+            parse_background_reply(b"\x1b]11;rgb:1c/1c/1c\x1b\\"),
+            Some(true)
+        );
+        assert_eq!(parse_background_reply(b"\x1b[?1;2c"), None);
+        assert_eq!(parse_background_reply(b"\x1b]11;rgb:ffff/ffff\x07"), None);
+    }
+}
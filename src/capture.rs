@@ -0,0 +1,95 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use once_cell::sync::OnceCell;
+use ratatui::{
+    buffer::Buffer,
+    text::{Line, Span},
+};
+
+use crate::output::line_to_ansi;
+
+struct FrameCapture {
+    directory: PathBuf,
+    next_index: usize,
+}
+
+/// Frame dump target, only set when `--capture` is passed.
+static CAPTURE: OnceCell<Mutex<FrameCapture>> = OnceCell::new();
+
+pub(crate) fn enable(directory: &Path) -> Result<()> {
+    fs::create_dir_all(directory)
+        .with_context(|| format!("failed to create capture directory {}", directory.display()))?;
+    let _ = CAPTURE.set(Mutex::new(FrameCapture {
+        directory: directory.to_path_buf(),
+        next_index: 1,
+    }));
+    Ok(())
+}
+
+/// Renders each buffer row as one ANSI line, merging runs of equally styled cells.
+pub(crate) fn buffer_to_ansi(buffer: &Buffer) -> String {
+    let width = buffer.area.width as usize;
+    let mut output = String::new();
+    for row in buffer.content.chunks(width.max(1)) {
+        let mut spans: Vec<Span<'static>> = Vec::new();
+        for cell in row {
+            match spans.last_mut() {
+                Some(span) if span.style == cell.style() => {
+                    span.content.to_mut().push_str(cell.symbol());
+                }
+                _ => spans.push(Span::styled(cell.symbol().to_string(), cell.style())),
+            }
+        }
+        output.push_str(&line_to_ansi(&Line::from(spans)));
+        output.push('\n');
+    }
+    output
+}
+
+/// Writes the frame to the next numbered file; failures are logged rather than
+/// interrupting the session.
+pub(crate) fn record(buffer: &Buffer) {
+    let Some(capture) = CAPTURE.get() else {
+        return;
+    };
+    let Ok(mut capture) = capture.lock() else {
+        return;
+    };
+
+    let path = capture
+        .directory
+        .join(format!("frame-{:05}.txt", capture.next_index));
+    capture.next_index += 1;
+    if let Err(error) = fs::write(&path, buffer_to_ansi(buffer)) {
+        tracing::warn!(path = %path.display(), %error, "failed to write captured frame");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{
+        buffer::Buffer,
+        layout::Rect,
+        style::{Color, Style},
+    };
+
+    use super::buffer_to_ansi;
+
+    #[test]
+    fn buffer_rows_become_ansi_lines() {
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 2, 2));
+        buffer.set_string(0, 0, "ab", Style::default().fg(Color::Red));
+
+        let text = buffer_to_ansi(&buffer);
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "\x1b[0;38;5;1mab\x1b[0m");
+        assert_eq!(lines[1], "\x1b[0m  \x1b[0m");
+    }
+}
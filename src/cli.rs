@@ -1,9 +1,33 @@
-use anyhow::{Result, bail};
-use clap::Parser;
+use std::{
+    io::{self, IsTerminal},
+    path::PathBuf,
+};
 
-use crate::model::{StrategyArg, StrategyId, ThemeMode};
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand, ValueEnum};
+use regex::Regex;
+
+use crate::{
+    difftool::DifftoolPaths,
+    external_diff::ExternalDiffArgs,
+    git::get_repository_root,
+    grep::GrepSides,
+    mergetool::MergetoolPaths,
+    model::{
+        ComparisonRequest, DiffOptions, ExportFormat, OutputFormat, Pickaxe, RenderOptions,
+        StrategyArg, StrategyId, ThemeMode, ViewMode, ViewSettings,
+    },
+    patch::DEFAULT_CONTEXT_LINES,
+    project_config::ProjectConfig,
+    terminal::InlineHeight,
+};
 
 const DEFAULT_HEAD_REF: &str = "HEAD";
+const DEFAULT_LOG_MAX_COUNT: usize = 200;
+const DEFAULT_MAX_FILES: usize = 300;
+const DEFAULT_OUTPUT_WIDTH: u16 = 160;
+const DEFAULT_SCREEN_SIZE: &str = "120x40";
+const DEFAULT_HEX_DUMP_KB: &str = "4";
 
 #[derive(Parser, Debug)]
 #[command(
@@ -12,61 +36,782 @@ const DEFAULT_HEAD_REF: &str = "HEAD";
     after_help = r#"Examples:
   deff
   deff --strategy upstream-ahead
+  deff --strict
+  deff --fetch
   deff --include-uncommitted
   deff --only-uncommitted
+  deff --staged
+  deff --unstaged
   deff --strategy range --base <git-ref> [--head <git-ref>]
   deff --strategy range --base <git-ref> --include-uncommitted
+  deff --amend
   deff --theme dark
+  deff --high-contrast
+  deff --height 40%
+  deff --pickaxe-S <string>
+  deff --pickaxe-G <regex>
+  deff --ignore-matching-lines <regex>
+  deff --hex-dump [<kb>]
+  deff --structural
+  deff --csv [<key-column>]
+  deff --pretty
+  deff --strategy range --base <git-ref> --author <pattern>
+  deff show <ref1> <ref2> -- <path>
+  deff --tab main..feature --tab v1.0..release/1.x
+  deff --merge-parents [--head <merge-commit>]
+  deff --per-commit --base main
+  deff --goto src/lib.rs:120
+  deff --owner @org/team
+  deff --coverage coverage/lcov.info
+  deff --annotations clippy.json --annotations eslint.json
+  deff --base v1.0 --max-files 1000
+  deff --output review.txt
+  deff --output review.ansi --output-format ansi --output-width 200
+  deff --keys "j j l r q"
+  deff --keys-file demo.keys --screen-size 100x30
+  deff --scrolloff 5
+  deff --first-change
+  deff --context 3
+  deff --view unified
+  deff --ruler 100
+  deff --separator " ┃ "
+  deff --bordered
+  deff --capture frames/
+  deff --profile
+  deff --log-file deff.log
+  deff --reviewer alice
+  deff --review-state-dir .deff
+  deff --review-notes
+  RUST_LOG=deff=trace deff --log-file deff.log
+  deff log [<revision>] [--max-count <n>]
+  deff --patches outgoing/ [--base origin/main]
+  deff --mergetool "$LOCAL" "$BASE" "$REMOTE" "$MERGED"
+  deff --files "$LOCAL" "$REMOTE"
+  git diff | deff --stdin
+  deff < changes.patch
+  deff preview-revert <commit>
+  git status --short | fzf --preview 'deff preview {2}'
+  deff export --format markdown --base <git-ref> [--output <path>]
+  deff status --base <git-ref> [--json]
+  deff grep <regex> [--added | --removed] [--base <git-ref>]
+  deff signoff [--print] [--base <git-ref>]
+  deff rebase-plan --base origin/main [--output plan.txt]
+  deff hook pre-push [--warn] [--install]
 
 Key bindings:
   h / left-arrow   previous file
   l / right-arrow  next file
-  j / down-arrow   scroll down
-  k / up-arrow     scroll up
+  t                file list beside the panes (j/k + enter opens a file, esc back to the diff, t closes)
+  j / down-arrow   move the cursor line down
+  k / up-arrow     move the cursor line up
   ctrl-d           page down
   ctrl-u           page up
   g / home         top of file
   G / end          bottom of file
+  } / {            next / previous hunk, crossing into the next / previous file
+  V                start / end a visual line selection (esc clears it)
+  mouse click      move the cursor line to the clicked row
   mouse wheel      vertical scroll
   shift+wheel      horizontal scroll (hovered pane)
   h-wheel          horizontal scroll (hovered pane)
-  /                start in-diff search
+  tab              focus left pane / right pane / both
+  < / >            horizontal scroll (focused pane, or both)
+  f                scroll sideways to the first difference on the cursor line
+  e                show the cursor line whole (lines cut off at the pane edge end in …)
+  /                start in-diff search (jumps as you type, esc restores)
+  ?                search only added/deleted lines
   n / N            next / previous search match
+  ] / [            next / previous comparison tab (--tab)
+  o                symbol outline (enter jumps to symbol)
+  i                commits in the compared range
+  K                lint annotations for the current file (enter jumps)
+  D                diff summary (per-language and per-file line counts)
+  W                hide / show files with whitespace-only changes
+  zc / zo / za     fold / unfold / toggle the indented block at the cursor
+  zM / zR          fold all unchanged blocks / unfold everything
+  U                unified view: one pane with -/+ gutters (or back to split)
+  c                changes only: fold unchanged lines away from the hunks (--context N)
+  + / -            show / hide 10 more context lines around the hunk at the cursor
+  zz / zt / zb     center / top / bottom the cursor line
+  enter            hunk menu: copy / stage / discard / mark reviewed / comment
+  |                pipe focused hunk (or file) to a shell command or [pipe] name
+  m                commit the staged changes with a message, then reload (uncommitted reviews)
+  L                read the current file again (e.g. after editing it) without reloading the rest
+  B                compare the current file from another commit that touched it (or back from the base)
+  y                copy a forge permalink to the cursor line (or selection)
+  O                open the current file at the head commit in the browser
+  P                export the selected changed lines as a patch (clipboard + .git/deff/selection.patch)
   r                toggle reviewed for current file
+  R                next unreviewed file (wraps around)
+  A / X            mark every file reviewed / clear every reviewed mark (asks first)
+  u / ctrl-r       undo / redo the last review mark change
   q                quit"#
 )]
 struct Cli {
-    #[arg(long, value_enum)]
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(long, value_enum, global = true)]
     strategy: Option<StrategyArg>,
-    #[arg(long)]
+    #[arg(long, global = true)]
     base: Option<String>,
-    #[arg(long, default_value = DEFAULT_HEAD_REF)]
+    #[arg(long, default_value = DEFAULT_HEAD_REF, global = true)]
     head: String,
-    #[arg(long)]
+    #[arg(long, global = true)]
     include_uncommitted: bool,
-    #[arg(long)]
+    #[arg(long, global = true)]
     only_uncommitted: bool,
-    #[arg(long, value_enum, default_value_t = ThemeMode::Auto)]
+    /// Compare HEAD with the index, like `git diff --cached` (`--strategy staged`).
+    #[arg(long, global = true, conflicts_with_all = ["strategy", "unstaged"])]
+    staged: bool,
+    /// Compare the index with the working tree, like `git diff` (`--strategy unstaged`).
+    #[arg(long, global = true, conflicts_with = "strategy")]
+    unstaged: bool,
+    /// Compare HEAD~1 with the index: what `git commit --amend` would record.
+    #[arg(long, global = true)]
+    amend: bool,
+    /// Fail when the branch has no upstream instead of comparing against the default branch.
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Run `git fetch` for the comparison's remote before resolving it.
+    #[arg(long, global = true)]
+    fetch: bool,
+    /// Open the BASE..HEAD comparison in a tab (HEAD defaults to HEAD); repeat to open
+    /// several and switch between them with ] and [.
+    #[arg(long = "tab", value_name = "BASE..HEAD")]
+    tabs: Vec<String>,
+    /// Open the review on this file (relative to the current directory), with the
+    /// cursor on the given head-side line.
+    #[arg(long, value_name = "PATH[:LINE]")]
+    goto: Option<String>,
+    /// When --head is a merge commit, open one tab per parent showing what the merge
+    /// changed relative to that parent.
+    #[arg(long)]
+    merge_parents: bool,
+    /// Open one tab per commit of the comparison, oldest first, to step through them
+    /// with ] and [.
+    #[arg(long)]
+    per_commit: bool,
+    /// Only show files where the number of occurrences of STRING changed, like `git log -S`.
+    #[arg(
+        long = "pickaxe-S",
+        value_name = "STRING",
+        conflicts_with = "pickaxe_regex",
+        global = true
+    )]
+    pickaxe_string: Option<String>,
+    /// Only show files with an added or removed line matching REGEX, like `git log -G`.
+    #[arg(long = "pickaxe-G", value_name = "REGEX", global = true)]
+    pickaxe_regex: Option<String>,
+    /// Show binary files as hex dumps of their first KB kilobytes (default 4) with changed
+    /// rows highlighted.
+    #[arg(
+        long,
+        value_name = "KB",
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_HEX_DUMP_KB,
+        global = true
+    )]
+    hex_dump: Option<usize>,
+    /// Compare `.json` / `.yaml` files by key path and `.rs` / `.go` files by syntax tree
+    /// tokens, so reordered keys and reformatting are not shown as changes.
+    #[arg(long, global = true)]
+    structural: bool,
+    /// Compare `.csv` / `.tsv` files as aligned tables, pairing rows on this column (a
+    /// 1-based number or a header name; default 1) and marking changed cells with `*`.
+    #[arg(
+        long,
+        value_name = "KEY_COLUMN",
+        num_args = 0..=1,
+        default_missing_value = "1",
+        global = true
+    )]
+    csv: Option<String>,
+    /// Re-indent minified JS, JSON, and CSS (one enormous line) on both sides before
+    /// diffing.
+    #[arg(long, global = true)]
+    pretty: bool,
+    /// Ignore hunks whose changed lines all match this POSIX extended regex (like
+    /// `diff -I`).
+    #[arg(long, value_name = "REGEX", global = true)]
+    ignore_matching_lines: Option<String>,
+    /// Only show files touched by a commit in the range whose author matches PATTERN, as
+    /// `git log --author` matches it.
+    #[arg(long, value_name = "PATTERN", global = true)]
+    author: Option<String>,
+    /// Only show files whose CODEOWNERS entry lists this owner.
+    #[arg(long, value_name = "OWNER", global = true)]
+    owner: Option<String>,
+    /// lcov tracefile used to flag added lines without test coverage.
+    #[arg(long, value_name = "LCOV")]
+    coverage: Option<PathBuf>,
+    /// Lint output to show on head-side lines (cargo/clippy JSON, eslint JSON, or SARIF).
+    #[arg(long, value_name = "FILE")]
+    annotations: Vec<PathBuf>,
+    /// Ask before loading more than this many files (0 disables the prompt).
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_FILES)]
+    max_files: usize,
+    /// Keep this many rows visible above jump targets and a focused hunk.
+    #[arg(long, value_name = "N", default_value_t = 0, global = true)]
+    scrolloff: usize,
+    /// Open each file at its first change instead of line 1 (default with
+    /// `git config deff.firstChange true`).
+    #[arg(long, global = true)]
+    first_change: bool,
+    /// Start in the changes-only view (`c`), keeping N unchanged lines around each
+    /// change; also the context of unified output (`deff export`, `deff preview
+    /// --unified`).
+    #[arg(long, value_name = "N", global = true)]
+    context: Option<usize>,
+    /// Show the sides in two panes, or interleaved in one with `-`/`+` gutters (`U`
+    /// switches while reviewing).
+    #[arg(long, value_enum, default_value_t = ViewMode::Split, global = true)]
+    view: ViewMode,
+    /// Draw a vertical guide after this many characters in both panes, so over-long
+    /// lines stand out.
+    #[arg(long, value_name = "COLUMN", global = true)]
+    ruler: Option<usize>,
+    /// Text drawn between the two panes (default " | ").
+    #[arg(long, value_name = "TEXT", global = true)]
+    separator: Option<String>,
+    /// Draw each pane in a box whose top border names its ref.
+    #[arg(long, global = true)]
+    bordered: bool,
+    /// Browse a `git format-patch` series (a directory of patches or an mbox) applied on
+    /// top of --base (default HEAD), one entry per patch.
+    #[arg(long, value_name = "DIR_OR_MBOX")]
+    patches: Option<PathBuf>,
+    /// Resolve the conflicts in MERGED by taking the left (LOCAL) or right (REMOTE) side,
+    /// for use as a `git mergetool` command.
+    #[arg(
+        long,
+        num_args = 4,
+        value_names = ["LOCAL", "BASE", "REMOTE", "MERGED"],
+        conflicts_with = "patches"
+    )]
+    mergetool: Option<Vec<PathBuf>>,
+    /// Compare two files outside any comparison, for use as a `git difftool` command
+    /// (`deff --files "$LOCAL" "$REMOTE"`); `/dev/null` stands for a missing side.
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["LEFT", "RIGHT"],
+        conflicts_with_all = ["patches", "mergetool"]
+    )]
+    files: Option<Vec<PathBuf>>,
+    /// Review a unified diff read from stdin (`git diff | deff --stdin`), no repository
+    /// needed; implied when stdin is redirected and no other mode is chosen.
+    #[arg(long, conflicts_with_all = ["patches", "mergetool", "files"])]
+    stdin: bool,
+    /// Render every file into this report file instead of opening the TUI.
+    #[arg(long, value_name = "PATH")]
+    output: Option<PathBuf>,
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Plain)]
+    output_format: OutputFormat,
+    /// Column width of the side-by-side report written by --output.
+    #[arg(long, value_name = "COLUMNS", default_value_t = DEFAULT_OUTPUT_WIDTH)]
+    output_width: u16,
+    /// Replay these keys (e.g. "j j zc ctrl-d q") without a TTY and print the final frame.
+    #[arg(long, value_name = "KEYS", conflicts_with = "keys_file")]
+    keys: Option<String>,
+    /// Like --keys, reading the key script from a file (`#` starts a comment).
+    #[arg(long, value_name = "PATH")]
+    keys_file: Option<PathBuf>,
+    /// Off-screen terminal size used by --keys / --keys-file.
+    #[arg(long, value_name = "COLSxROWS", default_value = DEFAULT_SCREEN_SIZE)]
+    screen_size: String,
+    /// Write every rendered frame (text with ANSI styling) to numbered files in this directory.
+    #[arg(long, value_name = "DIR", global = true)]
+    capture: Option<PathBuf>,
+    /// Print a timing breakdown of the loading phases on exit.
+    #[arg(long, global = true)]
+    profile: bool,
+    /// Write debug logs (git invocations, timings, parse anomalies) to this file.
+    #[arg(long, value_name = "PATH", global = true)]
+    log_file: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t = ThemeMode::Auto, global = true)]
     theme: ThemeMode,
+    /// Mark changes with bold foreground colours and reverse video instead of background
+    /// tints and syntax colours.
+    #[arg(long, global = true)]
+    high_contrast: bool,
+    /// Show files without syntax colours. Files over 512 KiB are always shown this way.
+    #[arg(long, global = true)]
+    no_syntax: bool,
+    /// Draw the UI inline below the prompt in this many rows (or percent of the terminal)
+    /// instead of taking over the screen.
+    #[arg(long, value_name = "ROWS|N%", global = true)]
+    height: Option<String>,
+    /// Keep reviewed state separate per reviewer (defaults to `git config deff.reviewer`).
+    #[arg(long, value_name = "NAME", global = true)]
+    reviewer: Option<String>,
+    /// Keep reviewed state and comments in this repository-relative directory (e.g. a
+    /// tracked `.deff`) instead of `.git/deff` (defaults to `git config deff.reviewStateDir`).
+    #[arg(long, value_name = "DIR", global = true)]
+    review_state_dir: Option<PathBuf>,
+    /// Also record reviewed state and comments as `refs/notes/deff` notes on the head
+    /// commit (defaults to `git config deff.reviewNotes`).
+    #[arg(long, global = true)]
+    review_notes: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Browse recent commits and open the selected commit's diff.
+    Log {
+        #[arg(default_value = DEFAULT_HEAD_REF)]
+        revision: String,
+        #[arg(long, default_value_t = DEFAULT_LOG_MAX_COUNT)]
+        max_count: usize,
+    },
+    /// Write a review report (reviewed checklist, diffstats, diffs) for the comparison.
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Markdown)]
+        format: ExportFormat,
+        /// Report file to write; defaults to stdout.
+        #[arg(long = "output", value_name = "PATH")]
+        export_output: Option<PathBuf>,
+    },
+    /// Report review progress for the comparison without opening the TUI; exits 1
+    /// while any file is unreviewed.
+    Status {
+        /// Print a JSON object instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Git hook entry points that check review progress of the upstream-ahead comparison.
+    Hook {
+        #[arg(value_enum)]
+        hook: GitHook,
+        /// Print the unreviewed files but let git continue.
+        #[arg(long)]
+        warn: bool,
+        /// Write a hook script calling this command into the repository's hooks directory.
+        #[arg(long)]
+        install: bool,
+    },
+    /// Review one file's changes between two refs, e.g. `deff show v1.0 v1.1 -- src/lib.rs`.
+    Show {
+        #[arg(value_name = "REF1")]
+        old_ref: String,
+        #[arg(value_name = "REF2")]
+        new_ref: String,
+        #[arg(last = true, required = true, value_name = "PATH")]
+        path: PathBuf,
+    },
+    /// Print the changed lines of the comparison that match a regex as `path:line:
+    /// content`; exits 1 when nothing matches.
+    Grep {
+        pattern: String,
+        /// Only search added lines.
+        #[arg(long, conflicts_with = "removed")]
+        added: bool,
+        /// Only search removed lines.
+        #[arg(long)]
+        removed: bool,
+    },
+    /// Once every file of the comparison is reviewed, add a `Reviewed-by:` trailer for
+    /// your git identity to the head commit; exits 1 while any file is unreviewed.
+    Signoff {
+        /// Print the trailer (e.g. for a merge commit message) instead of amending the
+        /// head commit.
+        #[arg(long)]
+        print: bool,
+    },
+    /// Review the comparison's commits one by one, marking each as looking fine, needing
+    /// an edit, or to be squashed, and write the marks as a `git rebase -i` todo list.
+    RebasePlan {
+        /// Todo list file to write; defaults to stdout.
+        #[arg(long = "output", value_name = "PATH")]
+        todo_output: Option<PathBuf>,
+    },
+    /// Review what reverting a commit would change on HEAD, with conflicts flagged.
+    PreviewRevert { commit: String },
+    /// Print one file's diff to stdout, e.g. as an `fzf --preview` command over `git
+    /// status`; compares the working tree with HEAD, or with --base when given.
+    Preview {
+        path: PathBuf,
+        /// Print unified hunks instead of the side-by-side panes.
+        #[arg(long)]
+        unified: bool,
+        /// Columns to render (defaults to $FZF_PREVIEW_COLUMNS, then 80).
+        #[arg(long, value_name = "COLUMNS")]
+        width: Option<u16>,
+        #[arg(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Ansi)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum GitHook {
+    PrePush,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum CliCommand {
+    Review,
+    Log {
+        revision: String,
+        max_count: usize,
+    },
+    Export {
+        format: ExportFormat,
+        output_path: Option<PathBuf>,
+    },
+    Status {
+        json: bool,
+    },
+    Hook {
+        hook: GitHook,
+        warn: bool,
+        install: bool,
+    },
+    Patches {
+        path: PathBuf,
+    },
+    Show {
+        path: PathBuf,
+    },
+    Grep {
+        pattern: String,
+        sides: GrepSides,
+    },
+    Signoff {
+        print: bool,
+    },
+    RebasePlan {
+        output_path: Option<PathBuf>,
+    },
+    PreviewRevert {
+        commit: String,
+    },
+    Preview {
+        path: PathBuf,
+        unified: bool,
+        width: Option<u16>,
+        format: OutputFormat,
+    },
+    ExternalDiff(ExternalDiffArgs),
+    Mergetool(MergetoolPaths),
+    Difftool(DifftoolPaths),
+    StdinPatch,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum KeyScriptSource {
+    Inline(String),
+    File(PathBuf),
+}
+
+/// `--goto PATH[:LINE]`; the line is 1-based.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct GotoTarget {
+    pub(crate) path: PathBuf,
+    pub(crate) line: Option<usize>,
+}
+
+/// One `--tab BASE..HEAD` comparison.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct TabRange {
+    pub(crate) base_ref: String,
+    pub(crate) head_ref: String,
+    /// Shown in place of `BASE..HEAD`.
+    pub(crate) summary: Option<String>,
 }
 
 #[derive(Clone, Debug)]
 pub(crate) struct CliOptions {
+    pub(crate) command: CliCommand,
     pub(crate) strategy_id: StrategyId,
     pub(crate) base_ref: Option<String>,
     pub(crate) head_ref: String,
     pub(crate) include_uncommitted: bool,
     pub(crate) only_uncommitted: bool,
+    pub(crate) strict: bool,
+    pub(crate) fetch: bool,
+    pub(crate) tabs: Vec<TabRange>,
+    pub(crate) merge_parents: bool,
+    pub(crate) per_commit: bool,
+    pub(crate) goto: Option<GotoTarget>,
+    pub(crate) pickaxe: Option<Pickaxe>,
+    pub(crate) ignore_matching_lines: Option<String>,
+    pub(crate) hex_dump_bytes: Option<usize>,
+    pub(crate) structural: bool,
+    pub(crate) csv_key: Option<String>,
+    pub(crate) pretty_minified: bool,
+    pub(crate) author: Option<String>,
+    pub(crate) owner: Option<String>,
+    pub(crate) coverage_path: Option<PathBuf>,
+    pub(crate) annotation_paths: Vec<PathBuf>,
+    pub(crate) max_files: usize,
+    pub(crate) scrolloff: usize,
+    pub(crate) first_change: bool,
+    pub(crate) context: Option<usize>,
+    pub(crate) view: ViewMode,
+    pub(crate) ruler: Option<usize>,
+    pub(crate) separator: Option<String>,
+    pub(crate) bordered: bool,
+    pub(crate) output_path: Option<PathBuf>,
+    pub(crate) output_format: OutputFormat,
+    pub(crate) output_width: u16,
+    pub(crate) key_script: Option<KeyScriptSource>,
+    pub(crate) screen_size: (u16, u16),
+    pub(crate) capture_dir: Option<PathBuf>,
+    pub(crate) profile: bool,
+    pub(crate) log_file: Option<PathBuf>,
     pub(crate) theme_mode: ThemeMode,
+    pub(crate) high_contrast: bool,
+    /// The terminal's background, when `--theme auto` asked it.
+    pub(crate) dark_background: Option<bool>,
+    pub(crate) no_syntax: bool,
+    pub(crate) inline_height: Option<InlineHeight>,
+    pub(crate) reviewer: Option<String>,
+    pub(crate) review_state_dir: Option<PathBuf>,
+    pub(crate) review_notes: bool,
+    /// The repository's `.deff.toml`, empty outside a repository.
+    pub(crate) project: ProjectConfig,
+}
+
+fn uses_comparison_options(value: &Cli) -> bool {
+    value.base.is_some() || uses_comparison_options_besides_base(value)
+}
+
+/// `--patches` reuses `--base` as the commit the series is applied to.
+fn uses_comparison_options_besides_base(value: &Cli) -> bool {
+    value.strategy.is_some()
+        || value.staged
+        || value.unstaged
+        || value.head != DEFAULT_HEAD_REF
+        || value.include_uncommitted
+        || value.only_uncommitted
+        || value.amend
+        || value.strict
+        || value.fetch
+        || value.pickaxe_string.is_some()
+        || value.pickaxe_regex.is_some()
+        || value.ignore_matching_lines.is_some()
+        || value.author.is_some()
+        || value.owner.is_some()
+        || value.coverage.is_some()
+        || !value.annotations.is_empty()
 }
 
 impl TryFrom<Cli> for CliOptions {
     type Error = anyhow::Error;
 
-    fn try_from(value: Cli) -> Result<Self> {
+    fn try_from(mut value: Cli) -> Result<Self> {
+        if value.staged {
+            value.strategy = Some(StrategyArg::Staged);
+        } else if value.unstaged {
+            value.strategy = Some(StrategyArg::Unstaged);
+        }
+        let comparison_options_used = uses_comparison_options(&value);
+        if value.patches.is_some()
+            && (value.command.is_some()
+                || uses_comparison_options_besides_base(&value)
+                || value.output.is_some())
+        {
+            bail!("--patches only accepts --base <git-ref> as the commit to apply the series to");
+        }
+        if value.mergetool.is_some()
+            && (value.command.is_some() || comparison_options_used || value.output.is_some())
+        {
+            bail!("--mergetool does not accept comparison options");
+        }
+        if value.files.is_some()
+            && (value.command.is_some() || comparison_options_used || value.output.is_some())
+        {
+            bail!("--files does not accept comparison options");
+        }
+        if value.stdin
+            && (value.command.is_some() || comparison_options_used || value.output.is_some())
+        {
+            bail!("--stdin does not accept comparison options");
+        }
+        let command = match value.command {
+            None => match (value.patches, value.mergetool, value.files) {
+                (Some(path), _, _) => CliCommand::Patches { path },
+                (None, None, Some(paths)) => {
+                    let [left, right] = <[PathBuf; 2]>::try_from(paths)
+                        .map_err(|_| anyhow::anyhow!("--files takes LEFT RIGHT"))?;
+                    CliCommand::Difftool(DifftoolPaths { left, right })
+                }
+                (None, Some(paths), _) => {
+                    let [local, base, remote, merged] =
+                        <[PathBuf; 4]>::try_from(paths).map_err(|_| {
+                            anyhow::anyhow!("--mergetool takes LOCAL BASE REMOTE MERGED")
+                        })?;
+                    CliCommand::Mergetool(MergetoolPaths {
+                        local,
+                        base,
+                        remote,
+                        merged,
+                    })
+                }
+                (None, None, None) if value.stdin => CliCommand::StdinPatch,
+                (None, None, None) => CliCommand::Review,
+            },
+            Some(Command::Log {
+                revision,
+                max_count,
+            }) => {
+                if comparison_options_used || value.output.is_some() {
+                    bail!("deff log does not accept comparison options");
+                }
+                if max_count == 0 {
+                    bail!("--max-count must be greater than zero");
+                }
+                CliCommand::Log {
+                    revision,
+                    max_count,
+                }
+            }
+            Some(Command::Export {
+                format,
+                export_output,
+            }) => {
+                if value.output.is_some() {
+                    bail!("deff export writes its own report; use `deff export --output <path>`");
+                }
+                CliCommand::Export {
+                    format,
+                    output_path: export_output,
+                }
+            }
+            Some(Command::Status { json }) => {
+                if value.output.is_some() {
+                    bail!("deff status prints to stdout and does not accept --output");
+                }
+                CliCommand::Status { json }
+            }
+            Some(Command::Hook {
+                hook,
+                warn,
+                install,
+            }) => {
+                // The hook always checks what a push would publish: the upstream-ahead commits.
+                if comparison_options_used || value.output.is_some() {
+                    bail!("deff hook does not accept comparison options");
+                }
+                CliCommand::Hook {
+                    hook,
+                    warn,
+                    install,
+                }
+            }
+            Some(Command::Show {
+                old_ref,
+                new_ref,
+                path,
+            }) => {
+                if value.strategy.is_some()
+                    || value.base.is_some()
+                    || value.head != DEFAULT_HEAD_REF
+                    || value.include_uncommitted
+                    || value.only_uncommitted
+                {
+                    bail!("deff show compares the two refs it is given");
+                }
+                value.base = Some(old_ref);
+                value.head = new_ref;
+                CliCommand::Show { path }
+            }
+            Some(Command::Grep {
+                pattern,
+                added,
+                removed,
+            }) => {
+                if value.output.is_some() {
+                    bail!("deff grep prints to stdout and does not accept --output");
+                }
+                Regex::new(&pattern)
+                    .with_context(|| format!("invalid deff grep regex: {pattern}"))?;
+                let sides = match (added, removed) {
+                    (true, _) => GrepSides::Added,
+                    (_, true) => GrepSides::Removed,
+                    _ => GrepSides::Both,
+                };
+                CliCommand::Grep { pattern, sides }
+            }
+            Some(Command::Signoff { print }) => {
+                if value.output.is_some() {
+                    bail!("deff signoff does not accept --output");
+                }
+                CliCommand::Signoff { print }
+            }
+            Some(Command::RebasePlan { todo_output }) => {
+                if value.output.is_some() {
+                    bail!(
+                        "deff rebase-plan writes a todo list; use `deff rebase-plan --output <path>`"
+                    );
+                }
+                if value.include_uncommitted || value.only_uncommitted || value.amend {
+                    bail!("deff rebase-plan only covers committed changes");
+                }
+                CliCommand::RebasePlan {
+                    output_path: todo_output,
+                }
+            }
+            Some(Command::PreviewRevert { commit }) => {
+                if comparison_options_used || value.output.is_some() {
+                    bail!("deff preview-revert always compares HEAD with the reverted tree");
+                }
+                CliCommand::PreviewRevert { commit }
+            }
+            Some(Command::Preview {
+                path,
+                unified,
+                width,
+                format,
+            }) => {
+                if value.output.is_some() {
+                    bail!("deff preview prints to stdout and does not accept --output");
+                }
+                if width.is_some_and(|width| width < 20) {
+                    bail!("--width must be at least 20 columns");
+                }
+                CliCommand::Preview {
+                    path,
+                    unified,
+                    width,
+                    format,
+                }
+            }
+        };
+
+        // `git status` lists working tree changes, so a preview includes them: against
+        // HEAD by default, or on top of --base.
+        if matches!(command, CliCommand::Preview { .. }) {
+            if !comparison_options_used {
+                value.only_uncommitted = true;
+            } else if value.base.is_some()
+                && value.head == DEFAULT_HEAD_REF
+                && !value.only_uncommitted
+                && value.author.is_none()
+            {
+                value.include_uncommitted = true;
+            }
+        }
+
+        if value.amend
+            && (value.strategy.is_some()
+                || value.base.is_some()
+                || value.head != DEFAULT_HEAD_REF
+                || value.include_uncommitted
+                || value.only_uncommitted
+                || value.strict
+                || value.fetch
+                || value.author.is_some()
+                || !value.tabs.is_empty()
+                || value.merge_parents
+                || value.per_commit)
+        {
+            bail!("--amend always compares HEAD~1 with the index");
+        }
+
         let strategy_explicitly_set = value.strategy.is_some();
         let strategy_id = match value.strategy {
             Some(strategy) => StrategyId::from(strategy),
+            None if value.amend => StrategyId::Amend,
             None => {
                 if value.base.is_some() {
                     StrategyId::Range
@@ -87,6 +832,24 @@ impl TryFrom<Cli> for CliOptions {
             bail!("--base can only be used with --strategy range");
         }
 
+        if matches!(strategy_id, StrategyId::Staged | StrategyId::Unstaged)
+            && (value.base.is_some()
+                || value.head != DEFAULT_HEAD_REF
+                || value.include_uncommitted
+                || value.only_uncommitted
+                || value.fetch
+                || value.author.is_some()
+                || value.per_commit)
+        {
+            bail!(
+                "--strategy {strategy_id} splits the uncommitted changes at the index and cannot be combined with --base, --head, --fetch, --author, --per-commit, or other uncommitted modes"
+            );
+        }
+
+        if value.strict && (strategy_id != StrategyId::UpstreamAhead || value.only_uncommitted) {
+            bail!("--strict only applies to the upstream-ahead strategy");
+        }
+
         if value.only_uncommitted {
             if strategy_explicitly_set {
                 bail!("--only-uncommitted cannot be combined with --strategy");
@@ -100,26 +863,444 @@ impl TryFrom<Cli> for CliOptions {
             if value.include_uncommitted {
                 bail!("--only-uncommitted cannot be combined with --include-uncommitted");
             }
+            if value.fetch {
+                bail!("--only-uncommitted cannot be combined with --fetch");
+            }
+        }
+
+        if value.author.is_some() {
+            if value.only_uncommitted || value.include_uncommitted {
+                bail!(
+                    "--author only applies to committed changes and cannot be combined with uncommitted modes"
+                );
+            }
+            if value.author.as_deref() == Some("") {
+                bail!("--author pattern cannot be empty");
+            }
+        }
+
+        if value.output.is_none()
+            && (value.output_format != OutputFormat::Plain
+                || value.output_width != DEFAULT_OUTPUT_WIDTH)
+        {
+            bail!("--output-format and --output-width require --output <path>");
+        }
+        if value.output_width < 20 {
+            bail!("--output-width must be at least 20 columns");
+        }
+
+        let key_script = match (value.keys, value.keys_file) {
+            (Some(keys), _) => Some(KeyScriptSource::Inline(keys)),
+            (None, Some(path)) => Some(KeyScriptSource::File(path)),
+            (None, None) => None,
+        };
+        if key_script.is_some()
+            && (!matches!(
+                command,
+                CliCommand::Review
+                    | CliCommand::Show { .. }
+                    | CliCommand::StdinPatch
+                    | CliCommand::Difftool(_)
+            ) || value.output.is_some())
+        {
+            bail!("--keys / --keys-file only apply to the interactive review");
         }
+        if key_script.is_none() && value.screen_size != DEFAULT_SCREEN_SIZE {
+            bail!("--screen-size requires --keys or --keys-file");
+        }
+        let screen_size = parse_screen_size(&value.screen_size)?;
 
         if value.include_uncommitted && value.head != DEFAULT_HEAD_REF {
             bail!("--include-uncommitted currently requires --head HEAD");
         }
 
+        if value.pickaxe_string.as_deref() == Some("") || value.pickaxe_regex.as_deref() == Some("")
+        {
+            bail!("pickaxe pattern cannot be empty");
+        }
+        let pickaxe = match (value.pickaxe_string, value.pickaxe_regex) {
+            (Some(_), Some(_)) => bail!("--pickaxe-S cannot be combined with --pickaxe-G"),
+            (Some(value), None) => Some(Pickaxe::String(value)),
+            (None, Some(pattern)) => Some(Pickaxe::Regex(pattern)),
+            (None, None) => None,
+        };
+
+        if value.ignore_matching_lines.as_deref() == Some("") {
+            bail!("--ignore-matching-lines pattern cannot be empty");
+        }
+        if matches!(value.csv.as_deref(), Some("" | "0")) {
+            bail!("--csv key column must be a 1-based column number or a header name");
+        }
+        if value.hex_dump == Some(0) {
+            bail!("--hex-dump must show at least 1 KB");
+        }
+        if value.ruler == Some(0) {
+            bail!("--ruler column must be at least 1");
+        }
+        if value.bordered && value.separator.is_some() {
+            bail!("--bordered panes share a border, so --separator does not apply");
+        }
+        if !value.tabs.is_empty() {
+            if !matches!(command, CliCommand::Review) || value.output.is_some() {
+                bail!("--tab only applies to the interactive review");
+            }
+            if strategy_explicitly_set
+                || value.base.is_some()
+                || value.head != DEFAULT_HEAD_REF
+                || value.include_uncommitted
+                || value.only_uncommitted
+                || value.strict
+                || value.fetch
+            {
+                bail!(
+                    "--tab cannot be combined with --strategy, --base, --head, --strict, --fetch, or uncommitted modes"
+                );
+            }
+        }
+        if value.merge_parents {
+            if !matches!(command, CliCommand::Review) || value.output.is_some() {
+                bail!("--merge-parents only applies to the interactive review");
+            }
+            if !value.tabs.is_empty()
+                || strategy_explicitly_set
+                || value.base.is_some()
+                || value.include_uncommitted
+                || value.only_uncommitted
+                || value.strict
+            {
+                bail!("--merge-parents only accepts --head <merge-commit> as its comparison");
+            }
+        }
+        if value.per_commit {
+            if !matches!(command, CliCommand::Review) || value.output.is_some() {
+                bail!("--per-commit only applies to the interactive review");
+            }
+            if !value.tabs.is_empty()
+                || value.merge_parents
+                || value.include_uncommitted
+                || value.only_uncommitted
+            {
+                bail!(
+                    "--per-commit splits one committed range, so it cannot be combined with --tab, --merge-parents, or uncommitted modes"
+                );
+            }
+        }
+        if value.goto.is_some()
+            && (!matches!(command, CliCommand::Review)
+                || value.output.is_some()
+                || !value.tabs.is_empty()
+                || value.merge_parents
+                || value.per_commit)
+        {
+            bail!("--goto only applies to the interactive review of a single comparison");
+        }
+        let goto = value.goto.as_deref().map(parse_goto_target).transpose()?;
+        let tabs = value
+            .tabs
+            .iter()
+            .map(|tab| parse_tab_range(tab))
+            .collect::<Result<_>>()?;
+
         Ok(Self {
+            command,
             strategy_id,
             base_ref: value.base,
             head_ref: value.head,
             include_uncommitted: value.include_uncommitted,
             only_uncommitted: value.only_uncommitted,
+            strict: value.strict,
+            fetch: value.fetch,
+            tabs,
+            merge_parents: value.merge_parents,
+            per_commit: value.per_commit,
+            goto,
+            pickaxe,
+            ignore_matching_lines: value.ignore_matching_lines,
+            hex_dump_bytes: value.hex_dump.map(|kilobytes| kilobytes * 1024),
+            structural: value.structural,
+            csv_key: value.csv,
+            pretty_minified: value.pretty,
+            author: value.author,
+            owner: value.owner,
+            coverage_path: value.coverage,
+            annotation_paths: value.annotations,
+            max_files: value.max_files,
+            scrolloff: value.scrolloff,
+            first_change: value.first_change,
+            context: value.context,
+            view: value.view,
+            ruler: value.ruler,
+            separator: value.separator,
+            bordered: value.bordered,
+            output_path: value.output,
+            output_format: value.output_format,
+            output_width: value.output_width,
+            key_script,
+            screen_size,
+            capture_dir: value.capture,
+            profile: value.profile,
+            log_file: value.log_file,
             theme_mode: value.theme,
+            high_contrast: value.high_contrast,
+            dark_background: None,
+            no_syntax: value.no_syntax,
+            inline_height: value
+                .height
+                .as_deref()
+                .map(parse_inline_height)
+                .transpose()?,
+            reviewer: value.reviewer,
+            review_state_dir: value.review_state_dir,
+            review_notes: value.review_notes,
+            project: ProjectConfig::default(),
         })
     }
 }
 
+fn parse_screen_size(value: &str) -> Result<(u16, u16)> {
+    let parsed = value
+        .split_once('x')
+        .and_then(|(columns, rows)| Some((columns.parse().ok()?, rows.parse().ok()?)));
+    match parsed {
+        Some((columns, rows)) if columns >= 20 && rows >= 10 => Ok((columns, rows)),
+        _ => bail!("--screen-size must look like 120x40 (at least 20x10)"),
+    }
+}
+
+fn parse_goto_target(value: &str) -> Result<GotoTarget> {
+    let (path, line) = match value.rsplit_once(':') {
+        Some((path, line))
+            if !line.is_empty() && line.bytes().all(|byte| byte.is_ascii_digit()) =>
+        {
+            (
+                path,
+                Some(line.parse::<usize>().ok().filter(|line| *line > 0)),
+            )
+        }
+        _ => (value, None),
+    };
+    if path.is_empty() || line == Some(None) {
+        bail!("--goto must look like PATH or PATH:LINE with a line of at least 1, got {value}");
+    }
+    Ok(GotoTarget {
+        path: PathBuf::from(path),
+        line: line.flatten(),
+    })
+}
+
+fn parse_tab_range(value: &str) -> Result<TabRange> {
+    let (base_ref, head_ref) = value.split_once("..").unwrap_or((value, ""));
+    if base_ref.is_empty() || head_ref.starts_with('.') {
+        bail!("--tab must look like BASE..HEAD or BASE, got {value}");
+    }
+    Ok(TabRange {
+        base_ref: base_ref.to_string(),
+        head_ref: if head_ref.is_empty() {
+            DEFAULT_HEAD_REF.to_string()
+        } else {
+            head_ref.to_string()
+        },
+        summary: None,
+    })
+}
+
+fn parse_inline_height(value: &str) -> Result<InlineHeight> {
+    let parsed = match value.strip_suffix('%') {
+        Some(percent) => percent
+            .parse()
+            .ok()
+            .filter(|percent| (1..=100).contains(percent))
+            .map(InlineHeight::Percent),
+        None => value
+            .parse()
+            .ok()
+            .filter(|rows| *rows > 0)
+            .map(InlineHeight::Rows),
+    };
+    parsed.with_context(|| {
+        format!("--height must be a row count or a percentage like 40%, got {value}")
+    })
+}
+
+impl CliOptions {
+    pub(crate) fn comparison_request(&self) -> ComparisonRequest {
+        ComparisonRequest {
+            strategy_id: self.strategy_id,
+            base_ref: self.base_ref.clone(),
+            head_ref: self.head_ref.clone(),
+            only_uncommitted: self.only_uncommitted,
+            strict: self.strict,
+        }
+    }
+
+    pub(crate) fn view_settings(&self) -> ViewSettings {
+        ViewSettings {
+            scrolloff: self.scrolloff,
+            open_at_first_change: self.first_change,
+            changes_only: self.context.is_some(),
+            context_lines: self.context_lines(),
+            view_mode: self.view,
+            render: self.render_options(),
+        }
+    }
+
+    pub(crate) fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            theme_mode: self.theme_mode,
+            dark_background: self.dark_background,
+            ruler: self.ruler,
+            separator: self.separator.clone(),
+            bordered: self.bordered,
+            high_contrast: self.high_contrast,
+        }
+    }
+
+    /// A `theme` from the config files applies unless `--theme` or `DEFF_THEME` chose one.
+    fn apply_project_config(&mut self, project: ProjectConfig) {
+        if self.theme_mode == ThemeMode::Auto
+            && std::env::var_os("DEFF_THEME").is_none()
+            && let Some(theme) = project.theme
+        {
+            self.theme_mode = theme;
+        }
+        self.project = project;
+    }
+
+    /// `--context`, else the project's `context`, else git's default.
+    pub(crate) fn context_lines(&self) -> usize {
+        self.context
+            .or(self.project.context_lines)
+            .unwrap_or(DEFAULT_CONTEXT_LINES)
+    }
+
+    /// Whether the command opens the interactive terminal; the others print and exit.
+    pub(crate) fn starts_terminal(&self) -> bool {
+        let prints = matches!(
+            self.command,
+            CliCommand::Status { .. }
+                | CliCommand::Grep { .. }
+                | CliCommand::Signoff { .. }
+                | CliCommand::Hook { .. }
+                | CliCommand::Export { .. }
+                | CliCommand::Preview { .. }
+        );
+        !prints && self.key_script.is_none() && self.output_path.is_none()
+    }
+
+    pub(crate) fn diff_options(&self) -> DiffOptions {
+        DiffOptions {
+            ignore_matching_lines: self.ignore_matching_lines.clone(),
+            hex_dump_bytes: self.hex_dump_bytes,
+            structural: self.structural,
+            csv_key: self.csv_key.clone(),
+            pretty_minified: self.pretty_minified,
+            no_syntax: self.no_syntax,
+            // Settings from git config are filled in once the repository is known.
+            ..DiffOptions::default()
+        }
+    }
+}
+
+fn is_git_file_mode(value: &str) -> bool {
+    value == "." || (value.len() == 6 && value.bytes().all(|byte| matches!(byte, b'0'..=b'7')))
+}
+
+/// Recognizes git's external diff driver call, `path old-file old-hex old-mode new-file
+/// new-hex new-mode`, with `new-path xfrm-msg` appended for renames and copies.
+fn parse_external_diff_args(args: &[String]) -> Option<CliCommand> {
+    if !matches!(args.len(), 7 | 9) || !is_git_file_mode(&args[3]) || !is_git_file_mode(&args[6]) {
+        return None;
+    }
+    let side_file = |file: &str, mode: &str| (mode != ".").then(|| PathBuf::from(file));
+    Some(CliCommand::ExternalDiff(ExternalDiffArgs {
+        path: args[0].clone(),
+        new_path: args
+            .get(7)
+            .filter(|new_path| **new_path != args[0])
+            .cloned(),
+        old_file: side_file(&args[1], &args[3]),
+        old_hex: args[2].clone(),
+        new_file: side_file(&args[4], &args[6]),
+        new_hex: args[5].clone(),
+    }))
+}
+
+/// Takes the strategy and base from `.deff.toml` for commands that review the default
+/// comparison, unless the command line chooses one.
+fn apply_project_comparison(value: &mut Cli, project: &ProjectConfig) {
+    let reviews_default_comparison = matches!(
+        value.command,
+        None | Some(
+            Command::Export { .. }
+                | Command::Status { .. }
+                | Command::Grep { .. }
+                | Command::Signoff { .. }
+                | Command::RebasePlan { .. }
+        )
+    );
+    let comparison_chosen = value.strategy.is_some()
+        || value.staged
+        || value.unstaged
+        || value.base.is_some()
+        || value.only_uncommitted
+        || value.amend
+        || value.strict
+        || !value.tabs.is_empty()
+        || value.merge_parents
+        || value.patches.is_some()
+        || value.mergetool.is_some()
+        || value.files.is_some()
+        || value.stdin;
+    if reviews_default_comparison && !comparison_chosen {
+        value.strategy = project.strategy;
+        value.base = project.base.clone();
+    }
+}
+
+/// `deff < changes.patch`: stdin is redirected, the output is a terminal, and nothing
+/// else says what to review.
+fn is_patch_redirected_to_stdin(value: &Cli) -> bool {
+    value.command.is_none()
+        && !uses_comparison_options(value)
+        && value.tabs.is_empty()
+        && !value.merge_parents
+        && !value.per_commit
+        && value.goto.is_none()
+        && value.patches.is_none()
+        && value.mergetool.is_none()
+        && value.files.is_none()
+        && value.output.is_none()
+        && value.keys.is_none()
+        && value.keys_file.is_none()
+        && !io::stdin().is_terminal()
+        && io::stdout().is_terminal()
+}
+
+fn load_project_config() -> Result<ProjectConfig> {
+    let repository_root = std::env::current_dir()
+        .ok()
+        .and_then(|directory| get_repository_root(&directory).ok());
+    ProjectConfig::load(repository_root.as_deref())
+}
+
 pub(crate) fn parse_cli_options() -> Result<CliOptions> {
-    let cli = Cli::parse();
-    CliOptions::try_from(cli)
+    let args: Vec<String> = std::env::args_os()
+        .skip(1)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    if let Some(command) = parse_external_diff_args(&args) {
+        let mut options = CliOptions::try_from(Cli::parse_from(["deff"]))?;
+        options.command = command;
+        options.apply_project_config(load_project_config()?);
+        return Ok(options);
+    }
+    let mut cli = Cli::parse();
+    cli.stdin = cli.stdin || is_patch_redirected_to_stdin(&cli);
+    let project = load_project_config()?;
+    apply_project_comparison(&mut cli, &project);
+    let mut options = CliOptions::try_from(cli)?;
+    options.apply_project_config(project);
+    Ok(options)
 }
 
 #[cfg(test)]
@@ -128,15 +1309,129 @@ mod tests {
 
     fn base_cli() -> Cli {
         Cli {
+            command: None,
             strategy: None,
             base: None,
             head: DEFAULT_HEAD_REF.to_string(),
             include_uncommitted: false,
             only_uncommitted: false,
+            staged: false,
+            unstaged: false,
+            amend: false,
+            strict: false,
+            fetch: false,
+            tabs: Vec::new(),
+            merge_parents: false,
+            per_commit: false,
+            goto: None,
+            pickaxe_string: None,
+            pickaxe_regex: None,
+            hex_dump: None,
+            structural: false,
+            csv: None,
+            pretty: false,
+            ignore_matching_lines: None,
+            author: None,
+            owner: None,
+            coverage: None,
+            annotations: Vec::new(),
+            max_files: DEFAULT_MAX_FILES,
+            scrolloff: 0,
+            first_change: false,
+            context: None,
+            view: ViewMode::Split,
+            ruler: None,
+            separator: None,
+            bordered: false,
+            patches: None,
+            mergetool: None,
+            files: None,
+            stdin: false,
+            output: None,
+            output_format: OutputFormat::Plain,
+            output_width: DEFAULT_OUTPUT_WIDTH,
+            keys: None,
+            keys_file: None,
+            screen_size: DEFAULT_SCREEN_SIZE.to_string(),
+            capture: None,
+            profile: false,
+            log_file: None,
             theme: ThemeMode::Auto,
+            high_contrast: false,
+            no_syntax: false,
+            height: None,
+            reviewer: None,
+            review_state_dir: None,
+            review_notes: false,
         }
     }
 
+    #[test]
+    fn external_diff_args_are_recognized_by_their_modes() {
+        let args = |values: &[&str]| {
+            values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            parse_external_diff_args(&args(&[
+                "src/new.rs",
+                "/dev/null",
+                ".",
+                ".",
+                "src/new.rs",
+                "abc123",
+                "100644",
+            ])),
+            Some(CliCommand::ExternalDiff(ExternalDiffArgs {
+                path: "src/new.rs".to_string(),
+                new_path: None,
+                old_file: None,
+                old_hex: ".".to_string(),
+                new_file: Some(PathBuf::from("src/new.rs")),
+                new_hex: "abc123".to_string(),
+            }))
+        );
+        let renamed = parse_external_diff_args(&args(&[
+            "a.rs",
+            "/tmp/x_a.rs",
+            "abc",
+            "100644",
+            "b.rs",
+            "def",
+            "100755",
+            "b.rs",
+            "similarity index 90%\n",
+        ]));
+        assert!(matches!(
+            renamed,
+            Some(CliCommand::ExternalDiff(ExternalDiffArgs { new_path: Some(path), .. }))
+                if path == "b.rs"
+        ));
+        assert_eq!(
+            parse_external_diff_args(&args(&["log", "a", "b", "c", "d", "e", "f"])),
+            None
+        );
+    }
+
+    #[test]
+    fn strict_only_applies_to_upstream_ahead() {
+        let mut cli = base_cli();
+        cli.strict = true;
+        assert!(CliOptions::try_from(cli).expect("valid").strict);
+
+        let mut cli = base_cli();
+        cli.strict = true;
+        cli.base = Some("main".to_string());
+        let error = CliOptions::try_from(cli).expect_err("range rejects --strict");
+        assert_eq!(
+            error.to_string(),
+            "--strict only applies to the upstream-ahead strategy"
+        );
+    }
+
     #[test]
     fn only_uncommitted_sets_flag_on_options() {
         let mut cli = base_cli();
@@ -148,6 +1443,48 @@ mod tests {
         assert!(!options.include_uncommitted);
     }
 
+    #[test]
+    fn staged_and_unstaged_split_the_uncommitted_changes_at_the_index() {
+        for (flag, strategy_id) in [
+            ("--staged", StrategyId::Staged),
+            ("--unstaged", StrategyId::Unstaged),
+        ] {
+            let cli = Cli::try_parse_from(["deff", flag]).expect("index arguments should parse");
+            let options = CliOptions::try_from(cli).expect("cli options should parse");
+            assert_eq!(options.strategy_id, strategy_id);
+        }
+        let cli =
+            Cli::try_parse_from(["deff", "--strategy", "staged"]).expect("strategy should parse");
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+        assert_eq!(options.strategy_id, StrategyId::Staged);
+
+        assert!(Cli::try_parse_from(["deff", "--staged", "--unstaged"]).is_err());
+        for args in [
+            &["deff", "--staged", "--base", "main"][..],
+            &["deff", "--unstaged", "--include-uncommitted"],
+            &["deff", "--staged", "--fetch"],
+        ] {
+            let cli = Cli::try_parse_from(args).expect("index arguments should parse");
+            assert!(CliOptions::try_from(cli).is_err(), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn only_interactive_commands_start_the_terminal() {
+        let starts_terminal = |args: &[&str]| {
+            let cli = Cli::try_parse_from(args).expect("arguments should parse");
+            CliOptions::try_from(cli)
+                .expect("cli options should parse")
+                .starts_terminal()
+        };
+
+        assert!(starts_terminal(&["deff"]));
+        assert!(starts_terminal(&["deff", "log"]));
+        assert!(!starts_terminal(&["deff", "status"]));
+        assert!(!starts_terminal(&["deff", "grep", "TODO"]));
+        assert!(!starts_terminal(&["deff", "--keys", "jq"]));
+    }
+
     #[test]
     fn only_uncommitted_rejects_strategy() {
         let mut cli = base_cli();
@@ -176,4 +1513,405 @@ mod tests {
                 .contains("--only-uncommitted cannot be combined with --head")
         );
     }
+
+    #[test]
+    fn log_command_rejects_comparison_options() {
+        let mut cli = base_cli();
+        cli.command = Some(Command::Log {
+            revision: DEFAULT_HEAD_REF.to_string(),
+            max_count: DEFAULT_LOG_MAX_COUNT,
+        });
+        cli.only_uncommitted = true;
+
+        let error = CliOptions::try_from(cli).expect_err("comparison options should be rejected");
+        assert!(
+            error
+                .to_string()
+                .contains("deff log does not accept comparison options")
+        );
+    }
+
+    #[test]
+    fn ignore_matching_lines_rejects_an_empty_pattern() {
+        let mut cli = base_cli();
+        cli.ignore_matching_lines = Some(String::new());
+
+        let error = CliOptions::try_from(cli).expect_err("empty pattern should be rejected");
+        assert!(error.to_string().contains("cannot be empty"));
+    }
+
+    #[test]
+    fn hex_dump_defaults_to_four_kilobytes() {
+        let cli = Cli::try_parse_from(["deff", "--hex-dump"]).expect("hex dump flag should parse");
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+        assert_eq!(options.diff_options().hex_dump_bytes, Some(4096));
+
+        let cli =
+            Cli::try_parse_from(["deff", "--hex-dump", "64"]).expect("hex dump size should parse");
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+        assert_eq!(options.hex_dump_bytes, Some(64 * 1024));
+
+        let cli =
+            Cli::try_parse_from(["deff", "--hex-dump=0"]).expect("hex dump size should parse");
+        assert!(CliOptions::try_from(cli).is_err());
+    }
+
+    #[test]
+    fn author_rejects_uncommitted_modes() {
+        let mut cli = base_cli();
+        cli.author = Some("alice".to_string());
+        cli.include_uncommitted = true;
+
+        let error = CliOptions::try_from(cli).expect_err("uncommitted mode should be rejected");
+        assert!(error.to_string().contains("--author only applies"));
+    }
+
+    #[test]
+    fn output_format_requires_output_path() {
+        let mut cli = base_cli();
+        cli.output_format = OutputFormat::Ansi;
+
+        let error = CliOptions::try_from(cli).expect_err("format without output should fail");
+        assert!(error.to_string().contains("require --output <path>"));
+    }
+
+    #[test]
+    fn export_accepts_comparison_options_after_subcommand() {
+        let cli = Cli::try_parse_from([
+            "deff", "export", "--format", "markdown", "--base", "main", "--output", "r.md",
+        ])
+        .expect("export arguments should parse");
+
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+
+        assert_eq!(options.base_ref.as_deref(), Some("main"));
+        assert_eq!(
+            options.command,
+            CliCommand::Export {
+                format: ExportFormat::Markdown,
+                output_path: Some(PathBuf::from("r.md")),
+            }
+        );
+    }
+
+    #[test]
+    fn status_parses_json_flag_and_rejects_output() {
+        let cli = Cli::try_parse_from(["deff", "status", "--json", "--base", "main"])
+            .expect("status arguments should parse");
+
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+
+        assert_eq!(options.command, CliCommand::Status { json: true });
+        assert_eq!(options.base_ref.as_deref(), Some("main"));
+
+        let cli = Cli::try_parse_from(["deff", "--output", "r.txt", "status"])
+            .expect("status arguments should parse");
+        assert!(CliOptions::try_from(cli).is_err());
+    }
+
+    #[test]
+    fn hook_parses_pre_push_and_rejects_comparison_options() {
+        let cli = Cli::try_parse_from(["deff", "hook", "pre-push", "--warn"])
+            .expect("hook arguments should parse");
+
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+
+        assert_eq!(
+            options.command,
+            CliCommand::Hook {
+                hook: GitHook::PrePush,
+                warn: true,
+                install: false,
+            }
+        );
+        assert_eq!(options.strategy_id, StrategyId::UpstreamAhead);
+
+        let cli = Cli::try_parse_from(["deff", "hook", "pre-push", "--base", "main"])
+            .expect("hook arguments should parse");
+        assert!(CliOptions::try_from(cli).is_err());
+    }
+
+    #[test]
+    fn patches_accept_a_base_but_no_other_comparison_options() {
+        let cli = Cli::try_parse_from(["deff", "--patches", "series.mbox", "--base", "v1.0"])
+            .expect("patches arguments should parse");
+
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+
+        assert_eq!(
+            options.command,
+            CliCommand::Patches {
+                path: PathBuf::from("series.mbox"),
+            }
+        );
+        assert_eq!(options.base_ref.as_deref(), Some("v1.0"));
+
+        let cli = Cli::try_parse_from(["deff", "--patches", "outgoing", "--only-uncommitted"])
+            .expect("patches arguments should parse");
+        assert!(CliOptions::try_from(cli).is_err());
+    }
+
+    #[test]
+    fn mergetool_takes_four_paths_and_no_comparison_options() {
+        let cli = Cli::try_parse_from(["deff", "--mergetool", "l", "b", "r", "m"])
+            .expect("mergetool arguments should parse");
+        assert_eq!(
+            CliOptions::try_from(cli)
+                .expect("cli options should parse")
+                .command,
+            CliCommand::Mergetool(MergetoolPaths {
+                local: PathBuf::from("l"),
+                base: PathBuf::from("b"),
+                remote: PathBuf::from("r"),
+                merged: PathBuf::from("m"),
+            })
+        );
+
+        assert!(Cli::try_parse_from(["deff", "--mergetool", "l", "b", "r"]).is_err());
+        let cli = Cli::try_parse_from(["deff", "--mergetool", "l", "b", "r", "m", "--base", "x"])
+            .expect("mergetool arguments should parse");
+        assert!(CliOptions::try_from(cli).is_err());
+    }
+
+    #[test]
+    fn files_takes_two_paths_keys_and_no_comparison_options() {
+        let cli = Cli::try_parse_from(["deff", "--files", "old.rs", "new.rs"])
+            .expect("files arguments should parse");
+        assert_eq!(
+            CliOptions::try_from(cli)
+                .expect("cli options should parse")
+                .command,
+            CliCommand::Difftool(DifftoolPaths {
+                left: PathBuf::from("old.rs"),
+                right: PathBuf::from("new.rs"),
+            })
+        );
+
+        assert!(Cli::try_parse_from(["deff", "--files", "old.rs"]).is_err());
+        let cli = Cli::try_parse_from(["deff", "--files", "a", "b", "--strategy", "range"])
+            .expect("files arguments should parse");
+        assert!(CliOptions::try_from(cli).is_err());
+
+        let cli = Cli::try_parse_from(["deff", "--files", "a", "b", "--keys", "j q"])
+            .expect("files arguments should parse");
+        assert_eq!(
+            CliOptions::try_from(cli)
+                .expect("--keys applies to --files")
+                .key_script,
+            Some(KeyScriptSource::Inline("j q".to_string()))
+        );
+    }
+
+    #[test]
+    fn stdin_reviews_a_patch_and_takes_no_comparison_options() {
+        let cli = Cli::try_parse_from(["deff", "--stdin", "--keys", "j q"])
+            .expect("stdin arguments should parse");
+        assert_eq!(
+            CliOptions::try_from(cli)
+                .expect("cli options should parse")
+                .command,
+            CliCommand::StdinPatch
+        );
+
+        let cli = Cli::try_parse_from(["deff", "--stdin", "--base", "main"])
+            .expect("stdin arguments should parse");
+        assert!(CliOptions::try_from(cli).is_err());
+        assert!(Cli::try_parse_from(["deff", "--stdin", "--files", "a", "b"]).is_err());
+    }
+
+    #[test]
+    fn show_compares_one_path_between_two_refs() {
+        let cli = Cli::try_parse_from(["deff", "show", "v1.0", "v1.1", "--", "src/lib.rs"])
+            .expect("show arguments should parse");
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+        assert_eq!(
+            options.command,
+            CliCommand::Show {
+                path: PathBuf::from("src/lib.rs"),
+            }
+        );
+        assert_eq!(options.strategy_id, StrategyId::Range);
+        assert_eq!(
+            (options.base_ref.as_deref(), options.head_ref.as_str()),
+            (Some("v1.0"), "v1.1")
+        );
+
+        assert!(Cli::try_parse_from(["deff", "show", "v1.0", "v1.1", "src/lib.rs"]).is_err());
+        let cli = Cli::try_parse_from(["deff", "show", "a", "b", "--base", "c", "--", "x"])
+            .expect("show arguments should parse");
+        assert!(CliOptions::try_from(cli).is_err());
+    }
+
+    #[test]
+    fn tabs_parse_ranges_and_reject_other_comparisons() {
+        let cli = Cli::try_parse_from(["deff", "--tab", "main..feature", "--tab", "v1.0"])
+            .expect("tab arguments should parse");
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+        assert_eq!(
+            options.tabs,
+            vec![
+                TabRange {
+                    base_ref: "main".to_string(),
+                    head_ref: "feature".to_string(),
+                    summary: None,
+                },
+                TabRange {
+                    base_ref: "v1.0".to_string(),
+                    head_ref: "HEAD".to_string(),
+                    summary: None,
+                },
+            ]
+        );
+
+        for args in [
+            &["deff", "--tab", "main...feature"][..],
+            &["deff", "--tab", "..feature"],
+            &["deff", "--tab", "main..", "--base", "v1.0"],
+            &["deff", "--tab", "main..", "status"],
+        ] {
+            let cli = Cli::try_parse_from(args).expect("tab arguments should parse");
+            assert!(CliOptions::try_from(cli).is_err(), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn goto_targets_split_an_optional_line() {
+        assert_eq!(
+            parse_goto_target("src/lib.rs:120").expect("goto should parse"),
+            GotoTarget {
+                path: PathBuf::from("src/lib.rs"),
+                line: Some(120),
+            }
+        );
+        assert_eq!(
+            parse_goto_target("notes:draft.md").expect("goto should parse"),
+            GotoTarget {
+                path: PathBuf::from("notes:draft.md"),
+                line: None,
+            }
+        );
+        assert!(parse_goto_target("src/lib.rs:0").is_err());
+        assert!(parse_goto_target(":12").is_err());
+    }
+
+    #[test]
+    fn amend_compares_the_parent_with_the_working_tree() {
+        let mut cli = base_cli();
+        cli.amend = true;
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+        assert_eq!(options.strategy_id, StrategyId::Amend);
+        assert_eq!(options.base_ref, None);
+
+        let mut cli = base_cli();
+        cli.amend = true;
+        cli.base = Some("main".to_string());
+        assert!(CliOptions::try_from(cli).is_err());
+    }
+
+    #[test]
+    fn merge_parents_only_accepts_head() {
+        let mut cli = base_cli();
+        cli.merge_parents = true;
+        cli.head = "v2.0".to_string();
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+        assert!(options.merge_parents);
+        assert_eq!(options.head_ref, "v2.0");
+
+        let mut cli = base_cli();
+        cli.merge_parents = true;
+        cli.base = Some("main".to_string());
+        assert!(CliOptions::try_from(cli).is_err());
+    }
+
+    #[test]
+    fn per_commit_splits_a_committed_range_only() {
+        let cli = Cli::try_parse_from(["deff", "--per-commit", "--base", "main"])
+            .expect("per-commit arguments should parse");
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+        assert!(options.per_commit);
+        assert_eq!(options.base_ref.as_deref(), Some("main"));
+
+        for args in [
+            &["deff", "--per-commit", "--include-uncommitted"][..],
+            &["deff", "--per-commit", "--merge-parents"],
+            &["deff", "--per-commit", "--tab", "main.."],
+            &["deff", "--per-commit", "--output", "review.txt"],
+        ] {
+            let cli = Cli::try_parse_from(args).expect("per-commit arguments should parse");
+            assert!(CliOptions::try_from(cli).is_err(), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn preview_compares_the_working_tree_with_head_or_base() {
+        let cli = Cli::try_parse_from(["deff", "preview", "src/lib.rs", "--unified"])
+            .expect("preview arguments should parse");
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+        assert_eq!(
+            options.command,
+            CliCommand::Preview {
+                path: PathBuf::from("src/lib.rs"),
+                unified: true,
+                width: None,
+                format: OutputFormat::Ansi,
+            }
+        );
+        assert!(options.only_uncommitted);
+
+        let cli = Cli::try_parse_from(["deff", "preview", "a.rs", "--base", "main"])
+            .expect("preview arguments should parse");
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+        assert_eq!(options.strategy_id, StrategyId::Range);
+        assert!(options.include_uncommitted && !options.only_uncommitted);
+
+        let cli = Cli::try_parse_from(["deff", "preview", "a.rs", "--width", "5"])
+            .expect("preview arguments should parse");
+        assert!(CliOptions::try_from(cli).is_err());
+    }
+
+    #[test]
+    fn height_accepts_rows_or_a_percentage() {
+        let mut cli = base_cli();
+        cli.height = Some("40%".to_string());
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+        assert_eq!(options.inline_height, Some(InlineHeight::Percent(40)));
+
+        let mut cli = base_cli();
+        cli.height = Some("15".to_string());
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+        assert_eq!(options.inline_height, Some(InlineHeight::Rows(15)));
+
+        for invalid in ["0", "150%", "half"] {
+            let mut cli = base_cli();
+            cli.height = Some(invalid.to_string());
+            assert!(
+                CliOptions::try_from(cli).is_err(),
+                "{invalid} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn keys_parse_screen_size_and_reject_other_commands() {
+        let mut cli = base_cli();
+        cli.keys = Some("j q".to_string());
+        cli.screen_size = "80x24".to_string();
+
+        let options = CliOptions::try_from(cli).expect("cli options should parse");
+        assert_eq!(options.screen_size, (80, 24));
+        assert_eq!(
+            options.key_script,
+            Some(KeyScriptSource::Inline("j q".to_string()))
+        );
+
+        let mut cli = base_cli();
+        cli.keys = Some("q".to_string());
+        cli.screen_size = "wide".to_string();
+        assert!(CliOptions::try_from(cli).is_err());
+
+        let mut cli = base_cli();
+        cli.keys = Some("q".to_string());
+        cli.output = Some(PathBuf::from("report.txt"));
+        assert!(CliOptions::try_from(cli).is_err());
+    }
 }
@@ -0,0 +1,52 @@
+use std::io::{self, IsTerminal, Write};
+
+use anyhow::{Context, Result, bail};
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(input: &[u8]) -> String {
+    let mut output = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for position in 0..4 {
+            if position <= chunk.len() {
+                let index = (group >> (18 - 6 * position)) & 0x3f;
+                output.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+/// Copies through the terminal with an OSC 52 escape, which also works over SSH and
+/// inside tmux (with `set-clipboard on`).
+pub(crate) fn copy_to_clipboard(text: &str) -> Result<()> {
+    let mut stdout = io::stdout();
+    if !stdout.is_terminal() {
+        bail!("stdout is not a terminal");
+    }
+    write!(stdout, "\x1b]52;c;{}\x07", encode_base64(text.as_bytes()))
+        .and_then(|()| stdout.flush())
+        .context("failed to write clipboard escape")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::encode_base64;
+
+    #[test]
+    fn base64_pads_partial_groups() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"f"), "Zg==");
+        assert_eq!(encode_base64(b"fo"), "Zm8=");
+        assert_eq!(encode_base64(b"foobar"), "Zm9vYmFy");
+    }
+}
@@ -0,0 +1,206 @@
+use std::{fs, path::Path};
+
+use regex::Regex;
+
+use crate::model::{DiffFileDescriptor, DiffFileView};
+
+const CODEOWNERS_LOCATIONS: [&str; 4] = [
+    ".github/CODEOWNERS",
+    ".gitlab/CODEOWNERS",
+    "docs/CODEOWNERS",
+    "CODEOWNERS",
+];
+
+#[derive(Debug)]
+struct CodeOwnersRule {
+    matcher: Regex,
+    owners: Vec<String>,
+}
+
+/// Ownership rules from the first CODEOWNERS file found; the last matching rule wins.
+#[derive(Debug, Default)]
+pub(crate) struct CodeOwners {
+    rules: Vec<CodeOwnersRule>,
+}
+
+fn glob_to_regex_source(glob: &str) -> String {
+    let mut source = String::new();
+    let mut chars = glob.chars().peekable();
+
+    while let Some(character) = chars.next() {
+        match character {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    source.push_str("(?:.*/)?");
+                } else {
+                    source.push_str(".*");
+                }
+            }
+            '*' => source.push_str("[^/]*"),
+            '?' => source.push_str("[^/]"),
+            '[' => {
+                source.push('[');
+                for class_character in chars.by_ref() {
+                    if class_character == ']' {
+                        break;
+                    }
+                    if class_character == '\\' {
+                        source.push('\\');
+                    }
+                    source.push(class_character);
+                }
+                source.push(']');
+            }
+            _ => source.push_str(&regex::escape(&character.to_string())),
+        }
+    }
+
+    source
+}
+
+/// Translates a gitignore-style pattern (as in CODEOWNERS) into an anchored regex that
+/// also matches everything below a matching directory. A wildcard in the last segment
+/// matches only at that level, so `docs/*` leaves `docs/a/b.md` out.
+pub(crate) fn compile_pattern(pattern: &str) -> Option<Regex> {
+    let directory_only = pattern.ends_with('/');
+    let trimmed = pattern.trim_end_matches('/');
+    let anchored = trimmed.starts_with('/') || trimmed.contains('/');
+    let trimmed = trimmed.trim_start_matches('/');
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let prefix = if anchored { "" } else { "(?:.*/)?" };
+    let last_segment = trimmed.rsplit('/').next().unwrap_or(trimmed);
+    let suffix = if directory_only {
+        "/.*"
+    } else if last_segment.contains(['*', '?', '[']) {
+        ""
+    } else {
+        "(?:/.*)?"
+    };
+    Regex::new(&format!(
+        "^{prefix}{}{suffix}$",
+        glob_to_regex_source(trimmed)
+    ))
+    .ok()
+}
+
+impl CodeOwners {
+    pub(crate) fn load(repo_root: &Path) -> Self {
+        CODEOWNERS_LOCATIONS
+            .iter()
+            .find_map(|location| fs::read_to_string(repo_root.join(location)).ok())
+            .map(|content| Self::parse(&content))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn parse(content: &str) -> Self {
+        let mut rules = Vec::new();
+
+        for raw_line in content.lines() {
+            let line = raw_line.trim();
+            // GitLab section headers look like `[Section]` or `^[Optional Section] @owner`.
+            if line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with('[')
+                || line.starts_with("^[")
+            {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let Some(pattern) = tokens.next() else {
+                continue;
+            };
+            let owners = tokens
+                .take_while(|token| !token.starts_with('#'))
+                .map(str::to_string)
+                .collect();
+            if let Some(matcher) = compile_pattern(pattern) {
+                rules.push(CodeOwnersRule { matcher, owners });
+            }
+        }
+
+        Self { rules }
+    }
+
+    pub(crate) fn owners_for(&self, path: &str) -> Vec<String> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matcher.is_match(path))
+            .map(|rule| rule.owners.clone())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn owners_for_descriptor(&self, descriptor: &DiffFileDescriptor) -> Vec<String> {
+        descriptor
+            .head_path
+            .as_deref()
+            .or(descriptor.base_path.as_deref())
+            .map(|path| self.owners_for(path))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn assign_owners(&self, files: &mut [DiffFileView]) {
+        for file in files {
+            file.owners = self.owners_for_descriptor(&file.descriptor);
+        }
+    }
+}
+
+/// Compares owners ignoring case and an optional leading `@`.
+pub(crate) fn is_owned_by(owners: &[String], owner: &str) -> bool {
+    let wanted = owner.trim_start_matches('@');
+    owners.iter().any(|candidate| {
+        candidate
+            .trim_start_matches('@')
+            .eq_ignore_ascii_case(wanted)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CodeOwners, is_owned_by};
+
+    #[test]
+    fn last_matching_rule_wins() {
+        let owners = CodeOwners::parse(
+            "* @org/everyone\n\
+             *.rs @org/rust # inline comment\n\
+             /docs/ @org/docs\n\
+             src/legacy/** @alice\n",
+        );
+
+        assert_eq!(owners.owners_for("README.md"), vec!["@org/everyone"]);
+        assert_eq!(owners.owners_for("src/lib.rs"), vec!["@org/rust"]);
+        assert_eq!(owners.owners_for("docs/guide/intro.md"), vec!["@org/docs"]);
+        assert_eq!(owners.owners_for("src/legacy/old.rs"), vec!["@alice"]);
+    }
+
+    #[test]
+    fn unanchored_names_match_at_any_depth_and_sections_are_skipped() {
+        let owners = CodeOwners::parse("[Backend]\nbuild @ci\n^[Optional] @nobody\n");
+
+        assert_eq!(owners.owners_for("tools/build/script.sh"), vec!["@ci"]);
+        assert!(owners.owners_for("src/builder.rs").is_empty());
+        assert!(is_owned_by(&owners.owners_for("build"), "CI"));
+    }
+
+    #[test]
+    fn a_trailing_wildcard_covers_direct_children_only() {
+        let owners = CodeOwners::parse(
+            "docs/* @org/docs
+docs/** @org/writers
+apps/* @org/apps
+",
+        );
+
+        assert_eq!(owners.owners_for("docs/a/b.md"), vec!["@org/writers"]);
+        assert!(owners.owners_for("apps/web/index.ts").is_empty());
+        assert_eq!(owners.owners_for("apps/README.md"), vec!["@org/apps"]);
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+
+use crate::model::{DiffFileView, PaneSide};
+
+/// Line coverage parsed from an lcov tracefile, keyed by repository-relative path.
+#[derive(Debug, Default)]
+pub(crate) struct CoverageReport {
+    uncovered_by_path: HashMap<String, HashSet<usize>>,
+}
+
+fn normalize_source_path(raw_path: &str, repo_root: &Path) -> String {
+    let path = Path::new(raw_path);
+    let relative = path.strip_prefix(repo_root).unwrap_or(path);
+    let normalized = relative.to_string_lossy().replace('\\', "/");
+    normalized.trim_start_matches("./").to_string()
+}
+
+impl CoverageReport {
+    pub(crate) fn load(lcov_path: &Path, repo_root: &Path) -> Result<Self> {
+        let content = fs::read_to_string(lcov_path)
+            .with_context(|| format!("failed to read coverage file {}", lcov_path.display()))?;
+        Ok(Self::parse(&content, repo_root))
+    }
+
+    pub(crate) fn parse(content: &str, repo_root: &Path) -> Self {
+        let mut uncovered_by_path: HashMap<String, HashSet<usize>> = HashMap::new();
+        let mut current_path: Option<String> = None;
+
+        for line in content.lines() {
+            let line = line.trim();
+            if let Some(source_path) = line.strip_prefix("SF:") {
+                current_path = Some(normalize_source_path(source_path, repo_root));
+            } else if line == "end_of_record" {
+                current_path = None;
+            } else if let (Some(path), Some(entry)) = (&current_path, line.strip_prefix("DA:")) {
+                let mut fields = entry.split(',');
+                let line_number = fields.next().and_then(|value| value.parse::<usize>().ok());
+                let hits = fields.next().and_then(|value| value.parse::<u64>().ok());
+                if let (Some(line_number), Some(0)) = (line_number, hits)
+                    && line_number > 0
+                {
+                    uncovered_by_path
+                        .entry(path.clone())
+                        .or_default()
+                        .insert(line_number - 1);
+                }
+            }
+        }
+
+        Self { uncovered_by_path }
+    }
+
+    /// Marks added head-side lines that the report lists as executable but never hit.
+    pub(crate) fn assign_uncovered_lines(&self, files: &mut [DiffFileView]) {
+        for file in files {
+            let Some(uncovered) = file
+                .descriptor
+                .head_path
+                .as_deref()
+                .and_then(|path| self.uncovered_by_path.get(path))
+            else {
+                continue;
+            };
+            file.right_uncovered_line_indexes = file
+                .right_added_line_indexes
+                .iter()
+                .copied()
+                .filter(|row| {
+                    file.source_line(PaneSide::Right, *row)
+                        .is_some_and(|line| uncovered.contains(&line))
+                })
+                .collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::CoverageReport;
+
+    #[test]
+    fn parse_collects_zero_hit_lines_relative_to_repo_root() {
+        let report = CoverageReport::parse(
+            "TN:\n\
+             SF:/work/repo/src/lib.rs\n\
+             DA:1,4\n\
+             DA:2,0\n\
+             DA:5,0\n\
+             end_of_record\n\
+             SF:./src/main.rs\n\
+             DA:3,0\n\
+             end_of_record\n",
+            Path::new("/work/repo"),
+        );
+
+        let lib_lines = &report.uncovered_by_path["src/lib.rs"];
+        assert!(lib_lines.contains(&1) && lib_lines.contains(&4));
+        assert!(!lib_lines.contains(&0));
+        assert!(report.uncovered_by_path["src/main.rs"].contains(&2));
+    }
+}
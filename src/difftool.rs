@@ -0,0 +1,162 @@
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, bail};
+
+use crate::{
+    git::run_git_text,
+    model::{DiffFileDescriptor, FileContentSource, ResolvedComparison, StrategyId},
+};
+
+/// Git passes this for the missing side of an added or deleted file.
+const NULL_DEVICE: &str = "/dev/null";
+/// Stands in for the blob hash of a missing side.
+const MISSING_HASH: &str = "0000000000000000000000000000000000000000";
+
+/// The two paths of `deff --files`, such as `git difftool`'s `$LOCAL $REMOTE`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct DifftoolPaths {
+    pub(crate) left: PathBuf,
+    pub(crate) right: PathBuf,
+}
+
+fn is_missing(path: &Path) -> bool {
+    path == Path::new(NULL_DEVICE) || !path.exists()
+}
+
+fn side(path: &Path) -> Option<&Path> {
+    (!is_missing(path)).then_some(path)
+}
+
+impl DifftoolPaths {
+    /// The existing sides, `None` for `/dev/null` or a path that does not exist.
+    pub(crate) fn files(&self) -> Result<(Option<&Path>, Option<&Path>)> {
+        for path in [&self.left, &self.right] {
+            if path.is_dir() {
+                bail!(
+                    "--files compares two files, and {} is a directory",
+                    path.display()
+                );
+            }
+        }
+        match (side(&self.left), side(&self.right)) {
+            (None, None) => bail!(
+                "--files: neither {} nor {} exists",
+                self.left.display(),
+                self.right.display()
+            ),
+            sides => Ok(sides),
+        }
+    }
+
+    /// Named after the right path, which `git difftool` points at the file being
+    /// changed while the left is a temporary copy.
+    pub(crate) fn descriptor(&self) -> DiffFileDescriptor {
+        let (left_missing, right_missing) = (is_missing(&self.left), is_missing(&self.right));
+        let raw_status = match (left_missing, right_missing) {
+            (true, _) => "A",
+            (_, true) => "D",
+            _ => "M",
+        };
+        let source = |missing: bool| {
+            if missing {
+                FileContentSource::Missing
+            } else {
+                FileContentSource::Commit
+            }
+        };
+        let named = if right_missing {
+            &self.left
+        } else {
+            &self.right
+        };
+        DiffFileDescriptor {
+            raw_status: raw_status.to_string(),
+            display_path: named.display().to_string(),
+            base_path: (!left_missing).then(|| self.left.display().to_string()),
+            head_path: (!right_missing).then(|| self.right.display().to_string()),
+            base_source: source(left_missing),
+            head_source: source(right_missing),
+            base_mode: None,
+            head_mode: None,
+        }
+    }
+
+    /// Keyed by the blob hashes of both files, so review marks follow the contents.
+    pub(crate) fn comparison(&self, repo_root: &Path) -> Result<ResolvedComparison> {
+        let hash = |path: &Path| -> Result<String> {
+            if is_missing(path) {
+                return Ok(MISSING_HASH.to_string());
+            }
+            Ok(run_git_text(
+                [
+                    OsStr::new("hash-object"),
+                    OsStr::new("--"),
+                    path.as_os_str(),
+                ],
+                repo_root,
+            )?
+            .trim()
+            .to_string())
+        };
+        Ok(ResolvedComparison {
+            strategy_id: StrategyId::Range,
+            base_ref: self.left.display().to_string(),
+            head_ref: self.right.display().to_string(),
+            base_commit: hash(&self.left)?,
+            head_commit: hash(&self.right)?,
+            summary: format!("files {}", self.descriptor().display_path),
+            details: vec![format!(
+                "files: {} .. {}",
+                self.left.display(),
+                self.right.display()
+            )],
+            ahead_count: None,
+            includes_uncommitted: false,
+            range_commits: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::DifftoolPaths;
+    use crate::model::FileContentSource;
+
+    #[test]
+    fn a_null_device_side_makes_the_file_added_or_deleted() {
+        let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        let paths = DifftoolPaths {
+            left: PathBuf::from("/dev/null"),
+            right: manifest.clone(),
+        };
+        let added = paths.descriptor();
+        assert_eq!(added.raw_status, "A");
+        assert_eq!(added.base_source, FileContentSource::Missing);
+        assert_eq!(added.display_path, manifest.display().to_string());
+        assert!(paths.files().is_ok());
+
+        let deleted = DifftoolPaths {
+            left: manifest.clone(),
+            right: PathBuf::from("/dev/null"),
+        }
+        .descriptor();
+        assert_eq!(deleted.raw_status, "D");
+        assert_eq!(deleted.head_path, None);
+
+        let neither = DifftoolPaths {
+            left: PathBuf::from("/dev/null"),
+            right: PathBuf::from("/nonexistent/deff"),
+        };
+        assert!(neither.files().is_err());
+        let directory = DifftoolPaths {
+            left: manifest,
+            right: PathBuf::from(env!("CARGO_MANIFEST_DIR")),
+        };
+        assert!(directory.files().is_err());
+    }
+}
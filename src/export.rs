@@ -0,0 +1,112 @@
+use crate::{
+    model::{DiffFileView, ResolvedComparison},
+    patch::build_unified_diff_lines,
+};
+
+/// Picks a backtick fence longer than any backtick run inside the block.
+pub(crate) fn code_fence(lines: &[String]) -> String {
+    let longest_run = lines
+        .iter()
+        .flat_map(|line| line.split(|character| character != '`'))
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    "`".repeat((longest_run + 1).max(3))
+}
+
+/// Review record with a reviewed checklist, per-file diffstats, and fenced diffs.
+pub(crate) fn render_markdown_report(
+    files: &[DiffFileView],
+    comparison: &ResolvedComparison,
+    reviewed_flags: &[bool],
+    context_lines: usize,
+) -> String {
+    let reviewed_count = reviewed_flags.iter().filter(|reviewed| **reviewed).count();
+    let total_added: usize = files
+        .iter()
+        .map(|file| file.right_added_line_indexes.len())
+        .sum();
+    let total_deleted: usize = files
+        .iter()
+        .map(|file| file.left_deleted_line_indexes.len())
+        .sum();
+
+    let mut lines = vec![
+        format!("# deff review: `{}`", comparison.summary),
+        String::new(),
+        format!("- strategy: {}", comparison.strategy_id),
+    ];
+    lines.extend(
+        comparison
+            .details
+            .iter()
+            .map(|detail| format!("- {detail}")),
+    );
+    lines.push(format!(
+        "- {} files changed, +{total_added} -{total_deleted}",
+        files.len()
+    ));
+    lines.push(format!("- reviewed: {reviewed_count}/{}", files.len()));
+    lines.push(String::new());
+    lines.push("## Files".to_string());
+    lines.push(String::new());
+
+    for (index, file) in files.iter().enumerate() {
+        let reviewed = reviewed_flags.get(index).copied().unwrap_or(false);
+        lines.push(format!(
+            "- [{}] `{}` ({}, +{} -{})",
+            if reviewed { "x" } else { " " },
+            file.descriptor.display_path,
+            file.descriptor.raw_status,
+            file.right_added_line_indexes.len(),
+            file.left_deleted_line_indexes.len()
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push("## Diffs".to_string());
+    for file in files {
+        let diff_lines = build_unified_diff_lines(file, context_lines);
+        lines.push(String::new());
+        lines.push(format!("### `{}`", file.descriptor.display_path));
+        lines.push(String::new());
+        if diff_lines.is_empty() {
+            lines.push("_No line changes (mode, rename, or binary change)._".to_string());
+            continue;
+        }
+        let fence = code_fence(&diff_lines);
+        lines.push(format!("{fence}diff"));
+        lines.extend(diff_lines);
+        lines.push(fence);
+    }
+
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{code_fence, render_markdown_report};
+    use crate::model::{DiffFileView, ResolvedComparison};
+
+    #[test]
+    fn markdown_report_lists_checklist_and_fenced_diffs() {
+        let comparison = ResolvedComparison::for_test();
+        let files = vec![
+            DiffFileView::for_test_change("a.rs"),
+            DiffFileView::for_test_change("b.rs"),
+        ];
+
+        let report = render_markdown_report(&files, &comparison, &[true, false], 3);
+
+        assert!(report.contains("- reviewed: 1/2"));
+        assert!(report.contains("- [x] `a.rs` (M, +1 -1)"));
+        assert!(report.contains("- [ ] `b.rs` (M, +1 -1)"));
+        assert!(report.contains("```diff\n@@ -1,1 +1,1 @@\n-old\n+new\n```"));
+    }
+
+    #[test]
+    fn code_fence_outgrows_backticks_in_content() {
+        assert_eq!(code_fence(&["+let s = \"````\";".to_string()]), "`````");
+    }
+}
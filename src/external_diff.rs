@@ -0,0 +1,123 @@
+use std::path::{Path, PathBuf};
+
+use crate::model::{DiffFileDescriptor, FileContentSource, ResolvedComparison, StrategyId};
+
+/// Git's environment for external diff drivers: which of the changed files this call is.
+const PATH_COUNTER_ENV: &str = "GIT_DIFF_PATH_COUNTER";
+const PATH_TOTAL_ENV: &str = "GIT_DIFF_PATH_TOTAL";
+
+/// One `GIT_EXTERNAL_DIFF` call. Git runs it from the top of the working tree, once
+/// per changed file; a side that does not exist has no file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ExternalDiffArgs {
+    pub(crate) path: String,
+    /// Set for renames and copies.
+    pub(crate) new_path: Option<String>,
+    pub(crate) old_file: Option<PathBuf>,
+    pub(crate) old_hex: String,
+    pub(crate) new_file: Option<PathBuf>,
+    pub(crate) new_hex: String,
+}
+
+fn short_hex(hex: &str) -> &str {
+    &hex[..hex.len().min(7)]
+}
+
+impl ExternalDiffArgs {
+    fn head_path(&self) -> &str {
+        self.new_path.as_deref().unwrap_or(&self.path)
+    }
+
+    /// Git hands over the working tree file itself (rather than a temporary copy) when
+    /// the new side is the working tree, which keeps staging and discarding hunks usable.
+    pub(crate) fn descriptor(&self) -> DiffFileDescriptor {
+        let head_path = self.head_path().to_string();
+        let head_source = match &self.new_file {
+            None => FileContentSource::Missing,
+            Some(file) if file == Path::new(&head_path) => FileContentSource::WorkingTree,
+            Some(_) => FileContentSource::Commit,
+        };
+        let (raw_status, base_source) = match (&self.old_file, &self.new_file) {
+            (None, _) => ("A", FileContentSource::Missing),
+            (_, None) => ("D", FileContentSource::Commit),
+            _ if self.new_path.is_some() => ("R", FileContentSource::Commit),
+            _ => ("M", FileContentSource::Commit),
+        };
+        DiffFileDescriptor {
+            raw_status: raw_status.to_string(),
+            display_path: match &self.new_path {
+                Some(new_path) => format!("{} -> {new_path}", self.path),
+                None => self.path.clone(),
+            },
+            base_path: self.old_file.as_ref().map(|_| self.path.clone()),
+            head_path: self.new_file.as_ref().map(|_| head_path),
+            base_source,
+            head_source,
+            base_mode: None,
+            head_mode: None,
+        }
+    }
+
+    /// Scoped to the path, so review marks on a file carry over between `git diff` runs.
+    pub(crate) fn comparison(&self) -> ResolvedComparison {
+        let position = match (
+            std::env::var(PATH_COUNTER_ENV),
+            std::env::var(PATH_TOTAL_ENV),
+        ) {
+            (Ok(counter), Ok(total)) => format!("  ({counter}/{total})"),
+            _ => String::new(),
+        };
+        ResolvedComparison {
+            strategy_id: StrategyId::Range,
+            base_ref: format!("a/{}", self.path),
+            head_ref: format!("b/{}", self.head_path()),
+            base_commit: self.old_hex.clone(),
+            head_commit: self.new_hex.clone(),
+            summary: format!("{}{position}", self.descriptor().display_path),
+            details: vec![
+                "git external diff".to_string(),
+                format!(
+                    "blobs: {}..{}",
+                    short_hex(&self.old_hex),
+                    short_hex(&self.new_hex)
+                ),
+            ],
+            ahead_count: None,
+            includes_uncommitted: false,
+            range_commits: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::ExternalDiffArgs;
+    use crate::model::FileContentSource;
+
+    #[test]
+    fn working_tree_new_side_keeps_its_source() {
+        let args = ExternalDiffArgs {
+            path: "src/a.rs".to_string(),
+            new_path: None,
+            old_file: Some(PathBuf::from("/tmp/git-blob-abc/a.rs")),
+            old_hex: "1".repeat(40),
+            new_file: Some(PathBuf::from("src/a.rs")),
+            new_hex: "0".repeat(40),
+        };
+        let descriptor = args.descriptor();
+        assert_eq!(descriptor.raw_status, "M");
+        assert_eq!(descriptor.head_source, FileContentSource::WorkingTree);
+        assert_eq!(descriptor.base_source, FileContentSource::Commit);
+
+        let added = ExternalDiffArgs {
+            old_file: None,
+            ..args
+        }
+        .descriptor();
+        assert_eq!(added.raw_status, "A");
+        assert_eq!(added.base_path, None);
+        assert_eq!(added.base_source, FileContentSource::Missing);
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::git::run_git_text;
+
+const DEFAULT_REMOTE: &str = "origin";
+/// git config key holding a URL template for forges deff does not recognize.
+const URL_TEMPLATE_CONFIG_KEY: &str = "deff.forgeUrlTemplate";
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum ForgeKind {
+    GitHub,
+    GitLab,
+    Bitbucket,
+    /// `{host}`, `{repo}`, `{commit}`, `{path}`, `{line}`, and `{end_line}` placeholders.
+    Template(String),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum ForgeAction {
+    CopyPermalink,
+    OpenInBrowser,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ForgeRequest {
+    pub(crate) action: ForgeAction,
+    pub(crate) location: BlobLocation,
+}
+
+/// A file (and optionally one of its lines, or a range of them) at a specific commit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct BlobLocation {
+    pub(crate) commit: String,
+    pub(crate) path: String,
+    pub(crate) line: Option<usize>,
+    /// Last line of a multi-line range; `None` for a single line.
+    pub(crate) end_line: Option<usize>,
+}
+
+/// The web address of a hosted repository, derived from a git remote URL.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ForgeRemote {
+    kind: ForgeKind,
+    host: String,
+    repo_path: String,
+}
+
+impl ForgeRemote {
+    pub(crate) fn load(repo_root: &Path) -> Result<Self> {
+        let url = run_git_text(["remote", "get-url", DEFAULT_REMOTE], repo_root)
+            .with_context(|| format!("no `{DEFAULT_REMOTE}` remote"))?;
+        let url = url.trim();
+        let template = run_git_text(["config", "--get", URL_TEMPLATE_CONFIG_KEY], repo_root)
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty());
+        match Self::parse(url, template) {
+            Some(remote) => Ok(remote),
+            None => bail!(
+                "`{DEFAULT_REMOTE}` is not a GitHub, GitLab, or Bitbucket remote: {url} \
+                 (set `git config {URL_TEMPLATE_CONFIG_KEY}` for other forges)"
+            ),
+        }
+    }
+
+    /// Accepts `git@host:owner/repo.git`, `ssh://git@host/owner/repo`, and
+    /// `https://host/owner/repo.git` style URLs. A configured template wins over
+    /// host detection.
+    fn parse(url: &str, template: Option<String>) -> Option<Self> {
+        let (host, repo_path) = if let Some((_, rest)) = url.split_once("://") {
+            let rest = rest
+                .rsplit_once('@')
+                .map_or(rest, |(_, host_part)| host_part);
+            rest.split_once('/')?
+        } else {
+            let rest = url.rsplit_once('@').map_or(url, |(_, host_part)| host_part);
+            rest.split_once(':')?
+        };
+        // ssh://host:2222/owner/repo carries a port that the web UI does not use.
+        let host = host.split_once(':').map_or(host, |(name, _)| name);
+        let repo_path = repo_path.trim_matches('/');
+        let repo_path = repo_path.strip_suffix(".git").unwrap_or(repo_path);
+        if host.is_empty() || !repo_path.contains('/') {
+            return None;
+        }
+
+        let kind = if let Some(template) = template {
+            ForgeKind::Template(template)
+        } else if host.contains("github") {
+            ForgeKind::GitHub
+        } else if host.contains("gitlab") {
+            ForgeKind::GitLab
+        } else if host.contains("bitbucket") {
+            ForgeKind::Bitbucket
+        } else {
+            return None;
+        };
+        Some(Self {
+            kind,
+            host: host.to_string(),
+            repo_path: repo_path.to_string(),
+        })
+    }
+
+    pub(crate) fn blob_url(&self, location: &BlobLocation) -> String {
+        let web_url = format!("https://{}/{}", self.host, self.repo_path);
+        let (blob, anchor, range_separator) = match &self.kind {
+            ForgeKind::GitHub => ("blob", "#L", "-L"),
+            ForgeKind::GitLab => ("-/blob", "#L", "-"),
+            ForgeKind::Bitbucket => ("src", "#lines-", ":"),
+            ForgeKind::Template(template) => return self.expand_template(template, location),
+        };
+        let mut url = format!("{web_url}/{blob}/{}/{}", location.commit, location.path);
+        if let Some(line) = location.line {
+            url.push_str(&format!("{anchor}{line}"));
+            if let Some(end_line) = location.end_line {
+                url.push_str(&format!("{range_separator}{end_line}"));
+            }
+        }
+        url
+    }
+
+    /// Without a line, the `#...{line}` fragment is dropped so the whole file opens.
+    /// `{end_line}` falls back to `{line}` for single-line locations.
+    fn expand_template(&self, template: &str, location: &BlobLocation) -> String {
+        let template = match (location.line, template.find("{line}")) {
+            (None, Some(line_start)) => match template[..line_start].rfind('#') {
+                Some(fragment_start) => &template[..fragment_start],
+                None => template,
+            },
+            _ => template,
+        };
+        template
+            .replace("{host}", &self.host)
+            .replace("{repo}", &self.repo_path)
+            .replace("{commit}", &location.commit)
+            .replace("{path}", &location.path)
+            .replace(
+                "{end_line}",
+                &location
+                    .end_line
+                    .or(location.line)
+                    .map(|line| line.to_string())
+                    .unwrap_or_default(),
+            )
+            .replace(
+                "{line}",
+                &location
+                    .line
+                    .map(|line| line.to_string())
+                    .unwrap_or_default(),
+            )
+    }
+}
+
+fn browser_command(url: &str) -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", "", url]);
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg(url);
+        command
+    } else {
+        let mut command = Command::new("xdg-open");
+        command.arg(url);
+        command
+    }
+}
+
+/// Hands the URL to the platform opener without waiting for the browser.
+pub(crate) fn open_in_browser(url: &str) -> Result<()> {
+    browser_command(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+        .context("failed to launch the browser opener")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlobLocation, ForgeRemote};
+
+    fn location(line: Option<usize>) -> BlobLocation {
+        BlobLocation {
+            commit: "abc123".to_string(),
+            path: "src/main.rs".to_string(),
+            line,
+            end_line: None,
+        }
+    }
+
+    #[test]
+    fn parses_ssh_and_https_remotes() {
+        for url in [
+            "git@github.com:flamestro/deff.git",
+            "https://github.com/flamestro/deff",
+            "ssh://git@github.com:22/flamestro/deff.git",
+        ] {
+            let remote = ForgeRemote::parse(url, None).expect("remote parses");
+            assert_eq!(
+                remote.blob_url(&location(Some(7))),
+                "https://github.com/flamestro/deff/blob/abc123/src/main.rs#L7",
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn gitlab_links_use_dash_blob_and_unknown_hosts_are_rejected() {
+        let remote =
+            ForgeRemote::parse("git@gitlab.com:group/sub/project.git", None).expect("parses");
+        assert_eq!(
+            remote.blob_url(&location(None)),
+            "https://gitlab.com/group/sub/project/-/blob/abc123/src/main.rs"
+        );
+        assert_eq!(
+            remote.blob_url(&BlobLocation {
+                end_line: Some(9),
+                ..location(Some(4))
+            }),
+            "https://gitlab.com/group/sub/project/-/blob/abc123/src/main.rs#L4-9"
+        );
+        assert!(ForgeRemote::parse("/srv/git/project.git", None).is_none());
+        assert!(ForgeRemote::parse("https://example.com/owner/repo", None).is_none());
+    }
+
+    #[test]
+    fn bitbucket_and_template_remotes_build_urls() {
+        let bitbucket =
+            ForgeRemote::parse("git@bitbucket.org:team/repo.git", None).expect("parses");
+        assert_eq!(
+            bitbucket.blob_url(&location(Some(3))),
+            "https://bitbucket.org/team/repo/src/abc123/src/main.rs#lines-3"
+        );
+
+        let template = "https://{host}/{repo}/src/commit/{commit}/{path}#L{line}".to_string();
+        let gitea = ForgeRemote::parse("https://git.example.com/team/repo.git", Some(template))
+            .expect("parses");
+        assert_eq!(
+            gitea.blob_url(&location(Some(3))),
+            "https://git.example.com/team/repo/src/commit/abc123/src/main.rs#L3"
+        );
+        assert_eq!(
+            gitea.blob_url(&location(None)),
+            "https://git.example.com/team/repo/src/commit/abc123/src/main.rs"
+        );
+    }
+}
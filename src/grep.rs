@@ -0,0 +1,112 @@
+use regex::Regex;
+
+use crate::model::{DiffFileView, PaneSide};
+
+/// Which changed lines `deff grep` searches.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum GrepSides {
+    Both,
+    Added,
+    Removed,
+}
+
+/// Changed lines matching `pattern` as `path:line: content`, file by file with removed
+/// lines first. Removed lines are numbered in the base file and use its path.
+pub(crate) fn grep_changed_lines(
+    files: &[DiffFileView],
+    pattern: &Regex,
+    sides: GrepSides,
+) -> Vec<String> {
+    let mut matches = Vec::new();
+    for file in files {
+        let display_path = file.descriptor.display_path.as_str();
+        let searched = [
+            (
+                GrepSides::Removed,
+                PaneSide::Left,
+                file.descriptor.base_path.as_deref(),
+                &file.left_lines,
+                &file.left_deleted_line_indexes,
+            ),
+            (
+                GrepSides::Added,
+                PaneSide::Right,
+                file.descriptor.head_path.as_deref(),
+                &file.right_lines,
+                &file.right_added_line_indexes,
+            ),
+        ];
+        for (side, pane, path, lines, changed) in searched {
+            if sides != GrepSides::Both && sides != side {
+                continue;
+            }
+            let mut rows: Vec<usize> = changed.iter().copied().collect();
+            rows.sort_unstable();
+            for row in rows {
+                let (Some(line), Some(line_index)) = (lines.get(row), file.source_line(pane, row))
+                else {
+                    continue;
+                };
+                if pattern.is_match(line) {
+                    matches.push(format!(
+                        "{}:{}: {line}",
+                        path.unwrap_or(display_path),
+                        line_index + 1
+                    ));
+                }
+            }
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use regex::Regex;
+
+    use super::{GrepSides, grep_changed_lines};
+    use crate::model::{DiffFileDescriptor, DiffFileView};
+
+    fn create_view() -> DiffFileView {
+        let to_lines = |raw: &[&str]| raw.iter().map(|line| line.to_string()).collect();
+        let view = DiffFileView::for_test("new.rs");
+        DiffFileView {
+            descriptor: DiffFileDescriptor {
+                raw_status: "R100".to_string(),
+                display_path: "old.rs -> new.rs".to_string(),
+                base_path: Some("old.rs".to_string()),
+                ..view.descriptor.clone()
+            },
+            left_lines: to_lines(&["fn a() {}", "// TODO old", "fn b() {}"]),
+            right_lines: to_lines(&["fn a() {}", "// TODO new", "fn c() {}", "// TODO kept"]),
+            left_deleted_line_indexes: HashSet::from([1, 2]),
+            right_added_line_indexes: HashSet::from([1, 2]),
+            ..view
+        }
+    }
+
+    #[test]
+    fn grep_searches_only_changed_lines_on_the_chosen_sides() {
+        let files = vec![create_view()];
+        let pattern = Regex::new("TODO").expect("pattern should compile");
+
+        assert_eq!(
+            grep_changed_lines(&files, &pattern, GrepSides::Both),
+            vec!["old.rs:2: // TODO old", "new.rs:2: // TODO new"]
+        );
+        assert_eq!(
+            grep_changed_lines(&files, &pattern, GrepSides::Added),
+            vec!["new.rs:2: // TODO new"]
+        );
+        assert!(
+            grep_changed_lines(
+                &files,
+                &Regex::new("fn c").expect("pattern should compile"),
+                GrepSides::Removed
+            )
+            .is_empty()
+        );
+    }
+}
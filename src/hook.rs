@@ -0,0 +1,133 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    cli::GitHook,
+    git::run_git_text,
+    model::{DiffFileView, ResolvedComparison},
+    summary::pluralize,
+};
+
+/// Marks scripts written by `--install`, so reinstalling may overwrite them.
+const HOOK_SCRIPT_MARKER: &str = "# Installed by deff";
+
+pub(crate) fn hook_name(hook: GitHook) -> &'static str {
+    match hook {
+        GitHook::PrePush => "pre-push",
+    }
+}
+
+fn hook_script(hook: GitHook, warn: bool) -> String {
+    let warn_arg = if warn { " --warn" } else { "" };
+    format!(
+        "#!/bin/sh\n{HOOK_SCRIPT_MARKER}; remove this file to disable the review check.\nexec deff hook {}{warn_arg}\n",
+        hook_name(hook)
+    )
+}
+
+/// Writes the hook script into the hooks directory git uses (honoring `core.hooksPath`),
+/// refusing to replace a hook deff did not write.
+pub(crate) fn install_hook(repo_root: &Path, hook: GitHook, warn: bool) -> Result<PathBuf> {
+    let hook_path = run_git_text(
+        [
+            "rev-parse",
+            "--git-path",
+            &format!("hooks/{}", hook_name(hook)),
+        ],
+        repo_root,
+    )?;
+    let hook_path = repo_root.join(hook_path.trim());
+    if let Ok(existing) = fs::read_to_string(&hook_path)
+        && !existing.contains(HOOK_SCRIPT_MARKER)
+    {
+        bail!(
+            "{} already exists and was not installed by deff",
+            hook_path.display()
+        );
+    }
+    if let Some(parent) = hook_path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    fs::write(&hook_path, hook_script(hook, warn))
+        .with_context(|| format!("failed to write {}", hook_path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("failed to make {} executable", hook_path.display()))?;
+    }
+    Ok(hook_path)
+}
+
+/// Explains which files block the push, or `None` when everything is reviewed.
+pub(crate) fn build_pre_push_report(
+    files: &[DiffFileView],
+    comparison: &ResolvedComparison,
+    reviewed_flags: &[bool],
+    warn: bool,
+) -> Option<String> {
+    let unreviewed: Vec<&str> = files
+        .iter()
+        .zip(reviewed_flags)
+        .filter(|(_, reviewed)| !**reviewed)
+        .map(|(file, _)| file.descriptor.display_path.as_str())
+        .collect();
+    if unreviewed.is_empty() {
+        return None;
+    }
+
+    let mut lines = vec![format!(
+        "deff: {}/{} {} ahead of {} {} unreviewed:",
+        unreviewed.len(),
+        files.len(),
+        pluralize(files.len(), "file", "files"),
+        comparison.base_ref,
+        pluralize(files.len(), "is", "are")
+    )];
+    lines.extend(unreviewed.iter().map(|path| format!("  {path}")));
+    lines.push(if warn {
+        "deff: pushing anyway (--warn)".to_string()
+    } else {
+        "deff: run `deff` to review them, or `git push --no-verify` to skip this check".to_string()
+    });
+    lines.push(String::new());
+    Some(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GitHook, build_pre_push_report, hook_script};
+    use crate::model::{DiffFileView, ResolvedComparison, StrategyId};
+
+    #[test]
+    fn pre_push_report_lists_unreviewed_files_until_all_are_reviewed() {
+        let comparison = ResolvedComparison {
+            strategy_id: StrategyId::UpstreamAhead,
+            base_ref: "origin/main".to_string(),
+            summary: "origin/main..HEAD".to_string(),
+            ahead_count: Some(1),
+            ..ResolvedComparison::for_test()
+        };
+        let files = vec![
+            DiffFileView::for_test_change("a.rs"),
+            DiffFileView::for_test_change("b.rs"),
+        ];
+
+        let report = build_pre_push_report(&files, &comparison, &[true, false], false)
+            .expect("unreviewed files are reported");
+        assert!(
+            report.starts_with("deff: 1/2 files ahead of origin/main are unreviewed:\n  b.rs\n")
+        );
+        assert!(report.contains("--no-verify"));
+        assert_eq!(
+            build_pre_push_report(&files, &comparison, &[true, true], false),
+            None
+        );
+        assert!(hook_script(GitHook::PrePush, true).contains("exec deff hook pre-push --warn\n"));
+    }
+}
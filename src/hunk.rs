@@ -0,0 +1,207 @@
+use std::{
+    ffi::OsStr,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    clipboard::copy_to_clipboard,
+    export::code_fence,
+    git::{get_git_dir, run_git},
+    review::ReviewStore,
+};
+
+/// Scratch file `git apply` reads the hunk patch from, relative to the git dir.
+const HUNK_PATCH_FILE: &str = "deff/hunk.patch";
+/// Review comments are appended here as Markdown, relative to the review state dir.
+const COMMENTS_FILE: &str = "comments.md";
+
+/// What to do with a hunk picked from the Enter menu, once it leaves the UI.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum HunkOperation {
+    Copy,
+    Stage,
+    Discard,
+    Comment(String),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct HunkRequest {
+    pub(crate) operation: HunkOperation,
+    pub(crate) path: String,
+    /// One-based first line of the hunk.
+    pub(crate) line: usize,
+    pub(crate) patch: String,
+}
+
+/// Writes `contents` to `relative_path` under the git dir, creating directories.
+pub(crate) fn write_git_dir_file(
+    repo_root: &Path,
+    relative_path: &str,
+    contents: &str,
+) -> Result<PathBuf> {
+    let path = get_git_dir(repo_root)?.join(relative_path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    fs::write(&path, contents).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Puts `\r` back on the hunk's body lines, which deff shows without it, leaving the
+/// file headers alone. A line followed by `\ No newline at end of file` ends the file
+/// without any line ending, so it stays as it is.
+fn with_crlf_body(patch: &str) -> String {
+    let mut in_body = false;
+    let mut converted = String::with_capacity(patch.len());
+    let mut lines = patch.lines().peekable();
+    while let Some(line) = lines.next() {
+        in_body |= line.starts_with("@@");
+        converted.push_str(line);
+        let ends_file = lines.peek().is_some_and(|next| next.starts_with('\\'));
+        if in_body && !line.starts_with("@@") && !line.starts_with('\\') && !ends_file {
+            converted.push('\r');
+        }
+        converted.push('\n');
+    }
+    converted
+}
+
+/// Whether the index copy of `path` uses CRLF line endings. `git apply` compares against
+/// it after normalizing the working tree (`core.autocrlf`), so it decides for both
+/// staging and discarding.
+fn is_crlf_in_index(repo_root: &Path, path: &str) -> bool {
+    run_git(["show", &format!(":{path}")], repo_root)
+        .is_ok_and(|content| content.windows(2).any(|pair| pair == b"\r\n"))
+}
+
+fn apply_hunk_patch(repo_root: &Path, request: &HunkRequest, mode_arg: &str) -> Result<()> {
+    let patch = if is_crlf_in_index(repo_root, &request.path) {
+        with_crlf_body(&request.patch)
+    } else {
+        request.patch.clone()
+    };
+    let path = write_git_dir_file(repo_root, HUNK_PATCH_FILE, &patch)?;
+    run_git(
+        [OsStr::new("apply"), OsStr::new(mode_arg), path.as_os_str()],
+        repo_root,
+    )?;
+    Ok(())
+}
+
+fn format_comment(request: &HunkRequest, text: &str) -> String {
+    let patch_lines: Vec<String> = request.patch.lines().map(ToOwned::to_owned).collect();
+    let fence = code_fence(&patch_lines);
+    format!(
+        "### `{}` line {}\n\n{text}\n\n{fence}diff\n{}\n{fence}\n\n",
+        request.path,
+        request.line,
+        patch_lines.join("\n")
+    )
+}
+
+fn append_comment(state_dir: &Path, entry: &str) -> Result<PathBuf> {
+    let path = state_dir.join(COMMENTS_FILE);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(entry.as_bytes()))
+        .with_context(|| format!("failed to append to {}", path.display()))?;
+    Ok(path)
+}
+
+/// What the request would do, e.g. `stage hunk at src/lib.rs:12`.
+pub(crate) fn describe_hunk_request(request: &HunkRequest) -> String {
+    let action = match request.operation {
+        HunkOperation::Copy => "copy hunk at",
+        HunkOperation::Stage => "stage hunk at",
+        HunkOperation::Discard => "discard hunk at",
+        HunkOperation::Comment(_) => "save a comment on",
+    };
+    format!("{action} {}:{}", request.path, request.line)
+}
+
+/// Runs a hunk menu action and describes the result for the footer. Comments go to the
+/// review state dir so they travel with shared review state, and to the review note
+/// when notes are on.
+pub(crate) fn run_hunk_request(
+    repo_root: &Path,
+    review_store: &ReviewStore,
+    request: &HunkRequest,
+) -> String {
+    let location = format!("{}:{}", request.path, request.line);
+    match &request.operation {
+        HunkOperation::Copy => match copy_to_clipboard(&request.patch) {
+            Ok(()) => format!("copied hunk at {location}"),
+            Err(error) => format!("hunk not copied: {error:#}"),
+        },
+        HunkOperation::Stage => match apply_hunk_patch(repo_root, request, "--cached") {
+            Ok(()) => format!("staged hunk at {location}"),
+            Err(error) => format!("stage: {error:#}"),
+        },
+        // The loaded views are not rebuilt, so the discarded hunk stays on screen.
+        HunkOperation::Discard => match apply_hunk_patch(repo_root, request, "-R") {
+            Ok(()) => format!("discarded hunk at {location} (restart deff to refresh)"),
+            Err(error) => format!("discard: {error:#}"),
+        },
+        HunkOperation::Comment(text) => {
+            let entry = format_comment(request, text);
+            match append_comment(review_store.state_dir(), &entry)
+                .and_then(|path| review_store.record_comment(&entry).map(|()| path))
+            {
+                Ok(path) => format!("comment on {location} saved to {}", path.display()),
+                Err(error) => format!("comment: {error:#}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        HunkOperation, HunkRequest, describe_hunk_request, format_comment, with_crlf_body,
+    };
+
+    #[test]
+    fn comments_quote_the_hunk_patch() {
+        let request = HunkRequest {
+            operation: HunkOperation::Comment("why?".to_string()),
+            path: "src/lib.rs".to_string(),
+            line: 4,
+            patch: "@@ -4,1 +4,1 @@\n-a\n+b\n".to_string(),
+        };
+
+        assert_eq!(
+            format_comment(&request, "why?"),
+            "### `src/lib.rs` line 4\n\nwhy?\n\n```diff\n@@ -4,1 +4,1 @@\n-a\n+b\n```\n\n"
+        );
+        assert_eq!(
+            describe_hunk_request(&request),
+            "save a comment on src/lib.rs:4"
+        );
+    }
+
+    #[test]
+    fn crlf_bodies_keep_the_headers_and_unterminated_last_lines() {
+        let patch =
+            "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n";
+
+        assert_eq!(
+            with_crlf_body(patch),
+            "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n a\r\n-b\r\n+c\r\n"
+        );
+        assert_eq!(
+            with_crlf_body("@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+c\n"),
+            "@@ -1,2 +1,2 @@\n a\r\n-b\n\\ No newline at end of file\n+c\r\n"
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use once_cell::sync::OnceCell;
+
+use crate::keys::parse_key;
+
+/// What a key does in the review, named for the `[keys]` table of the config files.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) enum Action {
+    Quit,
+    PreviousFile,
+    NextFile,
+    NextUnreviewedFile,
+    CursorUp,
+    CursorDown,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    PreviousTab,
+    NextTab,
+    HunkMenu,
+    VisualSelect,
+    ClearSelection,
+    Search,
+    SearchBackward,
+    Pipe,
+    Commit,
+    ExpandLine,
+    ToggleView,
+    ReloadFile,
+    PickFileBase,
+    CyclePane,
+    ScrollLeft,
+    ScrollRight,
+    FirstDifference,
+    ChangesOnly,
+    CopyPermalink,
+    OpenInBrowser,
+    SelectionPatch,
+    Outline,
+    CommitSummary,
+    DiffSummary,
+    Annotations,
+    HideWhitespaceOnly,
+    FoldPrefix,
+    NextMatch,
+    PreviousMatch,
+    NextHunk,
+    PreviousHunk,
+    UndoReview,
+    RedoReview,
+    FileList,
+    MoreContext,
+    LessContext,
+    ReviewAll,
+    UnreviewAll,
+    ToggleReviewed,
+}
+
+/// Each action's config name and default keys.
+const DEFAULT_BINDINGS: &[(Action, &str, &[&str])] = &[
+    (Action::Quit, "quit", &["q", "Q"]),
+    (Action::PreviousFile, "previous-file", &["h", "left"]),
+    (Action::NextFile, "next-file", &["l", "right"]),
+    (Action::NextUnreviewedFile, "next-unreviewed-file", &["R"]),
+    (Action::CursorUp, "cursor-up", &["k", "up"]),
+    (Action::CursorDown, "cursor-down", &["j", "down"]),
+    (Action::PageUp, "page-up", &["ctrl-u", "pgup"]),
+    (Action::PageDown, "page-down", &["ctrl-d", "pgdn"]),
+    (Action::Top, "top", &["g", "home"]),
+    (Action::Bottom, "bottom", &["G", "end"]),
+    (Action::PreviousTab, "previous-tab", &["["]),
+    (Action::NextTab, "next-tab", &["]"]),
+    (Action::HunkMenu, "hunk-menu", &["enter"]),
+    (Action::VisualSelect, "visual-select", &["V"]),
+    (Action::ClearSelection, "clear-selection", &["esc"]),
+    (Action::Search, "search", &["/"]),
+    (Action::SearchBackward, "search-backward", &["?"]),
+    (Action::Pipe, "pipe", &["|"]),
+    (Action::Commit, "commit", &["m"]),
+    (Action::ExpandLine, "expand-line", &["e"]),
+    (Action::ToggleView, "toggle-view", &["U"]),
+    (Action::ReloadFile, "reload-file", &["L"]),
+    (Action::PickFileBase, "pick-file-base", &["B"]),
+    (Action::CyclePane, "cycle-pane", &["tab"]),
+    (Action::ScrollLeft, "scroll-left", &["<"]),
+    (Action::ScrollRight, "scroll-right", &[">"]),
+    (Action::FirstDifference, "first-difference", &["f"]),
+    (Action::ChangesOnly, "changes-only", &["c"]),
+    (Action::CopyPermalink, "copy-permalink", &["y"]),
+    (Action::OpenInBrowser, "open-in-browser", &["O"]),
+    (Action::SelectionPatch, "selection-patch", &["P"]),
+    (Action::Outline, "outline", &["o"]),
+    (Action::CommitSummary, "commit-summary", &["i"]),
+    (Action::DiffSummary, "diff-summary", &["D"]),
+    (Action::Annotations, "annotations", &["K"]),
+    (Action::HideWhitespaceOnly, "hide-whitespace-only", &["W"]),
+    (Action::FoldPrefix, "fold-prefix", &["z"]),
+    (Action::NextMatch, "next-match", &["n"]),
+    (Action::PreviousMatch, "previous-match", &["N"]),
+    (Action::NextHunk, "next-hunk", &["}"]),
+    (Action::PreviousHunk, "previous-hunk", &["{"]),
+    (Action::UndoReview, "undo-review", &["u"]),
+    (Action::RedoReview, "redo-review", &["ctrl-r"]),
+    (Action::FileList, "file-list", &["t"]),
+    (Action::MoreContext, "more-context", &["+"]),
+    (Action::LessContext, "less-context", &["-"]),
+    (Action::ReviewAll, "review-all", &["A"]),
+    (Action::UnreviewAll, "unreview-all", &["X"]),
+    (Action::ToggleReviewed, "toggle-reviewed", &["r"]),
+];
+
+static KEY_MAP: OnceCell<KeyMap> = OnceCell::new();
+
+impl Action {
+    fn from_name(name: &str) -> Option<Self> {
+        DEFAULT_BINDINGS
+            .iter()
+            .find(|(_, action_name, _)| *action_name == name)
+            .map(|(action, _, _)| *action)
+    }
+}
+
+/// Parses the keys bound to `name` in a `[keys]` table: one key or a list of them.
+pub(crate) fn parse_binding(name: &str, keys: &[&str]) -> Result<(Action, Vec<KeyEvent>)> {
+    let action =
+        Action::from_name(name).with_context(|| format!("keys: unknown action {name:?}"))?;
+    let keys = keys
+        .iter()
+        .map(|key| parse_key(key).with_context(|| format!("keys.{name}")))
+        .collect::<Result<_>>()?;
+    Ok((action, keys))
+}
+
+/// Terminals differ in whether Shift comes with an uppercase letter, so letters are
+/// matched by case and Shift only counts for other keys.
+fn normalize(key: KeyEvent) -> (KeyCode, KeyModifiers) {
+    match key.code {
+        KeyCode::Char(character) => {
+            let character = if key.modifiers.contains(KeyModifiers::SHIFT) {
+                character.to_ascii_uppercase()
+            } else {
+                character
+            };
+            (
+                KeyCode::Char(character),
+                key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT),
+            )
+        }
+        code => (
+            code,
+            key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT),
+        ),
+    }
+}
+
+/// Which action each key runs in the review.
+#[derive(Clone, Debug)]
+pub(crate) struct KeyMap {
+    actions: HashMap<(KeyCode, KeyModifiers), Action>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+impl KeyMap {
+    /// The default keys, with each action in `overrides` moved to its listed keys. A key
+    /// an override takes no longer runs the action it ran by default.
+    pub(crate) fn new(overrides: &[(Action, Vec<KeyEvent>)]) -> Self {
+        let mut actions = HashMap::new();
+        for (action, _, keys) in DEFAULT_BINDINGS {
+            if overrides.iter().any(|(overridden, _)| overridden == action) {
+                continue;
+            }
+            for key in *keys {
+                let key = parse_key(key).expect("default keys should parse");
+                actions.insert(normalize(key), *action);
+            }
+        }
+        for (action, keys) in overrides {
+            for key in keys {
+                actions.insert(normalize(*key), *action);
+            }
+        }
+        Self { actions }
+    }
+
+    pub(crate) fn action(&self, key: KeyEvent) -> Option<Action> {
+        self.actions.get(&normalize(key)).copied()
+    }
+}
+
+/// Sets the keys of the session's reviews; later calls are ignored.
+pub(crate) fn set_key_map(key_map: KeyMap) {
+    let _ = KEY_MAP.set(key_map);
+}
+
+pub(crate) fn key_map() -> &'static KeyMap {
+    KEY_MAP.get_or_init(KeyMap::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::{Action, KeyMap, parse_binding};
+
+    #[test]
+    fn overrides_move_an_action_to_new_keys() {
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+        let defaults = KeyMap::default();
+        assert_eq!(
+            defaults.action(key(KeyCode::Char('G'), KeyModifiers::SHIFT)),
+            Some(Action::Bottom)
+        );
+        assert_eq!(
+            defaults.action(key(KeyCode::Char('g'), KeyModifiers::SHIFT)),
+            Some(Action::Bottom)
+        );
+        assert_eq!(
+            defaults.action(key(KeyCode::Char('d'), KeyModifiers::CONTROL)),
+            Some(Action::PageDown)
+        );
+        assert_eq!(
+            defaults.action(key(KeyCode::Char('d'), KeyModifiers::NONE)),
+            None
+        );
+
+        let overrides = [
+            parse_binding("next-file", &["n", "right"]).expect("binding parses"),
+            parse_binding("toggle-reviewed", &["space"]).expect("binding parses"),
+        ];
+        let remapped = KeyMap::new(&overrides);
+        let plain = |character| key(KeyCode::Char(character), KeyModifiers::NONE);
+        assert_eq!(remapped.action(plain('n')), Some(Action::NextFile));
+        assert_eq!(remapped.action(plain('l')), None);
+        assert_eq!(remapped.action(plain(' ')), Some(Action::ToggleReviewed));
+        assert_eq!(remapped.action(plain('r')), None);
+        assert_eq!(remapped.action(plain('N')), Some(Action::PreviousMatch));
+
+        assert!(parse_binding("fly", &["f"]).is_err());
+        assert!(parse_binding("quit", &["ctrl-nope"]).is_err());
+    }
+}
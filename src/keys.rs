@@ -0,0 +1,130 @@
+use anyhow::{Result, bail};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+fn parse_named_key(name: &str) -> Option<KeyCode> {
+    let code = match name.to_ascii_lowercase().as_str() {
+        "enter" | "ret" | "cr" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "space" => KeyCode::Char(' '),
+        "bs" | "backspace" => KeyCode::Backspace,
+        "del" | "delete" => KeyCode::Delete,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pgup" | "pageup" => KeyCode::PageUp,
+        "pgdn" | "pagedown" => KeyCode::PageDown,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Uppercase letters carry SHIFT, the way terminals report them.
+fn char_key(character: char, modifiers: KeyModifiers) -> KeyEvent {
+    let modifiers = if character.is_ascii_uppercase() {
+        modifiers | KeyModifiers::SHIFT
+    } else {
+        modifiers
+    };
+    KeyEvent::new(KeyCode::Char(character), modifiers)
+}
+
+fn parse_modified_key(token: &str) -> Option<Result<KeyEvent>> {
+    let (prefix, key) = token.split_once('-')?;
+    let modifier = match prefix.to_ascii_lowercase().as_str() {
+        "ctrl" | "c" => KeyModifiers::CONTROL,
+        "alt" | "m" => KeyModifiers::ALT,
+        "shift" | "s" => KeyModifiers::SHIFT,
+        _ => return None,
+    };
+
+    let mut characters = key.chars();
+    Some(match (characters.next(), characters.next()) {
+        (Some(character), None) => Ok(char_key(character, modifier)),
+        _ => match parse_named_key(key) {
+            Some(code) => Ok(KeyEvent::new(code, modifier)),
+            None => Err(anyhow::anyhow!("unknown key {token}")),
+        },
+    })
+}
+
+/// One key, named (`enter`, `pgdn`), modified (`ctrl-d`), or a single character.
+pub(crate) fn parse_key(token: &str) -> Result<KeyEvent> {
+    if let Some(code) = parse_named_key(token) {
+        return Ok(KeyEvent::new(code, KeyModifiers::NONE));
+    }
+    if let Some(key) = parse_modified_key(token) {
+        return key;
+    }
+    let mut characters = token.chars();
+    match (characters.next(), characters.next()) {
+        (Some(character), None) => Ok(char_key(character, KeyModifiers::NONE)),
+        _ => bail!("unknown key {token:?}"),
+    }
+}
+
+/// Parses a whitespace-separated key script such as `j j zc ctrl-d /needle enter q`.
+///
+/// Tokens are named keys (`enter`, `esc`, `space`, `up`, `pgdn`, ...), modified keys
+/// (`ctrl-d`, `alt-x`, `shift-tab`), or literal text typed one character at a time.
+/// `#` starts a comment that runs to the end of the line.
+pub(crate) fn parse_key_script(script: &str) -> Result<Vec<KeyEvent>> {
+    let mut keys = Vec::new();
+
+    for line in script.lines() {
+        let line = line.split_once('#').map_or(line, |(before, _)| before);
+        for token in line.split_whitespace() {
+            if let Some(code) = parse_named_key(token) {
+                keys.push(KeyEvent::new(code, KeyModifiers::NONE));
+            } else if let Some(key) = parse_modified_key(token) {
+                keys.push(key?);
+            } else {
+                keys.extend(
+                    token
+                        .chars()
+                        .map(|character| char_key(character, KeyModifiers::NONE)),
+                );
+            }
+        }
+    }
+
+    if keys.is_empty() {
+        bail!("key script is empty");
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::parse_key_script;
+
+    #[test]
+    fn parses_named_modified_and_literal_tokens() {
+        let keys = parse_key_script("j G ctrl-d # page down\n/ab enter").expect("script parses");
+
+        assert_eq!(
+            keys,
+            vec![
+                KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE),
+                KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT),
+                KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL),
+                KeyEvent::new(KeyCode::Char('/'), KeyModifiers::NONE),
+                KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE),
+                KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE),
+                KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_modified_keys_and_empty_scripts() {
+        assert!(parse_key_script("ctrl-nope").is_err());
+        assert!(parse_key_script("  # nothing\n").is_err());
+    }
+}
@@ -1,35 +1,1141 @@
+mod annotations;
 mod app;
+mod background;
+mod capture;
 mod cli;
-mod diff;
-mod git;
-mod model;
-mod render;
-mod review;
-mod syntax;
+mod clipboard;
+mod codeowners;
+mod coverage;
+mod difftool;
+mod export;
+mod external_diff;
+mod forge;
+mod grep;
+mod hook;
+mod hunk;
+mod keymap;
+mod keys;
+mod logging;
+mod mergetool;
+mod outline;
+mod output;
+mod patch;
+mod patches;
+mod pipe;
+mod preview;
+mod project_config;
+mod rebase_plan;
+mod revert;
+mod signoff;
+mod stats;
+mod stdin_patch;
+mod summary;
 mod terminal;
-mod text;
+mod view_loader;
 
-use anyhow::{Context, Result};
+// The diff pipeline lives in `deff-core`; these keep it addressable as `crate::model`,
+// `crate::render`, and so on from the TUI modules.
+use deff_core::{diff, fold, git, model, profile, render, review, text, view_cache};
+
+use std::{
+    fs,
+    io::{self, IsTerminal, Read},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    thread,
+};
+
+use anyhow::{Context, Result, bail};
+use crossterm::event::KeyEvent;
+use regex::Regex;
 
 use crate::{
-    cli::parse_cli_options,
-    diff::{build_file_views, get_diff_file_descriptors},
-    git::{get_repository_root, resolve_comparison},
-    model::{ResolvedComparison, StrategyId},
-    render::set_theme_mode_override,
-    review::ReviewStore,
-    terminal::start_interactive_review,
+    annotations::AnnotationSet,
+    app::StartPosition,
+    background::query_dark_background,
+    cli::{CliCommand, CliOptions, GitHook, KeyScriptSource, TabRange, parse_cli_options},
+    codeowners::{CodeOwners, is_owned_by},
+    coverage::CoverageReport,
+    diff::{
+        build_file_view_from_files, get_diff_file_descriptors, group_by_top_level_directory,
+        loading_file_view, top_level_directory,
+    },
+    difftool::DifftoolPaths,
+    export::render_markdown_report,
+    external_diff::ExternalDiffArgs,
+    git::{
+        check_diff_regex, fetch_remote, flags_trailing_whitespace, get_repository_root,
+        list_commit_parents, list_paths_touched_by_author, list_range_commits, list_recent_commits,
+        resolve_commit_comparison, resolve_comparison, resolve_fetch_remote, run_git_text,
+    },
+    grep::{GrepSides, grep_changed_lines},
+    hook::{build_pre_push_report, hook_name, install_hook},
+    keymap::{KeyMap, set_key_map},
+    keys::parse_key_script,
+    mergetool::{MergeDocument, MergetoolPaths, Resolution},
+    model::{
+        CommitSummary, DiffFileDescriptor, DiffFileView, DiffOptions, ExportFormat, LoadProgress,
+        OutputFormat, Pickaxe, ResolvedComparison, StrategyId, ThemeMode, ViewSettings,
+    },
+    output::{render_report, write_report},
+    patches::{PatchSeries, load_patch_series},
+    pipe::set_pipe_commands,
+    preview::{DEFAULT_PREVIEW_COLUMNS, render_preview, repo_relative_path, untracked_descriptor},
+    profile::Phase,
+    project_config::ProjectConfig,
+    rebase_plan::{RebaseMark, format_rebase_todo},
+    revert::build_revert_preview,
+    review::{ReviewStore, default_review_state_dir},
+    signoff::{reviewed_by_trailer, sign_off_head},
+    stdin_patch::{parse_unified_diff, stdin_comparison},
+    summary::{build_review_summary, build_status_json, build_status_text, pluralize},
+    terminal::{
+        FileReloader, ReviewExit, ReviewTab, TerminalUi, reattach_stdin_to_tty,
+        run_headless_review, run_list_picker, run_loading, run_message_prompt, run_review,
+        run_review_tabs, set_inline_height, with_terminal,
+    },
+    view_cache::ViewCache,
+    view_loader::ViewLoader,
 };
 
-pub fn run() -> Result<()> {
-    let options = parse_cli_options()?;
-    set_theme_mode_override(options.theme_mode);
+/// git config key naming the reviewer when `--reviewer` is not given.
+const REVIEWER_CONFIG_KEY: &str = "deff.reviewer";
+/// git config key for a repository-relative review state directory (`--review-state-dir`).
+const REVIEW_STATE_DIR_CONFIG_KEY: &str = "deff.reviewStateDir";
+/// git config key that turns on `--review-notes`.
+const REVIEW_NOTES_CONFIG_KEY: &str = "deff.reviewNotes";
+/// git config key that turns on `--first-change`.
+const FIRST_CHANGE_CONFIG_KEY: &str = "deff.firstChange";
+/// Set by fzf to the preview window's width.
+const FZF_PREVIEW_COLUMNS_ENV: &str = "FZF_PREVIEW_COLUMNS";
+
+/// Exit status is `FAILURE` when `deff status` finds unreviewed files.
+pub fn run() -> Result<ExitCode> {
+    let mut options = parse_cli_options()?;
+    options.first_change = options.first_change
+        || std::env::current_dir()
+            .is_ok_and(|directory| read_git_config_flag(&directory, FIRST_CHANGE_CONFIG_KEY));
+    if options.theme_mode == ThemeMode::Auto
+        && options.starts_terminal()
+        && io::stdin().is_terminal()
+        && io::stdout().is_terminal()
+    {
+        options.dark_background = query_dark_background();
+    }
+    set_key_map(KeyMap::new(&options.project.keys));
+    set_pipe_commands(options.project.pipe.clone());
+    if let Some(height) = options.inline_height {
+        set_inline_height(height);
+    }
+    if options.profile {
+        profile::enable();
+    }
+    if let Some(capture_dir) = &options.capture_dir {
+        capture::enable(capture_dir)?;
+    }
+    if let Some(log_path) = logging::init(options.log_file.as_deref())? {
+        tracing::info!(version = env!("CARGO_PKG_VERSION"), log_path = %log_path.display(), "deff starting");
+        if options.log_file.is_none() {
+            eprintln!("deff: RUST_LOG is set, logging to {}", log_path.display());
+        }
+    }
+
+    let result = match options.command {
+        CliCommand::Status { json } => run_status(&options, json),
+        CliCommand::Grep { ref pattern, sides } => run_grep(&options, pattern, sides),
+        CliCommand::Signoff { print } => run_signoff(&options, print),
+        CliCommand::Hook {
+            hook,
+            warn,
+            install,
+        } => run_hook(&options, hook, warn, install),
+        CliCommand::Mergetool(ref paths) => run_mergetool(&options, paths),
+        CliCommand::Difftool(ref paths) => {
+            run_difftool(&options, paths).map(|()| ExitCode::SUCCESS)
+        }
+        CliCommand::StdinPatch => run_stdin_patch(&options).map(|()| ExitCode::SUCCESS),
+        _ => run_with_options(&options).map(|()| ExitCode::SUCCESS),
+    };
+    if let Some(report) = profile::report() {
+        eprintln!("{report}");
+    }
+    result
+}
+
+struct PreparedReview {
+    repository_root: PathBuf,
+    comparison: ResolvedComparison,
+    descriptors: Vec<DiffFileDescriptor>,
+    view_sources: ViewSources,
+    review_store: ReviewStore,
+    /// The repository-relative `--goto` path, known to be in `descriptors`.
+    goto_path: Option<String>,
+}
+
+/// What file views are built with and decorated by; kept apart from the review store
+/// so `L` can rebuild a view while the review holds the store.
+struct ViewSources {
+    code_owners: CodeOwners,
+    coverage: Option<CoverageReport>,
+    annotations: AnnotationSet,
+    diff_options: DiffOptions,
+    project_config: ProjectConfig,
+    view_cache: ViewCache,
+}
+
+enum Preparation {
+    Ready(Box<PreparedReview>),
+    Empty(String),
+}
+
+fn run_with_options(options: &CliOptions) -> Result<()> {
+    let tab_options;
+    let options = if options.merge_parents || options.per_commit {
+        tab_options = CliOptions {
+            tabs: if options.merge_parents {
+                merge_parent_tabs(options)?
+            } else {
+                per_commit_tabs(options)?
+            },
+            ..options.clone()
+        };
+        &tab_options
+    } else {
+        options
+    };
+
+    if let CliCommand::Log {
+        revision,
+        max_count,
+    } = &options.command
+    {
+        let current_directory =
+            std::env::current_dir().context("failed to read current directory")?;
+        let repository_root = get_repository_root(&current_directory)?;
+        let state_location = ReviewStateLocation::resolve(options, &repository_root)?;
+        return run_commit_browser(
+            &repository_root,
+            &state_location,
+            revision,
+            *max_count,
+            options.view_settings(),
+        );
+    }
+
+    if let CliCommand::Patches { path } = &options.command {
+        let current_directory =
+            std::env::current_dir().context("failed to read current directory")?;
+        let repository_root = get_repository_root(&current_directory)?;
+        let state_location = ReviewStateLocation::resolve(options, &repository_root)?;
+        let base = options.base_ref.as_deref().unwrap_or("HEAD");
+        let series = load_patch_series(&repository_root, &current_directory.join(path), base)?;
+        if series.commits.is_empty() {
+            println!("No patches found in {}.", path.display());
+            return Ok(());
+        }
+        return run_patch_browser(
+            &repository_root,
+            &state_location,
+            &series,
+            &format!("deff patches | {} | on {base}", path.display()),
+            options.view_settings(),
+        );
+    }
+
+    if let CliCommand::RebasePlan { output_path } = &options.command {
+        return run_rebase_plan(options, output_path.as_deref());
+    }
+
+    if let CliCommand::PreviewRevert { commit } = &options.command {
+        let current_directory =
+            std::env::current_dir().context("failed to read current directory")?;
+        let repository_root = get_repository_root(&current_directory)?;
+        let state_location = ReviewStateLocation::resolve(options, &repository_root)?;
+        let preview = build_revert_preview(&repository_root, commit)?;
+        let code_owners = CodeOwners::load(&repository_root);
+        let settings = options.view_settings();
+        return with_terminal(|terminal| {
+            if let Some(message) = open_comparison(
+                terminal,
+                &repository_root,
+                &state_location,
+                &preview.comparison,
+                &code_owners,
+                &preview.conflicts,
+                settings,
+            )? {
+                show_message(terminal, &[message])?;
+            }
+            Ok(())
+        });
+    }
+
+    if let CliCommand::ExternalDiff(args) = &options.command {
+        return run_external_diff(options, args);
+    }
+
+    if let CliCommand::Preview {
+        path,
+        unified,
+        width,
+        format,
+    } = &options.command
+    {
+        return run_preview(options, path, *unified, *width, *format);
+    }
+
+    if let CliCommand::Export {
+        format: ExportFormat::Markdown,
+        output_path,
+    } = &options.command
+    {
+        let prepared = match prepare_review(options)? {
+            Preparation::Ready(prepared) => prepared,
+            Preparation::Empty(message) => {
+                println!("{message}");
+                return Ok(());
+            }
+        };
+        let file_views =
+            load_file_views(&prepared, &prepared.descriptors, &LoadProgress::default())
+                .unwrap_or_default();
+        let reviewed_flags = prepared.review_store.reviewed_flags_for_files(&file_views);
+        let context_lines = options.context_lines();
+        let report = render_markdown_report(
+            &file_views,
+            &prepared.comparison,
+            &reviewed_flags,
+            context_lines,
+        );
+        match output_path {
+            Some(output_path) => {
+                fs::write(output_path, report)
+                    .with_context(|| format!("failed to write report {}", output_path.display()))?;
+                println!(
+                    "Wrote {} files to {}.",
+                    file_views.len(),
+                    output_path.display()
+                );
+            }
+            None => print!("{report}"),
+        }
+        return Ok(());
+    }
+
+    if let Some(key_script) = &options.key_script {
+        let keys = read_key_script(key_script)?;
+        let mut reviews = if options.tabs.is_empty() {
+            match prepare_review(options)? {
+                Preparation::Ready(prepared) => vec![*prepared],
+                Preparation::Empty(message) => {
+                    println!("{message}");
+                    return Ok(());
+                }
+            }
+        } else {
+            prepare_tabs(options)?
+        };
+        let mut tab_files: Vec<Vec<DiffFileView>> = reviews
+            .iter()
+            .map(|prepared| {
+                load_file_views(prepared, &prepared.descriptors, &LoadProgress::default())
+                    .unwrap_or_default()
+            })
+            .collect();
+        let repository_root = reviews[0].repository_root.clone();
+        let start = start_position(&reviews[0], options, &reviews[0].descriptors);
+        let mut tabs = review_tabs(&mut reviews, &mut tab_files);
+        tabs[0].start = start;
+        let frame = run_headless_review(
+            &repository_root,
+            tabs,
+            options.view_settings(),
+            keys,
+            options.screen_size,
+        )?;
+        print!("{frame}");
+        return Ok(());
+    }
+
+    if let Some(output_path) = &options.output_path {
+        let prepared = match prepare_review(options)? {
+            Preparation::Ready(prepared) => prepared,
+            Preparation::Empty(message) => {
+                println!("{message}");
+                return Ok(());
+            }
+        };
+        let file_views =
+            load_file_views(&prepared, &prepared.descriptors, &LoadProgress::default())
+                .unwrap_or_default();
+        write_report(
+            output_path,
+            &file_views,
+            &prepared.comparison,
+            options.output_format,
+            options.output_width,
+            &options.render_options(),
+        )?;
+        println!(
+            "Wrote {} files to {}.",
+            file_views.len(),
+            output_path.display()
+        );
+        return Ok(());
+    }
+
+    // Without a terminal there is no review to open, but a comparison with nothing to
+    // review, or one that fails to resolve, is still reported as it would be on screen.
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        if !options.tabs.is_empty() {
+            prepare_tabs(options)?;
+        } else if let Preparation::Empty(message) = prepare_review(options)? {
+            println!("{message}");
+            return Ok(());
+        }
+    }
+
+    let summary = with_terminal(|terminal| {
+        if !options.tabs.is_empty() {
+            return open_tabs(terminal, options);
+        }
+        // The fetch runs here under a loading screen instead of silently in prepare_review.
+        let prepare_options = CliOptions {
+            fetch: false,
+            ..options.clone()
+        };
+        loop {
+            let prepared = match fetch_with_progress(terminal, options) {
+                Ok(true) => prepare_review(&prepare_options),
+                Ok(false) => return Ok(None),
+                Err(error) => Err(error),
+            };
+            let message = match prepared {
+                Ok(Preparation::Ready(prepared)) => {
+                    match open_review(terminal, options, *prepared)? {
+                        ReviewEnd::Summary(summary) => return Ok(summary),
+                        ReviewEnd::Committed => continue,
+                    }
+                }
+                Ok(Preparation::Empty(message)) => vec![message],
+                Err(error) => {
+                    tracing::warn!(error = %format!("{error:#}"), "failed to prepare review");
+                    format!("{error:#}").lines().map(str::to_string).collect()
+                }
+            };
+
+            if !offer_retry(terminal, &message)? {
+                return Ok(None);
+            }
+        }
+    })?;
+    // Printed once the alternate screen is gone, so it stays in the shell scrollback.
+    if let Some(summary) = summary {
+        print!("{summary}");
+    }
+    Ok(())
+}
+
+/// `git mergetool` entry point: a list of the conflicts in MERGED, each resolved from a
+/// prompt showing both sides, plus a side-by-side view of LOCAL and REMOTE. Exits 0 only
+/// when MERGED was saved with every conflict resolved, so `trustExitCode` can be set.
+fn run_mergetool(options: &CliOptions, paths: &MergetoolPaths) -> Result<ExitCode> {
+    let merged_text = fs::read_to_string(&paths.merged)
+        .with_context(|| format!("failed to read {}", paths.merged.display()))?;
+    let mut document = MergeDocument::parse(&merged_text);
+    let conflict_count = document.conflict_count();
+    if conflict_count == 0 {
+        println!("No conflict markers in {}.", paths.merged.display());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let current_directory = std::env::current_dir().context("failed to read current directory")?;
+    let repository_root = get_repository_root(&current_directory)?;
+    let comparison = paths.comparison(&repository_root, conflict_count)?;
+    let mut file_views = vec![build_file_view_from_files(
+        &repository_root,
+        &paths.descriptor(),
+        Some(&current_directory.join(&paths.local)),
+        Some(&current_directory.join(&paths.remote)),
+        &diff_options(options, &repository_root)?,
+    )];
+    let mut review_store =
+        ReviewStateLocation::resolve(options, &repository_root)?.load_store(&comparison)?;
+
+    let title = format!("deff mergetool  {}", paths.merged.display());
+    let choices = [
+        format!("Take {}", Resolution::Left.label()),
+        format!("Take {}", Resolution::Right.label()),
+        format!("Take {}", Resolution::Both.label()),
+        "Leave unresolved".to_string(),
+        "Back".to_string(),
+    ];
+    let resolutions = [
+        Some(Resolution::Left),
+        Some(Resolution::Right),
+        Some(Resolution::Both),
+        None,
+    ];
+    let saved = with_terminal(|terminal| {
+        let mut selected = 0;
+        loop {
+            let mut items: Vec<String> = document
+                .conflicts()
+                .enumerate()
+                .map(|(index, conflict)| {
+                    format!(
+                        "[{}/{conflict_count}]  line {}  {}",
+                        index + 1,
+                        conflict.start_line + 1,
+                        conflict.resolution.map_or("unresolved", Resolution::label)
+                    )
+                })
+                .collect();
+            items.push("View LOCAL and REMOTE side by side".to_string());
+            items.push(format!(
+                "Save {} ({} unresolved) and exit",
+                paths.merged.display(),
+                document.unresolved_count()
+            ));
+            items.push("Quit without saving".to_string());
+
+            let Some(index) = run_list_picker(terminal, &title, &items, selected)? else {
+                return Ok(false);
+            };
+            selected = index;
+            match index.checked_sub(conflict_count) {
+                None => {
+                    let preview = document
+                        .conflicts()
+                        .nth(index)
+                        .map(|conflict| conflict.preview_lines())
+                        .unwrap_or_default();
+                    if let Some(choice) =
+                        run_message_prompt(terminal, &items[index], &preview, &choices)?
+                        && let Some(resolution) = resolutions.get(choice)
+                    {
+                        document.resolve(index, *resolution);
+                        selected = (index + 1).min(conflict_count);
+                    }
+                }
+                Some(0) => run_review(
+                    terminal,
+                    &repository_root,
+                    &mut file_views,
+                    &comparison,
+                    &mut review_store,
+                    options.view_settings(),
+                )?,
+                Some(1) => return Ok(true),
+                Some(_) => return Ok(false),
+            }
+        }
+    })?;
+
+    if !saved {
+        eprintln!("deff: {} left unchanged", paths.merged.display());
+        return Ok(ExitCode::FAILURE);
+    }
+    fs::write(&paths.merged, document.render())
+        .with_context(|| format!("failed to write {}", paths.merged.display()))?;
+    match document.unresolved_count() {
+        0 => Ok(ExitCode::SUCCESS),
+        unresolved => {
+            eprintln!(
+                "deff: {unresolved} of {conflict_count} conflicts in {} are still unresolved",
+                paths.merged.display()
+            );
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+/// One file of `GIT_EXTERNAL_DIFF=deff git diff`. When git pipes the output into its
+/// pager there is no terminal to draw on, so the file is printed as a plain report.
+fn run_external_diff(options: &CliOptions, args: &ExternalDiffArgs) -> Result<()> {
+    let current_directory = std::env::current_dir().context("failed to read current directory")?;
+    let repository_root = get_repository_root(&current_directory)?;
+    let comparison = args.comparison();
+    let old_file = args
+        .old_file
+        .as_ref()
+        .map(|file| current_directory.join(file));
+    let new_file = args
+        .new_file
+        .as_ref()
+        .map(|file| current_directory.join(file));
+    let mut file_views = vec![build_file_view_from_files(
+        &repository_root,
+        &args.descriptor(),
+        old_file.as_deref(),
+        new_file.as_deref(),
+        &diff_options(options, &repository_root)?,
+    )];
+    options.project.apply_to_views(&mut file_views);
+
+    if !io::stdout().is_terminal() {
+        print!(
+            "{}",
+            render_report(
+                &file_views,
+                &comparison,
+                OutputFormat::Plain,
+                options.output_width,
+                &options.render_options(),
+            )
+        );
+        return Ok(());
+    }
+    let state_location = ReviewStateLocation::resolve(options, &repository_root)?;
+    let mut review_store = state_location.load_store(&comparison)?;
+    with_terminal(|terminal| {
+        run_review(
+            terminal,
+            &repository_root,
+            &mut file_views,
+            &comparison,
+            &mut review_store,
+            options.view_settings(),
+        )
+    })
+}
+
+/// `deff --files LEFT RIGHT`, the `git difftool` entry point: the two files side by
+/// side, or printed as a plain report when stdout is not a terminal. Outside a
+/// repository the review marks are kept under the system temp directory.
+fn run_difftool(options: &CliOptions, paths: &DifftoolPaths) -> Result<()> {
+    let current_directory = std::env::current_dir().context("failed to read current directory")?;
+    let repository_root = get_repository_root(&current_directory).ok();
+    let root = repository_root
+        .clone()
+        .unwrap_or_else(|| current_directory.clone());
+    let (left_file, right_file) = paths.files()?;
+    let comparison = paths.comparison(&root)?;
+    let diff_options = match &repository_root {
+        Some(repository_root) => diff_options(options, repository_root)?,
+        None => options.diff_options(),
+    };
+    let mut file_views = vec![build_file_view_from_files(
+        &root,
+        &paths.descriptor(),
+        left_file
+            .map(|file| current_directory.join(file))
+            .as_deref(),
+        right_file
+            .map(|file| current_directory.join(file))
+            .as_deref(),
+        &diff_options,
+    )];
+    options.project.apply_to_views(&mut file_views);
+    let mut review_store = match &repository_root {
+        Some(repository_root) => {
+            ReviewStateLocation::resolve(options, repository_root)?.load_store(&comparison)?
+        }
+        None => ReviewStore::load(
+            &std::env::temp_dir().join("deff"),
+            &comparison,
+            options.reviewer.as_deref(),
+        )?,
+    };
+
+    if let Some(key_script) = &options.key_script {
+        let tab = ReviewTab {
+            files: &mut file_views,
+            comparison: &comparison,
+            review_store: &mut review_store,
+            start: None,
+            reload_file: None,
+            loader: None,
+        };
+        let frame = run_headless_review(
+            &root,
+            vec![tab],
+            options.view_settings(),
+            read_key_script(key_script)?,
+            options.screen_size,
+        )?;
+        print!("{frame}");
+        return Ok(());
+    }
+    if !io::stdout().is_terminal() {
+        print!(
+            "{}",
+            render_report(
+                &file_views,
+                &comparison,
+                OutputFormat::Plain,
+                options.output_width,
+                &options.render_options(),
+            )
+        );
+        return Ok(());
+    }
+    with_terminal(|terminal| {
+        run_review(
+            terminal,
+            &root,
+            &mut file_views,
+            &comparison,
+            &mut review_store,
+            options.view_settings(),
+        )
+    })
+}
+
+/// `git diff | deff --stdin`: reviews the patch on stdin. Outside a repository the review
+/// marks are kept under the system temp directory.
+fn run_stdin_patch(options: &CliOptions) -> Result<()> {
+    let mut input = Vec::new();
+    io::stdin()
+        .read_to_end(&mut input)
+        .context("failed to read the patch from stdin")?;
+    let current_directory = std::env::current_dir().context("failed to read current directory")?;
+    let repository_root = get_repository_root(&current_directory).ok();
+    let diff_options = match &repository_root {
+        Some(repository_root) => diff_options(options, repository_root)?,
+        None => options.diff_options(),
+    };
+    let mut file_views: Vec<DiffFileView> = parse_unified_diff(&String::from_utf8_lossy(&input))
+        .into_iter()
+        .map(|file| file.build_view(&diff_options))
+        .collect();
+    if file_views.is_empty() {
+        bail!("no unified diff found on stdin");
+    }
+    options.project.apply_to_views(&mut file_views);
+    let comparison = stdin_comparison();
+    let mut review_store = match &repository_root {
+        Some(repository_root) => {
+            ReviewStateLocation::resolve(options, repository_root)?.load_store(&comparison)?
+        }
+        None => ReviewStore::load(
+            &std::env::temp_dir().join("deff"),
+            &comparison,
+            options.reviewer.as_deref(),
+        )?,
+    };
+    let root = repository_root.unwrap_or(current_directory);
+    // Lines between hunks are not in the patch, so they start folded away.
+    let settings = ViewSettings {
+        changes_only: true,
+        ..options.view_settings()
+    };
+
+    if let Some(key_script) = &options.key_script {
+        let tab = ReviewTab {
+            files: &mut file_views,
+            comparison: &comparison,
+            review_store: &mut review_store,
+            start: None,
+            reload_file: None,
+            loader: None,
+        };
+        let frame = run_headless_review(
+            &root,
+            vec![tab],
+            settings,
+            read_key_script(key_script)?,
+            options.screen_size,
+        )?;
+        print!("{frame}");
+        return Ok(());
+    }
+    if !io::stdout().is_terminal() {
+        print!(
+            "{}",
+            render_report(
+                &file_views,
+                &comparison,
+                OutputFormat::Plain,
+                options.output_width,
+                &options.render_options(),
+            )
+        );
+        return Ok(());
+    }
+    reattach_stdin_to_tty()?;
+    with_terminal(|terminal| {
+        run_review(
+            terminal,
+            &root,
+            &mut file_views,
+            &comparison,
+            &mut review_store,
+            settings,
+        )
+    })
+}
+
+/// `deff preview`: one file of the comparison printed to stdout. Untracked files count
+/// as added when the comparison includes the working tree.
+fn run_preview(
+    options: &CliOptions,
+    path: &Path,
+    unified: bool,
+    width: Option<u16>,
+    format: OutputFormat,
+) -> Result<()> {
+    let current_directory = std::env::current_dir().context("failed to read current directory")?;
+    let repository_root = get_repository_root(&current_directory)?;
+    let relative_path = resolve_repo_path(&current_directory, path)?;
+    let columns = width
+        .or_else(|| {
+            std::env::var(FZF_PREVIEW_COLUMNS_ENV)
+                .ok()?
+                .trim()
+                .parse()
+                .ok()
+        })
+        .unwrap_or(DEFAULT_PREVIEW_COLUMNS)
+        .max(20);
+
+    let file_view = match prepare_review(options)? {
+        Preparation::Ready(prepared) => prepared
+            .descriptors
+            .iter()
+            .find(|descriptor| descriptor_has_path(descriptor, &relative_path))
+            .and_then(|descriptor| {
+                load_file_views(
+                    &prepared,
+                    std::slice::from_ref(descriptor),
+                    &LoadProgress::default(),
+                )
+            })
+            .and_then(|views| views.into_iter().next()),
+        Preparation::Empty(_) => None,
+    };
+    let file_view = match file_view {
+        Some(file_view) => file_view,
+        None if (options.only_uncommitted || options.include_uncommitted)
+            && !run_git_text(
+                [
+                    "ls-files",
+                    "--others",
+                    "--exclude-standard",
+                    "--",
+                    &relative_path,
+                ],
+                &repository_root,
+            )?
+            .trim()
+            .is_empty() =>
+        {
+            build_file_view_from_files(
+                &repository_root,
+                &untracked_descriptor(&relative_path),
+                None,
+                Some(&repository_root.join(&relative_path)),
+                &diff_options(options, &repository_root)?,
+            )
+        }
+        None => {
+            println!("{relative_path}: no changes");
+            return Ok(());
+        }
+    };
+    let context_lines = options.context_lines();
+    print!(
+        "{}",
+        render_preview(
+            &file_view,
+            format,
+            unified,
+            context_lines,
+            columns,
+            &options.render_options(),
+        )
+    );
+    Ok(())
+}
+
+fn run_status(options: &CliOptions, json: bool) -> Result<ExitCode> {
+    let prepared = match prepare_review(options)? {
+        Preparation::Ready(prepared) => prepared,
+        Preparation::Empty(message) => {
+            if json {
+                let status = serde_json::json!({
+                    "message": message,
+                    "reviewed": 0,
+                    "total": 0,
+                    "unreviewed": [],
+                    "files": [],
+                });
+                println!("{status}");
+            } else {
+                println!("{message}");
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+    };
+    let file_views = load_file_views(&prepared, &prepared.descriptors, &LoadProgress::default())
+        .unwrap_or_default();
+    let reviewed_flags = prepared.review_store.reviewed_flags_for_files(&file_views);
+    if json {
+        let status = build_status_json(&file_views, &prepared.comparison, &reviewed_flags);
+        println!("{status:#}");
+    } else {
+        print!(
+            "{}",
+            build_status_text(&file_views, &prepared.comparison, &reviewed_flags)
+        );
+    }
+    Ok(if reviewed_flags.iter().all(|reviewed| *reviewed) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// `deff grep`: prints the matching changed lines; like grep, exits 1 without a match.
+fn run_grep(options: &CliOptions, pattern: &str, sides: GrepSides) -> Result<ExitCode> {
+    let pattern = Regex::new(pattern).with_context(|| format!("invalid regex: {pattern}"))?;
+    let prepared = match prepare_review(options)? {
+        Preparation::Ready(prepared) => prepared,
+        Preparation::Empty(message) => {
+            eprintln!("{message}");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let file_views = load_file_views(&prepared, &prepared.descriptors, &LoadProgress::default())
+        .unwrap_or_default();
+    let matches = grep_changed_lines(&file_views, &pattern, sides);
+    for line in &matches {
+        println!("{line}");
+    }
+    Ok(if matches.is_empty() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// `deff signoff`: adds (or prints) a `Reviewed-by:` trailer once every file is reviewed;
+/// exits 1, listing the unreviewed files, until then.
+fn run_signoff(options: &CliOptions, print: bool) -> Result<ExitCode> {
+    let prepared = match prepare_review(options)? {
+        Preparation::Ready(prepared) => prepared,
+        Preparation::Empty(message) => {
+            eprintln!("{message}");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let file_views = load_file_views(&prepared, &prepared.descriptors, &LoadProgress::default())
+        .context("failed to load the comparison")?;
+    let reviewed_flags = prepared.review_store.reviewed_flags_for_files(&file_views);
+    let unreviewed: Vec<&str> = file_views
+        .iter()
+        .zip(&reviewed_flags)
+        .filter(|(_, reviewed)| !**reviewed)
+        .map(|(file, _)| file.descriptor.display_path.as_str())
+        .collect();
+    if !unreviewed.is_empty() {
+        eprintln!(
+            "{} of {} {} {} not reviewed yet:",
+            unreviewed.len(),
+            file_views.len(),
+            pluralize(file_views.len(), "file", "files"),
+            pluralize(unreviewed.len(), "is", "are")
+        );
+        for path in unreviewed {
+            eprintln!("  {path}");
+        }
+        return Ok(ExitCode::FAILURE);
+    }
+
+    let trailer = reviewed_by_trailer(&prepared.repository_root)?;
+    if print {
+        println!("{trailer}");
+    } else if sign_off_head(&prepared.repository_root, &prepared.comparison, &trailer)? {
+        println!("Added \"{trailer}\" to {}.", prepared.comparison.head_ref);
+    } else {
+        println!(
+            "{} already has \"{trailer}\".",
+            prepared.comparison.head_ref
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Git hooks need a usable exit status even when the comparison cannot be resolved (for
+/// example a branch with no upstream and no default branch), so those cases let git
+/// continue.
+fn run_hook(options: &CliOptions, hook: GitHook, warn: bool, install: bool) -> Result<ExitCode> {
+    if install {
+        let current_directory =
+            std::env::current_dir().context("failed to read current directory")?;
+        let repository_root = get_repository_root(&current_directory)?;
+        let path = install_hook(&repository_root, hook, warn)?;
+        println!("Installed {} hook at {}.", hook_name(hook), path.display());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let prepared = match prepare_review(options) {
+        Ok(Preparation::Ready(prepared)) => prepared,
+        Ok(Preparation::Empty(_)) => return Ok(ExitCode::SUCCESS),
+        Err(error) => {
+            eprintln!("deff: skipping review check: {error:#}");
+            return Ok(ExitCode::SUCCESS);
+        }
+    };
+    let file_views = load_file_views(&prepared, &prepared.descriptors, &LoadProgress::default())
+        .unwrap_or_default();
+    let reviewed_flags = prepared.review_store.reviewed_flags_for_files(&file_views);
+    match build_pre_push_report(&file_views, &prepared.comparison, &reviewed_flags, warn) {
+        Some(report) => {
+            eprint!("{report}");
+            Ok(if warn {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            })
+        }
+        None => Ok(ExitCode::SUCCESS),
+    }
+}
+
+/// The command line's diff options plus those git config decides.
+/// Git compiles `--ignore-matching-lines` here, so a pattern it cannot read fails
+/// before any file loads.
+fn diff_options(options: &CliOptions, repository_root: &Path) -> Result<DiffOptions> {
+    if let Some(pattern) = &options.ignore_matching_lines {
+        check_diff_regex(
+            repository_root,
+            &format!("--ignore-matching-lines={pattern}"),
+            "--ignore-matching-lines",
+        )?;
+    }
+    Ok(DiffOptions {
+        flag_trailing_whitespace: flags_trailing_whitespace(repository_root),
+        ..options.diff_options()
+    })
+}
+
+fn read_git_config(repository_root: &Path, key: &str) -> Option<String> {
+    run_git_text(["config", "--get", key], repository_root)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn read_git_config_flag(repository_root: &Path, key: &str) -> bool {
+    read_git_config(repository_root, key)
+        .is_some_and(|value| matches!(value.as_str(), "true" | "yes" | "on" | "1"))
+}
+
+/// The keys of `--keys`, or of the `--keys-file` script.
+fn read_key_script(source: &KeyScriptSource) -> Result<Vec<KeyEvent>> {
+    let script = match source {
+        KeyScriptSource::Inline(script) => script.clone(),
+        KeyScriptSource::File(path) => fs::read_to_string(path)
+            .with_context(|| format!("failed to read key script {}", path.display()))?,
+    };
+    parse_key_script(&script)
+}
+
+/// Where reviewed state and comments are kept, and for which reviewer.
+struct ReviewStateLocation {
+    state_dir: PathBuf,
+    reviewer: Option<String>,
+    /// Set when the review is also recorded in git notes.
+    notes_root: Option<PathBuf>,
+}
 
+impl ReviewStateLocation {
+    /// Command-line options win over `.deff.toml`, which wins over `git config`. A
+    /// configured state directory is relative to the repository root so it can be
+    /// tracked and shared; without one, state stays private in `.git/deff`.
+    fn resolve(options: &CliOptions, repository_root: &Path) -> Result<Self> {
+        let state_dir = match options
+            .review_state_dir
+            .clone()
+            .or_else(|| options.project.review_state_dir.clone())
+            .or_else(|| {
+                read_git_config(repository_root, REVIEW_STATE_DIR_CONFIG_KEY).map(PathBuf::from)
+            }) {
+            Some(state_dir) => repository_root.join(state_dir),
+            None => default_review_state_dir(repository_root)?,
+        };
+        let reviewer = options
+            .reviewer
+            .clone()
+            .or_else(|| read_git_config(repository_root, REVIEWER_CONFIG_KEY));
+        let notes = options.review_notes
+            || options
+                .project
+                .review_notes
+                .unwrap_or_else(|| read_git_config_flag(repository_root, REVIEW_NOTES_CONFIG_KEY));
+        Ok(Self {
+            state_dir,
+            reviewer,
+            notes_root: notes.then(|| repository_root.to_path_buf()),
+        })
+    }
+
+    fn load_store(&self, comparison: &ResolvedComparison) -> Result<ReviewStore> {
+        let mut store = ReviewStore::load(&self.state_dir, comparison, self.reviewer.as_deref())?;
+        if let Some(repository_root) = &self.notes_root {
+            store.attach_notes(repository_root, &comparison.head_commit);
+        }
+        Ok(store)
+    }
+}
+
+/// Shows `message` inside the TUI and returns whether the user chose to retry.
+fn offer_retry(terminal: &mut TerminalUi, message: &[String]) -> Result<bool> {
+    let choices = ["Retry".to_string(), "Quit".to_string()];
+    Ok(run_message_prompt(terminal, "deff", message, &choices)? == Some(0))
+}
+
+/// Runs `--fetch` behind a loading screen showing git's progress. Returns `false` when
+/// the fetch was cancelled.
+fn fetch_with_progress(terminal: &mut TerminalUi, options: &CliOptions) -> Result<bool> {
+    if !options.fetch {
+        return Ok(true);
+    }
     let current_directory = std::env::current_dir().context("failed to read current directory")?;
     let repository_root = get_repository_root(&current_directory)?;
-    let resolved_comparison = resolve_comparison(&repository_root, &options)?;
+    let remote = resolve_fetch_remote(&repository_root, &options.comparison_request())?;
+    let title = format!("deff  fetching {remote}");
+    match run_loading(terminal, &title, 0, |progress| {
+        Some(fetch_remote(&repository_root, &remote, progress))
+    })? {
+        Some(result) => result.map(|()| true),
+        None => Ok(false),
+    }
+}
+
+/// `path` as typed in `current_directory`, relative to the repository root.
+fn resolve_repo_path(current_directory: &Path, path: &Path) -> Result<String> {
+    let prefix = run_git_text(["rev-parse", "--show-prefix"], current_directory)?;
+    repo_relative_path(prefix.trim(), path)
+        .with_context(|| format!("{} is outside the repository", path.display()))
+}
 
-    let comparison = if options.include_uncommitted {
+fn descriptor_has_path(descriptor: &DiffFileDescriptor, path: &str) -> bool {
+    [&descriptor.head_path, &descriptor.base_path]
+        .into_iter()
+        .flatten()
+        .any(|descriptor_path| descriptor_path == path)
+}
+
+fn prepare_review(options: &CliOptions) -> Result<Preparation> {
+    let current_directory = std::env::current_dir().context("failed to read current directory")?;
+    let repository_root = get_repository_root(&current_directory)?;
+
+    if options.fetch {
+        let remote = resolve_fetch_remote(&repository_root, &options.comparison_request())?;
+        fetch_remote(&repository_root, &remote, &LoadProgress::default())?;
+    }
+
+    if let Some(pickaxe @ Pickaxe::Regex(_)) = &options.pickaxe {
+        check_diff_regex(&repository_root, &pickaxe.git_arg(), "--pickaxe-G")?;
+    }
+
+    let resolved_comparison = profile::time(Phase::ComparisonResolution, || {
+        resolve_comparison(&repository_root, &options.comparison_request())
+    })?;
+
+    let mut comparison = if options.include_uncommitted {
         let mut details = resolved_comparison.details.clone();
         details.push("uncommitted: included".to_string());
         ResolvedComparison {
@@ -41,22 +1147,725 @@ pub fn run() -> Result<()> {
     } else {
         resolved_comparison
     };
+    comparison.range_commits = profile::time(Phase::ComparisonResolution, || {
+        list_range_commits(&repository_root, &comparison)
+    })?;
+    if let Some(pickaxe) = &options.pickaxe {
+        comparison
+            .details
+            .push(format!("pickaxe: {}", pickaxe.git_arg()));
+    }
+    if let Some(pattern) = &options.ignore_matching_lines {
+        comparison
+            .details
+            .push(format!("ignoring lines matching: {pattern}"));
+    }
+    if let Some(author) = &options.author {
+        comparison.details.push(format!("author: {author}"));
+    }
+    if let Some(owner) = &options.owner {
+        comparison.details.push(format!("owner: {owner}"));
+    }
 
     if comparison.strategy_id == StrategyId::UpstreamAhead
         && !comparison.includes_uncommitted
         && comparison.ahead_count.is_some_and(|ahead| ahead == 0)
     {
-        println!("No local commits ahead of {}.", comparison.base_ref);
-        return Ok(());
+        return Ok(Preparation::Empty(format!(
+            "No local commits ahead of {}.",
+            comparison.base_ref
+        )));
     }
 
-    let descriptors = get_diff_file_descriptors(&repository_root, &comparison)?;
+    let mut descriptors = profile::time(Phase::DescriptorListing, || {
+        get_diff_file_descriptors(&repository_root, &comparison, options.pickaxe.as_ref())
+    })?;
+    // Asking for one path shows it even when the project excludes it.
+    if !matches!(
+        options.command,
+        CliCommand::Show { .. } | CliCommand::Preview { .. }
+    ) {
+        descriptors.retain(|descriptor| !options.project.is_excluded(descriptor));
+    }
+    if let Some(author) = &options.author {
+        let author_paths = profile::time(Phase::DescriptorListing, || {
+            list_paths_touched_by_author(&repository_root, &comparison, author)
+        })?;
+        descriptors.retain(|descriptor| {
+            [&descriptor.head_path, &descriptor.base_path]
+                .into_iter()
+                .flatten()
+                .any(|path| author_paths.contains(path))
+        });
+    }
+    if let CliCommand::Show { path } = &options.command {
+        let path = resolve_repo_path(&current_directory, path)?;
+        descriptors.retain(|descriptor| descriptor_has_path(descriptor, &path));
+        if descriptors.is_empty() {
+            return Ok(Preparation::Empty(format!(
+                "{path} did not change in {}.",
+                comparison.summary
+            )));
+        }
+        comparison.details.push(format!("path: {path}"));
+    }
+    let code_owners = CodeOwners::load(&repository_root);
+    if let Some(owner) = &options.owner {
+        descriptors.retain(|descriptor| {
+            is_owned_by(&code_owners.owners_for_descriptor(descriptor), owner)
+        });
+    }
     if descriptors.is_empty() {
-        println!("No changed files found for {}.", comparison.summary);
+        return Ok(Preparation::Empty(match &options.pickaxe {
+            Some(pickaxe) => format!(
+                "No changed files matching pickaxe {} found for {}.",
+                pickaxe.git_arg(),
+                comparison.summary
+            ),
+            None => format!("No changed files found for {}.", comparison.summary),
+        }));
+    }
+    let goto_path = match &options.goto {
+        Some(goto) => {
+            let path = resolve_repo_path(&current_directory, &goto.path)?;
+            if !descriptors
+                .iter()
+                .any(|descriptor| descriptor_has_path(descriptor, &path))
+            {
+                return Ok(Preparation::Empty(format!(
+                    "--goto: {path} did not change in {}.",
+                    comparison.summary
+                )));
+            }
+            Some(path)
+        }
+        None => None,
+    };
+
+    let coverage = options
+        .coverage_path
+        .as_deref()
+        .map(|path| CoverageReport::load(path, &repository_root))
+        .transpose()?;
+    let mut annotations = AnnotationSet::default();
+    for path in &options.annotation_paths {
+        annotations.extend(AnnotationSet::load(path, &repository_root)?);
+    }
+    let state_location = ReviewStateLocation::resolve(options, &repository_root)?;
+    if let Some(reviewer) = &state_location.reviewer {
+        comparison.details.push(format!("reviewer: {reviewer}"));
+    }
+    let review_store = state_location.load_store(&comparison)?;
+    let diff_options = diff_options(options, &repository_root)?;
+    let view_cache = ViewCache::open(&repository_root)?;
+
+    Ok(Preparation::Ready(Box::new(PreparedReview {
+        repository_root,
+        comparison,
+        descriptors,
+        view_sources: ViewSources {
+            code_owners,
+            coverage,
+            annotations,
+            diff_options,
+            project_config: options.project.clone(),
+            view_cache,
+        },
+        review_store,
+        goto_path,
+    })))
+}
+
+/// How `open_review` ended: with the summary to print on exit, or with a commit made
+/// from the review, after which the comparison is prepared again.
+enum ReviewEnd {
+    Summary(Option<String>),
+    Committed,
+}
+
+/// Runs the review. The summary is `None` when the review was abandoned before the
+/// files loaded.
+fn open_review(
+    terminal: &mut TerminalUi,
+    options: &CliOptions,
+    mut prepared: PreparedReview,
+) -> Result<ReviewEnd> {
+    let descriptors = std::mem::take(&mut prepared.descriptors);
+    let Some(descriptors) = confirm_large_comparison(terminal, descriptors, options.max_files)?
+    else {
+        return Ok(ReviewEnd::Summary(None));
+    };
+
+    // The review opens once its first file is built; the others load behind it.
+    let start = start_position(&prepared, options, &descriptors);
+    let first_index = start.map_or(0, |start| start.file_index);
+    let (view_sources, repository_root) = (&prepared.view_sources, &prepared.repository_root);
+    let comparison = &prepared.comparison;
+    let load_view = |file_index: usize| {
+        view_sources
+            .load(
+                repository_root,
+                comparison,
+                std::slice::from_ref(&descriptors[file_index]),
+                &LoadProgress::default(),
+            )?
+            .pop()
+    };
+    let mut file_views: Vec<DiffFileView> = descriptors.iter().map(loading_file_view).collect();
+    let mut loaded = vec![false; descriptors.len()];
+    if let Some(view) = load_view(first_index) {
+        file_views[first_index] = view;
+        loaded[first_index] = true;
+    }
+    let exit = thread::scope(|scope| {
+        let tab = ReviewTab {
+            files: &mut file_views,
+            comparison,
+            review_store: &mut prepared.review_store,
+            start,
+            reload_file: Some(view_sources.file_reloader(repository_root)),
+            loader: Some(ViewLoader::spawn(scope, loaded, first_index, load_view)),
+        };
+        run_review_tabs(
+            terminal,
+            repository_root,
+            vec![tab],
+            options.view_settings(),
+        )
+    })?;
+    if exit == ReviewExit::Committed {
+        return Ok(ReviewEnd::Committed);
+    }
+    let reviewed_flags = prepared.review_store.reviewed_flags_for_files(&file_views);
+    Ok(ReviewEnd::Summary(Some(build_review_summary(
+        &file_views,
+        &prepared.comparison,
+        &reviewed_flags,
+    ))))
+}
+
+/// `--merge-parents`: one tab per parent of the merge commit at `--head`, so what the
+/// merge brought in from each side can be read separately.
+fn merge_parent_tabs(options: &CliOptions) -> Result<Vec<TabRange>> {
+    let current_directory = std::env::current_dir().context("failed to read current directory")?;
+    let repository_root = get_repository_root(&current_directory)?;
+    let parent_count = list_commit_parents(&repository_root, &options.head_ref)?.len();
+    if parent_count < 2 {
+        bail!("{} is not a merge commit", options.head_ref);
+    }
+    Ok((1..=parent_count)
+        .map(|number| TabRange {
+            base_ref: format!("{}^{number}", options.head_ref),
+            head_ref: options.head_ref.clone(),
+            summary: None,
+        })
+        .collect())
+}
+
+/// `--per-commit`: one tab per commit of the comparison, oldest first, each against its
+/// first parent and titled with the commit's subject and author.
+fn per_commit_tabs(options: &CliOptions) -> Result<Vec<TabRange>> {
+    let current_directory = std::env::current_dir().context("failed to read current directory")?;
+    let repository_root = get_repository_root(&current_directory)?;
+    if options.fetch {
+        let remote = resolve_fetch_remote(&repository_root, &options.comparison_request())?;
+        fetch_remote(&repository_root, &remote, &LoadProgress::default())?;
+    }
+    let comparison = resolve_comparison(&repository_root, &options.comparison_request())?;
+    let commits = list_range_commits(&repository_root, &comparison)?;
+    if commits.is_empty() {
+        bail!("No commits to step through in {}.", comparison.summary);
+    }
+    Ok(commits
+        .iter()
+        .rev()
+        .map(|commit| TabRange {
+            base_ref: format!("{}^", commit.short_hash),
+            head_ref: commit.short_hash.clone(),
+            summary: Some(format!(
+                "{} {} ({})",
+                commit.short_hash, commit.subject, commit.author
+            )),
+        })
+        .collect())
+}
+
+/// Every `--tab` comparison, prepared like a range review; a tab without changes is an
+/// error rather than an empty tab, except for `--per-commit`, which leaves out commits
+/// with nothing to show.
+fn prepare_tabs(options: &CliOptions) -> Result<Vec<PreparedReview>> {
+    let mut reviews = Vec::with_capacity(options.tabs.len());
+    for tab in &options.tabs {
+        let tab_options = CliOptions {
+            strategy_id: StrategyId::Range,
+            base_ref: Some(tab.base_ref.clone()),
+            head_ref: tab.head_ref.clone(),
+            fetch: options.fetch && !options.per_commit,
+            ..options.clone()
+        };
+        match prepare_review(&tab_options)? {
+            Preparation::Ready(mut prepared) => {
+                if let Some(summary) = &tab.summary {
+                    prepared.comparison.summary = summary.clone();
+                }
+                reviews.push(*prepared);
+            }
+            Preparation::Empty(_) if options.per_commit => {}
+            Preparation::Empty(message) => bail!("{message}"),
+        }
+    }
+    if reviews.is_empty() {
+        bail!("No commit in the range changes anything to review.");
+    }
+    Ok(reviews)
+}
+
+fn review_tabs<'a>(
+    reviews: &'a mut [PreparedReview],
+    tab_files: &'a mut [Vec<DiffFileView>],
+) -> Vec<ReviewTab<'a>> {
+    reviews
+        .iter_mut()
+        .zip(tab_files)
+        .map(|(prepared, files)| ReviewTab {
+            files,
+            comparison: &prepared.comparison,
+            review_store: &mut prepared.review_store,
+            start: None,
+            reload_file: Some(
+                prepared
+                    .view_sources
+                    .file_reloader(&prepared.repository_root),
+            ),
+            loader: None,
+        })
+        .collect()
+}
+
+/// `--tab`: loads each comparison in turn, then reviews them side by side in tabs.
+fn open_tabs(terminal: &mut TerminalUi, options: &CliOptions) -> Result<Option<String>> {
+    let mut reviews = loop {
+        match prepare_tabs(options) {
+            Ok(reviews) => break reviews,
+            Err(error) => {
+                let message: Vec<String> =
+                    format!("{error:#}").lines().map(str::to_string).collect();
+                if !offer_retry(terminal, &message)? {
+                    return Ok(None);
+                }
+            }
+        }
+    };
+
+    let mut tab_files = Vec::with_capacity(reviews.len());
+    for prepared in &mut reviews {
+        let descriptors = std::mem::take(&mut prepared.descriptors);
+        let Some(descriptors) = confirm_large_comparison(terminal, descriptors, options.max_files)?
+        else {
+            return Ok(None);
+        };
+        let loading_title = format!("deff review  {}", prepared.comparison.summary);
+        let prepared = &*prepared;
+        let Some(file_views) =
+            run_loading(terminal, &loading_title, descriptors.len(), |progress| {
+                load_file_views(prepared, &descriptors, progress)
+            })?
+        else {
+            return Ok(None);
+        };
+        tab_files.push(file_views);
+    }
+
+    let repository_root = reviews[0].repository_root.clone();
+    run_review_tabs(
+        terminal,
+        &repository_root,
+        review_tabs(&mut reviews, &mut tab_files),
+        options.view_settings(),
+    )?;
+    Ok(Some(
+        reviews
+            .iter()
+            .zip(&tab_files)
+            .map(|(prepared, files)| {
+                let reviewed_flags = prepared.review_store.reviewed_flags_for_files(files);
+                build_review_summary(files, &prepared.comparison, &reviewed_flags)
+            })
+            .collect(),
+    ))
+}
+
+/// Where `--goto` opens the review; the file can be missing when `--max-files` left it
+/// out.
+fn start_position(
+    prepared: &PreparedReview,
+    options: &CliOptions,
+    descriptors: &[DiffFileDescriptor],
+) -> Option<StartPosition> {
+    let path = prepared.goto_path.as_deref()?;
+    let file_index = descriptors
+        .iter()
+        .position(|descriptor| descriptor_has_path(descriptor, path))?;
+    let line = options
+        .goto
+        .as_ref()
+        .and_then(|goto| goto.line)
+        .unwrap_or(1);
+    Some(StartPosition {
+        file_index,
+        line_index: line - 1,
+    })
+}
+
+fn load_file_views(
+    prepared: &PreparedReview,
+    descriptors: &[DiffFileDescriptor],
+    progress: &LoadProgress,
+) -> Option<Vec<DiffFileView>> {
+    prepared.view_sources.load(
+        &prepared.repository_root,
+        &prepared.comparison,
+        descriptors,
+        progress,
+    )
+}
+
+impl ViewSources {
+    /// Builds the views for `descriptors` and attaches owners, coverage, annotations,
+    /// and the project's syntax overrides and collapsed paths.
+    fn load(
+        &self,
+        repository_root: &Path,
+        comparison: &ResolvedComparison,
+        descriptors: &[DiffFileDescriptor],
+        progress: &LoadProgress,
+    ) -> Option<Vec<DiffFileView>> {
+        let mut file_views = self.view_cache.build_file_views(
+            repository_root,
+            comparison,
+            descriptors,
+            &self.diff_options,
+            progress,
+        )?;
+        self.code_owners.assign_owners(&mut file_views);
+        if let Some(coverage) = &self.coverage {
+            coverage.assign_uncovered_lines(&mut file_views);
+        }
+        self.annotations.assign_annotations(&mut file_views);
+        self.project_config.apply_to_views(&mut file_views);
+        Some(file_views)
+    }
+
+    /// `L` and `B` in the review: reads one file again, like `load` did.
+    fn file_reloader<'a>(&'a self, repository_root: &'a Path) -> FileReloader<'a> {
+        Box::new(move |comparison, descriptor| {
+            self.load(
+                repository_root,
+                comparison,
+                std::slice::from_ref(descriptor),
+                &LoadProgress::default(),
+            )?
+            .pop()
+        })
+    }
+}
+
+/// Asks before loading more than `max_files` files, offering to load everything, load
+/// only the first `max_files`, or narrow the list to one top-level directory.
+fn confirm_large_comparison(
+    terminal: &mut TerminalUi,
+    mut descriptors: Vec<DiffFileDescriptor>,
+    max_files: usize,
+) -> Result<Option<Vec<DiffFileDescriptor>>> {
+    while max_files > 0 && descriptors.len() > max_files {
+        let title = format!(
+            "{} files changed (more than --max-files {max_files})",
+            descriptors.len()
+        );
+        let items = vec![
+            format!("Load all {} files", descriptors.len()),
+            format!("Load only the first {max_files} files"),
+            "Filter by top-level directory".to_string(),
+            "Quit".to_string(),
+        ];
+
+        match run_list_picker(terminal, &title, &items, 0)? {
+            Some(0) => break,
+            Some(1) => descriptors.truncate(max_files),
+            Some(2) => {
+                let directories = group_by_top_level_directory(&descriptors);
+                let directory_items: Vec<String> = directories
+                    .iter()
+                    .map(|(directory, count)| format!("{directory}  ({count} files)"))
+                    .collect();
+                if let Some(index) =
+                    run_list_picker(terminal, "Filter by directory", &directory_items, 0)?
+                {
+                    let directory = &directories[index].0;
+                    descriptors
+                        .retain(|descriptor| top_level_directory(descriptor) == directory.as_str());
+                }
+            }
+            _ => return Ok(None),
+        }
+    }
+
+    Ok(Some(descriptors))
+}
+
+fn run_commit_browser(
+    repository_root: &Path,
+    state_location: &ReviewStateLocation,
+    revision: &str,
+    max_count: usize,
+    settings: ViewSettings,
+) -> Result<()> {
+    let code_owners = CodeOwners::load(repository_root);
+    with_terminal(|terminal| {
+        let commits = loop {
+            let message = match list_recent_commits(repository_root, revision, max_count) {
+                Ok(commits) if !commits.is_empty() => break commits,
+                Ok(_) => vec![format!("No commits found for {revision}.")],
+                Err(error) => format!("{error:#}").lines().map(str::to_string).collect(),
+            };
+            if !offer_retry(terminal, &message)? {
+                return Ok(());
+            }
+        };
+
+        let items: Vec<String> = commits.iter().map(CommitSummary::format_row).collect();
+        let title = format!("deff log | {revision} | {} commits", commits.len());
+        let mut selected = 0;
+        while let Some(index) = run_list_picker(terminal, &title, &items, selected)? {
+            selected = index;
+            review_commit(
+                terminal,
+                repository_root,
+                state_location,
+                &commits[index],
+                &code_owners,
+                settings.clone(),
+            )?;
+        }
+        Ok(())
+    })
+}
+
+/// Lists the patches of a series; picking one shows its mail before the diff.
+fn run_patch_browser(
+    repository_root: &Path,
+    state_location: &ReviewStateLocation,
+    series: &PatchSeries,
+    title: &str,
+    settings: ViewSettings,
+) -> Result<()> {
+    let code_owners = CodeOwners::load(repository_root);
+    let count = series.commits.len();
+    let items: Vec<String> = series
+        .commits
+        .iter()
+        .enumerate()
+        .map(|(index, commit)| format!("[{}/{count}]  {}", index + 1, commit.subject))
+        .collect();
+    let choices = ["Review".to_string(), "Back".to_string()];
+    with_terminal(|terminal| {
+        let mut selected = 0;
+        while let Some(index) = run_list_picker(terminal, title, &items, selected)? {
+            selected = index;
+            let commit = &series.commits[index];
+            if run_message_prompt(terminal, &items[index], &series.messages[index], &choices)?
+                != Some(0)
+            {
+                continue;
+            }
+            review_commit(
+                terminal,
+                repository_root,
+                state_location,
+                commit,
+                &code_owners,
+                settings.clone(),
+            )?;
+        }
+        Ok(())
+    })
+}
+
+/// `deff rebase-plan`: the comparison's commits, oldest first, each reviewed and then
+/// marked; the marks become a `git rebase -i` todo list once the list is closed.
+fn run_rebase_plan(options: &CliOptions, output_path: Option<&Path>) -> Result<()> {
+    let current_directory = std::env::current_dir().context("failed to read current directory")?;
+    let repository_root = get_repository_root(&current_directory)?;
+    let state_location = ReviewStateLocation::resolve(options, &repository_root)?;
+    let comparison = resolve_comparison(&repository_root, &options.comparison_request())?;
+    let mut commits = list_range_commits(&repository_root, &comparison)?;
+    if commits.is_empty() {
+        println!("No commits to rebase in {}.", comparison.summary);
         return Ok(());
     }
+    commits.reverse();
+
+    let code_owners = CodeOwners::load(&repository_root);
+    let settings = options.view_settings();
+    let title = format!(
+        "deff rebase-plan | {} | enter: review and mark, q: write the todo list",
+        comparison.summary
+    );
+    let mut choices: Vec<String> = RebaseMark::ALL
+        .iter()
+        .map(|mark| mark.label().to_string())
+        .collect();
+    choices.push("no mark".to_string());
+    let mut marks: Vec<Option<RebaseMark>> = vec![None; commits.len()];
+    with_terminal(|terminal| {
+        let mut selected = 0;
+        loop {
+            let items: Vec<String> = commits
+                .iter()
+                .zip(&marks)
+                .map(|(commit, mark)| {
+                    let label = mark.map_or("-", RebaseMark::label);
+                    format!("{label:<13}  {}", commit.format_row())
+                })
+                .collect();
+            let Some(index) = run_list_picker(terminal, &title, &items, selected)? else {
+                return Ok(());
+            };
+            review_commit(
+                terminal,
+                &repository_root,
+                &state_location,
+                &commits[index],
+                &code_owners,
+                settings.clone(),
+            )?;
+            let prompt = vec![format!("Mark {}", commits[index].subject)];
+            if let Some(choice) = run_message_prompt(terminal, &items[index], &prompt, &choices)? {
+                marks[index] = RebaseMark::ALL.get(choice).copied();
+                selected = (index + 1).min(commits.len() - 1);
+            } else {
+                selected = index;
+            }
+        }
+    })?;
+
+    let todo = format_rebase_todo(
+        &comparison.summary,
+        &comparison.base_commit,
+        &commits,
+        &marks,
+    );
+    match output_path {
+        Some(output_path) => {
+            fs::write(output_path, todo)
+                .with_context(|| format!("failed to write todo list {}", output_path.display()))?;
+            println!(
+                "Wrote the plan for {} commits to {}.",
+                commits.len(),
+                output_path.display()
+            );
+        }
+        None => print!("{todo}"),
+    }
+    Ok(())
+}
+
+/// Opens a commit's review, showing why instead when it cannot be reviewed.
+fn review_commit(
+    terminal: &mut TerminalUi,
+    repository_root: &Path,
+    state_location: &ReviewStateLocation,
+    commit: &CommitSummary,
+    code_owners: &CodeOwners,
+    settings: ViewSettings,
+) -> Result<()> {
+    match open_commit(
+        terminal,
+        repository_root,
+        state_location,
+        commit,
+        code_owners,
+        settings,
+    ) {
+        Ok(None) => Ok(()),
+        Ok(Some(message)) => show_message(terminal, &[message]),
+        Err(error) => {
+            let message: Vec<String> = format!("{error:#}").lines().map(str::to_string).collect();
+            show_message(terminal, &message)
+        }
+    }
+}
 
-    let file_views = build_file_views(&repository_root, &comparison, &descriptors);
-    let review_store = ReviewStore::load(&repository_root, &comparison)?;
-    start_interactive_review(&file_views, &comparison, review_store)
+fn show_message(terminal: &mut TerminalUi, message: &[String]) -> Result<()> {
+    run_message_prompt(terminal, "deff", message, &["Back".to_string()])?;
+    Ok(())
+}
+
+/// Reviews a single commit, returning a message to show instead when it has no changes.
+fn open_commit(
+    terminal: &mut TerminalUi,
+    repository_root: &Path,
+    state_location: &ReviewStateLocation,
+    commit: &CommitSummary,
+    code_owners: &CodeOwners,
+    settings: ViewSettings,
+) -> Result<Option<String>> {
+    let comparison = resolve_commit_comparison(repository_root, commit)?;
+    open_comparison(
+        terminal,
+        repository_root,
+        state_location,
+        &comparison,
+        code_owners,
+        &AnnotationSet::default(),
+        settings,
+    )
+}
+
+/// Loads and reviews an already resolved comparison, returning a message to show
+/// instead when it has no changes.
+fn open_comparison(
+    terminal: &mut TerminalUi,
+    repository_root: &Path,
+    state_location: &ReviewStateLocation,
+    comparison: &ResolvedComparison,
+    code_owners: &CodeOwners,
+    annotations: &AnnotationSet,
+    settings: ViewSettings,
+) -> Result<Option<String>> {
+    let descriptors = get_diff_file_descriptors(repository_root, comparison, None)?;
+    if descriptors.is_empty() {
+        return Ok(Some(format!(
+            "No changed files found for {}.",
+            comparison.summary
+        )));
+    }
+
+    let loading_title = format!("deff  {}", comparison.summary);
+    let view_cache = ViewCache::open(repository_root)?;
+    let Some(mut file_views) =
+        run_loading(terminal, &loading_title, descriptors.len(), |progress| {
+            view_cache.build_file_views(
+                repository_root,
+                comparison,
+                &descriptors,
+                &DiffOptions::default(),
+                progress,
+            )
+        })?
+    else {
+        return Ok(None);
+    };
+    code_owners.assign_owners(&mut file_views);
+    annotations.assign_annotations(&mut file_views);
+    let mut review_store = state_location.load_store(comparison)?;
+    run_review(
+        terminal,
+        repository_root,
+        &mut file_views,
+        comparison,
+        &mut review_store,
+        settings,
+    )?;
+    Ok(None)
 }
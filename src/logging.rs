@@ -0,0 +1,42 @@
+use std::{
+    env,
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_LOG_FILTER: &str = "deff=debug";
+const DEFAULT_LOG_FILE_NAME: &str = "deff.log";
+
+/// Starts writing `tracing` events to a file. Logging is off unless `--log-file` is given
+/// or `RUST_LOG` is set; the latter alone logs to `deff.log` in the temp directory.
+/// Returns the path being written, if any.
+pub(crate) fn init(log_file: Option<&Path>) -> Result<Option<PathBuf>> {
+    let env_filter = env::var("RUST_LOG").ok().filter(|value| !value.is_empty());
+    let log_path = match (log_file, &env_filter) {
+        (Some(path), _) => path.to_path_buf(),
+        (None, Some(_)) => env::temp_dir().join(DEFAULT_LOG_FILE_NAME),
+        (None, None) => return Ok(None),
+    };
+
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .with_context(|| format!("failed to open log file {}", log_path.display()))?;
+    let filter = EnvFilter::try_new(env_filter.as_deref().unwrap_or(DEFAULT_LOG_FILTER))
+        .context("invalid RUST_LOG filter")?;
+
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(Mutex::new(file))
+        .with_ansi(false)
+        .with_thread_names(true)
+        .try_init()
+        .map_err(|error| anyhow::anyhow!("failed to initialize logging: {error}"))?;
+
+    Ok(Some(log_path))
+}
@@ -1,6 +1,11 @@
-fn main() {
-    if let Err(error) = deff::run() {
-        eprintln!("deff failed: {error}");
-        std::process::exit(1);
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    match deff::run() {
+        Ok(exit_code) => exit_code,
+        Err(error) => {
+            eprintln!("deff failed: {error}");
+            ExitCode::FAILURE
+        }
     }
 }
@@ -0,0 +1,271 @@
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+
+use crate::{
+    git::run_git_text,
+    model::{DiffFileDescriptor, FileContentSource, ResolvedComparison, StrategyId},
+};
+
+/// The four paths `git mergetool` passes as `$LOCAL $BASE $REMOTE $MERGED`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct MergetoolPaths {
+    pub(crate) local: PathBuf,
+    pub(crate) base: PathBuf,
+    pub(crate) remote: PathBuf,
+    pub(crate) merged: PathBuf,
+}
+
+impl MergetoolPaths {
+    /// LOCAL against REMOTE, the two sides a conflict is resolved from.
+    pub(crate) fn descriptor(&self) -> DiffFileDescriptor {
+        let path = self.merged.display().to_string();
+        DiffFileDescriptor {
+            raw_status: "U".to_string(),
+            display_path: path.clone(),
+            base_path: Some(path.clone()),
+            head_path: Some(path),
+            base_source: FileContentSource::Commit,
+            head_source: FileContentSource::Commit,
+            base_mode: None,
+            head_mode: None,
+        }
+    }
+
+    pub(crate) fn comparison(
+        &self,
+        repo_root: &Path,
+        unresolved: usize,
+    ) -> Result<ResolvedComparison> {
+        let hash = |path: &Path| -> Result<String> {
+            Ok(run_git_text(
+                [
+                    OsStr::new("hash-object"),
+                    OsStr::new("--"),
+                    path.as_os_str(),
+                ],
+                repo_root,
+            )?
+            .trim()
+            .to_string())
+        };
+        Ok(ResolvedComparison {
+            strategy_id: StrategyId::Range,
+            base_ref: "LOCAL".to_string(),
+            head_ref: "REMOTE".to_string(),
+            base_commit: hash(&self.local)?,
+            head_commit: hash(&self.remote)?,
+            summary: format!("mergetool {}", self.merged.display()),
+            details: vec![format!("unresolved conflicts: {unresolved}")],
+            ahead_count: None,
+            includes_uncommitted: false,
+            range_commits: Vec::new(),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Resolution {
+    Left,
+    Right,
+    Both,
+}
+
+impl Resolution {
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::Left => "left (LOCAL)",
+            Self::Right => "right (REMOTE)",
+            Self::Both => "both (LOCAL, then REMOTE)",
+        }
+    }
+}
+
+/// One `<<<<<<<` ... `>>>>>>>` region. Lines keep their line endings so resolving
+/// writes the file back byte for byte outside the conflicts.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Conflict {
+    /// Zero-based line of the `<<<<<<<` marker in MERGED.
+    pub(crate) start_line: usize,
+    pub(crate) ours: Vec<String>,
+    pub(crate) theirs: Vec<String>,
+    /// The whole region, markers and diff3 base section included, for when it stays
+    /// unresolved.
+    raw: Vec<String>,
+    pub(crate) resolution: Option<Resolution>,
+}
+
+impl Conflict {
+    /// Both sides between markers, for the resolution prompt.
+    pub(crate) fn preview_lines(&self) -> Vec<String> {
+        let trim = |line: &String| line.trim_end_matches(['\n', '\r']).to_string();
+        let mut lines = vec!["<<<<<<< LOCAL".to_string()];
+        lines.extend(self.ours.iter().map(trim));
+        lines.push("=======".to_string());
+        lines.extend(self.theirs.iter().map(trim));
+        lines.push(">>>>>>> REMOTE".to_string());
+        lines
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum MergeChunk {
+    Clean(Vec<String>),
+    Conflict(Conflict),
+}
+
+/// MERGED as clean runs and conflict regions.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct MergeDocument {
+    chunks: Vec<MergeChunk>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ConflictSection {
+    Ours,
+    Base,
+    Theirs,
+}
+
+fn is_marker(line: &str, marker: &str) -> bool {
+    let line = line.trim_end_matches(['\n', '\r']);
+    line.strip_prefix(marker)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+}
+
+impl MergeDocument {
+    pub(crate) fn parse(text: &str) -> Self {
+        let mut chunks = Vec::new();
+        let mut clean = Vec::new();
+        let mut current: Option<(Conflict, ConflictSection)> = None;
+
+        for (index, line) in text.split_inclusive('\n').enumerate() {
+            let line = line.to_string();
+            let Some((conflict, section)) = current.as_mut() else {
+                if is_marker(&line, "<<<<<<<") {
+                    chunks.push(MergeChunk::Clean(std::mem::take(&mut clean)));
+                    current = Some((
+                        Conflict {
+                            start_line: index,
+                            ours: Vec::new(),
+                            theirs: Vec::new(),
+                            raw: vec![line],
+                            resolution: None,
+                        },
+                        ConflictSection::Ours,
+                    ));
+                } else {
+                    clean.push(line);
+                }
+                continue;
+            };
+
+            conflict.raw.push(line.clone());
+            let mut finished = false;
+            match *section {
+                ConflictSection::Ours if is_marker(&line, "|||||||") => {
+                    *section = ConflictSection::Base;
+                }
+                ConflictSection::Ours | ConflictSection::Base if is_marker(&line, "=======") => {
+                    *section = ConflictSection::Theirs;
+                }
+                ConflictSection::Ours => conflict.ours.push(line),
+                ConflictSection::Base => {}
+                ConflictSection::Theirs if is_marker(&line, ">>>>>>>") => finished = true,
+                ConflictSection::Theirs => conflict.theirs.push(line),
+            }
+            if finished && let Some((conflict, _)) = current.take() {
+                chunks.push(MergeChunk::Conflict(conflict));
+            }
+        }
+        // A region left open at the end of the file is not a conflict git wrote.
+        if let Some((conflict, _)) = current {
+            clean.extend(conflict.raw);
+        }
+        chunks.push(MergeChunk::Clean(clean));
+        chunks.retain(|chunk| !matches!(chunk, MergeChunk::Clean(lines) if lines.is_empty()));
+        Self { chunks }
+    }
+
+    pub(crate) fn conflicts(&self) -> impl Iterator<Item = &Conflict> {
+        self.chunks.iter().filter_map(|chunk| match chunk {
+            MergeChunk::Conflict(conflict) => Some(conflict),
+            MergeChunk::Clean(_) => None,
+        })
+    }
+
+    pub(crate) fn conflict_count(&self) -> usize {
+        self.conflicts().count()
+    }
+
+    pub(crate) fn unresolved_count(&self) -> usize {
+        self.conflicts()
+            .filter(|conflict| conflict.resolution.is_none())
+            .count()
+    }
+
+    pub(crate) fn resolve(&mut self, conflict_index: usize, resolution: Option<Resolution>) {
+        if let Some(conflict) = self
+            .chunks
+            .iter_mut()
+            .filter_map(|chunk| match chunk {
+                MergeChunk::Conflict(conflict) => Some(conflict),
+                MergeChunk::Clean(_) => None,
+            })
+            .nth(conflict_index)
+        {
+            conflict.resolution = resolution;
+        }
+    }
+
+    /// The file to write back: resolved regions replaced by the chosen side(s),
+    /// unresolved ones kept with their markers.
+    pub(crate) fn render(&self) -> String {
+        let mut output = String::new();
+        for chunk in &self.chunks {
+            let lines = match chunk {
+                MergeChunk::Clean(lines) => lines.clone(),
+                MergeChunk::Conflict(conflict) => match conflict.resolution {
+                    None => conflict.raw.clone(),
+                    Some(Resolution::Left) => conflict.ours.clone(),
+                    Some(Resolution::Right) => conflict.theirs.clone(),
+                    Some(Resolution::Both) => {
+                        [conflict.ours.as_slice(), conflict.theirs.as_slice()].concat()
+                    }
+                },
+            };
+            output.extend(lines);
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MergeDocument, Resolution};
+
+    #[test]
+    fn resolving_conflicts_keeps_clean_lines_and_unresolved_markers() {
+        let merged = "a\n<<<<<<< HEAD\nours\n||||||| base\nold\n=======\ntheirs\n>>>>>>> topic\nb\n<<<<<<< HEAD\nx\n=======\ny\n>>>>>>> topic\n";
+        let mut document = MergeDocument::parse(merged);
+
+        assert_eq!(document.conflict_count(), 2);
+        assert_eq!(document.render(), merged);
+        let first = document.conflicts().next().expect("first conflict");
+        assert_eq!(first.start_line, 1);
+        assert_eq!(first.ours, vec!["ours\n".to_string()]);
+        assert_eq!(first.theirs, vec!["theirs\n".to_string()]);
+
+        document.resolve(0, Some(Resolution::Right));
+        assert_eq!(document.unresolved_count(), 1);
+        assert_eq!(
+            document.render(),
+            "a\ntheirs\nb\n<<<<<<< HEAD\nx\n=======\ny\n>>>>>>> topic\n"
+        );
+        document.resolve(1, Some(Resolution::Both));
+        assert_eq!(document.render(), "a\ntheirs\nb\nx\ny\n");
+    }
+}
@@ -0,0 +1,214 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::{model::DiffFileView, text::leading_indent_width};
+
+static RUST_SYMBOL_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:async|const|unsafe|extern(?:\s+\S+)?)\s+)*(?:fn|struct|enum|trait|impl|mod|type|union|macro_rules!)\b",
+    )
+    .expect("rust symbol regex should be valid")
+});
+static PYTHON_SYMBOL_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:async\s+)?(?:def|class)\s+\w+")
+        .expect("python symbol regex should be valid")
+});
+static SCRIPT_SYMBOL_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^\s*(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:(?:async\s+)?function\*?\s*\w*\s*[(<]|class\s+\w+|interface\s+\w+|type\s+\w+\s*[=<]|enum\s+\w+|(?:const|let|var)\s+\w+\s*(?::[^=]+)?=\s*(?:async\s+)?(?:\([^)]*\)|\w+)\s*=>)",
+    )
+    .expect("script symbol regex should be valid")
+});
+static GO_SYMBOL_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:func\b|type\s+\w+\s+(?:struct|interface)\b)")
+        .expect("go symbol regex should be valid")
+});
+static JVM_SYMBOL_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^\s*(?:@\w+\s+)*(?:(?:public|private|protected|internal|static|final|abstract|sealed|open|override|data|suspend|inline|async|virtual|partial)\s+)*(?:(?:class|interface|enum|record|object|fun|struct)\s+\w+|[\w<>\[\],.?]+\s+\w+\s*\([^;]*$)",
+    )
+    .expect("jvm symbol regex should be valid")
+});
+static C_SYMBOL_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^(?:(?:class|struct|enum|union|namespace)\s+\w+|[A-Za-z_][\w\s\*&:<>,]*[\s\*&]\**[\w:~]+\s*\([^;]*$)",
+    )
+    .expect("c symbol regex should be valid")
+});
+static RUBY_SYMBOL_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:def|class|module)\s+\S+").expect("ruby symbol regex should be valid")
+});
+static SHELL_SYMBOL_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:function\s+[\w-]+|[\w-]+\s*\(\s*\))")
+        .expect("shell symbol regex should be valid")
+});
+static PHP_SYMBOL_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^\s*(?:(?:public|private|protected|static|abstract|final)\s+)*(?:function|class|interface|trait|enum)\s+\w+",
+    )
+    .expect("php symbol regex should be valid")
+});
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct OutlineSymbol {
+    pub(crate) line_index: usize,
+    pub(crate) depth: usize,
+    pub(crate) label: String,
+    pub(crate) changed: bool,
+}
+
+fn symbol_pattern(language: &str) -> Option<&'static Regex> {
+    match language {
+        "Rust" => Some(&RUST_SYMBOL_RE),
+        "Python" => Some(&PYTHON_SYMBOL_RE),
+        "JavaScript" | "JavaScript (Babel)" | "TypeScript (deff)" | "TSX (deff)" | "JSX (deff)" => {
+            Some(&SCRIPT_SYMBOL_RE)
+        }
+        "Go" => Some(&GO_SYMBOL_RE),
+        "Java" | "Kotlin (deff)" | "C#" | "Scala" => Some(&JVM_SYMBOL_RE),
+        "C" | "C++" | "Objective-C" | "Objective-C++" => Some(&C_SYMBOL_RE),
+        "Ruby" => Some(&RUBY_SYMBOL_RE),
+        "Bourne Again Shell (bash)" | "Shell-Unix-Generic" => Some(&SHELL_SYMBOL_RE),
+        "PHP" | "PHP Source" => Some(&PHP_SYMBOL_RE),
+        _ => None,
+    }
+}
+
+/// Lists definition-like lines of the head side (or the base side for deleted files),
+/// marking each symbol whose body range contains a changed line, and each container
+/// (such as an `impl` block) holding a changed symbol.
+pub(crate) fn build_outline(file: &DiffFileView) -> Vec<OutlineSymbol> {
+    let lines = file.primary_lines();
+    let Some(pattern) = file.primary_language().and_then(symbol_pattern) else {
+        return Vec::new();
+    };
+
+    let symbol_lines: Vec<(usize, usize)> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| pattern.is_match(line))
+        .map(|(line_index, line)| (line_index, leading_indent_width(line)))
+        .collect();
+    let min_indent = symbol_lines
+        .iter()
+        .map(|(_, indent)| *indent)
+        .min()
+        .unwrap_or(0);
+    let max_lines = file.line_count();
+
+    let mut symbols: Vec<OutlineSymbol> = symbol_lines
+        .iter()
+        .enumerate()
+        .map(|(position, (line_index, indent))| {
+            let range_end = symbol_lines
+                .get(position + 1)
+                .map(|(next_line_index, _)| *next_line_index)
+                .unwrap_or(max_lines);
+            OutlineSymbol {
+                line_index: *line_index,
+                depth: indent.saturating_sub(min_indent) / 2,
+                label: lines[*line_index]
+                    .trim()
+                    .trim_end_matches('{')
+                    .trim_end()
+                    .to_string(),
+                changed: (*line_index..range_end).any(|index| file.is_line_changed(index)),
+            }
+        })
+        .collect();
+
+    // Walking backwards, each symbol's children are already marked when it is reached.
+    for position in (0..symbols.len()).rev() {
+        let depth = symbols[position].depth;
+        let child_changed = symbols[position + 1..]
+            .iter()
+            .take_while(|child| child.depth > depth)
+            .any(|child| child.changed);
+        symbols[position].changed |= child_changed;
+    }
+    symbols
+}
+
+/// A symbol as `* line  label`, numbered by its line in the file.
+pub(crate) fn format_outline_symbol(file: &DiffFileView, symbol: &OutlineSymbol) -> String {
+    let line = file
+        .source_line(file.primary_side(), symbol.line_index)
+        .unwrap_or(symbol.line_index);
+    format!(
+        "{} {:>5}  {}{}",
+        if symbol.changed { "*" } else { " " },
+        line + 1,
+        "  ".repeat(symbol.depth),
+        symbol.label
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::build_outline;
+    use crate::model::DiffFileView;
+
+    fn create_view(language: &str, right_lines: &[&str], right_added: &[usize]) -> DiffFileView {
+        DiffFileView {
+            left_lines: right_lines.iter().map(|line| line.to_string()).collect(),
+            right_lines: right_lines.iter().map(|line| line.to_string()).collect(),
+            left_language: Some(language.to_string()),
+            right_language: Some(language.to_string()),
+            right_added_line_indexes: right_added.iter().copied().collect(),
+            ..DiffFileView::for_test("src/lib.rs")
+        }
+    }
+
+    #[test]
+    fn rust_outline_marks_symbols_containing_changes() {
+        let view = create_view(
+            "Rust",
+            &[
+                "use std::fs;",
+                "pub struct Config {",
+                "}",
+                "impl Config {",
+                "    pub(crate) fn load() -> Self {",
+                "        todo!()",
+                "    }",
+                "}",
+            ],
+            &[5],
+        );
+
+        let outline = build_outline(&view);
+        let summary: Vec<(usize, usize, bool)> = outline
+            .iter()
+            .map(|symbol| (symbol.line_index, symbol.depth, symbol.changed))
+            .collect();
+
+        assert_eq!(summary, vec![(1, 0, false), (3, 0, true), (4, 2, true)]);
+        assert_eq!(outline[2].label, "pub(crate) fn load() -> Self");
+    }
+
+    #[test]
+    fn python_outline_finds_classes_and_functions() {
+        let view = create_view(
+            "Python",
+            &[
+                "import os",
+                "class Loader:",
+                "    async def run(self):",
+                "        pass",
+            ],
+            &[],
+        );
+
+        let lines: Vec<usize> = build_outline(&view)
+            .iter()
+            .map(|symbol| symbol.line_index)
+            .collect();
+        assert_eq!(lines, vec![1, 2]);
+    }
+
+    #[test]
+    fn unknown_language_has_no_outline() {
+        let view = create_view("Plain Text", &["fn looks_like_rust() {}"], &[]);
+        assert!(build_outline(&view).is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::Line,
+};
+
+use crate::{
+    fold::{DisplayRow, build_display_rows},
+    model::{DiffFileView, OutputFormat, RenderOptions, ResolvedComparison},
+    render::render_file_lines,
+    text::fit_line,
+};
+
+/// Width of the `-`/`+`/`~` change marker column that plain reports prepend, since
+/// they cannot show the add/delete tint.
+const PLAIN_MARKER_WIDTH: usize = 2;
+
+fn color_index(color: Color) -> Option<u8> {
+    let index = match color {
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::Gray => 7,
+        Color::DarkGray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        Color::White => 15,
+        Color::Indexed(index) => index,
+        Color::Reset | Color::Rgb(..) => return None,
+    };
+    Some(index)
+}
+
+fn push_color_code(codes: &mut Vec<String>, color: Option<Color>, base: u8) {
+    match color {
+        Some(Color::Rgb(red, green, blue)) => codes.push(format!("{base};2;{red};{green};{blue}")),
+        Some(color) => {
+            if let Some(index) = color_index(color) {
+                codes.push(format!("{base};5;{index}"));
+            }
+        }
+        None => {}
+    }
+}
+
+fn ansi_prefix(style: Style) -> String {
+    let mut codes = vec!["0".to_string()];
+    push_color_code(&mut codes, style.fg, 38);
+    push_color_code(&mut codes, style.bg, 48);
+    for (modifier, code) in [
+        (Modifier::BOLD, "1"),
+        (Modifier::ITALIC, "3"),
+        (Modifier::UNDERLINED, "4"),
+        (Modifier::REVERSED, "7"),
+    ] {
+        if style.add_modifier.contains(modifier) {
+            codes.push(code.to_string());
+        }
+    }
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+pub(crate) fn line_to_ansi(line: &Line<'_>) -> String {
+    let mut output = String::new();
+    for span in &line.spans {
+        output.push_str(&ansi_prefix(line.style.patch(span.style)));
+        output.push_str(&span.content);
+    }
+    output.push_str("\x1b[0m");
+    output
+}
+
+pub(crate) fn line_to_plain(line: &Line<'_>) -> String {
+    line.spans
+        .iter()
+        .map(|span| span.content.as_ref())
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+fn change_marker(file: &DiffFileView, display_row: DisplayRow) -> &'static str {
+    let changed_lines = (display_row.first_line()..=display_row.last_line()).fold(
+        (false, false),
+        |(deleted, added), line_index| {
+            (
+                deleted || file.left_deleted_line_indexes.contains(&line_index),
+                added || file.right_added_line_indexes.contains(&line_index),
+            )
+        },
+    );
+    match changed_lines {
+        (true, true) => "~ ",
+        (true, false) => "- ",
+        (false, true) => "+ ",
+        (false, false) => "  ",
+    }
+}
+
+fn format_file_header(file: &DiffFileView) -> String {
+    let mut header = format!(
+        "==> {}  [{}]  +{} -{}",
+        file.descriptor.display_path,
+        file.descriptor.raw_status,
+        file.right_added_line_indexes.len(),
+        file.left_deleted_line_indexes.len()
+    );
+    if !file.owners.is_empty() {
+        header.push_str(&format!("  owners: {}", file.owners.join(" ")));
+    }
+    for note in [file.minified.note(), file.syntax_highlighting.note()]
+        .into_iter()
+        .flatten()
+    {
+        header.push_str(&format!("  [{note}]"));
+    }
+    header
+}
+
+/// One file's header and side-by-side rows, as `render_report` prints them.
+pub(crate) fn render_file_section(
+    file: &DiffFileView,
+    format: OutputFormat,
+    columns: u16,
+    options: &RenderOptions,
+) -> Vec<String> {
+    let pane_columns = match format {
+        OutputFormat::Plain => columns.saturating_sub(PLAIN_MARKER_WIDTH as u16),
+        OutputFormat::Ansi => columns,
+    };
+    let divider = "=".repeat(columns as usize);
+    let header = Line::styled(
+        fit_line(&format_file_header(file), columns as usize),
+        Style::default().add_modifier(Modifier::BOLD),
+    );
+    let mut output = vec![
+        divider.clone(),
+        match format {
+            OutputFormat::Plain => line_to_plain(&header),
+            OutputFormat::Ansi => line_to_ansi(&header),
+        },
+        divider,
+    ];
+
+    let display_rows = build_display_rows(file.line_count(), &[]);
+    let lines = render_file_lines(file, &[], pane_columns, options);
+    for (display_row, line) in display_rows.into_iter().zip(&lines) {
+        match format {
+            OutputFormat::Plain => output.push(format!(
+                "{}{}",
+                change_marker(file, display_row),
+                line_to_plain(line)
+            )),
+            OutputFormat::Ansi => output.push(line_to_ansi(line)),
+        }
+    }
+    output
+}
+
+/// Renders every file side by side, the way the TUI shows it, into one report.
+pub(crate) fn render_report(
+    files: &[DiffFileView],
+    comparison: &ResolvedComparison,
+    format: OutputFormat,
+    columns: u16,
+    options: &RenderOptions,
+) -> String {
+    let mut output = vec![
+        format!(
+            "deff report ({})  {}",
+            comparison.strategy_id, comparison.summary
+        ),
+        comparison.details.join(" | "),
+        format!("{} files", files.len()),
+    ];
+
+    for file in files {
+        output.push(String::new());
+        output.extend(render_file_section(file, format, columns, options));
+    }
+
+    output.push(String::new());
+    output.join("\n")
+}
+
+pub(crate) fn write_report(
+    path: &Path,
+    files: &[DiffFileView],
+    comparison: &ResolvedComparison,
+    format: OutputFormat,
+    columns: u16,
+    options: &RenderOptions,
+) -> Result<()> {
+    fs::write(
+        path,
+        render_report(files, comparison, format, columns, options),
+    )
+    .with_context(|| format!("failed to write report {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use ratatui::{
+        style::{Color, Style},
+        text::{Line, Span},
+    };
+
+    use super::{line_to_ansi, render_report};
+    use crate::model::{DiffFileView, OutputFormat, RenderOptions, ResolvedComparison};
+
+    fn create_test_file() -> DiffFileView {
+        DiffFileView {
+            review_key: "key".to_string(),
+            left_lines: vec!["a".to_string(), "b".to_string()],
+            right_lines: vec!["a".to_string(), "B".to_string(), "c".to_string()],
+            left_deleted_line_indexes: HashSet::from([1]),
+            right_added_line_indexes: HashSet::from([1, 2]),
+            left_max_content_length: 1,
+            right_max_content_length: 1,
+            ..DiffFileView::for_test("src/lib.rs")
+        }
+    }
+
+    #[test]
+    fn plain_report_marks_changed_rows() {
+        let report = render_report(
+            &[create_test_file()],
+            &ResolvedComparison::for_test(),
+            OutputFormat::Plain,
+            40,
+            &RenderOptions::default(),
+        );
+        let lines: Vec<&str> = report.lines().collect();
+
+        assert!(lines.contains(&"==> src/lib.rs  [M]  +2 -1"));
+        assert!(lines.iter().any(|line| line.starts_with("~   2 b")));
+        assert!(lines.iter().any(|line| line.starts_with("+    ")));
+        assert!(lines.iter().any(|line| line.starts_with("    1 a")));
+    }
+
+    #[test]
+    fn ansi_lines_encode_span_colors() {
+        let line = Line::from(vec![
+            Span::styled("x", Style::default().fg(Color::Rgb(1, 2, 3))),
+            Span::styled("y", Style::default().bg(Color::Red)),
+        ]);
+
+        assert_eq!(
+            line_to_ansi(&line),
+            "\x1b[0;38;2;1;2;3mx\x1b[0;48;5;1my\x1b[0m"
+        );
+    }
+}
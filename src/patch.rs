@@ -0,0 +1,437 @@
+use crate::model::{DiffFileView, FileContentSource, PaneSide};
+
+pub(crate) const DEFAULT_CONTEXT_LINES: usize = 3;
+
+/// The mode of a regular file, for sides whose mode is not known.
+const DEFAULT_MODE: &str = "100644";
+
+const NO_NEWLINE_MARKER: &str = "\\ No newline at end of file";
+
+/// One line of a unified diff, with the zero-based line indexes it came from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum PatchLine {
+    Context { left: usize, right: usize },
+    Deleted(usize),
+    Added(usize),
+}
+
+impl PatchLine {
+    fn is_change(self) -> bool {
+        !matches!(self, PatchLine::Context { .. })
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct PatchHunk {
+    pub(crate) left_start: usize,
+    pub(crate) left_count: usize,
+    pub(crate) right_start: usize,
+    pub(crate) right_count: usize,
+    pub(crate) lines: Vec<PatchLine>,
+}
+
+impl PatchHunk {
+    /// `@@ -a,b +c,d @@`, where an empty side points at the line before the hunk.
+    pub(crate) fn header(&self) -> String {
+        let side = |start: usize, count: usize| {
+            if count == 0 {
+                format!("{start},0")
+            } else {
+                format!("{},{count}", start + 1)
+            }
+        };
+        format!(
+            "@@ -{} +{} @@",
+            side(self.left_start, self.left_count),
+            side(self.right_start, self.right_count)
+        )
+    }
+}
+
+fn side_lines(lines: &[String], source: FileContentSource) -> &[String] {
+    // Missing sides hold a placeholder line for display only.
+    if source == FileContentSource::Missing {
+        &[]
+    } else {
+        lines
+    }
+}
+
+/// The rows showing a line of the side's file; filler rows and the placeholder of a
+/// missing side are left out.
+fn side_rows(file: &DiffFileView, side: PaneSide) -> Vec<usize> {
+    let (lines, source) = match side {
+        PaneSide::Left => (&file.left_lines, file.descriptor.base_source),
+        PaneSide::Right => (&file.right_lines, file.descriptor.head_source),
+    };
+    (0..side_lines(lines, source).len())
+        .filter(|row| file.source_line(side, *row).is_some())
+        .collect()
+}
+
+/// Walks both sides in order: unchanged lines pair up, deleted and added lines are
+/// emitted in between, deletions first. Lines are identified by their rows.
+pub(crate) fn align_patch_lines(file: &DiffFileView) -> Vec<PatchLine> {
+    let left_rows = side_rows(file, PaneSide::Left);
+    let right_rows = side_rows(file, PaneSide::Right);
+    let mut lines = Vec::with_capacity(left_rows.len().max(right_rows.len()));
+    let (mut left, mut right) = (0, 0);
+
+    while left < left_rows.len() || right < right_rows.len() {
+        if left < left_rows.len()
+            && (file.left_deleted_line_indexes.contains(&left_rows[left])
+                || right >= right_rows.len())
+        {
+            lines.push(PatchLine::Deleted(left_rows[left]));
+            left += 1;
+        } else if right < right_rows.len()
+            && (file.right_added_line_indexes.contains(&right_rows[right])
+                || left >= left_rows.len())
+        {
+            lines.push(PatchLine::Added(right_rows[right]));
+            right += 1;
+        } else {
+            lines.push(PatchLine::Context {
+                left: left_rows[left],
+                right: right_rows[right],
+            });
+            left += 1;
+            right += 1;
+        }
+    }
+
+    lines
+}
+
+/// Groups changes into hunks with `context_lines` unchanged lines around them, merging
+/// hunks whose context would overlap.
+pub(crate) fn build_patch_hunks(file: &DiffFileView, context_lines: usize) -> Vec<PatchHunk> {
+    group_patch_hunks(align_patch_lines(file), context_lines)
+}
+
+fn group_patch_hunks(lines: Vec<PatchLine>, context_lines: usize) -> Vec<PatchHunk> {
+    let change_positions: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.is_change())
+        .map(|(position, _)| position)
+        .collect();
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for position in change_positions {
+        let start = position.saturating_sub(context_lines);
+        let end = (position + context_lines + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let before = &lines[..start];
+            let hunk_lines = lines[start..end].to_vec();
+            let left_before = before
+                .iter()
+                .filter(|line| !matches!(line, PatchLine::Added(_)))
+                .count();
+            let right_before = before
+                .iter()
+                .filter(|line| !matches!(line, PatchLine::Deleted(_)))
+                .count();
+            PatchHunk {
+                left_start: left_before,
+                left_count: hunk_lines
+                    .iter()
+                    .filter(|line| !matches!(line, PatchLine::Added(_)))
+                    .count(),
+                right_start: right_before,
+                right_count: hunk_lines
+                    .iter()
+                    .filter(|line| !matches!(line, PatchLine::Deleted(_)))
+                    .count(),
+                lines: hunk_lines,
+            }
+        })
+        .collect()
+}
+
+fn format_patch_line(file: &DiffFileView, line: PatchLine) -> String {
+    let text = |lines: &[String], index: usize| lines.get(index).cloned().unwrap_or_default();
+    match line {
+        PatchLine::Context { left, .. } => format!(" {}", text(&file.left_lines, left)),
+        PatchLine::Deleted(left) => format!("-{}", text(&file.left_lines, left)),
+        PatchLine::Added(right) => format!("+{}", text(&file.right_lines, right)),
+    }
+}
+
+/// The row of the side's last line when the file ends without a newline.
+fn unterminated_last_row(file: &DiffFileView, side: PaneSide) -> Option<usize> {
+    let missing_final_newline = match side {
+        PaneSide::Left => file.left_missing_final_newline,
+        PaneSide::Right => file.right_missing_final_newline,
+    };
+    missing_final_newline
+        .then(|| side_rows(file, side).last().copied())
+        .flatten()
+}
+
+/// The hunk's header and lines, with git's `\ No newline at end of file` after a line
+/// that ends its file without one.
+fn format_patch_hunk(file: &DiffFileView, hunk: &PatchHunk) -> Vec<String> {
+    let left_end = unterminated_last_row(file, PaneSide::Left);
+    let right_end = unterminated_last_row(file, PaneSide::Right);
+    let mut output = vec![hunk.header()];
+    for line in &hunk.lines {
+        output.push(format_patch_line(file, *line));
+        let unterminated = match *line {
+            PatchLine::Context { left, .. } | PatchLine::Deleted(left) => Some(left) == left_end,
+            PatchLine::Added(right) => Some(right) == right_end,
+        };
+        if unterminated {
+            output.push(NO_NEWLINE_MARKER.to_string());
+        }
+    }
+    output
+}
+
+/// Keeps only the changes on rows `first..=last`. Unselected deletions stay behind as
+/// context and unselected additions are dropped, so the result still applies to the
+/// base side.
+fn select_patch_lines(file: &DiffFileView, first: usize, last: usize) -> Vec<PatchLine> {
+    let selected = |index: usize| (first..=last).contains(&index);
+    let mut lines = Vec::new();
+    let mut right = 0;
+    for line in align_patch_lines(file) {
+        let kept = match line {
+            PatchLine::Context { left, .. } => PatchLine::Context { left, right },
+            PatchLine::Deleted(left) if selected(left) => line,
+            PatchLine::Deleted(left) => PatchLine::Context { left, right },
+            PatchLine::Added(index) if selected(index) => line,
+            PatchLine::Added(_) => continue,
+        };
+        if !matches!(kept, PatchLine::Deleted(_)) {
+            right += 1;
+        }
+        lines.push(kept);
+    }
+    lines
+}
+
+/// A `git apply`-able patch of the file containing only the changed lines on rows
+/// `first..=last`, or `None` when no changed line is selected.
+pub(crate) fn build_selection_patch(
+    file: &DiffFileView,
+    first: usize,
+    last: usize,
+) -> Option<String> {
+    let lines = select_patch_lines(file, first, last);
+    let removes_every_line = lines
+        .iter()
+        .all(|line| matches!(line, PatchLine::Deleted(_)));
+    let hunks = group_patch_hunks(lines, DEFAULT_CONTEXT_LINES);
+    if hunks.is_empty() {
+        return None;
+    }
+
+    let descriptor = &file.descriptor;
+    let old_path = descriptor
+        .base_path
+        .as_ref()
+        .or(descriptor.head_path.as_ref())?;
+    let new_path = descriptor.head_path.as_ref().unwrap_or(old_path);
+    let mode = |mode: &Option<String>| mode.clone().unwrap_or_else(|| DEFAULT_MODE.to_string());
+    let mut output = vec![format!("diff --git a/{old_path} b/{new_path}")];
+    if descriptor.base_source == FileContentSource::Missing {
+        output.push(format!("new file mode {}", mode(&descriptor.head_mode)));
+        output.push("--- /dev/null".to_string());
+    } else {
+        output.push(format!("--- a/{old_path}"));
+    }
+    // A deleted file is only removed when the selection takes every line with it.
+    if descriptor.head_source == FileContentSource::Missing && removes_every_line {
+        output.push(format!("deleted file mode {}", mode(&descriptor.base_mode)));
+        output.push("+++ /dev/null".to_string());
+    } else {
+        output.push(format!("+++ b/{new_path}"));
+    }
+    for hunk in &hunks {
+        output.extend(format_patch_hunk(file, hunk));
+    }
+    output.push(String::new());
+    Some(output.join("\n"))
+}
+
+/// The file's hunks as unified diff lines (`@@` headers included, no file headers).
+pub(crate) fn build_unified_diff_lines(file: &DiffFileView, context_lines: usize) -> Vec<String> {
+    let mut output = Vec::new();
+    for hunk in build_patch_hunks(file, context_lines) {
+        output.extend(format_patch_hunk(file, &hunk));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        fs,
+        path::{Path, PathBuf},
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use super::{build_selection_patch, build_unified_diff_lines};
+    use crate::{
+        git::run_git,
+        model::{DiffFileView, FileContentSource},
+    };
+
+    fn create_test_file(
+        left_lines: &[&str],
+        right_lines: &[&str],
+        deleted: &[usize],
+        added: &[usize],
+    ) -> DiffFileView {
+        DiffFileView {
+            left_lines: left_lines.iter().map(|line| line.to_string()).collect(),
+            right_lines: right_lines.iter().map(|line| line.to_string()).collect(),
+            left_deleted_line_indexes: deleted.iter().copied().collect::<HashSet<_>>(),
+            right_added_line_indexes: added.iter().copied().collect::<HashSet<_>>(),
+            ..DiffFileView::for_test("a.txt")
+        }
+    }
+
+    #[test]
+    fn unified_diff_splits_distant_changes_into_hunks() {
+        let left: Vec<String> = (1..=12).map(|line| line.to_string()).collect();
+        let mut right = left.clone();
+        right[1] = "two".to_string();
+        right.push("13".to_string());
+        let left: Vec<&str> = left.iter().map(String::as_str).collect();
+        let right: Vec<&str> = right.iter().map(String::as_str).collect();
+        let file = create_test_file(&left, &right, &[1], &[1, 12]);
+
+        let lines = build_unified_diff_lines(&file, 1);
+
+        assert_eq!(
+            lines,
+            vec![
+                "@@ -1,3 +1,3 @@",
+                " 1",
+                "-2",
+                "+two",
+                " 3",
+                "@@ -12,1 +12,2 @@",
+                " 12",
+                "+13"
+            ]
+        );
+    }
+
+    #[test]
+    fn selection_patch_keeps_only_selected_changes() {
+        let file = create_test_file(
+            &["a", "b", "c", "d"],
+            &["a", "B", "c", "D"],
+            &[1, 3],
+            &[1, 3],
+        );
+
+        assert_eq!(
+            build_selection_patch(&file, 3, 3).as_deref(),
+            Some(
+                "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n\
+                 @@ -1,4 +1,4 @@\n a\n b\n c\n-d\n+D\n"
+            )
+        );
+        assert_eq!(build_selection_patch(&file, 0, 0), None);
+    }
+
+    #[test]
+    fn unterminated_last_lines_get_the_no_newline_marker() {
+        let file = DiffFileView {
+            left_missing_final_newline: true,
+            right_missing_final_newline: true,
+            ..create_test_file(&["a", "b"], &["a", "c"], &[1], &[1])
+        };
+        assert_eq!(
+            build_unified_diff_lines(&file, 3),
+            vec![
+                "@@ -1,2 +1,2 @@",
+                " a",
+                "-b",
+                "\\ No newline at end of file",
+                "+c",
+                "\\ No newline at end of file"
+            ]
+        );
+
+        let file = DiffFileView {
+            left_missing_final_newline: true,
+            right_missing_final_newline: true,
+            ..create_test_file(&["a", "b"], &["A", "b"], &[0], &[0])
+        };
+        assert_eq!(
+            build_selection_patch(&file, 0, 0).as_deref(),
+            Some(
+                "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n\
+                 @@ -1,2 +1,2 @@\n-a\n+A\n b\n\\ No newline at end of file\n"
+            )
+        );
+    }
+
+    fn create_temp_repo() -> PathBuf {
+        let now_nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time should be after unix epoch")
+            .as_nanos();
+        let repo_root = std::env::temp_dir().join(format!("deff-patch-test-{now_nanos}"));
+        fs::create_dir_all(&repo_root).expect("temp repo is created");
+        run_git(["init", "--quiet"], &repo_root).expect("git init succeeds");
+        repo_root
+    }
+
+    fn check_patch_applies(repo_root: &Path, patch: &str) {
+        fs::write(repo_root.join("selection.patch"), patch).expect("patch is written");
+        run_git(["apply", "--check", "selection.patch"], repo_root)
+            .unwrap_or_else(|error| panic!("{patch} does not apply: {error}"));
+    }
+
+    #[test]
+    fn selection_patches_apply_to_unterminated_and_new_files() {
+        let repo_root = create_temp_repo();
+        fs::write(repo_root.join("a.txt"), "a\nb").expect("file is written");
+        let file = DiffFileView {
+            left_missing_final_newline: true,
+            right_missing_final_newline: true,
+            ..create_test_file(&["a", "b"], &["a", "B"], &[1], &[1])
+        };
+        check_patch_applies(
+            &repo_root,
+            &build_selection_patch(&file, 1, 1).expect("a change is selected"),
+        );
+
+        let mut file = create_test_file(&["<missing>"], &["#!/bin/sh", "true"], &[], &[0, 1]);
+        file.descriptor.base_source = FileContentSource::Missing;
+        file.descriptor.base_path = None;
+        file.descriptor.head_path = Some("run.sh".to_string());
+        file.descriptor.head_mode = Some("100755".to_string());
+        let patch = build_selection_patch(&file, 0, 1).expect("a change is selected");
+        assert!(patch.contains("\nnew file mode 100755\n"));
+        check_patch_applies(&repo_root, &patch);
+
+        fs::remove_dir_all(&repo_root).expect("temp repo is removed");
+    }
+
+    #[test]
+    fn added_file_ignores_missing_placeholder() {
+        let mut file = create_test_file(&["<missing>"], &["a", "b"], &[], &[0, 1]);
+        file.descriptor.base_source = FileContentSource::Missing;
+
+        assert_eq!(
+            build_unified_diff_lines(&file, 3),
+            vec!["@@ -0,0 +1,2 @@", "+a", "+b"]
+        );
+    }
+}
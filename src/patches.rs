@@ -0,0 +1,211 @@
+use std::{
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    git::{get_git_dir, list_recent_commits, run_git_text, run_git_with},
+    model::CommitSummary,
+};
+
+/// Scratch space for the split mails and the temporary index, relative to the git dir.
+const PATCHES_SCRATCH_DIR: &str = "deff/patches";
+
+/// A `git format-patch` series turned into commits on top of its base, oldest first.
+pub(crate) struct PatchSeries {
+    pub(crate) commits: Vec<CommitSummary>,
+    /// Author, date, subject, and body of each patch mail.
+    pub(crate) messages: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct MailHeader {
+    author: String,
+    email: String,
+    date: String,
+    subject: String,
+}
+
+/// Parses the `Key: value` lines `git mailinfo` prints to stdout.
+fn parse_mail_header(raw: &str) -> MailHeader {
+    let mut header = MailHeader::default();
+    for line in raw.lines() {
+        let Some((key, value)) = line.split_once(": ") else {
+            continue;
+        };
+        let field = match key {
+            "Author" => &mut header.author,
+            "Email" => &mut header.email,
+            "Date" => &mut header.date,
+            "Subject" => &mut header.subject,
+            _ => continue,
+        };
+        *field = value.trim().to_string();
+    }
+    header
+}
+
+/// Files of a directory in name order (how `format-patch` numbers them), or the single
+/// mbox file itself.
+fn list_mailboxes(input: &Path) -> Result<Vec<PathBuf>> {
+    if !input.is_dir() {
+        return Ok(vec![input.to_path_buf()]);
+    }
+    let mut paths: Vec<PathBuf> = fs::read_dir(input)
+        .with_context(|| format!("failed to read patch directory {}", input.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Applies each mail of the series to `base` in a private index and records it as a
+/// dangling commit, so the series can be browsed like `deff log` without touching the
+/// working tree, the real index, or any ref. Mails without a diff (cover letters) are
+/// skipped.
+pub(crate) fn load_patch_series(repo_root: &Path, input: &Path, base: &str) -> Result<PatchSeries> {
+    let scratch_dir = get_git_dir(repo_root)?.join(PATCHES_SCRATCH_DIR);
+    if scratch_dir.exists() {
+        fs::remove_dir_all(&scratch_dir)
+            .with_context(|| format!("failed to clear {}", scratch_dir.display()))?;
+    }
+    let split_dir = scratch_dir.join("split");
+    fs::create_dir_all(&split_dir)
+        .with_context(|| format!("failed to create {}", split_dir.display()))?;
+
+    let mailboxes = list_mailboxes(input)?;
+    let mut split_args = vec![
+        "mailsplit".to_string(),
+        format!("-o{}", split_dir.display()),
+        "--".to_string(),
+    ];
+    split_args.extend(mailboxes.iter().map(|path| path.display().to_string()));
+    run_git_text(split_args, repo_root)?;
+    let mut mails: Vec<PathBuf> = fs::read_dir(&split_dir)
+        .with_context(|| format!("failed to read {}", split_dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    mails.sort();
+
+    let base_commit = run_git_text(["rev-parse", &format!("{base}^{{commit}}")], repo_root)?
+        .trim()
+        .to_string();
+    let index_path = scratch_dir.join("index");
+    let index_env = [("GIT_INDEX_FILE", index_path.as_os_str())];
+    run_git_with(["read-tree", &base_commit], repo_root, &index_env, None)?;
+
+    let message_path = scratch_dir.join("message");
+    let patch_path = scratch_dir.join("patch");
+    let mut parent = base_commit.clone();
+    let mut messages = Vec::new();
+    for mail in &mails {
+        let raw_mail =
+            fs::read(mail).with_context(|| format!("failed to read {}", mail.display()))?;
+        let header_output = run_git_with(
+            [
+                OsStr::new("mailinfo"),
+                message_path.as_os_str(),
+                patch_path.as_os_str(),
+            ],
+            repo_root,
+            &[],
+            Some(&raw_mail),
+        )?;
+        let header = parse_mail_header(&String::from_utf8_lossy(&header_output));
+        let body = fs::read_to_string(&message_path).unwrap_or_default();
+        if fs::metadata(&patch_path).map_or(0, |metadata| metadata.len()) == 0 {
+            continue;
+        }
+
+        if let Err(error) = run_git_with(
+            [
+                OsStr::new("apply"),
+                OsStr::new("--cached"),
+                patch_path.as_os_str(),
+            ],
+            repo_root,
+            &index_env,
+            None,
+        ) {
+            bail!(
+                "patch {} \"{}\" does not apply on top of {base}: {error:#}",
+                messages.len() + 1,
+                header.subject
+            );
+        }
+        let tree =
+            String::from_utf8_lossy(&run_git_with(["write-tree"], repo_root, &index_env, None)?)
+                .trim()
+                .to_string();
+        fs::write(&message_path, format!("{}\n\n{body}", header.subject))
+            .with_context(|| format!("failed to write {}", message_path.display()))?;
+        let ident_env = [
+            ("GIT_AUTHOR_NAME", OsStr::new(&header.author)),
+            ("GIT_AUTHOR_EMAIL", OsStr::new(&header.email)),
+            ("GIT_AUTHOR_DATE", OsStr::new(&header.date)),
+            ("GIT_COMMITTER_NAME", OsStr::new(&header.author)),
+            ("GIT_COMMITTER_EMAIL", OsStr::new(&header.email)),
+            ("GIT_COMMITTER_DATE", OsStr::new(&header.date)),
+        ];
+        let commit = run_git_with(
+            [
+                OsStr::new("commit-tree"),
+                OsStr::new(&tree),
+                OsStr::new("-p"),
+                OsStr::new(&parent),
+                OsStr::new("-F"),
+                message_path.as_os_str(),
+            ],
+            repo_root,
+            &ident_env,
+            None,
+        )?;
+        parent = String::from_utf8_lossy(&commit).trim().to_string();
+
+        let mut message = vec![
+            format!("Author: {} <{}>", header.author, header.email),
+            format!("Date:   {}", header.date),
+            String::new(),
+            header.subject,
+            String::new(),
+        ];
+        message.extend(body.lines().map(str::to_string));
+        messages.push(message);
+    }
+
+    let mut commits = if messages.is_empty() {
+        Vec::new()
+    } else {
+        list_recent_commits(
+            repo_root,
+            &format!("{base_commit}..{parent}"),
+            messages.len(),
+        )?
+    };
+    commits.reverse();
+    Ok(PatchSeries { commits, messages })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MailHeader, parse_mail_header};
+
+    #[test]
+    fn mail_header_reads_mailinfo_fields() {
+        let raw = "Author: Ada Lovelace\nEmail: ada@example.com\nSubject: Add the engine\nDate: Mon, 1 Jan 2024 10:00:00 +0000\n\n";
+
+        assert_eq!(
+            parse_mail_header(raw),
+            MailHeader {
+                author: "Ada Lovelace".to_string(),
+                email: "ada@example.com".to_string(),
+                date: "Mon, 1 Jan 2024 10:00:00 +0000".to_string(),
+                subject: "Add the engine".to_string(),
+            }
+        );
+    }
+}
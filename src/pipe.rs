@@ -0,0 +1,177 @@
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{Context, Result, anyhow};
+use once_cell::sync::OnceCell;
+
+static PIPE_COMMANDS: OnceCell<Vec<(String, String)>> = OnceCell::new();
+
+/// Sets the named commands of the session's reviews; later calls are ignored.
+pub(crate) fn set_pipe_commands(commands: Vec<(String, String)>) {
+    let _ = PIPE_COMMANDS.set(commands);
+}
+
+pub(crate) fn pipe_commands() -> &'static [(String, String)] {
+    PIPE_COMMANDS.get().map_or(&[], Vec::as_slice)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum PipeScope {
+    Hunk,
+    File,
+}
+
+impl PipeScope {
+    fn as_str(self) -> &'static str {
+        match self {
+            PipeScope::Hunk => "hunk",
+            PipeScope::File => "file",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct PipeRequest {
+    pub(crate) command: String,
+    pub(crate) scope: PipeScope,
+    pub(crate) path: String,
+    pub(crate) input: String,
+}
+
+impl PipeRequest {
+    pub(crate) fn title(&self) -> String {
+        format!(
+            "| {} ({} of {})",
+            self.command,
+            self.scope.as_str(),
+            self.path
+        )
+    }
+}
+
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
+
+fn split_output_lines(raw: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(raw)
+        .lines()
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+pub(crate) fn run_pipe_command(request: &PipeRequest, cwd: &Path) -> Result<Vec<String>> {
+    let mut child = shell_command(&request.command)
+        .current_dir(cwd)
+        .env("DEFF_FILE", &request.path)
+        .env("DEFF_PIPE_SCOPE", request.scope.as_str())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to start `{}`", request.command))?;
+
+    // Feed stdin from a separate thread so a command that produces output before
+    // draining its input cannot deadlock against us.
+    let mut stdin = child.stdin.take().context("failed to open command stdin")?;
+    let input = request.input.clone();
+    let writer = thread::spawn(move || {
+        let _ = stdin.write_all(input.as_bytes());
+    });
+
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("failed to wait for `{}`", request.command))?;
+    let _ = writer.join();
+
+    let mut lines = split_output_lines(&output.stdout);
+    lines.extend(split_output_lines(&output.stderr));
+    if lines.is_empty() {
+        lines.push("<no output>".to_string());
+    }
+    if !output.status.success() {
+        lines.push(format!("[{}]", output.status));
+    }
+
+    Ok(lines)
+}
+
+/// A pipe command running on its own thread, so the review keeps drawing and taking
+/// keys until its output is ready.
+pub(crate) struct RunningPipe {
+    pub(crate) request: PipeRequest,
+    handle: JoinHandle<Result<Vec<String>>>,
+}
+
+impl RunningPipe {
+    pub(crate) fn spawn(request: PipeRequest, cwd: &Path) -> Self {
+        let (thread_request, cwd) = (request.clone(), cwd.to_path_buf());
+        let handle = thread::spawn(move || run_pipe_command(&thread_request, &cwd));
+        Self { request, handle }
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// The command's output lines, or its error as one line; waits when it is still
+    /// running.
+    pub(crate) fn output_lines(self) -> Vec<String> {
+        let output = self
+            .handle
+            .join()
+            .unwrap_or_else(|_| Err(anyhow!("`{}` panicked", self.request.command)));
+        output.unwrap_or_else(|error| vec![format!("{error:#}")])
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::{PipeRequest, PipeScope, RunningPipe, run_pipe_command};
+
+    fn request(command: &str, input: &str) -> PipeRequest {
+        PipeRequest {
+            command: command.to_string(),
+            scope: PipeScope::File,
+            path: "src/main.rs".to_string(),
+            input: input.to_string(),
+        }
+    }
+
+    #[test]
+    fn pipe_command_receives_input_and_returns_output_lines() {
+        let lines = run_pipe_command(&request("tr a-z A-Z", "one\ntwo\n"), &std::env::temp_dir())
+            .expect("command should run");
+        assert_eq!(lines, vec!["ONE".to_string(), "TWO".to_string()]);
+    }
+
+    #[test]
+    fn pipe_command_reports_failing_exit_status() {
+        let lines = run_pipe_command(&request("echo oops >&2; exit 3", ""), &std::env::temp_dir())
+            .expect("command should run");
+        assert_eq!(lines[0], "oops");
+        assert!(lines[1].contains('3'));
+    }
+
+    #[test]
+    fn running_pipe_returns_the_output_from_its_thread() {
+        let pipe = RunningPipe::spawn(request("sleep 0.3; cat", "one\n"), &std::env::temp_dir());
+        assert!(!pipe.is_finished());
+        assert_eq!(pipe.output_lines(), vec!["one".to_string()]);
+
+        let pipe = RunningPipe::spawn(request("cat", ""), &std::env::temp_dir().join("missing"));
+        assert!(pipe.output_lines()[0].starts_with("failed to start `cat`"));
+    }
+}
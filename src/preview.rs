@@ -0,0 +1,116 @@
+use std::path::{Component, Path};
+
+use ratatui::{
+    style::{Color, Style},
+    text::Line,
+};
+
+use crate::{
+    model::{DiffFileDescriptor, DiffFileView, FileContentSource, OutputFormat, RenderOptions},
+    output::{line_to_ansi, render_file_section},
+    patch::build_unified_diff_lines,
+    text::truncate_to_width,
+};
+
+/// Preview width when neither `--width` nor fzf's `$FZF_PREVIEW_COLUMNS` says otherwise.
+pub(crate) const DEFAULT_PREVIEW_COLUMNS: u16 = 80;
+
+/// Joins `path` (as typed, relative to the current directory) onto the directory's
+/// repository prefix from `git rev-parse --show-prefix`, resolving `.` and `..` the way
+/// `git status` prints them. `None` when the path leaves the repository.
+pub(crate) fn repo_relative_path(prefix: &str, path: &Path) -> Option<String> {
+    let mut parts: Vec<String> = prefix
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .map(str::to_string)
+        .collect();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// A file `git status` lists as untracked, shown as added in full.
+pub(crate) fn untracked_descriptor(path: &str) -> DiffFileDescriptor {
+    DiffFileDescriptor {
+        raw_status: "??".to_string(),
+        display_path: path.to_string(),
+        base_path: None,
+        head_path: Some(path.to_string()),
+        base_source: FileContentSource::Missing,
+        head_source: FileContentSource::WorkingTree,
+        base_mode: None,
+        head_mode: None,
+    }
+}
+
+fn unified_line_style(line: &str) -> Style {
+    match line.chars().next() {
+        Some('@') => Style::default().fg(Color::Cyan),
+        Some('-') => Style::default().fg(Color::Red),
+        Some('+') => Style::default().fg(Color::Green),
+        _ => Style::default(),
+    }
+}
+
+/// The file's side-by-side section, or its unified hunks with `context_lines` around
+/// each, cut to `columns`.
+pub(crate) fn render_preview(
+    file: &DiffFileView,
+    format: OutputFormat,
+    unified: bool,
+    context_lines: usize,
+    columns: u16,
+    options: &RenderOptions,
+) -> String {
+    let mut output = if unified {
+        let mut lines = vec![format!(
+            "{}  [{}]",
+            file.descriptor.display_path, file.descriptor.raw_status
+        )];
+        lines.extend(build_unified_diff_lines(file, context_lines));
+        lines
+            .into_iter()
+            .map(|line| {
+                let line = truncate_to_width(&line, columns as usize);
+                match format {
+                    OutputFormat::Plain => line,
+                    OutputFormat::Ansi => {
+                        line_to_ansi(&Line::styled(line.clone(), unified_line_style(&line)))
+                    }
+                }
+            })
+            .collect()
+    } else {
+        render_file_section(file, format, columns, options)
+    };
+    output.push(String::new());
+    output.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::repo_relative_path;
+
+    #[test]
+    fn preview_paths_resolve_against_the_repository_prefix() {
+        assert_eq!(
+            repo_relative_path("src/app/", Path::new("../lib.rs")).as_deref(),
+            Some("src/lib.rs")
+        );
+        assert_eq!(
+            repo_relative_path("", Path::new("./README.md")).as_deref(),
+            Some("README.md")
+        );
+        assert_eq!(repo_relative_path("src/", Path::new("../../x")), None);
+    }
+}
@@ -0,0 +1,360 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use crossterm::event::KeyEvent;
+use regex::Regex;
+use toml::{Table, Value};
+use tracing::warn;
+
+use crate::{
+    codeowners::compile_pattern,
+    keymap::{Action, parse_binding},
+    model::{DiffFileDescriptor, DiffFileView, StrategyArg, ThemeMode},
+};
+
+/// Team-wide settings, checked in at the repository root.
+pub(crate) const PROJECT_CONFIG_FILE: &str = ".deff.toml";
+/// Personal settings, under `$XDG_CONFIG_HOME` (else `~/.config`).
+const USER_CONFIG_FILE: &str = "deff/config.toml";
+
+/// Settings from the user's `~/.config/deff/config.toml` and the repository's
+/// `.deff.toml`, which wins where both set something. Command-line flags override them,
+/// and they override `git config`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ProjectConfig {
+    pub(crate) strategy: Option<StrategyArg>,
+    pub(crate) base: Option<String>,
+    pub(crate) theme: Option<ThemeMode>,
+    /// Unchanged lines around each hunk in unified output (`deff export`,
+    /// `deff preview --unified`).
+    pub(crate) context_lines: Option<usize>,
+    pub(crate) review_state_dir: Option<PathBuf>,
+    pub(crate) review_notes: Option<bool>,
+    exclude: Vec<Regex>,
+    collapse: Vec<Regex>,
+    /// Later patterns win, as in `.gitattributes`.
+    syntax: Vec<(Regex, String)>,
+    /// Actions moved to other keys; later entries win.
+    pub(crate) keys: Vec<(Action, Vec<KeyEvent>)>,
+    /// Shell commands run by typing their name at the `|` prompt; later entries win.
+    /// Only the user's config may name them, so checking out a repository cannot make
+    /// a name run its commands.
+    pub(crate) pipe: Vec<(String, String)>,
+}
+
+fn expect_string<'a>(key: &str, value: &'a Value) -> Result<&'a str> {
+    value
+        .as_str()
+        .with_context(|| format!("{key} must be a string"))
+}
+
+fn compile_glob(key: &str, glob: &str) -> Result<Regex> {
+    compile_pattern(glob).with_context(|| format!("{key}: invalid pattern {glob:?}"))
+}
+
+fn parse_patterns(key: &str, value: &Value) -> Result<Vec<Regex>> {
+    value
+        .as_array()
+        .with_context(|| format!("{key} must be a list of path patterns"))?
+        .iter()
+        .map(|glob| compile_glob(key, expect_string(key, glob)?))
+        .collect()
+}
+
+fn is_path_matched(patterns: &[Regex], path: Option<&str>) -> bool {
+    path.is_some_and(|path| patterns.iter().any(|pattern| pattern.is_match(path)))
+}
+
+fn user_config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|directory| !directory.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join(USER_CONFIG_FILE))
+}
+
+impl ProjectConfig {
+    /// The user's config overridden by the repository's, when there is a repository.
+    pub(crate) fn load(repo_root: Option<&Path>) -> Result<Self> {
+        let user = match user_config_path() {
+            Some(path) => Self::load_file(&path)?,
+            None => Self::default(),
+        };
+        match repo_root {
+            Some(repo_root) => {
+                Ok(user.overridden_by(Self::load_file(&repo_root.join(PROJECT_CONFIG_FILE))?))
+            }
+            None => Ok(user),
+        }
+    }
+
+    /// An absent file is an empty config.
+    fn load_file(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                Self::parse(&content).map_err(|error| anyhow!("{}: {error:#}", path.display()))
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error).with_context(|| format!("failed to read {}", path.display())),
+        }
+    }
+
+    /// `other`'s settings where it has them. The comparison is taken as a whole, and
+    /// path patterns from both apply, `other`'s syntax overrides last. `other` is the
+    /// repository's config, whose `[pipe]` commands are ignored.
+    fn overridden_by(self, other: Self) -> Self {
+        if !other.pipe.is_empty() {
+            warn!(
+                "ignoring [pipe] in {PROJECT_CONFIG_FILE}; named pipe commands belong in the user config"
+            );
+        }
+        let (strategy, base) = if other.strategy.is_some() || other.base.is_some() {
+            (other.strategy, other.base)
+        } else {
+            (self.strategy, self.base)
+        };
+        Self {
+            strategy,
+            base,
+            theme: other.theme.or(self.theme),
+            context_lines: other.context_lines.or(self.context_lines),
+            review_state_dir: other.review_state_dir.or(self.review_state_dir),
+            review_notes: other.review_notes.or(self.review_notes),
+            exclude: [self.exclude, other.exclude].concat(),
+            collapse: [self.collapse, other.collapse].concat(),
+            syntax: [self.syntax, other.syntax].concat(),
+            keys: [self.keys, other.keys].concat(),
+            pipe: self.pipe,
+        }
+    }
+
+    pub(crate) fn parse(content: &str) -> Result<Self> {
+        let table: Table = content.parse()?;
+        let mut config = Self::default();
+        for (key, value) in &table {
+            match key.as_str() {
+                "strategy" => {
+                    config.strategy = Some(match expect_string(key, value)? {
+                        "upstream-ahead" => StrategyArg::UpstreamAhead,
+                        "range" => StrategyArg::Range,
+                        "staged" => StrategyArg::Staged,
+                        "unstaged" => StrategyArg::Unstaged,
+                        other => bail!(
+                            "strategy must be upstream-ahead, range, staged, or unstaged, not {other:?}"
+                        ),
+                    });
+                }
+                "base" => config.base = Some(expect_string(key, value)?.to_string()),
+                "theme" => {
+                    config.theme = Some(match expect_string(key, value)? {
+                        "auto" => ThemeMode::Auto,
+                        "dark" => ThemeMode::Dark,
+                        "light" => ThemeMode::Light,
+                        other => bail!("theme must be auto, dark, or light, not {other:?}"),
+                    });
+                }
+                "context" => {
+                    let lines = value
+                        .as_integer()
+                        .and_then(|lines| usize::try_from(lines).ok())
+                        .context("context must be a number of lines")?;
+                    config.context_lines = Some(lines);
+                }
+                "exclude" => config.exclude = parse_patterns(key, value)?,
+                "collapse" => config.collapse = parse_patterns(key, value)?,
+                "syntax" => {
+                    let overrides = value
+                        .as_table()
+                        .context("syntax must be a table of path pattern = syntax name")?;
+                    for (glob, name) in overrides {
+                        config.syntax.push((
+                            compile_glob(key, glob)?,
+                            expect_string(key, name)?.to_string(),
+                        ));
+                    }
+                }
+                "keys" => {
+                    let bindings = value
+                        .as_table()
+                        .context("keys must be a table of action = key or list of keys")?;
+                    for (name, keys) in bindings {
+                        let setting = format!("keys.{name}");
+                        let keys: Vec<&str> = match keys {
+                            Value::Array(keys) => keys
+                                .iter()
+                                .map(|key| expect_string(&setting, key))
+                                .collect::<Result<_>>()?,
+                            key => vec![expect_string(&setting, key)?],
+                        };
+                        config.keys.push(parse_binding(name, &keys)?);
+                    }
+                }
+                "pipe" => {
+                    let commands = value
+                        .as_table()
+                        .context("pipe must be a table of name = shell command")?;
+                    for (name, command) in commands {
+                        let command = expect_string(&format!("pipe.{name}"), command)?;
+                        config.pipe.push((name.clone(), command.to_string()));
+                    }
+                }
+                "review-state-dir" => {
+                    config.review_state_dir = Some(PathBuf::from(expect_string(key, value)?));
+                }
+                "review-notes" => {
+                    config.review_notes = Some(
+                        value
+                            .as_bool()
+                            .context("review-notes must be true or false")?,
+                    );
+                }
+                _ => bail!("unknown setting {key:?}"),
+            }
+        }
+
+        match (config.strategy, &config.base) {
+            (Some(StrategyArg::Range), None) => bail!("strategy = \"range\" needs a base"),
+            (Some(strategy), Some(_)) if strategy != StrategyArg::Range => {
+                bail!("base only applies to strategy = \"range\"")
+            }
+            _ => {}
+        }
+        Ok(config)
+    }
+
+    /// Whether every path of `descriptor` is excluded, so the file is left out of reviews.
+    pub(crate) fn is_excluded(&self, descriptor: &DiffFileDescriptor) -> bool {
+        !self.exclude.is_empty()
+            && [&descriptor.base_path, &descriptor.head_path]
+                .into_iter()
+                .flatten()
+                .all(|path| is_path_matched(&self.exclude, Some(path)))
+    }
+
+    /// Applies the syntax overrides and marks the files to show collapsed.
+    pub(crate) fn apply_to_views(&self, files: &mut [DiffFileView]) {
+        for file in files {
+            let paths = [
+                file.descriptor.base_path.as_deref(),
+                file.descriptor.head_path.as_deref(),
+            ];
+            file.collapsed = paths
+                .iter()
+                .any(|path| is_path_matched(&self.collapse, *path));
+
+            let syntax_for = |path: Option<&str>| {
+                let path = path?;
+                self.syntax
+                    .iter()
+                    .rev()
+                    .find(|(pattern, _)| pattern.is_match(path))
+                    .map(|(_, name)| name.clone())
+            };
+            if let Some(name) = syntax_for(paths[0]) {
+                file.left_language = Some(name);
+            }
+            if let Some(name) = syntax_for(paths[1]) {
+                file.right_language = Some(name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProjectConfig;
+    use crate::model::{DiffFileView, StrategyArg, ThemeMode};
+
+    fn create_view(path: &str) -> DiffFileView {
+        DiffFileView::for_test(path)
+    }
+
+    #[test]
+    fn settings_parse_and_apply_to_paths() {
+        let config = ProjectConfig::parse(
+            r#"
+strategy = "range"
+base = "origin/main"
+context = 5
+exclude = ["Cargo.lock", "vendor/"]
+collapse = ["*.snap"]
+
+[syntax]
+"*.tmpl" = "HTML"
+"mail/*.tmpl" = "Plain Text"
+"#,
+        )
+        .expect("config parses");
+        assert_eq!(config.strategy, Some(StrategyArg::Range));
+        assert_eq!(config.base.as_deref(), Some("origin/main"));
+        assert_eq!(config.context_lines, Some(5));
+        assert!(config.is_excluded(&create_view("Cargo.lock").descriptor));
+        assert!(config.is_excluded(&create_view("vendor/lib/a.rs").descriptor));
+        assert!(!config.is_excluded(&create_view("src/main.rs").descriptor));
+
+        let mut files = vec![
+            create_view("tests/snapshots/a.snap"),
+            create_view("web/page.tmpl"),
+            create_view("mail/welcome.tmpl"),
+        ];
+        config.apply_to_views(&mut files);
+        assert!(files[0].collapsed);
+        assert!(!files[1].collapsed);
+        assert_eq!(files[1].right_language.as_deref(), Some("HTML"));
+        assert_eq!(files[2].left_language.as_deref(), Some("Plain Text"));
+
+        assert!(ProjectConfig::parse("strategy = \"range\"").is_err());
+        assert!(ProjectConfig::parse("colour = true").is_err());
+    }
+
+    #[test]
+    fn repository_settings_override_the_users_and_patterns_add_up() {
+        let user = ProjectConfig::parse(
+            r#"
+strategy = "staged"
+theme = "light"
+context = 8
+exclude = ["*.lock"]
+
+[keys]
+next-file = ["n", "right"]
+quit = "x"
+
+[pipe]
+fmt = "rustfmt --emit stdout"
+"#,
+        )
+        .expect("user config parses");
+        let project = ProjectConfig::parse(
+            r#"
+base = "origin/main"
+context = 3
+exclude = ["vendor/"]
+
+[pipe]
+fmt = "prettier --stdin-filepath \"$DEFF_FILE\""
+"#,
+        )
+        .expect("project config parses");
+        let config = user.overridden_by(project);
+        assert_eq!(config.strategy, None);
+        assert_eq!(config.base.as_deref(), Some("origin/main"));
+        assert_eq!(config.theme, Some(ThemeMode::Light));
+        assert_eq!(config.context_lines, Some(3));
+        assert!(config.is_excluded(&create_view("Cargo.lock").descriptor));
+        assert!(config.is_excluded(&create_view("vendor/a.rs").descriptor));
+
+        assert_eq!(config.keys.len(), 2);
+        assert_eq!(
+            config.pipe,
+            [("fmt".to_string(), "rustfmt --emit stdout".to_string())]
+        );
+
+        assert!(ProjectConfig::parse("theme = \"solarized\"").is_err());
+        assert!(ProjectConfig::parse("[keys]\nquit = 1").is_err());
+        assert!(ProjectConfig::parse("[pipe]\nfmt = true").is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use crate::model::CommitSummary;
+
+/// The quick mark given to a commit after reviewing it in `deff rebase-plan`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum RebaseMark {
+    LooksFine,
+    NeedsEdit,
+    ShouldSquash,
+}
+
+impl RebaseMark {
+    /// In the order the mark prompt offers them.
+    pub(crate) const ALL: [Self; 3] = [Self::LooksFine, Self::NeedsEdit, Self::ShouldSquash];
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::LooksFine => "looks fine",
+            Self::NeedsEdit => "needs edit",
+            Self::ShouldSquash => "should squash",
+        }
+    }
+
+    fn command(self) -> &'static str {
+        match self {
+            Self::LooksFine => "pick",
+            Self::NeedsEdit => "edit",
+            Self::ShouldSquash => "squash",
+        }
+    }
+}
+
+/// A `git rebase -i` todo list for `commits` (oldest first), one command per mark.
+/// Unmarked commits are kept as `pick` under a comment, and so is a first commit marked
+/// squash, which has nothing to be squashed into.
+pub(crate) fn format_rebase_todo(
+    summary: &str,
+    base_commit: &str,
+    commits: &[CommitSummary],
+    marks: &[Option<RebaseMark>],
+) -> String {
+    let mut todo = format!(
+        "# deff rebase plan for {summary}\n\
+         # use with: git rebase -i {base_commit}\n\n"
+    );
+    for (index, commit) in commits.iter().enumerate() {
+        let mark = marks.get(index).copied().flatten();
+        let command = match mark {
+            None => {
+                todo.push_str("# not reviewed\n");
+                "pick"
+            }
+            Some(RebaseMark::ShouldSquash) if index == 0 => {
+                todo.push_str("# should squash, but no earlier commit is in the plan\n");
+                "pick"
+            }
+            Some(mark) => mark.command(),
+        };
+        todo.push_str(&format!(
+            "{command} {} {}\n",
+            commit.short_hash, commit.subject
+        ));
+    }
+    todo
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RebaseMark, format_rebase_todo};
+    use crate::model::CommitSummary;
+
+    fn commit(short_hash: &str, subject: &str) -> CommitSummary {
+        CommitSummary {
+            hash: short_hash.repeat(5),
+            short_hash: short_hash.to_string(),
+            author: "Ada".to_string(),
+            date: "2026-10-16".to_string(),
+            subject: subject.to_string(),
+        }
+    }
+
+    #[test]
+    fn todo_lists_use_the_marks_and_keep_unmarked_commits() {
+        let commits = vec![
+            commit("aaaaaaa", "Add parser"),
+            commit("bbbbbbb", "Fix typo"),
+            commit("ccccccc", "Add tests"),
+        ];
+        let marks = vec![
+            Some(RebaseMark::NeedsEdit),
+            Some(RebaseMark::ShouldSquash),
+            None,
+        ];
+
+        assert_eq!(
+            format_rebase_todo("main..HEAD", "1234567", &commits, &marks),
+            "# deff rebase plan for main..HEAD\n\
+             # use with: git rebase -i 1234567\n\n\
+             edit aaaaaaa Add parser\n\
+             squash bbbbbbb Fix typo\n\
+             # not reviewed\n\
+             pick ccccccc Add tests\n"
+        );
+        assert!(
+            format_rebase_todo("main..HEAD", "1234567", &commits[1..], &marks[1..])
+                .contains("# should squash, but no earlier commit is in the plan\npick bbbbbbb")
+        );
+    }
+}
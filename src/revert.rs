@@ -0,0 +1,297 @@
+use std::{collections::HashMap, ffi::OsStr, fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    annotations::AnnotationSet,
+    git::{get_git_dir, run_git, run_git_text, run_git_with, run_git_with_exit_code},
+    model::{Annotation, AnnotationSeverity, ResolvedComparison, StrategyId},
+};
+
+/// Scratch files for the three merge inputs and the temporary index, relative to the
+/// git dir.
+const REVERT_SCRATCH_DIR: &str = "deff/revert";
+/// The preview commit is never referenced, but `commit-tree` still needs an identity.
+const PREVIEW_IDENT: [(&str, &str); 4] = [
+    ("GIT_AUTHOR_NAME", "deff"),
+    ("GIT_AUTHOR_EMAIL", "deff@localhost"),
+    ("GIT_COMMITTER_NAME", "deff"),
+    ("GIT_COMMITTER_EMAIL", "deff@localhost"),
+];
+
+/// HEAD with a commit reverted, as a comparison from HEAD to a dangling commit, plus
+/// annotations on the conflict regions the revert would leave behind.
+pub(crate) struct RevertPreview {
+    pub(crate) comparison: ResolvedComparison,
+    pub(crate) conflicts: AnnotationSet,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct TreeEntry {
+    mode: String,
+    oid: String,
+}
+
+/// Parses `git ls-tree -r -z` output into path -> entry.
+fn parse_tree_entries(raw: &str) -> HashMap<String, TreeEntry> {
+    raw.split('\0')
+        .filter_map(|record| {
+            let (meta, path) = record.split_once('\t')?;
+            let mut fields = meta.split(' ');
+            let mode = fields.next()?.to_string();
+            let oid = fields.nth(1)?.to_string();
+            Some((path.to_string(), TreeEntry { mode, oid }))
+        })
+        .collect()
+}
+
+fn list_tree_entries(
+    repo_root: &Path,
+    commit: &str,
+    paths: &[String],
+) -> Result<HashMap<String, TreeEntry>> {
+    let mut args = vec!["ls-tree", "-r", "-z", commit, "--"];
+    args.extend(paths.iter().map(String::as_str));
+    Ok(parse_tree_entries(&run_git_text(args, repo_root)?))
+}
+
+/// Zero-based `(first, last)` lines of each `<<<<<<<` ... `>>>>>>>` region.
+fn find_conflict_regions(text: &str) -> Vec<(usize, usize)> {
+    let mut regions = Vec::new();
+    let mut start = None;
+    for (index, line) in text.lines().enumerate() {
+        if line.starts_with("<<<<<<< ") {
+            start = Some(index);
+        } else if line.starts_with(">>>>>>> ")
+            && let Some(first) = start.take()
+        {
+            regions.push((first, index));
+        }
+    }
+    regions
+}
+
+struct RevertIndex<'a> {
+    repo_root: &'a Path,
+    index_env: [(&'static str, &'a OsStr); 1],
+}
+
+impl RevertIndex<'_> {
+    fn set(&self, path: &str, entry: Option<&TreeEntry>) -> Result<()> {
+        match entry {
+            Some(entry) => run_git_with(
+                [
+                    "update-index",
+                    "--add",
+                    "--cacheinfo",
+                    &format!("{},{},{path}", entry.mode, entry.oid),
+                ],
+                self.repo_root,
+                &self.index_env,
+                None,
+            ),
+            None => run_git_with(
+                ["update-index", "--force-remove", "--", path],
+                self.repo_root,
+                &self.index_env,
+                None,
+            ),
+        }
+        .map(|_| ())
+    }
+}
+
+/// Three-way merges every file the commit touched, with the commit as base, HEAD as
+/// ours, and the commit's parent as theirs, which is what `git revert` does. Nothing
+/// outside `.git/deff/revert` is written; conflicted files keep their markers.
+pub(crate) fn build_revert_preview(repo_root: &Path, commit: &str) -> Result<RevertPreview> {
+    let target = run_git_text(["rev-parse", &format!("{commit}^{{commit}}")], repo_root)?
+        .trim()
+        .to_string();
+    let Ok(parent) = run_git_text(["rev-parse", &format!("{target}^1")], repo_root) else {
+        bail!("{commit} is a root commit; there is nothing to revert it to");
+    };
+    let parent = parent.trim().to_string();
+    let head = run_git_text(["rev-parse", "HEAD"], repo_root)?
+        .trim()
+        .to_string();
+    let short = run_git_text(["rev-parse", "--short", &target], repo_root)?
+        .trim()
+        .to_string();
+    let subject = run_git_text(["log", "-1", "--format=%s", &target], repo_root)?
+        .trim()
+        .to_string();
+
+    let paths: Vec<String> = run_git_text(
+        [
+            "diff",
+            "--no-renames",
+            "--name-only",
+            "-z",
+            &parent,
+            &target,
+        ],
+        repo_root,
+    )?
+    .split('\0')
+    .filter(|path| !path.is_empty())
+    .map(str::to_string)
+    .collect();
+    let base_entries = list_tree_entries(repo_root, &target, &paths)?;
+    let our_entries = list_tree_entries(repo_root, &head, &paths)?;
+    let their_entries = list_tree_entries(repo_root, &parent, &paths)?;
+
+    let scratch_dir = get_git_dir(repo_root)?.join(REVERT_SCRATCH_DIR);
+    fs::create_dir_all(&scratch_dir)
+        .with_context(|| format!("failed to create {}", scratch_dir.display()))?;
+    let index_path = scratch_dir.join("index");
+    let index = RevertIndex {
+        repo_root,
+        index_env: [("GIT_INDEX_FILE", index_path.as_os_str())],
+    };
+    run_git_with(["read-tree", &head], repo_root, &index.index_env, None)?;
+
+    let parent_label = format!("parent of {short}");
+    let mut conflicts = AnnotationSet::default();
+    let mut conflicted_paths = Vec::new();
+    for path in &paths {
+        let base = base_entries.get(path);
+        let ours = our_entries.get(path);
+        let theirs = their_entries.get(path);
+        if ours == base {
+            index.set(path, theirs)?;
+            continue;
+        }
+        if ours == theirs {
+            continue;
+        }
+        let (Some(ours), Some(base), Some(theirs)) = (ours, base, theirs) else {
+            // Added or deleted on one side and changed on the other: git keeps HEAD's
+            // version and reports a conflict, so do the same.
+            conflicted_paths.push(path.clone());
+            continue;
+        };
+
+        let mut inputs = Vec::new();
+        for (name, entry) in [("ours", ours), ("base", base), ("theirs", theirs)] {
+            let input_path = scratch_dir.join(name);
+            fs::write(
+                &input_path,
+                run_git(["cat-file", "blob", &entry.oid], repo_root)?,
+            )
+            .with_context(|| format!("failed to write {}", input_path.display()))?;
+            inputs.push(input_path);
+        }
+        let (conflict_count, merged) = run_git_with_exit_code(
+            [
+                OsStr::new("merge-file"),
+                OsStr::new("-p"),
+                OsStr::new("-L"),
+                OsStr::new("HEAD"),
+                OsStr::new("-L"),
+                OsStr::new(&short),
+                OsStr::new("-L"),
+                OsStr::new(&parent_label),
+                inputs[0].as_os_str(),
+                inputs[1].as_os_str(),
+                inputs[2].as_os_str(),
+            ],
+            repo_root,
+        )?;
+        if !(0..128).contains(&conflict_count) {
+            // merge-file refuses binary files; keep HEAD's version.
+            conflicted_paths.push(path.clone());
+            continue;
+        }
+        let oid = run_git_with(
+            ["hash-object", "-w", "--stdin"],
+            repo_root,
+            &[],
+            Some(&merged),
+        )?;
+        index.set(
+            path,
+            Some(&TreeEntry {
+                mode: ours.mode.clone(),
+                oid: String::from_utf8_lossy(&oid).trim().to_string(),
+            }),
+        )?;
+        if conflict_count > 0 {
+            conflicted_paths.push(path.clone());
+            for (first, last) in find_conflict_regions(&String::from_utf8_lossy(&merged)) {
+                conflicts.push(
+                    path.clone(),
+                    Annotation {
+                        line_index: first,
+                        severity: AnnotationSeverity::Error,
+                        message: format!(
+                            "revert conflict (lines {}-{}): HEAD changed this since {short}",
+                            first + 1,
+                            last + 1
+                        ),
+                        rule: None,
+                    },
+                );
+            }
+        }
+    }
+
+    let tree = run_git_with(["write-tree"], repo_root, &index.index_env, None)?;
+    let tree = String::from_utf8_lossy(&tree).trim().to_string();
+    let message = format!("Revert \"{subject}\"\n\nThis reverts commit {target}.");
+    let ident_env = PREVIEW_IDENT.map(|(key, value)| (key, OsStr::new(value)));
+    let revert_commit = run_git_with(
+        ["commit-tree", &tree, "-p", &head, "-m", &message],
+        repo_root,
+        &ident_env,
+        None,
+    )?;
+
+    let mut details = vec![format!("reverting: {short} {subject}")];
+    details.push(if conflicted_paths.is_empty() {
+        "conflicts: none".to_string()
+    } else {
+        format!(
+            "conflicts: {} ({})",
+            conflicted_paths.len(),
+            conflicted_paths.join(", ")
+        )
+    });
+    Ok(RevertPreview {
+        comparison: ResolvedComparison {
+            strategy_id: StrategyId::Range,
+            base_ref: "HEAD".to_string(),
+            head_ref: format!("revert {short}"),
+            base_commit: head,
+            head_commit: String::from_utf8_lossy(&revert_commit).trim().to_string(),
+            summary: format!("HEAD..revert {short}"),
+            details,
+            ahead_count: None,
+            includes_uncommitted: false,
+            range_commits: Vec::new(),
+        },
+        conflicts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TreeEntry, find_conflict_regions, parse_tree_entries};
+
+    #[test]
+    fn parses_ls_tree_records_and_conflict_regions() {
+        let entries =
+            parse_tree_entries("100644 blob abc123\tsrc/a b.rs\x00100755 blob def456\tx\0");
+        assert_eq!(
+            entries.get("src/a b.rs"),
+            Some(&TreeEntry {
+                mode: "100644".to_string(),
+                oid: "abc123".to_string(),
+            })
+        );
+        assert_eq!(entries.len(), 2);
+
+        let merged = "a\n<<<<<<< HEAD\nb\n=======\nc\n>>>>>>> parent of abc\nd\n";
+        assert_eq!(find_conflict_regions(merged), vec![(1, 5)]);
+    }
+}
@@ -0,0 +1,85 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    git::{run_git, run_git_text},
+    model::ResolvedComparison,
+};
+
+const TRAILER_KEY: &str = "Reviewed-by";
+
+/// `git var GIT_COMMITTER_IDENT` is `Name <email> <timestamp> <zone>`; keeps the
+/// `Name <email>` part.
+fn parse_ident(raw: &str) -> Option<&str> {
+    let mut fields = raw.trim().rsplitn(3, ' ');
+    let (_zone, _timestamp) = (fields.next()?, fields.next()?);
+    fields.next().filter(|ident| ident.ends_with('>'))
+}
+
+/// `Reviewed-by: Name <email>` for the configured git identity.
+pub(crate) fn reviewed_by_trailer(repo_root: &Path) -> Result<String> {
+    let raw = run_git_text(["var", "GIT_COMMITTER_IDENT"], repo_root)
+        .context("set user.name and user.email to sign off a review")?;
+    let ident =
+        parse_ident(&raw).with_context(|| format!("unexpected git identity {:?}", raw.trim()))?;
+    Ok(format!("{TRAILER_KEY}: {ident}"))
+}
+
+fn has_trailer(message: &str, trailer: &str) -> bool {
+    message.lines().any(|line| line.trim() == trailer)
+}
+
+/// Amends the reviewed head commit's message with `trailer`, leaving staged changes
+/// out; returns `false` when the message already has it. Only the checked-out commit
+/// can be amended, so other heads need the trailer printed instead.
+pub(crate) fn sign_off_head(
+    repo_root: &Path,
+    comparison: &ResolvedComparison,
+    trailer: &str,
+) -> Result<bool> {
+    if comparison.includes_uncommitted {
+        bail!("the review includes uncommitted changes; commit them first or use --print");
+    }
+    let checked_out = run_git_text(["rev-parse", "HEAD"], repo_root)?;
+    if checked_out.trim() != comparison.head_commit {
+        bail!(
+            "{} is not the checked-out commit; use --print to add the trailer yourself",
+            comparison.head_ref
+        );
+    }
+    let message = run_git_text(["show", "-s", "--format=%B", "HEAD"], repo_root)?;
+    if has_trailer(&message, trailer) {
+        return Ok(false);
+    }
+    run_git(
+        [
+            "commit",
+            "--amend",
+            "--only",
+            "--no-edit",
+            "--trailer",
+            trailer,
+        ],
+        repo_root,
+    )?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{has_trailer, parse_ident};
+
+    #[test]
+    fn identities_drop_the_timestamp_and_existing_trailers_are_found() {
+        assert_eq!(
+            parse_ident("Ada Lovelace <ada@example.com> 1760000000 +0200\n"),
+            Some("Ada Lovelace <ada@example.com>")
+        );
+        assert_eq!(parse_ident("nobody"), None);
+
+        let message = "Fix parser\n\nReviewed-by: Ada <ada@example.com>\n";
+        assert!(has_trailer(message, "Reviewed-by: Ada <ada@example.com>"));
+        assert!(!has_trailer(message, "Reviewed-by: Bob <bob@example.com>"));
+    }
+}
@@ -0,0 +1,186 @@
+use crate::model::DiffFileView;
+
+const UNKNOWN_LANGUAGE: &str = "Other";
+/// Widest change bar, given to the file with the most changed lines.
+const CHANGE_BAR_WIDTH: usize = 20;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct LanguageStats {
+    pub(crate) language: String,
+    pub(crate) file_count: usize,
+    pub(crate) added: usize,
+    pub(crate) deleted: usize,
+}
+
+fn file_language(file: &DiffFileView) -> &str {
+    file.right_language
+        .as_deref()
+        .or(file.left_language.as_deref())
+        .unwrap_or(UNKNOWN_LANGUAGE)
+}
+
+/// Aggregates added/deleted line counts by detected language, largest share first.
+pub(crate) fn build_language_stats(files: &[DiffFileView]) -> Vec<LanguageStats> {
+    let mut stats: Vec<LanguageStats> = Vec::new();
+
+    for file in files {
+        let language = file_language(file);
+        let index = match stats.iter().position(|entry| entry.language == language) {
+            Some(index) => index,
+            None => {
+                stats.push(LanguageStats {
+                    language: language.to_string(),
+                    file_count: 0,
+                    added: 0,
+                    deleted: 0,
+                });
+                stats.len() - 1
+            }
+        };
+        let entry = &mut stats[index];
+        entry.file_count += 1;
+        entry.added += file.right_added_line_indexes.len();
+        entry.deleted += file.left_deleted_line_indexes.len();
+    }
+
+    stats.sort_by(|left, right| {
+        (right.added + right.deleted)
+            .cmp(&(left.added + left.deleted))
+            .then_with(|| left.language.cmp(&right.language))
+    });
+    stats
+}
+
+fn format_percentage(part: usize, total: usize) -> String {
+    if total == 0 {
+        return "0.0%".to_string();
+    }
+    format!("{:.1}%", part as f64 * 100.0 / total as f64)
+}
+
+/// A `git diff --stat` style `+++--` bar, scaled so the most-changed file fills
+/// `CHANGE_BAR_WIDTH`; any change gets at least one mark, and each kind present gets at
+/// least one of its own.
+fn change_bar(added: usize, deleted: usize, max_changed: usize) -> String {
+    let changed = added + deleted;
+    if changed == 0 || max_changed == 0 {
+        return String::new();
+    }
+    let width = (changed * CHANGE_BAR_WIDTH).div_ceil(max_changed).max(1);
+    let mut added_width = (added * width + changed / 2) / changed;
+    if added > 0 && added_width == 0 {
+        added_width = 1;
+    }
+    if deleted > 0 && added_width == width && width > 1 {
+        added_width -= 1;
+    }
+    format!(
+        "{}{}",
+        "+".repeat(added_width),
+        "-".repeat(width - added_width)
+    )
+}
+
+pub(crate) fn build_diff_summary_lines(files: &[DiffFileView]) -> Vec<String> {
+    let language_stats = build_language_stats(files);
+    let total_added: usize = language_stats.iter().map(|entry| entry.added).sum();
+    let total_deleted: usize = language_stats.iter().map(|entry| entry.deleted).sum();
+    let total_changed = total_added + total_deleted;
+    let language_width = language_stats
+        .iter()
+        .map(|entry| entry.language.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut lines = vec![
+        format!(
+            "{} files changed, +{total_added} -{total_deleted}",
+            files.len()
+        ),
+        String::new(),
+        "by language:".to_string(),
+    ];
+    for entry in &language_stats {
+        lines.push(format!(
+            "  {:<language_width$}  {:>6}  +{:<6} -{:<6} {:>3} files",
+            entry.language,
+            format_percentage(entry.added + entry.deleted, total_changed),
+            entry.added,
+            entry.deleted,
+            entry.file_count,
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push("by file:".to_string());
+    let max_changed = files
+        .iter()
+        .map(|file| file.right_added_line_indexes.len() + file.left_deleted_line_indexes.len())
+        .max()
+        .unwrap_or(0);
+    for file in files {
+        let (added, deleted) = (
+            file.right_added_line_indexes.len(),
+            file.left_deleted_line_indexes.len(),
+        );
+        lines.push(format!(
+            "  +{added:<6} -{deleted:<6} {:<CHANGE_BAR_WIDTH$} {}",
+            change_bar(added, deleted, max_changed),
+            file.descriptor.display_path
+        ));
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{build_language_stats, change_bar};
+    use crate::model::DiffFileView;
+
+    fn create_view(
+        path: &str,
+        language: Option<&str>,
+        added: usize,
+        deleted: usize,
+    ) -> DiffFileView {
+        DiffFileView {
+            left_language: language.map(str::to_string),
+            right_language: language.map(str::to_string),
+            left_deleted_line_indexes: (0..deleted).collect::<HashSet<_>>(),
+            right_added_line_indexes: (0..added).collect::<HashSet<_>>(),
+            ..DiffFileView::for_test(path)
+        }
+    }
+
+    #[test]
+    fn language_stats_aggregate_and_sort_by_changed_lines() {
+        let files = vec![
+            create_view("src/lib.rs", Some("Rust"), 3, 1),
+            create_view("data.json", Some("JSON"), 90, 0),
+            create_view("src/main.rs", Some("Rust"), 2, 0),
+            create_view("LICENSE", None, 1, 0),
+        ];
+
+        let stats = build_language_stats(&files);
+
+        let languages: Vec<&str> = stats.iter().map(|entry| entry.language.as_str()).collect();
+        assert_eq!(languages, vec!["JSON", "Rust", "Other"]);
+        assert_eq!(
+            (stats[1].added, stats[1].deleted, stats[1].file_count),
+            (5, 1, 2)
+        );
+    }
+
+    #[test]
+    fn change_bars_scale_to_the_most_changed_file() {
+        assert_eq!(change_bar(30, 10, 40), "+++++++++++++++-----");
+        assert_eq!(change_bar(1, 1, 400), "+");
+        assert_eq!(change_bar(3, 1, 400), "+");
+        assert_eq!(change_bar(0, 20, 40), "----------");
+        assert_eq!(change_bar(2, 1, 6), "+++++++---");
+        assert_eq!(change_bar(0, 0, 40), "");
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::HashSet;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::{
+    diff::build_file_view_from_patch,
+    model::{
+        DiffFileDescriptor, DiffFileView, DiffOptions, FileContentSource, FileLineHighlights,
+        ResolvedComparison, StrategyId,
+    },
+};
+
+/// Stands in for the commits of a patch read from stdin, which names none.
+const STDIN_COMMIT: &str = "stdin";
+const NULL_DEVICE: &str = "/dev/null";
+
+static HUNK_HEADER_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^@@ -(\d+)(?:,(\d+))? \+(\d+)(?:,(\d+))? @@")
+        .expect("hunk header regex should be valid")
+});
+
+/// One file of a unified diff. Its sides hold the lines the hunks show at their line
+/// indexes, with empty lines standing in for the ones the patch leaves out.
+#[derive(Debug, Default)]
+pub(crate) struct PatchFile {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    added: bool,
+    deleted: bool,
+    /// `R` for a rename, `C` for a copy.
+    copy_or_rename: Option<char>,
+    similarity: String,
+    binary: bool,
+    has_hunks: bool,
+    left_lines: Vec<String>,
+    right_lines: Vec<String>,
+    deleted_line_indexes: HashSet<usize>,
+    added_line_indexes: HashSet<usize>,
+}
+
+fn put_line(lines: &mut Vec<String>, index: usize, line: &str) {
+    if lines.len() <= index {
+        lines.resize(index + 1, String::new());
+    }
+    lines[index] = line.to_string();
+}
+
+/// `a/src/lib.rs` from a `---`/`+++` line, without git's `a/`/`b/` prefix or the
+/// timestamp `diff -u` appends after a tab; `None` for `/dev/null`.
+fn parse_header_path(raw: &str, prefix: &str) -> Option<String> {
+    let path = raw.split('\t').next().unwrap_or(raw).trim_end();
+    let path = path
+        .strip_prefix('"')
+        .and_then(|path| path.strip_suffix('"'))
+        .unwrap_or(path);
+    if path == NULL_DEVICE {
+        return None;
+    }
+    Some(path.strip_prefix(prefix).unwrap_or(path).to_string())
+}
+
+/// The paths of `diff --git a/old b/new`. Only renames, binaries, and mode changes
+/// lack `---`/`+++` lines to take them from, so spaces in paths are split at ` b/`.
+fn parse_git_header_paths(rest: &str) -> (Option<String>, Option<String>) {
+    match rest
+        .strip_prefix("a/")
+        .and_then(|rest| rest.split_once(" b/"))
+    {
+        Some((old, new)) => (Some(old.to_string()), Some(new.to_string())),
+        None => (None, None),
+    }
+}
+
+impl PatchFile {
+    fn is_started(&self) -> bool {
+        self.old_path.is_some() || self.new_path.is_some() || self.added || self.deleted
+    }
+
+    fn descriptor(&self) -> DiffFileDescriptor {
+        let added = self.added || self.old_path.is_none();
+        let deleted = !added && (self.deleted || self.new_path.is_none());
+        let (old_path, new_path) = (
+            (!added).then(|| self.old_path.clone()).flatten(),
+            (!deleted).then(|| self.new_path.clone()).flatten(),
+        );
+        let (raw_status, display_path) = match (&self.copy_or_rename, &old_path, &new_path) {
+            (Some(status), Some(old), Some(new)) if old != new => (
+                format!("{status}{}", self.similarity),
+                format!("{old} -> {new}"),
+            ),
+            _ => (
+                if added {
+                    "A"
+                } else if deleted {
+                    "D"
+                } else {
+                    "M"
+                }
+                .to_string(),
+                new_path
+                    .clone()
+                    .or_else(|| old_path.clone())
+                    .unwrap_or_default(),
+            ),
+        };
+        let source = |missing: bool| {
+            if missing {
+                FileContentSource::Missing
+            } else {
+                FileContentSource::Commit
+            }
+        };
+        DiffFileDescriptor {
+            raw_status,
+            display_path,
+            base_path: old_path,
+            head_path: new_path,
+            base_source: source(added),
+            head_source: source(deleted),
+            base_mode: None,
+            head_mode: None,
+        }
+    }
+
+    /// Reads the hunk whose header is `header` from `lines`, stopping early at a line
+    /// that cannot belong to it.
+    fn read_hunk<'a>(&mut self, header: &str, lines: &mut impl Iterator<Item = &'a str>) {
+        let Some(captures) = HUNK_HEADER_RE.captures(header) else {
+            return;
+        };
+        let number = |index: usize, default: usize| {
+            captures
+                .get(index)
+                .map_or(Some(default), |value| value.as_str().parse().ok())
+                .unwrap_or(0)
+        };
+        let (mut left, mut left_remaining) = (number(1, 0).saturating_sub(1), number(2, 1));
+        let (mut right, mut right_remaining) = (number(3, 0).saturating_sub(1), number(4, 1));
+        self.has_hunks = true;
+
+        while left_remaining > 0 || right_remaining > 0 {
+            let Some(line) = lines.next() else {
+                return;
+            };
+            // Some tools strip the space off an empty context line.
+            let (marker, text) = match line.chars().next() {
+                Some(marker) => (marker, &line[marker.len_utf8()..]),
+                None => (' ', ""),
+            };
+            match marker {
+                ' ' if left_remaining > 0 && right_remaining > 0 => {
+                    put_line(&mut self.left_lines, left, text);
+                    put_line(&mut self.right_lines, right, text);
+                    (left, left_remaining) = (left + 1, left_remaining - 1);
+                    (right, right_remaining) = (right + 1, right_remaining - 1);
+                }
+                '-' if left_remaining > 0 => {
+                    put_line(&mut self.left_lines, left, text);
+                    self.deleted_line_indexes.insert(left);
+                    (left, left_remaining) = (left + 1, left_remaining - 1);
+                }
+                '+' if right_remaining > 0 => {
+                    put_line(&mut self.right_lines, right, text);
+                    self.added_line_indexes.insert(right);
+                    (right, right_remaining) = (right + 1, right_remaining - 1);
+                }
+                '\\' => {}
+                _ => return,
+            }
+        }
+    }
+
+    pub(crate) fn build_view(self, options: &DiffOptions) -> DiffFileView {
+        let descriptor = self.descriptor();
+        build_file_view_from_patch(
+            &descriptor,
+            options,
+            self.left_lines,
+            self.right_lines,
+            FileLineHighlights {
+                left_deleted_line_indexes: self.deleted_line_indexes,
+                right_added_line_indexes: self.added_line_indexes,
+            },
+            self.binary,
+        )
+    }
+}
+
+/// Splits the output of `git diff`, `git show`, `git format-patch`, or `diff -u` into
+/// its files. Text outside the file sections, such as commit messages, is skipped.
+pub(crate) fn parse_unified_diff(input: &str) -> Vec<PatchFile> {
+    let mut files = Vec::new();
+    let mut current = PatchFile::default();
+    let mut lines = input.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            files.push(std::mem::take(&mut current));
+            (current.old_path, current.new_path) = parse_git_header_paths(rest);
+            continue;
+        }
+        if let Some(old) = line.strip_prefix("--- ")
+            && let Some(new) = lines.peek().and_then(|next| next.strip_prefix("+++ "))
+        {
+            // Plain `diff -u` output has no `diff --git` line between files.
+            if current.has_hunks {
+                files.push(std::mem::take(&mut current));
+            }
+            current.old_path = parse_header_path(old, "a/");
+            current.new_path = parse_header_path(new, "b/");
+            current.added |= current.old_path.is_none();
+            current.deleted |= current.new_path.is_none();
+            lines.next();
+            continue;
+        }
+        if line.starts_with("@@ ") {
+            if current.is_started() {
+                current.read_hunk(line, &mut lines);
+            }
+            continue;
+        }
+        if line.starts_with("new file mode ") {
+            current.added = true;
+        } else if line.starts_with("deleted file mode ") {
+            current.deleted = true;
+        } else if let Some(path) = line.strip_prefix("rename from ") {
+            (current.old_path, current.copy_or_rename) = (Some(path.to_string()), Some('R'));
+        } else if let Some(path) = line.strip_prefix("copy from ") {
+            (current.old_path, current.copy_or_rename) = (Some(path.to_string()), Some('C'));
+        } else if let Some(path) = line
+            .strip_prefix("rename to ")
+            .or_else(|| line.strip_prefix("copy to "))
+        {
+            current.new_path = Some(path.to_string());
+        } else if let Some(similarity) = line.strip_prefix("similarity index ") {
+            current.similarity = similarity.trim_end_matches('%').to_string();
+        } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+            current.binary = true;
+        }
+    }
+    files.push(current);
+    files.retain(PatchFile::is_started);
+    files
+}
+
+/// One scope for every patch from stdin; review marks follow each file's changed lines.
+pub(crate) fn stdin_comparison() -> ResolvedComparison {
+    ResolvedComparison {
+        strategy_id: StrategyId::Range,
+        base_ref: "old".to_string(),
+        head_ref: "new".to_string(),
+        base_commit: STDIN_COMMIT.to_string(),
+        head_commit: STDIN_COMMIT.to_string(),
+        summary: "patch from stdin".to_string(),
+        details: vec!["patch from stdin".to_string()],
+        ahead_count: None,
+        includes_uncommitted: false,
+        range_commits: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::parse_unified_diff;
+    use crate::model::{DiffOptions, FileContentSource, PaneSide};
+
+    #[test]
+    fn git_diff_output_parses_into_files_with_their_changed_lines() {
+        let input = "\
+commit 0123456
+Author: A <a@example.com>
+
+    Change things
+
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -2,3 +2,4 @@ fn main() {
+ two
+-three
++THREE
++four
+
+@@ -10 +11 @@
+-ten
++TEN
+\\ No newline at end of file
+diff --git a/new.txt b/new.txt
+new file mode 100644
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1,2 @@
++a
++b
+diff --git a/old name.txt b/new name.txt
+similarity index 100%
+rename from old name.txt
+rename to new name.txt
+diff --git a/logo.png b/logo.png
+Binary files a/logo.png and b/logo.png differ
+";
+        let views: Vec<_> = parse_unified_diff(input)
+            .into_iter()
+            .map(|file| file.build_view(&DiffOptions::default()))
+            .collect();
+        assert_eq!(views.len(), 4);
+
+        let modified = &views[0];
+        assert_eq!(modified.descriptor.raw_status, "M");
+        assert_eq!(modified.descriptor.display_path, "src/lib.rs");
+        let left: Vec<_> = (0..modified.left_lines.len())
+            .filter_map(|row| modified.source_line(PaneSide::Left, row))
+            .collect();
+        assert_eq!(left, (0..10).collect::<Vec<_>>());
+        let changed_right: HashSet<&str> = modified
+            .right_added_line_indexes
+            .iter()
+            .map(|row| modified.right_lines[*row].as_str())
+            .collect();
+        assert_eq!(changed_right, HashSet::from(["THREE", "four", "TEN"]));
+
+        assert_eq!(views[1].descriptor.raw_status, "A");
+        assert_eq!(views[1].descriptor.base_source, FileContentSource::Missing);
+        assert_eq!(views[1].right_lines, ["a", "b"]);
+
+        assert_eq!(views[2].descriptor.raw_status, "R100");
+        assert_eq!(
+            views[2].descriptor.display_path,
+            "old name.txt -> new name.txt"
+        );
+        assert_eq!(
+            views[3].right_lines,
+            ["<binary file preview not available>"]
+        );
+
+        let plain = parse_unified_diff(
+            "--- a.txt\t2024-01-01\n+++ b.txt\t2024-01-02\n@@ -1 +1 @@\n-x\n+y\n\
+             --- c.txt\n+++ c.txt\n@@ -1 +1 @@\n-p\n+q\n",
+        );
+        assert_eq!(plain.len(), 2);
+        assert!(parse_unified_diff("no diff here\n").is_empty());
+    }
+}
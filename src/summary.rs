@@ -0,0 +1,162 @@
+use serde_json::{Value, json};
+
+use crate::model::{DiffFileView, ResolvedComparison};
+
+/// `singular` for a count of one, else `plural`.
+pub(crate) fn pluralize<'a>(count: usize, singular: &'a str, plural: &'a str) -> &'a str {
+    if count == 1 { singular } else { plural }
+}
+
+/// Plain-text review progress printed after the TUI exits: the comparison, how many
+/// files are reviewed, and which paths still need a look.
+pub(crate) fn build_review_summary(
+    files: &[DiffFileView],
+    comparison: &ResolvedComparison,
+    reviewed_flags: &[bool],
+) -> String {
+    let unreviewed: Vec<&str> = files
+        .iter()
+        .zip(reviewed_flags)
+        .filter(|(_, reviewed)| !**reviewed)
+        .map(|(file, _)| file.descriptor.display_path.as_str())
+        .collect();
+
+    let mut lines = vec![format!(
+        "deff review ({})  {}",
+        comparison.strategy_id, comparison.summary
+    )];
+    if unreviewed.is_empty() {
+        lines.push(format!(
+            "all {} {} reviewed",
+            files.len(),
+            pluralize(files.len(), "file", "files")
+        ));
+    } else {
+        lines.push(format!(
+            "reviewed {}/{} {}; unreviewed:",
+            files.len() - unreviewed.len(),
+            files.len(),
+            pluralize(files.len(), "file", "files")
+        ));
+        lines.extend(unreviewed.iter().map(|path| format!("  {path}")));
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// `deff status` text: one `[x]`/`[ ]` row per file with its review hash.
+pub(crate) fn build_status_text(
+    files: &[DiffFileView],
+    comparison: &ResolvedComparison,
+    reviewed_flags: &[bool],
+) -> String {
+    let reviewed_count = reviewed_flags.iter().filter(|reviewed| **reviewed).count();
+    let mut lines = vec![
+        format!(
+            "deff status ({})  {}",
+            comparison.strategy_id, comparison.summary
+        ),
+        format!(
+            "reviewed {reviewed_count}/{} {}",
+            files.len(),
+            pluralize(files.len(), "file", "files")
+        ),
+    ];
+    lines.extend(files.iter().zip(reviewed_flags).map(|(file, reviewed)| {
+        format!(
+            "[{}] {}  {}",
+            if *reviewed { "x" } else { " " },
+            file.review_key,
+            file.descriptor.display_path
+        )
+    }));
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// `deff status --json` object for CI scripts.
+pub(crate) fn build_status_json(
+    files: &[DiffFileView],
+    comparison: &ResolvedComparison,
+    reviewed_flags: &[bool],
+) -> Value {
+    let unreviewed: Vec<&str> = files
+        .iter()
+        .zip(reviewed_flags)
+        .filter(|(_, reviewed)| !**reviewed)
+        .map(|(file, _)| file.descriptor.display_path.as_str())
+        .collect();
+    let file_entries: Vec<Value> = files
+        .iter()
+        .zip(reviewed_flags)
+        .map(|(file, reviewed)| {
+            json!({
+                "path": file.descriptor.display_path,
+                "status": file.descriptor.raw_status,
+                "review_key": file.review_key,
+                "reviewed": reviewed,
+            })
+        })
+        .collect();
+    json!({
+        "comparison": {
+            "strategy": comparison.strategy_id.to_string(),
+            "summary": comparison.summary,
+            "base_commit": comparison.base_commit,
+            "head_commit": comparison.head_commit,
+        },
+        "reviewed": files.len() - unreviewed.len(),
+        "total": files.len(),
+        "unreviewed": unreviewed,
+        "files": file_entries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_review_summary, build_status_json, build_status_text};
+    use crate::model::{DiffFileView, ResolvedComparison};
+
+    #[test]
+    fn summary_lists_unreviewed_paths() {
+        let comparison = ResolvedComparison::for_test();
+        let files = vec![
+            DiffFileView::for_test_change("a.rs"),
+            DiffFileView::for_test_change("b.rs"),
+            DiffFileView::for_test_change("c.rs"),
+        ];
+
+        assert_eq!(
+            build_review_summary(&files, &comparison, &[true, false, false]),
+            "deff review (range)  main..HEAD\nreviewed 1/3 files; unreviewed:\n  b.rs\n  c.rs\n"
+        );
+        assert_eq!(
+            build_review_summary(&files, &comparison, &[true, true, true]),
+            "deff review (range)  main..HEAD\nall 3 files reviewed\n"
+        );
+        assert_eq!(
+            build_review_summary(&files[..1], &comparison, &[false]),
+            "deff review (range)  main..HEAD\nreviewed 0/1 file; unreviewed:\n  a.rs\n"
+        );
+    }
+
+    #[test]
+    fn status_reports_hashes_and_unreviewed_paths() {
+        let comparison = ResolvedComparison::for_test();
+        let files = vec![
+            DiffFileView::for_test_change("a.rs"),
+            DiffFileView::for_test_change("b.rs"),
+        ];
+
+        assert_eq!(
+            build_status_text(&files, &comparison, &[true, false]),
+            "deff status (range)  main..HEAD\nreviewed 1/2 files\n[x] a.rs  a.rs\n[ ] b.rs  b.rs\n"
+        );
+        let status = build_status_json(&files, &comparison, &[true, false]);
+        assert_eq!(status["reviewed"], 1);
+        assert_eq!(status["total"], 2);
+        assert_eq!(status["unreviewed"], serde_json::json!(["b.rs"]));
+        assert_eq!(status["files"][0]["review_key"], "a.rs");
+        assert_eq!(status["comparison"]["strategy"], "range");
+    }
+}
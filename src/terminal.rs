@@ -1,24 +1,58 @@
-use std::io::{self, IsTerminal};
+use std::{
+    collections::HashMap,
+    io::{self, IsTerminal, Stdout},
+    path::Path,
+    thread,
+    time::Duration,
+};
 
 use anyhow::{Context, Result, bail};
 use crossterm::{
     cursor::{Hide, Show},
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyEventKind},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+    },
     execute,
-    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+    terminal::{
+        self, EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+    },
 };
+use once_cell::sync::OnceCell;
 use ratatui::{
-    Terminal,
-    backend::{Backend, CrosstermBackend},
-    text::Text,
-    widgets::{Clear, Paragraph},
+    Frame, Terminal, TerminalOptions, Viewport,
+    backend::{Backend, CrosstermBackend, TestBackend},
+    buffer::Buffer,
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Text},
+    widgets::{Block, Clear, Paragraph},
 };
 
 use crate::{
-    app::{AppState, handle_keypress, handle_mouse},
-    model::{DiffFileView, ResolvedComparison},
-    render::render_frame,
+    app::{
+        AppState, ListPickerOutcome, ListPickerState, Popup, PopupKind, ReviewMark, StartPosition,
+        TabSwitch, handle_keypress, handle_mouse, handle_paste, hunk_review_keys,
+    },
+    capture,
+    clipboard::copy_to_clipboard,
+    diff::relayout_file_view,
+    forge::{ForgeAction, ForgeRemote, ForgeRequest, open_in_browser},
+    git::{list_path_commits, run_git_with, run_git_with_exit_code},
+    hunk::{describe_hunk_request, run_hunk_request, write_git_dir_file},
+    model::{
+        DiffFileDescriptor, DiffFileView, FileContentSource, LoadProgress, ResolvedComparison,
+        StrategyId, ViewMode, ViewSettings,
+    },
+    pipe::{RunningPipe, pipe_commands},
+    render::{
+        get_popup_area, get_popup_body_line_count, place_beside, render_file_list, render_frame,
+        render_list_frame, render_loading_frame, render_message_frame, render_tab_bar,
+        short_commit,
+    },
     review::ReviewStore,
+    text::normalize_content,
+    view_loader::ViewLoader,
 };
 
 fn draw_app<B: Backend>(
@@ -26,99 +60,960 @@ fn draw_app<B: Backend>(
     files: &[DiffFileView],
     comparison: &ResolvedComparison,
     app: &mut AppState,
+    tab_bar: Option<(&[String], usize)>,
 ) -> Result<()> {
-    let size = terminal.size()?;
-    let render_output = render_frame(
+    let size = ui_area(terminal)?;
+    let frame_columns = app.frame_columns(size.width);
+    let mut render_output = render_frame(
         files,
         comparison,
         app.file_index,
         app.scroll_offset,
         app.current_offsets(),
         app.reviewed_count(),
-        app.is_current_file_reviewed(),
-        app.search_status_text(),
+        app.current_file_review_state(),
+        app.hide_whitespace_only,
+        app.focused_pane,
+        app.cursor().line,
+        app.cursor().selection(),
+        app.status_text(),
         app.focused_hunk_lines.as_ref(),
-        size.width,
+        &app.display_rows(files),
+        app.view_mode,
+        frame_columns,
         size.height,
+        &app.settings.render,
     );
 
     app.scroll_offset = app.scroll_offset.min(render_output.max_scroll);
     app.set_current_offsets(render_output.clamped_pane_offsets);
+    if let Some((labels, active)) = tab_bar {
+        render_output.lines[0] = render_tab_bar(labels, active, frame_columns);
+    }
+    let mut lines = render_output.lines;
+    if let Some(list) = app.file_list {
+        let sidebar = render_file_list(
+            files,
+            &app.file_review_states(),
+            app.file_index,
+            list.focused.then_some(list.selected),
+            list.scroll,
+            size.width - frame_columns,
+            size.height,
+        );
+        lines = place_beside(sidebar, lines);
+    }
 
-    let text = Text::from(render_output.lines);
-    terminal.draw(move |frame| {
+    let text = Text::from(lines);
+    let popup = app.popup.as_ref();
+    let completed_frame = terminal.draw(move |frame| {
         let area = frame.area();
         frame.render_widget(Clear, area);
         frame.render_widget(Paragraph::new(text), area);
+        if let Some(popup) = popup {
+            render_popup(frame, popup);
+        }
     })?;
+    capture::record(completed_frame.buffer);
 
     Ok(())
 }
 
-fn run_event_loop<B: Backend>(
+pub(crate) type TerminalUi = Terminal<CrosstermBackend<Stdout>>;
+
+/// `--height`: draw in this many rows below the prompt instead of the alternate screen.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum InlineHeight {
+    Rows(u16),
+    Percent(u16),
+}
+
+/// Fewer rows than this leave no room for the diff between the header and footer.
+const MIN_INLINE_ROWS: u16 = 10;
+
+static INLINE_HEIGHT: OnceCell<InlineHeight> = OnceCell::new();
+
+pub(crate) fn set_inline_height(height: InlineHeight) {
+    let _ = INLINE_HEIGHT.set(height);
+}
+
+impl InlineHeight {
+    fn resolve(self, terminal_rows: u16) -> u16 {
+        let rows = match self {
+            Self::Rows(rows) => rows,
+            Self::Percent(percent) => (u32::from(terminal_rows) * u32::from(percent) / 100) as u16,
+        };
+        rows.max(MIN_INLINE_ROWS).min(terminal_rows)
+    }
+}
+
+/// The area frames are drawn into: the whole terminal, or the inline viewport.
+fn ui_area<B: Backend>(terminal: &mut Terminal<B>) -> Result<Rect> {
+    terminal.autoresize()?;
+    Ok(terminal.get_frame().area())
+}
+
+/// One comparison open in the review session; `--tab` opens several.
+pub(crate) struct ReviewTab<'a> {
+    pub(crate) files: &'a mut [DiffFileView],
+    pub(crate) comparison: &'a ResolvedComparison,
+    pub(crate) review_store: &'a mut ReviewStore,
+    pub(crate) start: Option<StartPosition>,
+    /// `L` and `B`: builds one file's view again from its descriptor, with the given
+    /// comparison; `None` when the compared contents cannot change, such as a commit's.
+    pub(crate) reload_file: Option<FileReloader<'a>>,
+    /// Builds the views `files` still holds placeholders for; `None` once all are built.
+    pub(crate) loader: Option<ViewLoader>,
+}
+
+pub(crate) type FileReloader<'a> =
+    Box<dyn Fn(&ResolvedComparison, &DiffFileDescriptor) -> Option<DiffFileView> + 'a>;
+
+pub(crate) fn run_review<B: Backend>(
     terminal: &mut Terminal<B>,
-    files: &[DiffFileView],
+    repo_root: &Path,
+    files: &mut [DiffFileView],
     comparison: &ResolvedComparison,
     review_store: &mut ReviewStore,
+    settings: ViewSettings,
 ) -> Result<()> {
-    let initial_reviewed = review_store.reviewed_flags_for_files(files);
-    let mut app = AppState::new(files.len(), initial_reviewed);
-    draw_app(terminal, files, comparison, &mut app)?;
+    let tab = ReviewTab {
+        files,
+        comparison,
+        review_store,
+        start: None,
+        reload_file: None,
+        loader: None,
+    };
+    run_review_tabs(terminal, repo_root, vec![tab], settings).map(|_| ())
+}
+
+/// Whether the review loop acts on the repository and the desktop. Headless runs only
+/// record what a key would have staged, discarded, committed, copied, opened, written,
+/// or run, as a footer notice.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Effects {
+    Live,
+    Recorded,
+}
+
+fn recorded_notice(action: &str) -> String {
+    format!("headless: would {action}")
+}
+
+/// How the review loop ended.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum ReviewExit {
+    Quit,
+    /// Staged changes were committed from the review, so the comparison is out of date.
+    Committed,
+}
+
+pub(crate) fn run_review_tabs<B: Backend>(
+    terminal: &mut Terminal<B>,
+    repo_root: &Path,
+    tabs: Vec<ReviewTab>,
+    settings: ViewSettings,
+) -> Result<ReviewExit> {
+    run_review_with_events(
+        terminal,
+        repo_root,
+        tabs,
+        settings,
+        Effects::Live,
+        |loading| {
+            if loading
+                && !event::poll(LOADING_POLL_INTERVAL).context("failed to poll terminal events")?
+            {
+                return Ok(None);
+            }
+            event::read()
+                .context("failed to read terminal event")
+                .map(Some)
+        },
+    )
+}
+
+/// Replays `keys` through the review loop on an off-screen terminal and returns the
+/// final frame as plain text. Review marks stay in memory and the keys' other effects
+/// are only recorded, so a script leaves the repository as it was.
+pub(crate) fn run_headless_review(
+    repo_root: &Path,
+    mut tabs: Vec<ReviewTab>,
+    settings: ViewSettings,
+    keys: Vec<KeyEvent>,
+    (columns, rows): (u16, u16),
+) -> Result<String> {
+    for tab in &mut tabs {
+        tab.review_store.keep_in_memory();
+    }
+    let mut terminal = Terminal::new(TestBackend::new(columns, rows))?;
+    let mut keys = keys.into_iter();
+    let next_key = |loading: bool| {
+        let key = keys.next();
+        if key.is_none() && loading {
+            thread::sleep(LOADING_POLL_INTERVAL);
+        }
+        Ok(key.map(Event::Key))
+    };
+    run_review_with_events(
+        &mut terminal,
+        repo_root,
+        tabs,
+        settings,
+        Effects::Recorded,
+        next_key,
+    )?;
+    Ok(buffer_to_text(terminal.backend().buffer()))
+}
+
+fn buffer_to_text(buffer: &Buffer) -> String {
+    let width = buffer.area.width as usize;
+    let mut output = String::new();
+    for row in buffer.content.chunks(width.max(1)) {
+        let line: String = row.iter().map(|cell| cell.symbol()).collect();
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+    output
+}
+
+fn render_popup(frame: &mut Frame, popup: &Popup) {
+    let area = get_popup_area(frame.area());
+    let body_line_count = get_popup_body_line_count(frame.area().height as usize);
+    let lines: Vec<Line<'static>> = popup
+        .lines
+        .iter()
+        .enumerate()
+        .skip(popup.scroll)
+        .take(body_line_count)
+        .map(|(index, line)| {
+            let content = normalize_content(line);
+            if popup.selected == Some(index) {
+                Line::styled(content, Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                Line::from(content)
+            }
+        })
+        .collect();
+    let position = if popup.lines.is_empty() {
+        String::new()
+    } else {
+        format!(
+            " {}-{}/{} ",
+            popup.scroll + 1,
+            (popup.scroll + body_line_count).min(popup.lines.len()),
+            popup.lines.len()
+        )
+    };
+
+    let action = match popup.kind {
+        PopupKind::FileBases { .. } => "compare from",
+        PopupKind::ConfirmBulkReview { .. } => "confirm",
+        _ => "jump",
+    };
+    let block = Block::bordered()
+        .title(format!(" {} ", popup.title))
+        .title_bottom(if popup.selected.is_some() {
+            format!("{position} j/k: select  enter: {action}  esc: close ")
+        } else {
+            format!("{position} j/k: scroll  esc: close ")
+        });
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Where `P` writes the selected-lines patch, relative to the git dir.
+const SELECTION_PATCH_FILE: &str = "deff/selection.patch";
+
+/// Writes the patch under the git dir, copies it to the clipboard, and describes the
+/// result.
+fn export_selection_patch(repo_root: &Path, patch: &str) -> String {
+    let path = match write_git_dir_file(repo_root, SELECTION_PATCH_FILE, patch) {
+        Ok(path) => path,
+        Err(error) => return format!("patch: {error:#}"),
+    };
+    match copy_to_clipboard(patch) {
+        Ok(()) => format!("patch copied and written to {}", path.display()),
+        Err(error) => format!(
+            "patch written to {} (not copied: {error:#})",
+            path.display()
+        ),
+    }
+}
+
+/// Commits the index with `message`, refusing when nothing is staged; git's own
+/// failure message (e.g. from a pre-commit hook) is returned otherwise. `--amend`
+/// reviews replace HEAD instead, which needs nothing staged to reword it.
+fn commit_staged_changes(repo_root: &Path, message: &str, amend: bool) -> Result<()> {
+    let mut args = vec!["commit", "--quiet"];
+    if amend {
+        args.push("--amend");
+    } else {
+        let (code, _) = run_git_with_exit_code(["diff", "--cached", "--quiet"], repo_root)?;
+        if code == 0 {
+            bail!("nothing staged; stage hunks from the hunk menu first");
+        }
+    }
+    args.extend(["--file", "-"]);
+    run_git_with(args, repo_root, &[], Some(message.as_bytes()))?;
+    Ok(())
+}
+
+/// How many commits `B` offers as bases for the current file.
+const FILE_BASE_COMMIT_LIMIT: usize = 50;
+
+/// Builds the current file's view again, from `file_base` when `B` re-based its left
+/// pane. A re-based view keeps the descriptor and review key of the comparison's own
+/// view, so review marks still apply to the comparison. `false` when the file could
+/// not be read.
+fn rebuild_current_file(
+    files: &mut [DiffFileView],
+    reload_file: &FileReloader,
+    comparison: &ResolvedComparison,
+    file_base: Option<&ResolvedComparison>,
+    review_store: &ReviewStore,
+    app: &mut AppState,
+    rows: u16,
+) -> bool {
+    let file = &mut files[app.file_index];
+    let view = match file_base {
+        None => reload_file(comparison, &file.descriptor),
+        Some(file_base) => {
+            let descriptor = DiffFileDescriptor {
+                base_path: file.descriptor.head_path.clone(),
+                base_source: FileContentSource::Commit,
+                base_mode: file.descriptor.head_mode.clone(),
+                ..file.descriptor.clone()
+            };
+            reload_file(file_base, &descriptor).map(|view| DiffFileView {
+                descriptor: file.descriptor.clone(),
+                review_key: file.review_key.clone(),
+                ..view
+            })
+        }
+    };
+    let Some(mut view) = view else {
+        return false;
+    };
+    relayout_file_view(&mut view, app.view_mode);
+    *file = view;
+    let file = &files[app.file_index];
+    app.reviewed_hunk_keys.extend(
+        hunk_review_keys(file)
+            .into_iter()
+            .filter(|key| review_store.is_reviewed(key)),
+    );
+    let (reviewed, stale) = (
+        review_store.is_reviewed(&file.review_key),
+        review_store.is_stale(file),
+    );
+    app.reload_current_file(files, rows, reviewed, stale);
+    true
+}
+
+/// The per-file state the review loop keeps next to a tab.
+struct TabFiles<'a> {
+    files: &'a mut [DiffFileView],
+    comparison: &'a ResolvedComparison,
+    reload_file: Option<&'a FileReloader<'a>>,
+    /// `B`: the comparison each re-based file is read with, by file index.
+    file_bases: &'a mut HashMap<usize, ResolvedComparison>,
+}
+
+impl TabFiles<'_> {
+    /// The comparison the current file is shown with: its `B` base, else the tab's.
+    fn current_comparison(&self, app: &AppState) -> &ResolvedComparison {
+        self.file_bases
+            .get(&app.file_index)
+            .unwrap_or(self.comparison)
+    }
+
+    /// `L`: reads the current file again and describes the result.
+    fn reload(&mut self, review_store: &ReviewStore, app: &mut AppState, rows: u16) -> String {
+        let Some(reload_file) = self.reload_file else {
+            return "reload: the compared contents cannot change here".to_string();
+        };
+        let path = self.files[app.file_index].descriptor.display_path.clone();
+        let file_base = self.file_bases.get(&app.file_index);
+        if rebuild_current_file(
+            self.files,
+            reload_file,
+            self.comparison,
+            file_base,
+            review_store,
+            app,
+            rows,
+        ) {
+            format!("reloaded {path}")
+        } else {
+            format!("reload: failed to read {path}")
+        }
+    }
+
+    /// `B`: opens the list of commits touching the current file, or says why not.
+    fn pick_base(&self, repo_root: &Path, app: &mut AppState) -> Option<String> {
+        if self.reload_file.is_none() {
+            return Some("compare from: the compared contents cannot change here".to_string());
+        }
+        let file = &self.files[app.file_index];
+        let Some(path) = file.descriptor.head_path.as_deref() else {
+            return Some(format!(
+                "compare from: {} is deleted",
+                file.descriptor.display_path
+            ));
+        };
+        let file_base = self.file_bases.get(&app.file_index);
+        let current_base = file_base.unwrap_or(self.comparison).base_commit.as_str();
+        match list_path_commits(
+            repo_root,
+            &self.comparison.head_commit,
+            path,
+            FILE_BASE_COMMIT_LIMIT,
+        ) {
+            Ok(commits) => {
+                app.show_file_base_picker(self.files, self.comparison, &commits, current_base);
+                None
+            }
+            Err(error) => Some(format!("compare from: {error:#}")),
+        }
+    }
+
+    /// Compares the current file from `commit`, or from the comparison's base again
+    /// when `commit` is that base, and describes the result.
+    fn set_base(
+        &mut self,
+        commit: &str,
+        review_store: &ReviewStore,
+        app: &mut AppState,
+        rows: u16,
+    ) -> String {
+        let Some(reload_file) = self.reload_file else {
+            return "compare from: the compared contents cannot change here".to_string();
+        };
+        let file_index = app.file_index;
+        if commit == self.comparison.base_commit {
+            self.file_bases.remove(&file_index);
+        } else {
+            self.file_bases.insert(
+                file_index,
+                ResolvedComparison {
+                    base_ref: "file base".to_string(),
+                    base_commit: commit.to_string(),
+                    ..self.comparison.clone()
+                },
+            );
+        }
+        let path = self.files[file_index].descriptor.display_path.clone();
+        let file_base = self.file_bases.get(&file_index);
+        if !rebuild_current_file(
+            self.files,
+            reload_file,
+            self.comparison,
+            file_base,
+            review_store,
+            app,
+            rows,
+        ) {
+            self.file_bases.remove(&file_index);
+            return format!("compare from: failed to read {path}");
+        }
+        match file_base {
+            Some(_) => format!("{path}: left pane at {}", short_commit(commit)),
+            None => format!("{path}: left pane back at the comparison base"),
+        }
+    }
+}
+
+/// Runs a copy/open request against the `origin` forge and describes the result.
+fn run_forge_request(repo_root: &Path, request: &ForgeRequest) -> String {
+    let url = match ForgeRemote::load(repo_root) {
+        Ok(remote) => remote.blob_url(&request.location),
+        Err(error) => return format!("forge: {error:#}"),
+    };
+    match request.action {
+        ForgeAction::CopyPermalink => match copy_to_clipboard(&url) {
+            Ok(()) => format!("copied {url}"),
+            Err(error) => format!("{url} (not copied: {error:#})"),
+        },
+        ForgeAction::OpenInBrowser => match open_in_browser(&url) {
+            Ok(()) => format!("opened {url}"),
+            Err(error) => format!("{url} (not opened: {error:#})"),
+        },
+    }
+}
+
+fn create_app_state(tab: &mut ReviewTab, settings: &ViewSettings, rows: u16) -> AppState {
+    let initial_reviewed = tab.review_store.reviewed_flags_for_files(tab.files);
+    let mut app = AppState::new(tab.files.len(), initial_reviewed);
+    if let Some(loader) = &tab.loader {
+        app.loading_by_file = loader.loading_by_file();
+    }
+    app.stale_by_file = tab.review_store.stale_flags_for_files(tab.files);
+    app.settings = settings.clone();
+    app.pipe_commands = pipe_commands().to_vec();
+    app.fold_collapsed_files(tab.files);
+    if settings.changes_only {
+        app.set_changes_only(tab.files, rows, true);
+    }
+    if settings.view_mode != ViewMode::Split {
+        app.set_view_mode(tab.files, settings.view_mode, rows);
+    }
+    app.reviewed_hunk_keys = tab
+        .files
+        .iter()
+        .flat_map(hunk_review_keys)
+        .filter(|key| tab.review_store.is_reviewed(key))
+        .collect();
+    match tab.start {
+        Some(start) => app.open_at(tab.files, rows, start),
+        None if tab.comparison.range_commits.len() > 1 => app.show_commit_summary(tab.comparison),
+        None => {}
+    }
+    app.place_new_file(tab.files, rows);
+    app
+}
+
+/// Swaps in the views `tab`'s loader built since the last call, and asks it for the
+/// file shown now.
+fn adopt_loaded_views(tab: &mut ReviewTab, app: &mut AppState, rows: u16) {
+    let Some(loader) = &tab.loader else {
+        return;
+    };
+    let (views, done) = loader.take_loaded();
+    loader.want(app.file_index);
+    for (file_index, mut view) in views {
+        // `L` or `B` may have built it already.
+        if !app.loading_by_file[file_index] {
+            continue;
+        }
+        relayout_file_view(&mut view, app.view_mode);
+        tab.files[file_index] = view;
+        let file = &tab.files[file_index];
+        app.reviewed_hunk_keys.extend(
+            hunk_review_keys(file)
+                .into_iter()
+                .filter(|key| tab.review_store.is_reviewed(key)),
+        );
+        let (reviewed, stale) = (
+            tab.review_store.is_reviewed(&file.review_key),
+            tab.review_store.is_stale(file),
+        );
+        app.adopt_loaded_file(tab.files, file_index, rows, reviewed, stale);
+    }
+    if done {
+        tab.loader = None;
+    }
+}
+
+/// The review event loop; it ends on quit, after a commit, or when `next_event` runs out
+/// of events. While views are loading or a pipe command runs, `next_event` is told so
+/// and may return `None` after a short wait instead, to let arriving views and the
+/// command's output be drawn. Each tab keeps its own
+/// view state, so switching back returns to the same place.
+fn run_review_with_events<B: Backend>(
+    terminal: &mut Terminal<B>,
+    repo_root: &Path,
+    mut tabs: Vec<ReviewTab>,
+    settings: ViewSettings,
+    effects: Effects,
+    mut next_event: impl FnMut(bool) -> Result<Option<Event>>,
+) -> Result<ReviewExit> {
+    let rows = ui_area(terminal)?.height;
+    let mut apps: Vec<AppState> = tabs
+        .iter_mut()
+        .map(|tab| create_app_state(tab, &settings, rows))
+        .collect();
+    let tab_labels: Vec<String> = tabs
+        .iter()
+        .map(|tab| tab.comparison.summary.clone())
+        .collect();
+    let mut file_bases: Vec<HashMap<usize, ResolvedComparison>> = vec![HashMap::new(); tabs.len()];
+    let mut active = 0;
+    // With the tab it was started from.
+    let mut running_pipe: Option<(usize, RunningPipe)> = None;
+    let tab_bar = |active: usize| (tab_labels.len() > 1).then_some((&tab_labels[..], active));
+    draw_app(
+        terminal,
+        tabs[active].files,
+        tabs[active].comparison,
+        &mut apps[active],
+        tab_bar(active),
+    )?;
 
     loop {
-        match event::read().context("failed to read terminal event")? {
-            Event::Key(key) => {
-                if !matches!(key.kind, KeyEventKind::Press | KeyEventKind::Repeat) {
-                    continue;
-                }
+        let loading = tabs.iter().any(|tab| tab.loader.is_some()) || running_pipe.is_some();
+        let event = match next_event(loading)? {
+            None if !loading => break,
+            event => event,
+        };
+        let size = ui_area(terminal)?;
+        for (tab, app) in tabs.iter_mut().zip(&mut apps) {
+            adopt_loaded_views(tab, app, size.height);
+        }
+        if running_pipe
+            .as_ref()
+            .is_some_and(|(_, pipe)| pipe.is_finished())
+            && let Some((tab_index, pipe)) = running_pipe.take()
+        {
+            let (title, command) = (pipe.request.title(), pipe.request.command.clone());
+            apps[tab_index].show_popup(Popup::new(title, pipe.output_lines()));
+            apps[tab_index].set_notice(format!("pipe: `{command}` finished"));
+        }
+        if let Some(event) = event {
+            let ReviewTab {
+                files,
+                comparison,
+                review_store,
+                reload_file,
+                ..
+            } = &mut tabs[active];
+            let mut tab_files = TabFiles {
+                files,
+                comparison,
+                reload_file: reload_file.as_ref(),
+                file_bases: &mut file_bases[active],
+            };
+            let app = &mut apps[active];
+            match event {
+                Event::Key(key) => {
+                    if !matches!(key.kind, KeyEventKind::Press | KeyEventKind::Repeat) {
+                        continue;
+                    }
 
-                let (_, rows) =
-                    crossterm::terminal::size().context("failed to read terminal size")?;
-                let outcome = handle_keypress(key, files, &mut app, rows);
+                    let outcome = handle_keypress(
+                        key,
+                        tab_files.files,
+                        tab_files.current_comparison(app),
+                        app,
+                        size.width,
+                        size.height,
+                    );
+                    let files = &*tab_files.files;
 
-                if let Some((file_index, reviewed)) = outcome.review_toggled {
-                    review_store.set_reviewed(&files[file_index].review_key, reviewed);
-                    review_store.persist()?;
-                }
+                    for mark in &outcome.review_updates {
+                        match mark {
+                            ReviewMark::File {
+                                file_index,
+                                reviewed,
+                            } => review_store.set_file_reviewed(&files[*file_index], *reviewed),
+                            ReviewMark::Hunk { key, reviewed, .. } => {
+                                review_store.set_reviewed(key, *reviewed)
+                            }
+                        }
+                    }
+                    if !outcome.review_updates.is_empty() {
+                        review_store.persist()?;
+                    }
+
+                    if let Some(request) = outcome.pipe_request {
+                        match (&running_pipe, effects) {
+                            (Some((_, pipe)), _) => app.set_notice(format!(
+                                "pipe: `{}` is still running",
+                                pipe.request.command
+                            )),
+                            (None, Effects::Recorded) => app
+                                .set_notice(recorded_notice(&format!("run `{}`", request.command))),
+                            (None, Effects::Live) => {
+                                app.set_notice(format!("pipe: running `{}`...", request.command));
+                                running_pipe =
+                                    Some((active, RunningPipe::spawn(request, repo_root)));
+                            }
+                        }
+                    }
+
+                    if let Some(request) = outcome.forge_request {
+                        app.set_notice(match effects {
+                            Effects::Live => run_forge_request(repo_root, &request),
+                            Effects::Recorded => recorded_notice(match request.action {
+                                ForgeAction::CopyPermalink => "copy a permalink",
+                                ForgeAction::OpenInBrowser => "open a permalink",
+                            }),
+                        });
+                    }
+
+                    if let Some(request) = outcome.hunk_request {
+                        app.set_notice(match effects {
+                            Effects::Live => run_hunk_request(repo_root, review_store, &request),
+                            Effects::Recorded => recorded_notice(&describe_hunk_request(&request)),
+                        });
+                    }
+
+                    if let Some(patch) = outcome.selection_patch {
+                        app.set_notice(match effects {
+                            Effects::Live => export_selection_patch(repo_root, &patch),
+                            Effects::Recorded => recorded_notice("export the selected lines"),
+                        });
+                    }
+
+                    if outcome.toggle_view {
+                        app.toggle_view_mode(tab_files.files, size.height);
+                    }
+
+                    if outcome.reload_file {
+                        let notice = tab_files.reload(review_store, app, size.height);
+                        app.set_notice(notice);
+                    }
+
+                    if outcome.pick_file_base
+                        && let Some(notice) = tab_files.pick_base(repo_root, app)
+                    {
+                        app.set_notice(notice);
+                    }
+
+                    if let Some(commit) = outcome.file_base {
+                        let notice = tab_files.set_base(&commit, review_store, app, size.height);
+                        app.set_notice(notice);
+                    }
+
+                    if let Some(message) = outcome.commit_message {
+                        let amend = tab_files.comparison.strategy_id == StrategyId::Amend;
+                        if effects == Effects::Recorded {
+                            app.set_notice(recorded_notice(if amend {
+                                "amend HEAD"
+                            } else {
+                                "commit the staged changes"
+                            }));
+                        } else {
+                            match commit_staged_changes(repo_root, &message, amend) {
+                                Ok(()) => return Ok(ReviewExit::Committed),
+                                Err(error) => app.set_notice(format!("commit: {error:#}")),
+                            }
+                        }
+                    }
 
-                if outcome.should_quit {
-                    break;
+                    active = match outcome.switch_tab {
+                        Some(TabSwitch::Next) => (active + 1) % tabs.len(),
+                        Some(TabSwitch::Previous) => (active + tabs.len() - 1) % tabs.len(),
+                        None => active,
+                    };
+
+                    if outcome.should_quit {
+                        break;
+                    }
                 }
+                // Inline mode reports rows of the whole terminal, not of the viewport.
+                Event::Mouse(mut mouse) if mouse.row >= size.y => {
+                    mouse.row -= size.y;
+                    handle_mouse(mouse, tab_files.files, app, size.width, size.height);
+                }
+                Event::Mouse(_) => {}
+                Event::Paste(text) => handle_paste(&text, tab_files.files, app, size.height),
+                Event::Resize(_, _) => {}
+                Event::FocusGained | Event::FocusLost => {}
             }
-            Event::Mouse(mouse) => {
-                let (columns, rows) =
-                    crossterm::terminal::size().context("failed to read terminal size")?;
-                handle_mouse(mouse, files, &mut app, columns, rows);
-            }
-            Event::Resize(_, _) => {}
-            Event::FocusGained | Event::FocusLost | Event::Paste(_) => {}
         }
 
-        draw_app(terminal, files, comparison, &mut app)?;
+        let comparison = file_bases[active]
+            .get(&apps[active].file_index)
+            .unwrap_or(tabs[active].comparison);
+        draw_app(
+            terminal,
+            tabs[active].files,
+            comparison,
+            &mut apps[active],
+            tab_bar(active),
+        )?;
     }
 
+    Ok(ReviewExit::Quit)
+}
+
+/// Shows a selectable list and returns the chosen index, or `None` when cancelled.
+pub(crate) fn run_list_picker<B: Backend>(
+    terminal: &mut Terminal<B>,
+    title: &str,
+    items: &[String],
+    initial_selected: usize,
+) -> Result<Option<usize>> {
+    let mut picker = ListPickerState::new(items.len(), initial_selected);
+
+    loop {
+        let size = ui_area(terminal)?;
+        picker.keep_selection_visible(size.height);
+        let text = Text::from(render_list_frame(
+            title,
+            items,
+            picker.selected,
+            picker.scroll,
+            size.width,
+            size.height,
+        ));
+        let completed_frame = terminal.draw(move |frame| {
+            let area = frame.area();
+            frame.render_widget(Clear, area);
+            frame.render_widget(Paragraph::new(text), area);
+        })?;
+        capture::record(completed_frame.buffer);
+
+        let Event::Key(key) = event::read().context("failed to read terminal event")? else {
+            continue;
+        };
+        if !matches!(key.kind, KeyEventKind::Press | KeyEventKind::Repeat) {
+            continue;
+        }
+
+        match picker.handle_key(key, size.height) {
+            ListPickerOutcome::Continue => {}
+            ListPickerOutcome::Select(index) => return Ok(Some(index)),
+            ListPickerOutcome::Cancel => return Ok(None),
+        }
+    }
+}
+
+/// Shows a message with a vertical list of choices; `None` when dismissed with q/Esc.
+pub(crate) fn run_message_prompt<B: Backend>(
+    terminal: &mut Terminal<B>,
+    title: &str,
+    message: &[String],
+    choices: &[String],
+) -> Result<Option<usize>> {
+    let mut picker = ListPickerState::new(choices.len(), 0);
+
+    loop {
+        let size = ui_area(terminal)?;
+        let text = Text::from(render_message_frame(
+            title,
+            message,
+            choices,
+            picker.selected,
+            size.width,
+            size.height,
+        ));
+        let completed_frame = terminal.draw(move |frame| {
+            let area = frame.area();
+            frame.render_widget(Clear, area);
+            frame.render_widget(Paragraph::new(text), area);
+        })?;
+        capture::record(completed_frame.buffer);
+
+        let Event::Key(key) = event::read().context("failed to read terminal event")? else {
+            continue;
+        };
+        if !matches!(key.kind, KeyEventKind::Press | KeyEventKind::Repeat) {
+            continue;
+        }
+
+        match picker.handle_key(key, size.height) {
+            ListPickerOutcome::Continue => {}
+            ListPickerOutcome::Select(index) => return Ok(Some(index)),
+            ListPickerOutcome::Cancel => return Ok(None),
+        }
+    }
+}
+
+const LOADING_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Runs `job` on a background thread while drawing a progress screen; `q`, `Esc`, or
+/// Ctrl-C cancel it. Returns `None` when the user cancelled.
+pub(crate) fn run_loading<B: Backend, T: Send>(
+    terminal: &mut Terminal<B>,
+    title: &str,
+    total: usize,
+    job: impl FnOnce(&LoadProgress) -> Option<T> + Send,
+) -> Result<Option<T>> {
+    let progress = LoadProgress::default();
+
+    thread::scope(|scope| {
+        let handle = scope.spawn(|| job(&progress));
+
+        while !handle.is_finished() {
+            let size = ui_area(terminal)?;
+            let text = Text::from(render_loading_frame(
+                title,
+                progress.completed(),
+                total,
+                progress.note().as_deref(),
+                progress.is_cancelled(),
+                size.width,
+                size.height,
+            ));
+            let completed_frame = terminal.draw(move |frame| {
+                let area = frame.area();
+                frame.render_widget(Clear, area);
+                frame.render_widget(Paragraph::new(text), area);
+            })?;
+            capture::record(completed_frame.buffer);
+
+            if !event::poll(LOADING_POLL_INTERVAL).context("failed to poll terminal events")? {
+                continue;
+            }
+            if let Event::Key(key) = event::read().context("failed to read terminal event")?
+                && key.kind == KeyEventKind::Press
+                && (matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                    || (key.modifiers.contains(KeyModifiers::CONTROL)
+                        && key.code == KeyCode::Char('c')))
+            {
+                progress.cancel();
+            }
+        }
+
+        let result = handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("file loading thread panicked"))?;
+        Ok(result.filter(|_| !progress.is_cancelled()))
+    })
+}
+
+/// Points stdin back at the controlling terminal once a piped patch has been read from
+/// it, so the review can take keys.
+#[cfg(unix)]
+pub(crate) fn reattach_stdin_to_tty() -> Result<()> {
+    use std::{fs::File, os::fd::AsRawFd};
+
+    let tty = File::open("/dev/tty").context("no terminal to review on; stdin held the patch")?;
+    // SAFETY: both descriptors are open; dup2 only replaces stdin's.
+    if unsafe { libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO) } < 0 {
+        return Err(io::Error::last_os_error()).context("failed to reattach stdin to /dev/tty");
+    }
     Ok(())
 }
 
-pub(crate) fn start_interactive_review(
-    files: &[DiffFileView],
-    comparison: &ResolvedComparison,
-    mut review_store: ReviewStore,
-) -> Result<()> {
+#[cfg(not(unix))]
+pub(crate) fn reattach_stdin_to_tty() -> Result<()> {
+    bail!("reviewing a patch from stdin needs a Unix terminal; use --output or a pager instead")
+}
+
+/// Enters the full-screen TUI (or the inline viewport with `--height`), runs `body`, and
+/// restores the terminal afterwards even when `body` fails.
+pub(crate) fn with_terminal<T>(body: impl FnOnce(&mut TerminalUi) -> Result<T>) -> Result<T> {
     if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
         bail!("Interactive TTY is required to run deff");
     }
 
+    let inline_rows = match INLINE_HEIGHT.get() {
+        Some(height) => {
+            let (_, rows) = terminal::size().context("failed to read terminal size")?;
+            Some(height.resolve(rows))
+        }
+        None => None,
+    };
+
     enable_raw_mode().context("failed to enable raw mode")?;
 
     let mut stdout = io::stdout();
-    if let Err(error) = execute!(stdout, EnterAlternateScreen, EnableMouseCapture, Hide) {
+    let setup = if inline_rows.is_some() {
+        execute!(stdout, EnableMouseCapture, EnableBracketedPaste, Hide)
+    } else {
+        execute!(
+            stdout,
+            EnterAlternateScreen,
+            EnableMouseCapture,
+            EnableBracketedPaste,
+            Hide
+        )
+    };
+    if let Err(error) = setup {
         let _ = disable_raw_mode();
         return Err(error).context("failed to initialize terminal UI");
     }
 
     let backend = CrosstermBackend::new(stdout);
-    let mut terminal = match Terminal::new(backend) {
+    let viewport = match inline_rows {
+        Some(rows) => Viewport::Inline(rows),
+        None => Viewport::Fullscreen,
+    };
+    let mut terminal = match Terminal::with_options(backend, TerminalOptions { viewport }) {
         Ok(terminal) => terminal,
         Err(error) => {
             let _ = disable_raw_mode();
@@ -126,27 +1021,51 @@ pub(crate) fn start_interactive_review(
             let _ = execute!(
                 cleanup_stdout,
                 Show,
-                DisableMouseCapture,
-                LeaveAlternateScreen
+                DisableBracketedPaste,
+                DisableMouseCapture
             );
+            if inline_rows.is_none() {
+                let _ = execute!(cleanup_stdout, LeaveAlternateScreen);
+            }
             return Err(error).context("failed to build terminal backend");
         }
     };
 
-    let run_result = run_event_loop(&mut terminal, files, comparison, &mut review_store);
+    let run_result = body(&mut terminal);
 
     let mut restore_error: Option<anyhow::Error> = None;
     if let Err(error) = disable_raw_mode() {
         restore_error = Some(error.into());
     }
-    if let Err(error) = execute!(
-        terminal.backend_mut(),
-        Show,
-        DisableMouseCapture,
-        LeaveAlternateScreen
-    ) && restore_error.is_none()
+    // Inline mode wipes its viewport and leaves the cursor where it started, so the
+    // shell prompt comes back right below the earlier output.
+    let restore = if inline_rows.is_some() {
+        terminal
+            .clear()
+            .map_err(anyhow::Error::from)
+            .and_then(|()| {
+                execute!(
+                    terminal.backend_mut(),
+                    Show,
+                    DisableBracketedPaste,
+                    DisableMouseCapture
+                )
+                .map_err(anyhow::Error::from)
+            })
+    } else {
+        execute!(
+            terminal.backend_mut(),
+            Show,
+            DisableBracketedPaste,
+            DisableMouseCapture,
+            LeaveAlternateScreen
+        )
+        .map_err(anyhow::Error::from)
+    };
+    if let Err(error) = restore
+        && restore_error.is_none()
     {
-        restore_error = Some(error.into());
+        restore_error = Some(error);
     }
     if let Err(error) = terminal.show_cursor()
         && restore_error.is_none()
@@ -0,0 +1,107 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+    },
+    thread::Scope,
+};
+
+use crate::model::DiffFileView;
+
+/// Views built on a background thread while the review is already open. The file the
+/// review shows and its neighbours are built before the rest.
+pub(crate) struct ViewLoader {
+    receiver: Receiver<(usize, DiffFileView)>,
+    wanted: Arc<AtomicUsize>,
+    loading_by_file: Vec<bool>,
+}
+
+/// The wanted file, then the next two and the previous one, then the first file not
+/// built yet.
+fn next_to_load(loaded: &[bool], wanted: usize) -> Option<usize> {
+    [
+        Some(wanted),
+        wanted.checked_add(1),
+        wanted.checked_add(2),
+        wanted.checked_sub(1),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|&index| loaded.get(index) == Some(&false))
+    .or_else(|| loaded.iter().position(|loaded| !loaded))
+}
+
+impl ViewLoader {
+    /// Builds every file `loaded` marks `false` with `build`, on a thread of `scope`.
+    /// Dropping the loader stops the thread after the file it is building.
+    pub(crate) fn spawn<'scope>(
+        scope: &'scope Scope<'scope, '_>,
+        loaded: Vec<bool>,
+        wanted: usize,
+        build: impl Fn(usize) -> Option<DiffFileView> + Send + 'scope,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let wanted = Arc::new(AtomicUsize::new(wanted));
+        let loading_by_file = loaded.iter().map(|loaded| !loaded).collect();
+        let worker_wanted = Arc::clone(&wanted);
+        scope.spawn(move || {
+            let mut loaded = loaded;
+            while let Some(index) = next_to_load(&loaded, worker_wanted.load(Ordering::Relaxed)) {
+                loaded[index] = true;
+                if let Some(view) = build(index)
+                    && sender.send((index, view)).is_err()
+                {
+                    break;
+                }
+            }
+        });
+        Self {
+            receiver,
+            wanted,
+            loading_by_file,
+        }
+    }
+
+    /// Which files were still to be built when the loader started.
+    pub(crate) fn loading_by_file(&self) -> Vec<bool> {
+        self.loading_by_file.clone()
+    }
+
+    /// Moves `file_index` to the front of the queue.
+    pub(crate) fn want(&self, file_index: usize) {
+        self.wanted.store(file_index, Ordering::Relaxed);
+    }
+
+    /// The views built since the last call, and whether every file is built.
+    pub(crate) fn take_loaded(&self) -> (Vec<(usize, DiffFileView)>, bool) {
+        let mut views = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(view) => views.push(view),
+                Err(TryRecvError::Empty) => return (views, false),
+                Err(TryRecvError::Disconnected) => return (views, true),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::next_to_load;
+
+    #[test]
+    fn the_wanted_file_and_its_neighbours_load_first() {
+        let mut loaded = vec![false; 6];
+        loaded[3] = true;
+        assert_eq!(next_to_load(&loaded, 3), Some(4));
+        loaded[4] = true;
+        assert_eq!(next_to_load(&loaded, 3), Some(5));
+        loaded[5] = true;
+        assert_eq!(next_to_load(&loaded, 3), Some(2));
+        loaded[2] = true;
+        assert_eq!(next_to_load(&loaded, 3), Some(0));
+        assert_eq!(next_to_load(&loaded, 1), Some(1));
+        assert_eq!(next_to_load(&[true, true], 0), None);
+    }
+}